@@ -19,7 +19,7 @@ use tauri::Manager;
 #[tauri::command]
 pub fn get_app_version(app: AppHandle) -> String {
     let version = app.config().version.clone().unwrap_or_else(|| "0.0.0".to_string());
-    println!("[Android Update] get_app_version: {}", version);
+    log::info!("[Android Update] get_app_version: {}", version);
     version
 }
 
@@ -30,32 +30,32 @@ pub fn get_app_version(app: AppHandle) -> String {
 pub async fn fetch_update_json(url: String, timeout_secs: u64) -> Result<String, String> {
     use std::time::Duration;
 
-    println!("[Android Update] fetch_update_json 开始");
-    println!("[Android Update] URL: {}", url);
-    println!("[Android Update] 超时: {} 秒", timeout_secs);
+    log::info!("[Android Update] fetch_update_json 开始");
+    log::info!("[Android Update] URL: {}", url);
+    log::info!("[Android Update] 超时: {} 秒", timeout_secs);
 
     let client = reqwest::Client::builder()
         .timeout(Duration::from_secs(timeout_secs))
         .build()
         .map_err(|e| {
-            eprintln!("[Android Update] 创建 HTTP 客户端失败: {}", e);
+            log::error!("[Android Update] 创建 HTTP 客户端失败: {}", e);
             format!("创建 HTTP 客户端失败: {}", e)
         })?;
 
-    println!("[Android Update] 发送请求...");
+    log::info!("[Android Update] 发送请求...");
     let response = client
         .get(&url)
         .send()
         .await
         .map_err(|e| {
-            eprintln!("[Android Update] 请求失败: {}", e);
+            log::error!("[Android Update] 请求失败: {}", e);
             format!("请求失败: {}", e)
         })?;
 
-    println!("[Android Update] 响应状态: {}", response.status());
+    log::info!("[Android Update] 响应状态: {}", response.status());
     if !response.status().is_success() {
         let err = format!("HTTP 错误: {}", response.status());
-        eprintln!("[Android Update] {}", err);
+        log::warn!("[Android Update] {}", err);
         return Err(err);
     }
 
@@ -63,12 +63,12 @@ pub async fn fetch_update_json(url: String, timeout_secs: u64) -> Result<String,
         .text()
         .await
         .map_err(|e| {
-            eprintln!("[Android Update] 读取响应失败: {}", e);
+            log::error!("[Android Update] 读取响应失败: {}", e);
             format!("读取响应失败: {}", e)
         })?;
 
-    println!("[Android Update] 响应长度: {} 字节", text.len());
-    println!("[Android Update] 响应内容: {}", &text[..text.len().min(200)]);
+    log::info!("[Android Update] 响应长度: {} 字节", text.len());
+    log::info!("[Android Update] 响应内容: {}", &text[..text.len().min(200)]);
     Ok(text)
 }
 
@@ -81,29 +81,29 @@ pub async fn download_apk(url: String, app: AppHandle) -> Result<String, String>
     use futures_util::StreamExt;
     use std::io::Write;
 
-    println!("[Android Update] ========== download_apk 开始 ==========");
-    println!("[Android Update] 下载 URL: {}", url);
+    log::info!("[Android Update] ========== download_apk 开始 ==========");
+    log::info!("[Android Update] 下载 URL: {}", url);
 
     let client = reqwest::Client::new();
-    println!("[Android Update] 发送下载请求...");
+    log::info!("[Android Update] 发送下载请求...");
     let response = client
         .get(&url)
         .send()
         .await
         .map_err(|e| {
-            eprintln!("[Android Update] 下载请求失败: {}", e);
+            log::error!("[Android Update] 下载请求失败: {}", e);
             format!("下载请求失败: {}", e)
         })?;
 
-    println!("[Android Update] 响应状态: {}", response.status());
+    log::info!("[Android Update] 响应状态: {}", response.status());
     if !response.status().is_success() {
         let err = format!("下载失败: HTTP {}", response.status());
-        eprintln!("[Android Update] {}", err);
+        log::warn!("[Android Update] {}", err);
         return Err(err);
     }
 
     let total = response.content_length().unwrap_or(0);
-    println!("[Android Update] 文件大小: {} bytes", total);
+    log::info!("[Android Update] 文件大小: {} bytes", total);
     let mut downloaded: u64 = 0;
 
     // 使用应用缓存目录（无需任何权限）
@@ -114,37 +114,37 @@ pub async fn download_apk(url: String, app: AppHandle) -> Result<String, String>
         .map_err(|e| format!("获取缓存目录失败: {}", e))?;
     let file_path = cache_dir.join("huanvae-chat-update.apk");
     let file_path_str = file_path.to_string_lossy().to_string();
-    println!("[Android Update] 保存路径: {}", file_path_str);
+    log::info!("[Android Update] 保存路径: {}", file_path_str);
 
     // 确保缓存目录存在
     if let Err(e) = std::fs::create_dir_all(&cache_dir) {
-        eprintln!("[Android Update] 创建缓存目录失败（可能已存在）: {}", e);
+        log::warn!("[Android Update] 创建缓存目录失败（可能已存在）: {}", e);
         // 继续尝试，目录可能已存在
     }
 
     // 创建文件
-    println!("[Android Update] 创建文件...");
+    log::info!("[Android Update] 创建文件...");
     let mut file =
         std::fs::File::create(&file_path).map_err(|e| {
-            eprintln!("[Android Update] 创建文件失败: {}", e);
+            log::error!("[Android Update] 创建文件失败: {}", e);
             format!("创建文件失败: {}", e)
         })?;
-    println!("[Android Update] 文件创建成功");
+    log::info!("[Android Update] 文件创建成功");
 
     // 流式下载
-    println!("[Android Update] 开始流式下载...");
+    log::info!("[Android Update] 开始流式下载...");
     let mut stream = response.bytes_stream();
     let mut last_log_percent: u8 = 0;
     
     while let Some(chunk_result) = stream.next().await {
         let chunk = chunk_result.map_err(|e| {
-            eprintln!("[Android Update] 下载数据失败: {}", e);
+            log::error!("[Android Update] 下载数据失败: {}", e);
             format!("下载数据失败: {}", e)
         })?;
 
         file.write_all(&chunk)
             .map_err(|e| {
-                eprintln!("[Android Update] 写入文件失败: {}", e);
+                log::error!("[Android Update] 写入文件失败: {}", e);
                 format!("写入文件失败: {}", e)
             })?;
 
@@ -159,7 +159,7 @@ pub async fn download_apk(url: String, app: AppHandle) -> Result<String, String>
 
         // 每 10% 输出一次日志
         if percent >= last_log_percent + 10 {
-            println!("[Android Update] 下载进度: {}% ({}/{})", percent, downloaded, total);
+            log::info!("[Android Update] 下载进度: {}% ({}/{})", percent, downloaded, total);
             last_log_percent = percent;
         }
 
@@ -167,13 +167,13 @@ pub async fn download_apk(url: String, app: AppHandle) -> Result<String, String>
     }
 
     // 确保写入完成
-    println!("[Android Update] 刷新缓冲区...");
+    log::info!("[Android Update] 刷新缓冲区...");
     file.flush().map_err(|e| {
-        eprintln!("[Android Update] 刷新文件失败: {}", e);
+        log::error!("[Android Update] 刷新文件失败: {}", e);
         format!("刷新文件失败: {}", e)
     })?;
 
-    println!(
+    log::info!(
         "[Android Update] ✓ 下载完成: {} ({} bytes)",
         file_path_str, downloaded
     );
@@ -0,0 +1,15 @@
+//! 命令行局域网传输工具
+//!
+//! 在无图形界面的机器上列出局域网设备、发送和接收文件，
+//! 用法见 `huanvae-lan --help`
+
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+fn main() {
+    std::process::exit(huanvae_chat_app_lib::run_lan_cli())
+}
+
+#[cfg(any(target_os = "android", target_os = "ios"))]
+fn main() {
+    eprintln!("huanvae-lan 仅支持桌面平台");
+    std::process::exit(1)
+}
@@ -41,6 +41,35 @@ pub fn get_friends() -> Result<Vec<LocalFriend>, String> {
     })
 }
 
+/// 获取单个好友
+pub fn get_friend(friend_id: &str) -> Result<Option<LocalFriend>, String> {
+    with_db!(db, {
+        let mut stmt = db
+            .prepare(
+                "SELECT friend_id, username, nickname, avatar_url, status, created_at, updated_at
+                 FROM friends
+                 WHERE friend_id = ?1",
+            )
+            .map_err(|e| e.to_string())?;
+
+        let friend = stmt
+            .query_row([friend_id], |row| {
+                Ok(LocalFriend {
+                    friend_id: row.get(0)?,
+                    username: row.get(1)?,
+                    nickname: row.get(2)?,
+                    avatar_url: row.get(3)?,
+                    status: row.get(4)?,
+                    created_at: row.get(5)?,
+                    updated_at: row.get(6)?,
+                })
+            })
+            .ok();
+
+        Ok(friend)
+    })
+}
+
 /// 保存单个好友
 pub fn save_friend(friend: &LocalFriend) -> Result<(), String> {
     with_db!(db, {
@@ -94,7 +123,7 @@ pub fn save_friends(friends: &[LocalFriend]) -> Result<(), String> {
         // 提交事务
         db.execute("COMMIT", []).map_err(|e| e.to_string())?;
 
-        println!("[DB] 保存 {} 个好友", friends.len());
+        log::info!("[DB] 保存 {} 个好友", friends.len());
         Ok(())
     })
 }
@@ -144,6 +173,49 @@ pub fn get_groups() -> Result<Vec<LocalGroup>, String> {
     })
 }
 
+/// 获取与指定用户共同所在的群组
+///
+/// 本地没有群成员表，按以下条件判断：
+/// - 该用户是群主
+/// - 本地缓存的群消息中有该用户发送的消息
+pub fn get_groups_shared_with(user_id: &str) -> Result<Vec<LocalGroup>, String> {
+    with_db!(db, {
+        let mut stmt = db
+            .prepare(
+                "SELECT g.group_id, g.name, g.avatar_url, g.owner_id, g.member_count, g.my_role, g.created_at, g.updated_at
+                 FROM groups g
+                 WHERE g.owner_id = ?1
+                    OR EXISTS (
+                        SELECT 1 FROM messages m
+                        WHERE m.conversation_type = 'group'
+                          AND m.conversation_id = g.group_id
+                          AND m.sender_id = ?1
+                    )
+                 ORDER BY g.updated_at DESC",
+            )
+            .map_err(|e| e.to_string())?;
+
+        let groups = stmt
+            .query_map([user_id], |row| {
+                Ok(LocalGroup {
+                    group_id: row.get(0)?,
+                    name: row.get(1)?,
+                    avatar_url: row.get(2)?,
+                    owner_id: row.get(3)?,
+                    member_count: row.get(4)?,
+                    my_role: row.get(5)?,
+                    created_at: row.get(6)?,
+                    updated_at: row.get(7)?,
+                })
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        Ok(groups)
+    })
+}
+
 /// 保存单个群组
 pub fn save_group(group: &LocalGroup) -> Result<(), String> {
     with_db!(db, {
@@ -199,7 +271,7 @@ pub fn save_groups(groups: &[LocalGroup]) -> Result<(), String> {
         // 提交事务
         db.execute("COMMIT", []).map_err(|e| e.to_string())?;
 
-        println!("[DB] 保存 {} 个群组", groups.len());
+        log::info!("[DB] 保存 {} 个群组", groups.len());
         Ok(())
     })
 }
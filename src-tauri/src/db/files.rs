@@ -18,6 +18,7 @@
 //! - `save_file_mapping`: 保存文件映射
 //! - `save_file_uuid_hash`: 保存 uuid->hash 映射
 //! - `get_file_hash_by_uuid`: 通过 uuid 查找 hash
+//! - `is_file_shared_with_user`: 判断文件是否在与某用户的聊天中出现过（局域网附件授权）
 
 use rusqlite::params;
 
@@ -135,3 +136,42 @@ pub fn get_file_hash_by_uuid(file_uuid: &str) -> Result<Option<String>, String>
         Ok(result)
     })
 }
+
+/// 判断文件是否在与指定用户的聊天中出现过
+///
+/// 用于局域网附件直传的授权：只向聊天关系中能看到该文件的用户提供文件
+/// - 好友会话：会话 ID 为 `friend_conversation_id`
+/// - 群聊会话：对方是群主，或本地缓存中有对方在该群发送的消息
+pub fn is_file_shared_with_user(
+    file_hash: &str,
+    user_id: &str,
+    friend_conversation_id: &str,
+) -> Result<bool, String> {
+    with_db!(db, {
+        let count: i64 = db
+            .query_row(
+                "SELECT COUNT(*) FROM messages m
+                 WHERE (m.file_hash = ?1
+                        OR m.file_uuid IN (SELECT file_uuid FROM file_uuid_hash WHERE file_hash = ?1))
+                   AND m.is_deleted = 0 AND m.is_recalled = 0
+                   AND (
+                       (m.conversation_type = 'friend' AND m.conversation_id = ?3)
+                       OR (m.conversation_type = 'group' AND EXISTS (
+                           SELECT 1 FROM groups g
+                           WHERE g.group_id = m.conversation_id
+                             AND (g.owner_id = ?2 OR EXISTS (
+                                 SELECT 1 FROM messages gm
+                                 WHERE gm.conversation_type = 'group'
+                                   AND gm.conversation_id = g.group_id
+                                   AND gm.sender_id = ?2
+                             ))
+                       ))
+                   )",
+                params![file_hash, user_id, friend_conversation_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+
+        Ok(count > 0)
+    })
+}
@@ -0,0 +1,239 @@
+//! 局域网消息投递队列模块
+//!
+//! 记录通过局域网点对点连接收发的聊天消息及其投递状态，包括：
+//! - `append_lan_message`: 在一个事务中分配 seq、保存消息（按 message_uuid 去重）并加入投递队列
+//! - `enqueue_lan_message`: 消息加入投递队列（pending）
+//! - `get_pending_lan_messages`: 获取发往某设备的待投递消息（按发送时间升序）
+//! - `record_lan_delivery_attempt`: 记录一次投递尝试
+//! - `mark_lan_message_delivered`: 标记消息已送达（收到对方回执）
+//! - `get_lan_delivery`: 查询单条消息的投递状态
+//!
+//! ## 说明
+//!
+//! 消息内容本身保存在 `messages` 表（conversation_type = 'lan'），
+//! 此表只保存投递相关状态，对方离线时消息保持 pending，设备重新上线后重发。
+
+use rusqlite::params;
+
+use super::types::{LocalConversation, LocalLanDelivery, LocalMessage};
+use super::{with_db, DB};
+
+/// 追加一条局域网聊天消息
+///
+/// 在同一个事务中完成：会话不存在时按 conversation 创建 → 按 message_uuid 去重 →
+/// 分配 seq（会话 last_seq + 1）→ 更新最后消息与未读数 → 保存消息 → outbox_peer
+/// 不为空时加入发往该设备的投递队列。并发投递或重发不会产生重复的 seq 或消息。
+///
+/// 返回保存的消息（seq 已填写）；消息已存在时返回 None
+pub fn append_lan_message(
+    conversation: &LocalConversation,
+    mut message: LocalMessage,
+    outbox_peer: Option<&str>,
+    is_incoming: bool,
+) -> Result<Option<LocalMessage>, String> {
+    let mut guard = DB.lock();
+    let db = guard
+        .as_mut()
+        .ok_or_else(|| "数据库未初始化".to_string())?;
+
+    let tx = db.transaction().map_err(|e| e.to_string())?;
+
+    let exists: i64 = tx
+        .query_row(
+            "SELECT COUNT(*) FROM messages WHERE message_uuid = ?",
+            params![message.message_uuid],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if exists > 0 {
+        return Ok(None);
+    }
+
+    tx.execute(
+        "INSERT OR IGNORE INTO conversations (id, type, name, last_seq, unread_count, updated_at)
+         VALUES (?, ?, ?, 0, 0, datetime('now'))",
+        params![conversation.id, conversation.conv_type, conversation.name],
+    )
+    .map_err(|e| e.to_string())?;
+
+    message.seq = tx
+        .query_row(
+            "UPDATE conversations SET last_seq = last_seq + 1, last_message = ?,
+             last_message_time = ?, unread_count = unread_count + ?, updated_at = datetime('now')
+             WHERE id = ? RETURNING last_seq",
+            params![
+                message.content,
+                message.send_time,
+                if is_incoming { 1 } else { 0 },
+                conversation.id,
+            ],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    tx.execute(
+        "INSERT INTO messages 
+         (message_uuid, conversation_id, conversation_type, sender_id, sender_name, 
+          sender_avatar, content, content_type, file_uuid, file_url, file_size, 
+          file_hash, image_width, image_height, seq, reply_to, is_recalled, is_deleted, send_time)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            message.message_uuid,
+            message.conversation_id,
+            message.conversation_type,
+            message.sender_id,
+            message.sender_name,
+            message.sender_avatar,
+            message.content,
+            message.content_type,
+            message.file_uuid,
+            message.file_url,
+            message.file_size,
+            message.file_hash,
+            message.image_width,
+            message.image_height,
+            message.seq,
+            message.reply_to,
+            if message.is_recalled { 1 } else { 0 },
+            if message.is_deleted { 1 } else { 0 },
+            message.send_time,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    if let Some(peer_device_id) = outbox_peer {
+        tx.execute(
+            "INSERT OR IGNORE INTO lan_message_outbox (message_uuid, peer_device_id, status)
+             VALUES (?, ?, 'pending')",
+            params![message.message_uuid, peer_device_id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(Some(message))
+}
+
+/// 消息加入投递队列
+pub fn enqueue_lan_message(message_uuid: &str, peer_device_id: &str) -> Result<(), String> {
+    with_db!(db, {
+        db.execute(
+            "INSERT OR IGNORE INTO lan_message_outbox (message_uuid, peer_device_id, status)
+             VALUES (?, ?, 'pending')",
+            params![message_uuid, peer_device_id],
+        )
+        .map_err(|e| e.to_string())?;
+
+        Ok(())
+    })
+}
+
+/// 获取发往指定设备的待投递消息
+pub fn get_pending_lan_messages(peer_device_id: &str) -> Result<Vec<LocalMessage>, String> {
+    with_db!(db, {
+        let mut stmt = db
+            .prepare(
+                "SELECT m.message_uuid, m.conversation_id, m.conversation_type, m.sender_id, 
+                 m.sender_name, m.sender_avatar, m.content, m.content_type, m.file_uuid, m.file_url, 
+                 m.file_size, m.file_hash, m.image_width, m.image_height, m.seq, m.reply_to, 
+                 m.is_recalled, m.is_deleted, m.send_time, m.created_at
+                 FROM lan_message_outbox o
+                 JOIN messages m ON m.message_uuid = o.message_uuid
+                 WHERE o.peer_device_id = ? AND o.status = 'pending' AND m.is_deleted = 0
+                 ORDER BY m.send_time ASC",
+            )
+            .map_err(|e| e.to_string())?;
+
+        let rows = stmt
+            .query_map([peer_device_id], |row| {
+                Ok(LocalMessage {
+                    message_uuid: row.get(0)?,
+                    conversation_id: row.get(1)?,
+                    conversation_type: row.get(2)?,
+                    sender_id: row.get(3)?,
+                    sender_name: row.get(4)?,
+                    sender_avatar: row.get(5)?,
+                    content: row.get(6)?,
+                    content_type: row.get(7)?,
+                    file_uuid: row.get(8)?,
+                    file_url: row.get(9)?,
+                    file_size: row.get(10)?,
+                    file_hash: row.get(11)?,
+                    image_width: row.get(12)?,
+                    image_height: row.get(13)?,
+                    seq: row.get(14)?,
+                    reply_to: row.get(15)?,
+                    is_recalled: row.get::<_, i64>(16)? != 0,
+                    is_deleted: row.get::<_, i64>(17)? != 0,
+                    send_time: row.get(18)?,
+                    created_at: row.get(19)?,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+
+        let mut messages = Vec::new();
+        for row in rows {
+            messages.push(row.map_err(|e| e.to_string())?);
+        }
+
+        Ok(messages)
+    })
+}
+
+/// 记录一次投递尝试
+pub fn record_lan_delivery_attempt(message_uuid: &str) -> Result<(), String> {
+    with_db!(db, {
+        db.execute(
+            "UPDATE lan_message_outbox SET attempts = attempts + 1, last_attempt_at = datetime('now')
+             WHERE message_uuid = ?",
+            params![message_uuid],
+        )
+        .map_err(|e| e.to_string())?;
+
+        Ok(())
+    })
+}
+
+/// 标记消息已送达
+pub fn mark_lan_message_delivered(message_uuid: &str) -> Result<(), String> {
+    with_db!(db, {
+        db.execute(
+            "UPDATE lan_message_outbox SET status = 'delivered', delivered_at = datetime('now')
+             WHERE message_uuid = ?",
+            params![message_uuid],
+        )
+        .map_err(|e| e.to_string())?;
+
+        Ok(())
+    })
+}
+
+/// 查询单条消息的投递状态
+pub fn get_lan_delivery(message_uuid: &str) -> Result<Option<LocalLanDelivery>, String> {
+    with_db!(db, {
+        let mut stmt = db
+            .prepare(
+                "SELECT message_uuid, peer_device_id, status, attempts, last_attempt_at, 
+                 delivered_at, created_at
+                 FROM lan_message_outbox WHERE message_uuid = ?",
+            )
+            .map_err(|e| e.to_string())?;
+
+        let result = stmt
+            .query_row([message_uuid], |row| {
+                Ok(LocalLanDelivery {
+                    message_uuid: row.get(0)?,
+                    peer_device_id: row.get(1)?,
+                    status: row.get(2)?,
+                    attempts: row.get(3)?,
+                    last_attempt_at: row.get(4)?,
+                    delivered_at: row.get(5)?,
+                    created_at: row.get(6)?,
+                })
+            })
+            .ok();
+
+        Ok(result)
+    })
+}
@@ -6,6 +6,7 @@
 //! - `save_messages`: 批量保存消息（使用事务）
 //! - `mark_message_recalled`: 标记消息为已撤回
 //! - `mark_message_deleted`: 标记消息为已删除（软删除）
+//! - `message_exists`: 检查消息是否已保存（用于去重）
 //!
 //! ## 消息排序
 //!
@@ -213,3 +214,18 @@ pub fn mark_message_deleted(message_uuid: &str) -> Result<(), String> {
         Ok(())
     })
 }
+
+/// 检查消息是否已保存
+pub fn message_exists(message_uuid: &str) -> Result<bool, String> {
+    with_db!(db, {
+        let count: i64 = db
+            .query_row(
+                "SELECT COUNT(*) FROM messages WHERE message_uuid = ?",
+                params![message_uuid],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+
+        Ok(count > 0)
+    })
+}
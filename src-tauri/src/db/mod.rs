@@ -9,6 +9,7 @@
 //! - `conversations`: 会话操作（增删改查、未读数管理）
 //! - `messages`: 消息操作（增删改查、撤回、批量保存）
 //! - `files`: 文件映射操作（hash->path 映射、uuid->hash 映射）
+//! - `lan_messages`: 局域网离线聊天的投递队列（待投递消息、送达回执）
 //!
 //! ## 数据库路径
 //!
@@ -25,6 +26,7 @@
 //! ## 重构记录
 //!
 //! - 2024-12: 从单文件 `database.rs` 拆分为模块化结构
+//! - 2026-10: 会话类型增加 `lan`（局域网离线聊天），旧数据库启动时自动重建表以更新约束
 
 use once_cell::sync::Lazy;
 use parking_lot::Mutex;
@@ -37,6 +39,7 @@ use crate::user_data;
 pub mod contacts;
 pub mod conversations;
 pub mod files;
+pub mod lan_messages;
 pub mod messages;
 pub mod types;
 
@@ -44,9 +47,10 @@ pub mod types;
 pub use contacts::*;
 pub use conversations::*;
 pub use files::{
-    delete_file_mapping, get_file_hash_by_uuid, get_file_mapping, save_file_mapping,
-    save_file_uuid_hash, update_file_mapping_verified,
+    delete_file_mapping, get_file_hash_by_uuid, get_file_mapping, is_file_shared_with_user,
+    save_file_mapping, save_file_uuid_hash, update_file_mapping_verified,
 };
+pub use lan_messages::*;
 pub use messages::*;
 pub use types::*;
 
@@ -77,13 +81,60 @@ macro_rules! with_db {
 // 在模块内部重新导出宏
 pub use with_db;
 
+// ============================================================================
+// 表结构
+// ============================================================================
+
+/// 会话表
+///
+/// type: friend（好友）、group（群聊）、lan（局域网离线聊天）
+const CREATE_CONVERSATIONS_TABLE: &str = "CREATE TABLE IF NOT EXISTS conversations (
+    id TEXT PRIMARY KEY,
+    type TEXT NOT NULL CHECK(type IN ('friend', 'group', 'lan')),
+    name TEXT NOT NULL,
+    avatar_url TEXT,
+    last_message TEXT,
+    last_message_time TEXT,
+    last_seq INTEGER NOT NULL DEFAULT 0,
+    unread_count INTEGER NOT NULL DEFAULT 0,
+    is_muted INTEGER NOT NULL DEFAULT 0,
+    is_pinned INTEGER NOT NULL DEFAULT 0,
+    updated_at TEXT NOT NULL,
+    synced_at TEXT
+)";
+
+/// 消息表
+const CREATE_MESSAGES_TABLE: &str = "CREATE TABLE IF NOT EXISTS messages (
+    message_uuid TEXT PRIMARY KEY,
+    conversation_id TEXT NOT NULL,
+    conversation_type TEXT NOT NULL CHECK(conversation_type IN ('friend', 'group', 'lan')),
+    sender_id TEXT NOT NULL,
+    sender_name TEXT,
+    sender_avatar TEXT,
+    content TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    file_uuid TEXT,
+    file_url TEXT,
+    file_size INTEGER,
+    file_hash TEXT,
+    image_width INTEGER,
+    image_height INTEGER,
+    seq INTEGER NOT NULL,
+    reply_to TEXT,
+    is_recalled INTEGER NOT NULL DEFAULT 0,
+    is_deleted INTEGER NOT NULL DEFAULT 0,
+    send_time TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (conversation_id) REFERENCES conversations(id)
+)";
+
 /// 初始化数据库连接并创建表
 pub fn init_database() -> Result<(), String> {
     let mut db_guard = DB.lock();
 
     // 如果已有连接，先关闭（可能是切换用户）
     if db_guard.is_some() {
-        println!("[DB] 关闭现有数据库连接");
+        log::info!("[DB] 关闭现有数据库连接");
         *db_guard = None;
     }
 
@@ -95,62 +146,31 @@ pub fn init_database() -> Result<(), String> {
             .map_err(|e| format!("创建数据库目录失败: {}", e))?;
     }
 
-    println!("[DB] 初始化数据库: {:?}", db_path);
+    log::info!("[DB] 初始化数据库: {:?}", db_path);
 
     let conn = Connection::open(&db_path).map_err(|e| format!("打开数据库失败: {}", e))?;
 
+    create_tables(&conn)?;
+
+    *db_guard = Some(conn);
+    log::info!("[DB] 数据库初始化完成");
+
+    Ok(())
+}
+
+/// 创建表结构并执行迁移（已存在的表和列会跳过）
+fn create_tables(conn: &Connection) -> Result<(), String> {
     // 启用外键约束
     conn.execute_batch("PRAGMA foreign_keys = ON;")
         .map_err(|e| format!("启用外键失败: {}", e))?;
 
     // 创建会话表
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS conversations (
-            id TEXT PRIMARY KEY,
-            type TEXT NOT NULL CHECK(type IN ('friend', 'group')),
-            name TEXT NOT NULL,
-            avatar_url TEXT,
-            last_message TEXT,
-            last_message_time TEXT,
-            last_seq INTEGER NOT NULL DEFAULT 0,
-            unread_count INTEGER NOT NULL DEFAULT 0,
-            is_muted INTEGER NOT NULL DEFAULT 0,
-            is_pinned INTEGER NOT NULL DEFAULT 0,
-            updated_at TEXT NOT NULL,
-            synced_at TEXT
-        )",
-        [],
-    )
-    .map_err(|e| format!("创建 conversations 表失败: {}", e))?;
+    conn.execute(CREATE_CONVERSATIONS_TABLE, [])
+        .map_err(|e| format!("创建 conversations 表失败: {}", e))?;
 
     // 创建消息表
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS messages (
-            message_uuid TEXT PRIMARY KEY,
-            conversation_id TEXT NOT NULL,
-            conversation_type TEXT NOT NULL CHECK(conversation_type IN ('friend', 'group')),
-            sender_id TEXT NOT NULL,
-            sender_name TEXT,
-            sender_avatar TEXT,
-            content TEXT NOT NULL,
-            content_type TEXT NOT NULL,
-            file_uuid TEXT,
-            file_url TEXT,
-            file_size INTEGER,
-            file_hash TEXT,
-            image_width INTEGER,
-            image_height INTEGER,
-            seq INTEGER NOT NULL,
-            reply_to TEXT,
-            is_recalled INTEGER NOT NULL DEFAULT 0,
-            is_deleted INTEGER NOT NULL DEFAULT 0,
-            send_time TEXT NOT NULL,
-            created_at TEXT NOT NULL DEFAULT (datetime('now')),
-            FOREIGN KEY (conversation_id) REFERENCES conversations(id)
-        )",
-        [],
-    )
-    .map_err(|e| format!("创建 messages 表失败: {}", e))?;
+    conn.execute(CREATE_MESSAGES_TABLE, [])
+        .map_err(|e| format!("创建 messages 表失败: {}", e))?;
 
     // 迁移：添加 image_width 和 image_height 列（旧数据库兼容）
     conn.execute("ALTER TABLE messages ADD COLUMN image_width INTEGER", [])
@@ -158,6 +178,9 @@ pub fn init_database() -> Result<(), String> {
     conn.execute("ALTER TABLE messages ADD COLUMN image_height INTEGER", [])
         .ok();
 
+    // 迁移：会话类型增加 'lan'（局域网离线聊天）
+    migrate_lan_conversation_type(conn)?;
+
     // 创建消息索引
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_messages_conv_seq ON messages(conversation_id, seq)",
@@ -237,6 +260,27 @@ pub fn init_database() -> Result<(), String> {
     )
     .map_err(|e| format!("创建 avatars 表失败: {}", e))?;
 
+    // 创建局域网消息投递队列表（离线聊天的待投递消息与回执状态）
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS lan_message_outbox (
+            message_uuid TEXT PRIMARY KEY,
+            peer_device_id TEXT NOT NULL,
+            status TEXT NOT NULL CHECK(status IN ('pending', 'delivered')),
+            attempts INTEGER NOT NULL DEFAULT 0,
+            last_attempt_at TEXT,
+            delivered_at TEXT,
+            created_at TEXT NOT NULL DEFAULT (datetime('now'))
+        )",
+        [],
+    )
+    .map_err(|e| format!("创建 lan_message_outbox 表失败: {}", e))?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_lan_outbox_peer ON lan_message_outbox(peer_device_id, status)",
+        [],
+    )
+    .ok();
+
     // 创建好友表
     conn.execute(
         "CREATE TABLE IF NOT EXISTS friends (
@@ -268,12 +312,75 @@ pub fn init_database() -> Result<(), String> {
     )
     .map_err(|e| format!("创建 groups 表失败: {}", e))?;
 
-    *db_guard = Some(conn);
-    println!("[DB] 数据库初始化完成");
-
     Ok(())
 }
 
+/// 迁移：重建 conversations/messages 表，使类型约束支持 'lan'
+///
+/// SQLite 不支持修改 CHECK 约束，只能重命名旧表 → 创建新表 → 复制数据 → 删除旧表。
+/// 新建的数据库已包含 'lan'，会直接跳过。
+fn migrate_lan_conversation_type(conn: &Connection) -> Result<(), String> {
+    let schema: String = conn
+        .query_row(
+            "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'conversations'",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("读取 conversations 表结构失败: {}", e))?;
+
+    if schema.contains("'lan'") {
+        return Ok(());
+    }
+
+    log::info!("[DB] 迁移会话类型约束（添加 lan）");
+
+    // legacy_alter_table: 重命名时不改写 messages 中指向 conversations 的外键
+    conn.execute_batch(
+        "PRAGMA foreign_keys = OFF;
+         PRAGMA legacy_alter_table = ON;",
+    )
+    .map_err(|e| e.to_string())?;
+
+    let result = conn.execute_batch(&format!(
+        "BEGIN;
+         ALTER TABLE messages RENAME TO messages_old;
+         ALTER TABLE conversations RENAME TO conversations_old;
+         {CREATE_CONVERSATIONS_TABLE};
+         {CREATE_MESSAGES_TABLE};
+         INSERT INTO conversations
+             (id, type, name, avatar_url, last_message, last_message_time, last_seq,
+              unread_count, is_muted, is_pinned, updated_at, synced_at)
+         SELECT id, type, name, avatar_url, last_message, last_message_time, last_seq,
+                unread_count, is_muted, is_pinned, updated_at, synced_at
+         FROM conversations_old;
+         INSERT INTO messages
+             (message_uuid, conversation_id, conversation_type, sender_id, sender_name,
+              sender_avatar, content, content_type, file_uuid, file_url, file_size,
+              file_hash, image_width, image_height, seq, reply_to, is_recalled, is_deleted,
+              send_time, created_at)
+         SELECT message_uuid, conversation_id, conversation_type, sender_id, sender_name,
+                sender_avatar, content, content_type, file_uuid, file_url, file_size,
+                file_hash, image_width, image_height, seq, reply_to, is_recalled, is_deleted,
+                send_time, created_at
+         FROM messages_old;
+         DROP TABLE messages_old;
+         DROP TABLE conversations_old;
+         COMMIT;"
+    ));
+
+    if result.is_err() {
+        conn.execute_batch("ROLLBACK;").ok();
+    }
+
+    conn.execute_batch(
+        "PRAGMA legacy_alter_table = OFF;
+         PRAGMA foreign_keys = ON;",
+    )
+    .map_err(|e| e.to_string())?;
+
+    result.map_err(|e| format!("迁移会话类型约束失败: {}", e))
+}
+
 // ============================================================================
 // 清理操作
 // ============================================================================
@@ -281,10 +388,13 @@ pub fn init_database() -> Result<(), String> {
 /// 仅清空消息缓存
 pub fn clear_messages() -> Result<(), String> {
     with_db!(db, {
-        db.execute_batch("DELETE FROM messages;")
-            .map_err(|e| e.to_string())?;
+        db.execute_batch(
+            "DELETE FROM lan_message_outbox;
+             DELETE FROM messages;",
+        )
+        .map_err(|e| e.to_string())?;
 
-        println!("[DB] 已清空消息缓存");
+        log::info!("[DB] 已清空消息缓存");
         Ok(())
     })
 }
@@ -293,7 +403,8 @@ pub fn clear_messages() -> Result<(), String> {
 pub fn clear_all_data() -> Result<(), String> {
     with_db!(db, {
         db.execute_batch(
-            "DELETE FROM messages;
+            "DELETE FROM lan_message_outbox;
+             DELETE FROM messages;
              DELETE FROM conversations;
              DELETE FROM file_mappings;
              DELETE FROM file_uuid_hash;
@@ -303,7 +414,135 @@ pub fn clear_all_data() -> Result<(), String> {
         )
         .map_err(|e| e.to_string())?;
 
-        println!("[DB] 已清空所有本地数据");
+        log::info!("[DB] 已清空所有本地数据");
         Ok(())
     })
-}
\ No newline at end of file
+}
+
+// ============================================================================
+// 测试辅助
+// ============================================================================
+
+/// 测试间共享全局数据库的锁（依赖全局 DB 状态的测试都需要持有）
+#[cfg(test)]
+pub(crate) static TEST_DB_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// 测试用内存数据库：持有期间全局 DB 为包含完整表结构的内存数据库，离开时恢复为未初始化
+#[cfg(test)]
+pub(crate) struct TestDatabase {
+    _lock: parking_lot::MutexGuard<'static, ()>,
+}
+
+#[cfg(test)]
+impl TestDatabase {
+    pub(crate) fn open() -> Self {
+        let lock = TEST_DB_LOCK.lock();
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        *DB.lock() = Some(conn);
+        Self { _lock: lock }
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestDatabase {
+    fn drop(&mut self) {
+        *DB.lock() = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lan_type_migration_keeps_existing_rows() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("PRAGMA foreign_keys = ON;").unwrap();
+
+        // 旧版本的表结构：类型约束不包含 'lan'
+        let old_conversations = CREATE_CONVERSATIONS_TABLE.replace(", 'lan'", "");
+        let old_messages = CREATE_MESSAGES_TABLE.replace(", 'lan'", "");
+        assert!(!old_conversations.contains("'lan'"));
+        conn.execute_batch(&format!("{old_conversations}; {old_messages};"))
+            .unwrap();
+
+        conn.execute(
+            "INSERT INTO conversations (id, type, name, last_seq, unread_count, updated_at)
+             VALUES ('friend-1', 'friend', 'Alice', 2, 1, '2026-01-01T00:00:00Z')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO messages
+                 (message_uuid, conversation_id, conversation_type, sender_id, content,
+                  content_type, image_width, seq, send_time)
+             VALUES ('msg-1', 'friend-1', 'friend', 'alice', 'hello', 'text', 640, 2,
+                     '2026-01-01T00:00:00Z')",
+            [],
+        )
+        .unwrap();
+        assert!(conn
+            .execute(
+                "INSERT INTO conversations (id, type, name, updated_at)
+                 VALUES ('lan-x', 'lan', 'x', 'now')",
+                [],
+            )
+            .is_err());
+
+        migrate_lan_conversation_type(&conn).unwrap();
+        // 已迁移的数据库再次调用直接跳过
+        migrate_lan_conversation_type(&conn).unwrap();
+
+        let (name, last_seq, unread): (String, i64, i64) = conn
+            .query_row(
+                "SELECT name, last_seq, unread_count FROM conversations WHERE id = 'friend-1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!((name.as_str(), last_seq, unread), ("Alice", 2, 1));
+
+        let (content, width): (String, Option<i64>) = conn
+            .query_row(
+                "SELECT content, image_width FROM messages WHERE message_uuid = 'msg-1'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((content.as_str(), width), ("hello", Some(640)));
+
+        // 旧表已删除，外键仍指向新的 conversations 表
+        let leftovers: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE name LIKE '%_old'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(leftovers, 0);
+        let messages_schema: String = conn
+            .query_row(
+                "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'messages'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(messages_schema.contains("REFERENCES conversations(id)"));
+
+        conn.execute(
+            "INSERT INTO conversations (id, type, name, updated_at)
+             VALUES ('lan-x', 'lan', 'x', 'now')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO messages
+                 (message_uuid, conversation_id, conversation_type, sender_id, content,
+                  content_type, seq, send_time)
+             VALUES ('msg-2', 'lan-x', 'lan', 'x', 'hi', 'text', 1, 'now')",
+            [],
+        )
+        .unwrap();
+    }
+}
@@ -4,6 +4,7 @@
 //! - `LocalConversation`: 本地会话记录
 //! - `LocalMessage`: 本地消息记录
 //! - `LocalFileMapping`: 本地文件映射（hash -> 本地路径）
+//! - `LocalLanDelivery`: 局域网消息投递状态
 //!
 //! 所有类型都实现了 Serialize/Deserialize，可通过 Tauri Commands 传输
 
@@ -102,3 +103,17 @@ pub struct LocalGroup {
     pub created_at: String,
     pub updated_at: Option<String>,
 }
+
+/// 局域网消息投递状态
+///
+/// status: pending（等待对方上线）、delivered（对方已确认收到）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalLanDelivery {
+    pub message_uuid: String,
+    pub peer_device_id: String,
+    pub status: String,
+    pub attempts: i64,
+    pub last_attempt_at: Option<String>,
+    pub delivered_at: Option<String>,
+    pub created_at: Option<String>,
+}
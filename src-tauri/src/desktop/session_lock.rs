@@ -156,7 +156,7 @@ pub fn check_session_lock(
     if !process_alive {
         // 进程已死，清理锁文件
         let _ = fs::remove_file(&lock_path);
-        println!("[SessionLock] 清理无效锁文件: {:?}", lock_path);
+        log::info!("[SessionLock] 清理无效锁文件: {:?}", lock_path);
         return Ok(SessionCheckResult {
             exists: false,
             process_alive: false,
@@ -164,7 +164,7 @@ pub fn check_session_lock(
         });
     }
 
-    println!(
+    log::info!(
         "[SessionLock] 检测到冲突: {} @ {}, PID: {}",
         user_id, server_url, lock.pid
     );
@@ -212,7 +212,7 @@ pub fn create_session_lock(
 
     fs::write(&lock_path, content).map_err(|e| format!("写入锁文件失败: {}", e))?;
 
-    println!(
+    log::info!(
         "[SessionLock] 创建会话锁: {} @ {}, PID: {}",
         user_id, server_url, lock.pid
     );
@@ -236,7 +236,7 @@ pub fn remove_session_lock(
 
     if lock_path.exists() {
         fs::remove_file(&lock_path).map_err(|e| format!("删除锁文件失败: {}", e))?;
-        println!(
+        log::info!(
             "[SessionLock] 移除会话锁: {} @ {}",
             user_id, server_url
         );
@@ -253,7 +253,7 @@ pub fn remove_session_lock(
 ///
 /// - `pid`: 目标进程 ID
 pub fn activate_existing_instance(pid: u32) -> Result<(), String> {
-    println!("[SessionLock] 尝试激活 PID {} 的窗口", pid);
+    log::info!("[SessionLock] 尝试激活 PID {} 的窗口", pid);
 
     #[cfg(target_os = "windows")]
     {
@@ -348,7 +348,7 @@ pub fn cleanup_stale_locks(app: &AppHandle) -> Result<(), String> {
     }
 
     if cleaned > 0 {
-        println!("[SessionLock] 清理了 {} 个过期锁文件", cleaned);
+        log::info!("[SessionLock] 清理了 {} 个过期锁文件", cleaned);
     }
 
     Ok(())
@@ -25,15 +25,15 @@ pub fn get_mac_address_cmd() -> Option<String> {
                 .map(|b| format!("{:02X}", b))
                 .collect::<Vec<_>>()
                 .join(":");
-            println!("[DeviceInfo] MAC 地址: {}", mac);
+            log::info!("[DeviceInfo] MAC 地址: {}", mac);
             Some(mac)
         }
         Ok(None) => {
-            println!("[DeviceInfo] 未找到网络接口");
+            log::info!("[DeviceInfo] 未找到网络接口");
             None
         }
         Err(e) => {
-            println!("[DeviceInfo] 获取 MAC 地址失败: {}", e);
+            log::error!("[DeviceInfo] 获取 MAC 地址失败: {}", e);
             None
         }
     }
@@ -6,6 +6,7 @@
 //! - 大文件优化：≥阈值的文件不复制，记录原始路径
 //! - 检查文件缓存状态（支持 local_path 和 original_path 回退）
 //! - 在系统文件管理器中显示本地文件
+//! - 局域网优先：好友设备在同一局域网且持有该文件时点对点获取，失败回退服务器
 //!
 //! ## 大文件处理策略
 //!
@@ -78,6 +79,7 @@ pub struct DownloadProgress {
 /// - `file_name`: 原始文件名
 /// - `file_type`: 文件类型 ("image" | "video" | "document")
 /// - `file_size`: 文件大小（可选，用于进度计算）
+/// - `sender_id`: 消息发送者 ID（可选，局域网直传时优先尝试其设备）
 /// - `window`: Tauri 窗口（用于发送进度事件）
 ///
 /// # 返回
@@ -90,13 +92,14 @@ pub async fn download_and_save_file(
     file_name: String,
     file_type: String,
     file_size: Option<u64>,
+    sender_id: Option<String>,
     window: Window,
 ) -> Result<String, String> {
     // 1. 检查是否已有本地缓存
     if let Ok(Some(mapping)) = db::get_file_mapping(&file_hash) {
         // 验证文件是否存在
         if std::path::Path::new(&mapping.local_path).exists() {
-            println!("[Download] 文件已缓存: {}", mapping.local_path);
+            log::info!("[Download] 文件已缓存: {}", mapping.local_path);
             return Ok(mapping.local_path);
         }
     }
@@ -126,7 +129,7 @@ pub async fn download_and_save_file(
     let local_path = save_dir.join(&local_filename);
     let local_path_str = local_path.to_string_lossy().to_string();
 
-    println!("[Download] 开始下载: {} -> {}", file_name, local_path_str);
+    log::info!("[Download] 开始下载: {} -> {}", file_name, local_path_str);
 
     // 5. 发送开始事件
     let _ = window.emit(
@@ -140,9 +143,104 @@ pub async fn download_and_save_file(
         },
     );
 
-    // 6. 使用全局 HTTP Client 发起下载请求（复用连接池）
+    // 6. 局域网优先：同一局域网内的好友设备持有该文件时直接获取
+    let lan_result = {
+        let window = window.clone();
+        let hash = file_hash.clone();
+        let mut last_emit_percent: f64 = 0.0;
+        let mut on_progress = move |downloaded: u64, total: u64| {
+            let percent = if total > 0 {
+                (downloaded as f64 / total as f64) * 100.0
+            } else {
+                0.0
+            };
+            if percent - last_emit_percent >= 1.0 || downloaded == total {
+                last_emit_percent = percent;
+                let _ = window.emit(
+                    "download-progress",
+                    DownloadProgress {
+                        file_hash: hash.clone(),
+                        downloaded,
+                        total,
+                        percent,
+                        status: "downloading".to_string(),
+                    },
+                );
+            }
+        };
+
+        crate::lan_transfer::attachments::fetch_attachment(
+            &file_hash,
+            sender_id.as_deref(),
+            file_size,
+            &local_path,
+            &mut on_progress,
+        )
+        .await
+    };
+
+    // 7. 回退到服务器下载
+    let (downloaded, total_size, content_type) = match lan_result {
+        Ok(downloaded) => {
+            let content_type = mime_guess::from_path(&file_name)
+                .first_or_octet_stream()
+                .to_string();
+            (downloaded, downloaded, content_type)
+        }
+        Err(e) => {
+            log::info!("[Download] 局域网获取不可用（{}），从服务器下载", e);
+            download_from_server(&url, &file_hash, file_size, &local_path, &window).await?
+        }
+    };
+
+    // 8. 保存文件映射到数据库
+    let now = chrono::Utc::now().to_rfc3339();
+    db::save_file_mapping(db::LocalFileMapping {
+        file_hash: file_hash.clone(),
+        local_path: local_path_str.clone(),
+        original_path: None, // 下载的文件不需要原始路径
+        is_large_file: false, // 下载的文件都缓存到本地
+        file_size: downloaded as i64,
+        file_name: file_name.clone(),
+        content_type,
+        source: "downloaded".to_string(),
+        last_verified: now,
+        created_at: None,
+    })?;
+
+    // 9. 发送完成事件
+    let _ = window.emit(
+        "download-progress",
+        DownloadProgress {
+            file_hash: file_hash.clone(),
+            downloaded,
+            total: total_size,
+            percent: 100.0,
+            status: "completed".to_string(),
+        },
+    );
+
+    log::info!(
+        "[Download] 下载完成: {} ({} bytes)",
+        local_path_str, downloaded
+    );
+
+    Ok(local_path_str)
+}
+
+/// 从服务器下载文件到指定路径
+///
+/// 返回 (已下载字节数, 总字节数, 内容类型)
+async fn download_from_server(
+    url: &str,
+    file_hash: &str,
+    file_size: Option<u64>,
+    local_path: &std::path::Path,
+    window: &Window,
+) -> Result<(u64, u64, String), String> {
+    // 使用全局 HTTP Client 发起下载请求（复用连接池）
     let response = HTTP_CLIENT
-        .get(&url)
+        .get(url)
         .send()
         .await
         .map_err(|e| format!("请求失败: {}", e))?;
@@ -160,8 +258,8 @@ pub async fn download_and_save_file(
         .unwrap_or("application/octet-stream")
         .to_string();
 
-    // 7. 异步流式写入文件（使用 8MB 缓冲区优化 IO 性能）
-    let file = tokio::fs::File::create(&local_path)
+    // 异步流式写入文件（使用 8MB 缓冲区优化 IO 性能）
+    let file = tokio::fs::File::create(local_path)
         .await
         .map_err(|e| format!("创建文件失败: {}", e))?;
     let mut writer = tokio::io::BufWriter::with_capacity(DOWNLOAD_BUFFER_SIZE, file);
@@ -192,7 +290,7 @@ pub async fn download_and_save_file(
             let _ = window.emit(
                 "download-progress",
                 DownloadProgress {
-                    file_hash: file_hash.clone(),
+                    file_hash: file_hash.to_string(),
                     downloaded,
                     total: total_size,
                     percent,
@@ -208,39 +306,7 @@ pub async fn download_and_save_file(
         .await
         .map_err(|e| format!("刷新缓冲区失败: {}", e))?;
 
-    // 8. 保存文件映射到数据库
-    let now = chrono::Utc::now().to_rfc3339();
-    db::save_file_mapping(db::LocalFileMapping {
-        file_hash: file_hash.clone(),
-        local_path: local_path_str.clone(),
-        original_path: None, // 下载的文件不需要原始路径
-        is_large_file: false, // 下载的文件都缓存到本地
-        file_size: downloaded as i64,
-        file_name: file_name.clone(),
-        content_type,
-        source: "downloaded".to_string(),
-        last_verified: now,
-        created_at: None,
-    })?;
-
-    // 9. 发送完成事件
-    let _ = window.emit(
-        "download-progress",
-        DownloadProgress {
-            file_hash: file_hash.clone(),
-            downloaded,
-            total: total_size,
-            percent: 100.0,
-            status: "completed".to_string(),
-        },
-    );
-
-    println!(
-        "[Download] 下载完成: {} ({} bytes)",
-        local_path_str, downloaded
-    );
-
-    Ok(local_path_str)
+    Ok((downloaded, total_size, content_type))
 }
 
 /// 清理文件名中的非法字符
@@ -377,14 +443,14 @@ pub fn copy_file_to_cache(
         // 检查缓存路径
         let existing_path = std::path::Path::new(&mapping.local_path);
         if existing_path.exists() && mapping.local_path.contains(&expected_cache_dir_str) {
-            println!("[CopyCache] 文件已在缓存目录: {}", mapping.local_path);
+            log::info!("[CopyCache] 文件已在缓存目录: {}", mapping.local_path);
             return Ok(mapping.local_path);
         }
         // 检查原始路径（大文件）
         if let Some(ref orig_path) = mapping.original_path
             && std::path::Path::new(orig_path).exists()
         {
-            println!("[CopyCache] 大文件原始路径有效: {}", orig_path);
+            log::info!("[CopyCache] 大文件原始路径有效: {}", orig_path);
             return Ok(orig_path.clone());
         }
     }
@@ -416,7 +482,7 @@ pub fn copy_file_to_cache(
 
     // 6. 大文件处理：不复制，记录原始路径
     if is_large_file {
-        println!(
+        log::info!(
             "[CopyCache] 大文件({}MB)，记录原始路径: {}",
             actual_size / 1024 / 1024,
             source_path
@@ -464,7 +530,7 @@ pub fn copy_file_to_cache(
         created_at: None,
     })?;
 
-    println!(
+    log::info!(
         "[CopyCache] 文件已缓存: {} -> {}",
         source_path, cache_path_str
     );
@@ -0,0 +1,558 @@
+/*!
+ * 局域网附件直传模块
+ *
+ * 聊天附件（图片、视频、文档）优先从同一局域网内持有该文件的好友设备获取，
+ * 无需经过服务器上传再下载
+ *
+ * 流程：
+ * 1. 下载附件前（download::download_and_save_file），按 file_hash 向局域网内的候选设备请求
+ * 2. 持有方校验请求设备与聊天关系，从 file_mappings 找到本地文件并直接返回
+ * 3. 请求方边接收边计算 SHA-256，写入临时文件；与 file_hash 一致才重命名为目标文件，
+ *    不一致时删除临时文件并尝试下一台设备
+ * 4. 所有候选设备都无法提供时，回退到服务器 URL 下载
+ *
+ * 候选设备（按优先级）：
+ * - 消息发送者的设备（调用方提供 sender_id 时）
+ * - 当前账号的其他设备
+ * - 好友的设备
+ *
+ * 授权规则（持有方）：
+ * - 请求设备必须是已发现的设备，且 TCP 来源 IP 与发现的 IP 一致
+ * - 当前账号的其他设备（user_id 来自未认证的 mDNS）还需是信任设备，或与本机有来自该 IP 的活跃连接，
+ *   才直接允许；否则按其他用户处理
+ * - 其他用户：该文件必须出现在与其的好友会话或共同群聊中（db::is_file_shared_with_user）
+ *
+ * 哈希规则（与前端上传时的 calculateSHA256 一致）：
+ * - SHA-256("|size:<文件大小>|" + 数据)
+ * - 不超过 3 × SAMPLE_SIZE 的文件哈希完整内容，更大的文件只哈希开头/中间/结尾各 SAMPLE_SIZE
+ *
+ * 更新日志：
+ * - 2026-10-18: 本账号设备需为信任设备或有活跃连接才跳过聊天关系校验
+ * - 2026-10-18: 获取的附件校验 SHA-256，不一致时丢弃并回退
+ */
+
+use super::config;
+use super::get_lan_transfer_state;
+use super::protocol::DiscoveredDevice;
+use super::server::get_active_peer_connections_map;
+use crate::db;
+use futures_util::StreamExt;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+
+/// 连接候选设备的超时（秒），超时后尝试下一台设备
+const CONNECT_TIMEOUT_SECS: u64 = 2;
+
+/// 附件哈希的采样大小（大文件只哈希开头/中间/结尾各 SAMPLE_SIZE）
+const SAMPLE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// 下载中的临时文件后缀
+const PARTIAL_SUFFIX: &str = ".lanpart";
+
+// ============================================================================
+// 错误类型
+// ============================================================================
+
+#[derive(Error, Debug)]
+pub enum AttachmentError {
+    #[error("未知设备: {0}")]
+    UnknownDevice(String),
+    #[error("无权访问该文件")]
+    Forbidden,
+    #[error("本地没有该文件")]
+    NotFound,
+    #[error("局域网内没有可提供该文件的设备")]
+    NoPeerAvailable,
+    #[error("数据库错误: {0}")]
+    Database(String),
+}
+
+// ============================================================================
+// 持有方：提供附件
+// ============================================================================
+
+/// 可提供的本地附件
+pub struct SharedAttachment {
+    /// 本地文件路径
+    pub path: PathBuf,
+    /// 文件大小
+    pub file_size: u64,
+    /// 内容类型
+    pub content_type: String,
+}
+
+/// 查找可提供给请求设备的附件（由 server 调用）
+pub fn resolve_shared_attachment(
+    file_hash: &str,
+    requester_device_id: &str,
+    peer_ip: &str,
+) -> Result<SharedAttachment, AttachmentError> {
+    let state = get_lan_transfer_state();
+
+    // 请求设备必须是已发现的设备，且 IP 一致（防止伪造 device_id）
+    let requester = {
+        let devices = state.devices.read();
+        devices.get(requester_device_id).cloned()
+    }
+    .filter(|d| d.ip_address == peer_ip)
+    .ok_or_else(|| AttachmentError::UnknownDevice(requester_device_id.to_string()))?;
+
+    if requester.user_id.is_empty() {
+        return Err(AttachmentError::Forbidden);
+    }
+
+    let my_user_id = state
+        .local_device
+        .read()
+        .as_ref()
+        .map(|d| d.user_id.clone())
+        .unwrap_or_default();
+
+    // user_id 来自 mDNS 广播，可被伪造：只有信任设备或已建立连接的设备才视为本账号设备
+    let is_own_device = !my_user_id.is_empty()
+        && requester.user_id == my_user_id
+        && is_verified_device(&requester);
+    if !is_own_device {
+        let conversation_id = friend_conversation_id(&my_user_id, &requester.user_id);
+        let shared = db::is_file_shared_with_user(file_hash, &requester.user_id, &conversation_id)
+            .map_err(AttachmentError::Database)?;
+        if !shared {
+            return Err(AttachmentError::Forbidden);
+        }
+    }
+
+    let mapping = db::get_file_mapping(file_hash)
+        .map_err(AttachmentError::Database)?
+        .ok_or(AttachmentError::NotFound)?;
+
+    // 优先使用缓存路径，回退到原始路径（大文件）
+    let path = [Some(mapping.local_path.clone()), mapping.original_path.clone()]
+        .into_iter()
+        .flatten()
+        .map(PathBuf::from)
+        .find(|p| p.is_file())
+        .ok_or(AttachmentError::NotFound)?;
+
+    let file_size = std::fs::metadata(&path)
+        .map_err(|_| AttachmentError::NotFound)?
+        .len();
+
+    Ok(SharedAttachment {
+        path,
+        file_size,
+        content_type: mapping.content_type,
+    })
+}
+
+/// 设备是否为信任设备，或与本机有来自其发现 IP 的活跃点对点连接
+fn is_verified_device(device: &DiscoveredDevice) -> bool {
+    config::is_device_trusted(&device.device_id)
+        || get_active_peer_connections_map()
+            .lock()
+            .values()
+            .any(|c| {
+                c.peer_device.device_id == device.device_id
+                    && c.peer_device.ip_address == device.ip_address
+            })
+}
+
+// ============================================================================
+// 请求方：获取附件
+// ============================================================================
+
+/// 从局域网内的设备获取附件并保存到 `dest`
+///
+/// 依次尝试候选设备，第一个成功的设备提供文件；返回写入的字节数。
+/// `expected_size` 已知时会校验文件大小，不一致视为失败。
+pub async fn fetch_attachment(
+    file_hash: &str,
+    sender_id: Option<&str>,
+    expected_size: Option<u64>,
+    dest: &Path,
+    on_progress: &mut (dyn FnMut(u64, u64) + Send),
+) -> Result<u64, AttachmentError> {
+    let state = get_lan_transfer_state();
+    let my_device_id = match state.local_device.read().as_ref() {
+        Some(local) => local.device_id.clone(),
+        None => return Err(AttachmentError::NoPeerAvailable),
+    };
+
+    for device in get_candidate_devices(sender_id) {
+        log::info!(
+            "[LanTransfer] 尝试从局域网设备获取附件: {} <- {} ({})",
+            file_hash, device.device_name, device.ip_address
+        );
+
+        match fetch_from_device(&device, &my_device_id, file_hash, expected_size, dest, on_progress)
+            .await
+        {
+            Ok(size) => {
+                log::info!(
+                    "[LanTransfer] ✅ 局域网附件获取成功: {} ({} bytes)",
+                    file_hash, size
+                );
+                return Ok(size);
+            }
+            Err(e) => {
+                log::error!(
+                    "[LanTransfer] 从 {} 获取附件失败: {}",
+                    device.device_name, e
+                );
+            }
+        }
+    }
+
+    Err(AttachmentError::NoPeerAvailable)
+}
+
+/// 从单台设备获取附件
+///
+/// 数据先写入临时文件，哈希与 file_hash 一致时才重命名为 `dest`；
+/// 失败时删除临时文件，不会留下未校验的数据
+async fn fetch_from_device(
+    device: &DiscoveredDevice,
+    my_device_id: &str,
+    file_hash: &str,
+    expected_size: Option<u64>,
+    dest: &Path,
+    on_progress: &mut (dyn FnMut(u64, u64) + Send),
+) -> Result<u64, String> {
+    let mut partial = dest.as_os_str().to_owned();
+    partial.push(PARTIAL_SUFFIX);
+    let partial = PathBuf::from(partial);
+
+    let result = download_verified(
+        device,
+        my_device_id,
+        file_hash,
+        expected_size,
+        &partial,
+        on_progress,
+    )
+    .await;
+
+    match result {
+        Ok(received) => {
+            if let Err(e) = tokio::fs::rename(&partial, dest).await {
+                let _ = tokio::fs::remove_file(&partial).await;
+                return Err(e.to_string());
+            }
+            Ok(received)
+        }
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&partial).await;
+            Err(e)
+        }
+    }
+}
+
+/// 下载附件到临时文件并校验哈希
+async fn download_verified(
+    device: &DiscoveredDevice,
+    my_device_id: &str,
+    file_hash: &str,
+    expected_size: Option<u64>,
+    partial: &Path,
+    on_progress: &mut (dyn FnMut(u64, u64) + Send),
+) -> Result<u64, String> {
+    let url = format!(
+        "http://{}:{}/api/attachment?fileHash={}&deviceId={}",
+        device.ip_address, device.port, file_hash, my_device_id
+    );
+
+    let client = reqwest::Client::builder()
+        .connect_timeout(std::time::Duration::from_secs(CONNECT_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let response = client.get(&url).send().await.map_err(|e| e.to_string())?;
+
+    if !response.status().is_success() {
+        return Err(format!("HTTP {}", response.status()));
+    }
+
+    let total = response.content_length().or(expected_size).unwrap_or(0);
+    if let Some(expected) = expected_size
+        && total != expected
+    {
+        return Err(format!("文件大小不一致: {} != {}", total, expected));
+    }
+
+    let mut file = tokio::fs::File::create(partial)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // 大小已知且会完整哈希时边接收边计算，否则下载完成后从临时文件计算
+    let mut hasher = (total > 0 && total <= SAMPLE_SIZE * 3).then(|| size_prefixed_hasher(total));
+
+    let mut received: u64 = 0;
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| e.to_string())?;
+        file.write_all(&chunk).await.map_err(|e| e.to_string())?;
+        if let Some(hasher) = hasher.as_mut() {
+            hasher.update(&chunk);
+        }
+        received += chunk.len() as u64;
+        on_progress(received, total);
+    }
+
+    file.flush().await.map_err(|e| e.to_string())?;
+    drop(file);
+
+    if total > 0 && received != total {
+        return Err(format!("数据不完整: {} / {}", received, total));
+    }
+
+    let actual = match hasher {
+        Some(hasher) => hex::encode(hasher.finalize()),
+        None => {
+            let partial = partial.to_path_buf();
+            tokio::task::spawn_blocking(move || attachment_hash(&partial))
+                .await
+                .map_err(|e| e.to_string())?
+                .map_err(|e| e.to_string())?
+        }
+    };
+
+    if !actual.eq_ignore_ascii_case(file_hash) {
+        return Err(format!("哈希不一致: {} != {}", actual, file_hash));
+    }
+
+    Ok(received)
+}
+
+/// 以 "|size:<文件大小>|" 开头的 SHA-256 计算器
+fn size_prefixed_hasher(file_size: u64) -> Sha256 {
+    let mut hasher = Sha256::new();
+    hasher.update(format!("|size:{}|", file_size).as_bytes());
+    hasher
+}
+
+/// 计算本地文件的附件哈希（规则见模块说明）
+fn attachment_hash(path: &Path) -> std::io::Result<String> {
+    let mut file = File::open(path)?;
+    let file_size = file.metadata()?.len();
+    let mut hasher = size_prefixed_hasher(file_size);
+
+    if file_size <= SAMPLE_SIZE * 3 {
+        std::io::copy(&mut file, &mut hasher)?;
+    } else {
+        let middle = (file_size - SAMPLE_SIZE) / 2;
+        for offset in [0, middle, file_size - SAMPLE_SIZE] {
+            file.seek(SeekFrom::Start(offset))?;
+            std::io::copy(&mut (&mut file).take(SAMPLE_SIZE), &mut hasher)?;
+        }
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// 获取候选设备（发送者设备 → 本账号其他设备 → 好友设备）
+fn get_candidate_devices(sender_id: Option<&str>) -> Vec<DiscoveredDevice> {
+    let state = get_lan_transfer_state();
+    let my_user_id = state
+        .local_device
+        .read()
+        .as_ref()
+        .map(|d| d.user_id.clone())
+        .unwrap_or_default();
+
+    let devices: Vec<DiscoveredDevice> = state
+        .devices
+        .read()
+        .values()
+        .filter(|d| !d.user_id.is_empty())
+        .cloned()
+        .collect();
+
+    let mut candidates: Vec<(u8, DiscoveredDevice)> = devices
+        .into_iter()
+        .filter_map(|d| {
+            let priority = if sender_id == Some(d.user_id.as_str()) {
+                0
+            } else if d.user_id == my_user_id {
+                1
+            } else if matches!(db::get_friend(&d.user_id), Ok(Some(_))) {
+                2
+            } else {
+                return None;
+            };
+            Some((priority, d))
+        })
+        .collect();
+
+    candidates.sort_by_key(|(priority, _)| *priority);
+    candidates.into_iter().map(|(_, d)| d).collect()
+}
+
+/// 好友会话 ID（与前端 getFriendConversationId 一致：两个 ID 按字典序排序）
+fn friend_conversation_id(user_id: &str, friend_id: &str) -> String {
+    let (a, b) = if user_id <= friend_id {
+        (user_id, friend_id)
+    } else {
+        (friend_id, user_id)
+    };
+    format!("conv-{}-{}", a, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+    use uuid::Uuid;
+
+    /// 启动只返回一次固定响应体的 HTTP 服务器，返回端口
+    async fn serve_once(body: &'static [u8]) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await;
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            socket.write_all(header.as_bytes()).await.unwrap();
+            socket.write_all(body).await.unwrap();
+        });
+        port
+    }
+
+    fn device(port: u16) -> DiscoveredDevice {
+        let now = chrono::Utc::now().to_rfc3339();
+        DiscoveredDevice {
+            device_id: "holder".to_string(),
+            device_name: "Holder".to_string(),
+            user_id: "friend".to_string(),
+            user_nickname: "Friend".to_string(),
+            ip_address: "127.0.0.1".to_string(),
+            port,
+            discovered_at: now.clone(),
+            last_seen: now,
+        }
+    }
+
+    fn hash_of(data: &[u8]) -> String {
+        let mut hasher = size_prefixed_hasher(data.len() as u64);
+        hasher.update(data);
+        hex::encode(hasher.finalize())
+    }
+
+    #[tokio::test]
+    async fn fetched_attachment_must_match_file_hash() {
+        let dir = std::env::temp_dir().join(format!("huanvae-attachment-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dest = dir.join("photo.jpg");
+        let partial = dir.join(format!("photo.jpg{}", PARTIAL_SUFFIX));
+        let expected = hash_of(b"original photo");
+
+        // 对方返回了与 file_hash 不一致的内容：丢弃，不留下任何文件
+        let port = serve_once(b"tampered photo").await;
+        let result =
+            fetch_from_device(&device(port), "me", &expected, Some(14), &dest, &mut |_, _| {})
+                .await;
+        assert!(result.unwrap_err().contains("哈希不一致"));
+        assert!(!dest.exists());
+        assert!(!partial.exists());
+
+        // 内容一致：重命名为目标文件
+        let port = serve_once(b"original photo").await;
+        let received =
+            fetch_from_device(&device(port), "me", &expected, Some(14), &dest, &mut |_, _| {})
+                .await
+                .unwrap();
+        assert_eq!(received, 14);
+        assert_eq!(std::fs::read(&dest).unwrap(), b"original photo");
+        assert!(!partial.exists());
+
+        // 下载后计算的哈希与边接收边计算的一致
+        assert_eq!(attachment_hash(&dest).unwrap(), expected);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn own_account_devices_must_be_verified() {
+        use crate::lan_transfer::protocol::{
+            DeviceInfo, PeerConnection, PeerConnectionStatus, PROTOCOL_VERSION,
+        };
+        use crate::lan_transfer::service::{LanTransferService, ServiceOptions};
+
+        let _db = db::TestDatabase::open();
+        let dir = std::env::temp_dir().join(format!("huanvae-attachment-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("photo.jpg");
+        std::fs::write(&file, b"photo").unwrap();
+        let now = chrono::Utc::now().to_rfc3339();
+        db::save_file_mapping(db::LocalFileMapping {
+            file_hash: "hash".to_string(),
+            local_path: file.to_string_lossy().to_string(),
+            original_path: None,
+            is_large_file: false,
+            file_size: 5,
+            file_name: "photo.jpg".to_string(),
+            content_type: "image/jpeg".to_string(),
+            source: "uploaded".to_string(),
+            last_verified: now.clone(),
+            created_at: None,
+        })
+        .unwrap();
+
+        let service = LanTransferService::new(ServiceOptions {
+            enable_mdns: false,
+            data_directory: Some(dir.clone()),
+            ..Default::default()
+        });
+        service.enter(|| {
+            *get_lan_transfer_state().local_device.write() = Some(DeviceInfo {
+                device_id: "me".to_string(),
+                device_name: "Desktop".to_string(),
+                user_id: "me-user".to_string(),
+                user_nickname: "Me".to_string(),
+                ip_address: "127.0.0.1".to_string(),
+                port: 0,
+                version: PROTOCOL_VERSION.to_string(),
+                os: std::env::consts::OS.to_string(),
+            });
+            // 广播的 user_id 与本账号相同，但既不是信任设备也没有连接
+            let mut requester = device(0);
+            requester.user_id = "me-user".to_string();
+            get_lan_transfer_state()
+                .devices
+                .write()
+                .insert(requester.device_id.clone(), requester.clone());
+            assert!(matches!(
+                resolve_shared_attachment("hash", "holder", "127.0.0.1"),
+                Err(AttachmentError::Forbidden)
+            ));
+
+            // 有来自该 IP 的活跃连接：视为本账号设备
+            get_active_peer_connections_map().lock().insert(
+                "conn".to_string(),
+                PeerConnection {
+                    connection_id: "conn".to_string(),
+                    peer_device: requester.clone(),
+                    established_at: now.clone(),
+                    status: PeerConnectionStatus::Connected,
+                    is_initiator: false,
+                },
+            );
+            let shared = resolve_shared_attachment("hash", "holder", "127.0.0.1").unwrap();
+            assert_eq!(shared.path, file);
+
+            // 信任设备：无连接也允许
+            get_active_peer_connections_map().lock().clear();
+            config::add_trusted_device("holder".to_string(), "Holder".to_string()).unwrap();
+            assert!(resolve_shared_attachment("hash", "holder", "127.0.0.1").is_ok());
+        });
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
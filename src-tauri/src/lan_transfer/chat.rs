@@ -0,0 +1,645 @@
+/*!
+ * 局域网聊天模块
+ *
+ * 服务器不可用时（服务器宕机、飞机/酒店等封闭局域网），通过点对点连接收发文本消息
+ *
+ * 功能：
+ * - 消息保存到本地 messages 表（conversation_type = 'lan'），与普通会话一起显示
+ * - 每台对端设备对应一个会话，会话 ID 为 `lan_{device_id}`
+ * - 送达回执：对方保存成功后 HTTP 响应即为回执，本地标记为 delivered
+ * - 投递队列：对方离线或未连接时消息写入 lan_message_outbox 保持 pending，
+ *   设备重新上线或重新建立连接后按发送顺序重发
+ * - 可以向已连接、已发现或已有聊天记录的设备发送消息
+ * - 接收方按 message_uuid 去重，重发不会产生重复消息
+ * - seq 分配、去重、保存消息与加入投递队列在同一个数据库事务中完成（db::append_lan_message），
+ *   并发收发不会产生重复的 seq 或消息
+ *
+ * 投递时机：
+ * - 发送消息时立即尝试投递（先投递更早的待发消息，保证顺序）
+ * - mDNS 重新发现设备时（discovery::handle_mdns_events）：尚未连接且有待发消息时
+ *   自动发起连接请求（对方确认或信任自动接受后投递），同一设备间隔 CONNECT_RETRY_INTERVAL
+ * - 点对点连接建立时（双方都会触发）
+ *
+ * 更新日志：
+ * - 2026-10-18: seq 分配与消息去重改为在同一个数据库事务中完成
+ * - 2026-10-18: 对方离线/未连接时消息也写入投递队列，重新发现设备时自动请求连接后投递
+ */
+
+use super::discovery::get_event_sender;
+use super::protocol::*;
+use super::server::get_active_peer_connections_map;
+use super::service;
+use super::{emit_lan_event, get_lan_transfer_state};
+use crate::db::{self, LocalConversation, LocalMessage};
+use chrono::Utc;
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use uuid::Uuid;
+
+/// 局域网会话类型（conversations.type / messages.conversation_type）
+pub const LAN_CONVERSATION_TYPE: &str = "lan";
+
+/// 单条消息投递超时（秒）
+const DELIVERY_TIMEOUT_SECS: u64 = 5;
+
+/// 为投递队列自动发起连接请求的最小间隔（同一设备）
+const CONNECT_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+// ============================================================================
+// 错误类型
+// ============================================================================
+
+#[derive(Error, Debug)]
+pub enum ChatError {
+    #[error("未知设备（需要已连接、已发现或有聊天记录的设备）: {0}")]
+    UnknownDevice(String),
+    #[error("本地服务未启动")]
+    ServiceNotRunning,
+    #[error("消息无效: {0}")]
+    InvalidMessage(String),
+    #[error("数据库错误: {0}")]
+    Database(String),
+}
+
+// ============================================================================
+// 投递状态
+// ============================================================================
+
+/// 聊天投递状态（每个服务实例一份，见 LanTransferService）
+#[derive(Default)]
+pub(crate) struct ChatState {
+    /// 正在投递队列的设备（防止同一设备并发重发导致乱序）
+    flushing_devices: Arc<Mutex<HashSet<String>>>,
+    /// 为投递队列发起连接请求的时间（设备 ID -> 时间，限制请求频率）
+    connect_requests: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+/// 获取正在投递队列的设备集合
+fn get_flushing_devices() -> Arc<Mutex<HashSet<String>>> {
+    service::current().chat.flushing_devices.clone()
+}
+
+// ============================================================================
+// 发送
+// ============================================================================
+
+/// 获取设备对应的局域网会话 ID
+pub fn lan_conversation_id(device_id: &str) -> String {
+    format!("lan_{}", device_id)
+}
+
+/// 向设备发送聊天消息
+///
+/// 消息先保存到本地并加入投递队列，再立即尝试投递；
+/// 对方不在线或未连接时保持 pending，返回的消息可直接显示在会话中
+pub async fn send_chat_message(device_id: &str, content: String) -> Result<LocalMessage, ChatError> {
+    if content.trim().is_empty() {
+        return Err(ChatError::InvalidMessage("消息内容为空".to_string()));
+    }
+
+    if content.len() > MAX_CHAT_MESSAGE_BYTES {
+        return Err(ChatError::InvalidMessage(format!(
+            "消息过长（{} 字节，上限 {} 字节）",
+            content.len(),
+            MAX_CHAT_MESSAGE_BYTES
+        )));
+    }
+
+    let local_device = get_local_from_device().ok_or(ChatError::ServiceNotRunning)?;
+
+    // 对方离线时也允许发送：已发现的设备或已有会话的设备
+    let peer = find_known_peer(device_id);
+    let conversation_id = lan_conversation_id(device_id);
+    if peer.is_none()
+        && db::get_conversation(&conversation_id)
+            .map_err(ChatError::Database)?
+            .is_none()
+    {
+        return Err(ChatError::UnknownDevice(device_id.to_string()));
+    }
+
+    let now = Utc::now().to_rfc3339();
+    let message = LocalMessage {
+        message_uuid: Uuid::new_v4().to_string(),
+        conversation_id,
+        conversation_type: LAN_CONVERSATION_TYPE.to_string(),
+        sender_id: local_device.user_id.clone(),
+        sender_name: Some(local_device.user_nickname.clone()),
+        sender_avatar: None,
+        content,
+        content_type: "text".to_string(),
+        file_uuid: None,
+        file_url: None,
+        file_size: None,
+        file_hash: None,
+        image_width: None,
+        image_height: None,
+        seq: 0,
+        reply_to: None,
+        is_recalled: false,
+        is_deleted: false,
+        send_time: now,
+        created_at: None,
+    };
+
+    let message = db::append_lan_message(
+        &new_conversation(device_id, peer.as_ref()),
+        message,
+        Some(device_id),
+        false,
+    )
+    .map_err(ChatError::Database)?
+    .ok_or_else(|| ChatError::Database("消息 ID 重复".to_string()))?;
+
+    // 按顺序投递队列（包含本条消息）
+    flush_outbox(device_id).await;
+
+    let delivered = db::get_lan_delivery(&message.message_uuid)
+        .map_err(ChatError::Database)?
+        .is_some_and(|d| d.status == "delivered");
+
+    if !delivered {
+        log::info!(
+            "[LanTransfer] 聊天消息暂未送达，已加入投递队列: {} -> {}",
+            message.message_uuid, device_id
+        );
+
+        let event = LanTransferEvent::LanChatMessageQueued {
+            conversation_id: message.conversation_id.clone(),
+            message_id: message.message_uuid.clone(),
+        };
+        let _ = get_event_sender().send(event.clone());
+        emit_lan_event(&event);
+    }
+
+    Ok(message)
+}
+
+/// 投递发往指定设备的待发消息
+///
+/// 按发送时间顺序逐条投递，遇到失败即停止（保证对方收到的顺序与发送顺序一致）
+pub async fn flush_outbox(device_id: &str) {
+    // 同一设备同时只允许一个投递任务
+    {
+        let flushing = get_flushing_devices();
+        let mut flushing = flushing.lock();
+        if !flushing.insert(device_id.to_string()) {
+            return;
+        }
+    }
+
+    do_flush_outbox(device_id).await;
+
+    let flushing = get_flushing_devices();
+    flushing.lock().remove(device_id);
+}
+
+/// 实际执行队列投递的内部函数
+///
+/// 尚未连接时为队列发起连接请求，连接建立后（transfer / server 中）会再次投递
+async fn do_flush_outbox(device_id: &str) {
+    let Some(from_device) = get_local_from_device() else {
+        return;
+    };
+
+    let pending = match db::get_pending_lan_messages(device_id) {
+        Ok(pending) => pending,
+        Err(e) => {
+            log::error!("[LanTransfer] 读取聊天投递队列失败: {}", e);
+            return;
+        }
+    };
+
+    if pending.is_empty() {
+        return;
+    }
+
+    let Some(connection) = find_peer_connection(device_id) else {
+        request_connection_for_outbox(device_id, pending.len());
+        return;
+    };
+
+    log::info!(
+        "[LanTransfer] 投递聊天消息队列: {} 条 -> {}",
+        pending.len(),
+        device_id
+    );
+
+    for message in pending {
+        if let Err(e) = deliver_message(&connection, &from_device, &message).await {
+            log::error!(
+                "[LanTransfer] 聊天消息投递失败，保留在队列中: {} ({})",
+                message.message_uuid, e
+            );
+            break;
+        }
+
+        if let Err(e) = db::mark_lan_message_delivered(&message.message_uuid) {
+            log::error!("[LanTransfer] 更新投递状态失败: {}", e);
+        }
+
+        let event = LanTransferEvent::LanChatMessageDelivered {
+            conversation_id: message.conversation_id.clone(),
+            message_id: message.message_uuid.clone(),
+        };
+        let _ = get_event_sender().send(event.clone());
+        emit_lan_event(&event);
+    }
+}
+
+/// 投递单条消息，成功返回即表示对方已保存（回执）
+async fn deliver_message(
+    connection: &PeerConnection,
+    from_device: &DiscoveredDevice,
+    message: &LocalMessage,
+) -> Result<(), String> {
+    // 设备重新上线后 IP 可能变化，优先使用最新发现的地址
+    let target = {
+        let state = get_lan_transfer_state();
+        let devices = state.devices.read();
+        devices
+            .get(&connection.peer_device.device_id)
+            .cloned()
+            .unwrap_or_else(|| connection.peer_device.clone())
+    };
+
+    db::record_lan_delivery_attempt(&message.message_uuid)?;
+
+    let body = LanChatMessage {
+        message_id: message.message_uuid.clone(),
+        connection_id: connection.connection_id.clone(),
+        from_device: from_device.clone(),
+        content: message.content.clone(),
+        content_type: message.content_type.clone(),
+        sent_at: message.send_time.clone(),
+    };
+
+    let url = format!(
+        "http://{}:{}/api/chat-message",
+        target.ip_address, target.port
+    );
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(&url)
+        .json(&body)
+        .timeout(std::time::Duration::from_secs(DELIVERY_TIMEOUT_SECS))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !response.status().is_success() {
+        return Err(format!("服务器返回错误: {}", response.status()));
+    }
+
+    Ok(())
+}
+
+/// 为投递队列向已发现的设备发起连接请求（后台执行，同一设备限制频率）
+fn request_connection_for_outbox(device_id: &str, pending: usize) {
+    let discovered = get_lan_transfer_state()
+        .devices
+        .read()
+        .contains_key(device_id);
+    if !discovered {
+        return;
+    }
+
+    {
+        let requests = service::current().chat.connect_requests.clone();
+        let mut requests = requests.lock();
+        if requests
+            .get(device_id)
+            .is_some_and(|at| at.elapsed() < CONNECT_RETRY_INTERVAL)
+        {
+            return;
+        }
+        requests.insert(device_id.to_string(), Instant::now());
+    }
+
+    log::info!(
+        "[LanTransfer] 有 {} 条待投递聊天消息，向 {} 发起连接请求",
+        pending, device_id
+    );
+
+    let device_id = device_id.to_string();
+    service::spawn(async move {
+        if let Err(e) = super::transfer::request_peer_connection(&device_id).await {
+            log::warn!("[LanTransfer] 为聊天投递发起连接失败: {} ({})", device_id, e);
+        }
+    });
+}
+
+// ============================================================================
+// 接收
+// ============================================================================
+
+/// 处理收到的聊天消息（由 server 在校验连接后调用）
+///
+/// 重复消息（对方重发）直接返回成功，使对方能正确标记为已送达
+pub fn handle_incoming_message(message: LanChatMessage) -> Result<(), ChatError> {
+    if message.content_type != "text" {
+        return Err(ChatError::InvalidMessage(format!(
+            "不支持的内容类型: {}",
+            message.content_type
+        )));
+    }
+
+    if message.content.len() > MAX_CHAT_MESSAGE_BYTES {
+        return Err(ChatError::InvalidMessage("消息过长".to_string()));
+    }
+
+    let conversation_id = lan_conversation_id(&message.from_device.device_id);
+    let local = LocalMessage {
+        message_uuid: message.message_id.clone(),
+        conversation_id: conversation_id.clone(),
+        conversation_type: LAN_CONVERSATION_TYPE.to_string(),
+        sender_id: message.from_device.user_id.clone(),
+        sender_name: Some(message.from_device.user_nickname.clone()),
+        sender_avatar: None,
+        content: message.content.clone(),
+        content_type: message.content_type.clone(),
+        file_uuid: None,
+        file_url: None,
+        file_size: None,
+        file_hash: None,
+        image_width: None,
+        image_height: None,
+        seq: 0,
+        reply_to: None,
+        is_recalled: false,
+        is_deleted: false,
+        send_time: message.sent_at.clone(),
+        created_at: None,
+    };
+    let saved = db::append_lan_message(
+        &new_conversation(&message.from_device.device_id, Some(&message.from_device)),
+        local,
+        None,
+        true,
+    )
+    .map_err(ChatError::Database)?;
+
+    if saved.is_none() {
+        log::info!(
+            "[LanTransfer] 重复的聊天消息，已忽略: {}",
+            message.message_id
+        );
+        return Ok(());
+    }
+
+    log::info!(
+        "[LanTransfer] 收到聊天消息: {} 来自 {}",
+        message.message_id, message.from_device.device_name
+    );
+
+    let event = LanTransferEvent::LanChatMessageReceived {
+        conversation_id,
+        message,
+    };
+    let _ = get_event_sender().send(event.clone());
+    emit_lan_event(&event);
+
+    Ok(())
+}
+
+// ============================================================================
+// 内部函数
+// ============================================================================
+
+/// 查找与设备的已连接点对点连接
+fn find_peer_connection(device_id: &str) -> Option<PeerConnection> {
+    let connections = get_active_peer_connections_map();
+    let connections = connections.lock();
+    connections
+        .values()
+        .find(|conn| {
+            conn.peer_device.device_id == device_id
+                && conn.status == PeerConnectionStatus::Connected
+        })
+        .cloned()
+}
+
+/// 查找设备信息（已连接的对端优先，其次是已发现的设备）
+fn find_known_peer(device_id: &str) -> Option<DiscoveredDevice> {
+    find_peer_connection(device_id)
+        .map(|conn| conn.peer_device)
+        .or_else(|| get_lan_transfer_state().devices.read().get(device_id).cloned())
+}
+
+/// 构建本机设备信息（作为消息发送方）
+fn get_local_from_device() -> Option<DiscoveredDevice> {
+    let state = get_lan_transfer_state();
+    let local = state.local_device.read();
+    let local = local.as_ref()?;
+    let now = Utc::now().to_rfc3339();
+
+    Some(DiscoveredDevice {
+        device_id: local.device_id.clone(),
+        device_name: local.device_name.clone(),
+        user_id: local.user_id.clone(),
+        user_nickname: local.user_nickname.clone(),
+        ip_address: local.ip_address.clone(),
+        port: local.port,
+        discovered_at: now.clone(),
+        last_seen: now,
+    })
+}
+
+/// 设备对应的新会话（会话已存在时只使用 ID，名称等保持不变）
+///
+/// peer 为空时（设备离线且未发现）会话名称使用设备 ID
+fn new_conversation(device_id: &str, peer: Option<&DiscoveredDevice>) -> LocalConversation {
+    let name = match peer {
+        Some(peer) if peer.user_nickname.is_empty() => peer.device_name.clone(),
+        Some(peer) => format!("{} ({})", peer.user_nickname, peer.device_name),
+        None => device_id.to_string(),
+    };
+
+    LocalConversation {
+        id: lan_conversation_id(device_id),
+        conv_type: LAN_CONVERSATION_TYPE.to_string(),
+        name,
+        avatar_url: None,
+        last_message: None,
+        last_message_time: None,
+        last_seq: 0,
+        unread_count: 0,
+        is_muted: false,
+        is_pinned: false,
+        updated_at: Utc::now().to_rfc3339(),
+        synced_at: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lan_transfer::service::{LanTransferService, ServiceOptions};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn peer(port: u16) -> DiscoveredDevice {
+        let now = Utc::now().to_rfc3339();
+        DiscoveredDevice {
+            device_id: "peer".to_string(),
+            device_name: "Laptop".to_string(),
+            user_id: "peer-user".to_string(),
+            user_nickname: "Alice".to_string(),
+            ip_address: "127.0.0.1".to_string(),
+            port,
+            discovered_at: now.clone(),
+            last_seen: now,
+        }
+    }
+
+    fn incoming(message_id: &str, content: &str) -> LanChatMessage {
+        LanChatMessage {
+            message_id: message_id.to_string(),
+            connection_id: "conn".to_string(),
+            from_device: peer(0),
+            content: content.to_string(),
+            content_type: "text".to_string(),
+            sent_at: Utc::now().to_rfc3339(),
+        }
+    }
+
+    fn service() -> Arc<LanTransferService> {
+        let service = LanTransferService::new(ServiceOptions {
+            enable_mdns: false,
+            ..Default::default()
+        });
+        *service.state().local_device.write() = Some(DeviceInfo {
+            device_id: "me".to_string(),
+            device_name: "Desktop".to_string(),
+            user_id: "me-user".to_string(),
+            user_nickname: "Me".to_string(),
+            ip_address: "127.0.0.1".to_string(),
+            port: 0,
+            version: PROTOCOL_VERSION.to_string(),
+            os: std::env::consts::OS.to_string(),
+        });
+        service
+    }
+
+    /// 启动对每个请求返回固定状态码的 HTTP 服务器，返回端口和收到的请求数
+    async fn serve(status: u16) -> (u16, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = [0u8; 4096];
+                let _ = socket.read(&mut request).await;
+                counter.fetch_add(1, Ordering::SeqCst);
+                let response = format!(
+                    "HTTP/1.1 {} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        (port, hits)
+    }
+
+    fn connect(port: u16) {
+        get_active_peer_connections_map().lock().insert(
+            "conn".to_string(),
+            PeerConnection {
+                connection_id: "conn".to_string(),
+                peer_device: peer(port),
+                established_at: Utc::now().to_rfc3339(),
+                status: PeerConnectionStatus::Connected,
+                is_initiator: true,
+            },
+        );
+    }
+
+    #[test]
+    fn incoming_messages_are_deduplicated_with_unique_seqs() {
+        let _db = db::TestDatabase::open();
+        let service = service();
+
+        // 并发收到同一批消息（含对方重发）
+        std::thread::scope(|scope| {
+            for round in 0..2 {
+                for i in 0..8 {
+                    let service = service.clone();
+                    scope.spawn(move || {
+                        service.enter(|| {
+                            handle_incoming_message(incoming(&format!("m{}", i), &format!("{}", round)))
+                        })
+                        .unwrap()
+                    });
+                }
+            }
+        });
+
+        let conversation_id = lan_conversation_id("peer");
+        let conversation = db::get_conversation(&conversation_id).unwrap().unwrap();
+        assert_eq!(conversation.name, "Alice (Laptop)");
+        assert_eq!((conversation.last_seq, conversation.unread_count), (8, 8));
+
+        let mut seqs: Vec<i64> = db::get_messages(&conversation_id, 100, None)
+            .unwrap()
+            .iter()
+            .map(|m| m.seq)
+            .collect();
+        seqs.sort();
+        assert_eq!(seqs, (1..=8).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn outbox_keeps_messages_until_acknowledged() {
+        let _db = db::TestDatabase::open();
+        let service = service();
+
+        service
+            .scope(async {
+                handle_incoming_message(incoming("m1", "hello")).unwrap();
+
+                // 对方未连接也未被发现：有聊天记录即可发送，消息留在队列中
+                let sent = send_chat_message("peer", "are you there?".into()).await.unwrap();
+                assert_eq!(sent.seq, 2);
+                let delivery = db::get_lan_delivery(&sent.message_uuid).unwrap().unwrap();
+                assert_eq!((delivery.status.as_str(), delivery.attempts), ("pending", 0));
+
+                // 对方返回错误：不算回执，保留在队列中
+                let (port, hits) = serve(500).await;
+                connect(port);
+                flush_outbox("peer").await;
+                assert_eq!(hits.load(Ordering::SeqCst), 1);
+                let delivery = db::get_lan_delivery(&sent.message_uuid).unwrap().unwrap();
+                assert_eq!((delivery.status.as_str(), delivery.attempts), ("pending", 1));
+
+                // 对方保存成功（HTTP 200 即回执）：标记为已送达，不再重发
+                let (port, hits) = serve(200).await;
+                connect(port);
+                flush_outbox("peer").await;
+                assert_eq!(hits.load(Ordering::SeqCst), 1);
+                let delivery = db::get_lan_delivery(&sent.message_uuid).unwrap().unwrap();
+                assert_eq!(delivery.status, "delivered");
+                assert!(db::get_pending_lan_messages("peer").unwrap().is_empty());
+
+                flush_outbox("peer").await;
+                assert_eq!(hits.load(Ordering::SeqCst), 1);
+            })
+            .await;
+    }
+
+    #[test]
+    fn unknown_devices_are_rejected() {
+        let _db = db::TestDatabase::open();
+        let service = service();
+
+        let result = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(service.scope(send_chat_message("stranger", "hi".into())));
+        assert!(matches!(result, Err(ChatError::UnknownDevice(_))));
+    }
+}
@@ -0,0 +1,595 @@
+/*!
+ * 命令行局域网传输工具
+ *
+ * 无图形界面的机器（CI 构建机、服务器）通过命令行向运行桌面应用的同事发送文件，
+ * 或接收文件到指定目录。复用 discovery / server / transfer，不依赖 Tauri 窗口
+ *
+ * 用法：
+ * - huanvae-lan list                                  列出局域网内的设备
+ * - huanvae-lan send <设备> <文件>...                  发送文件（设备可以是设备 ID、设备名或 IP）
+ * - huanvae-lan receive --dir <目录> [--accept-all]    接收文件，Ctrl+C 退出
+ *
+ * 通用选项：
+ * - --name <设备名>   对外显示的设备名（默认主机名）
+ * - --trust <文件>    信任配置（JSON），信任设备的传输请求自动接受
+ * - --wait <秒>       等待设备发现的时间（默认 5 秒）
+ *
+ * 信任配置文件格式：
+ * ```json
+ * { "trustedDevices": [{ "deviceId": "...", "deviceName": "..." }], "acceptAll": false }
+ * ```
+ *
+ * 说明：
+ * - 保存目录和信任列表只在本次运行的内存中生效，不修改桌面应用的配置文件
+ * - 不执行桌面应用配置的接收后处理动作（归类移动、解压、执行命令），文件保持在 --dir 目录中
+ * - 未信任设备的请求默认拒绝（receive 时可用 --accept-all 或 acceptAll 全部接受）
+ * - 进度输出到标准错误，结果输出到标准输出，便于脚本处理
+ * - 内部日志写入桌面应用的日志目录（logs/app.log），warn 及以上同时输出到标准错误
+ */
+
+use super::config::{self, PostReceiveActions, TrustedDevice};
+use super::discovery::get_event_sender;
+use super::get_lan_transfer_state;
+use super::protocol::*;
+use super::service;
+use super::transfer::{self, format_bytes};
+use chrono::Utc;
+use serde::Deserialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::broadcast;
+
+/// 命令行工具使用的用户 ID（对端显示为"命令行"用户）
+const CLI_USER_ID: &str = "cli";
+
+/// 命令行工具使用的昵称
+const CLI_USER_NICKNAME: &str = "命令行";
+
+/// 默认等待设备发现的时间（秒）
+const DEFAULT_WAIT_SECS: u64 = 5;
+
+/// 拒绝未信任设备时的原因
+const UNTRUSTED_REASON: &str = "未信任的设备";
+
+const USAGE: &str = "\
+用法:
+  huanvae-lan [选项] list
+  huanvae-lan [选项] send <设备> <文件>...
+  huanvae-lan [选项] receive --dir <目录> [--accept-all]
+
+选项:
+  --name <设备名>   对外显示的设备名（默认主机名）
+  --trust <文件>    信任配置文件（JSON）
+  --wait <秒>       等待设备发现的时间（默认 5 秒）";
+
+// ============================================================================
+// 错误类型
+// ============================================================================
+
+#[derive(Error, Debug)]
+pub enum CliError {
+    #[error("{0}")]
+    Usage(String),
+    #[error("读取信任配置失败: {0}")]
+    TrustFile(String),
+    #[error("文件无效: {0}")]
+    InvalidFile(String),
+    #[error("服务启动失败: {0}")]
+    Service(String),
+    #[error("未找到设备: {0}")]
+    DeviceNotFound(String),
+    #[error("对方拒绝了传输: {0}")]
+    Rejected(String),
+    #[error("传输失败: {0}")]
+    Transfer(String),
+    #[error("已取消")]
+    Interrupted,
+}
+
+// ============================================================================
+// 参数解析
+// ============================================================================
+
+#[derive(Debug)]
+enum CliCommand {
+    List,
+    Send { device: String, files: Vec<String> },
+    Receive { dir: PathBuf, accept_all: bool },
+}
+
+#[derive(Debug)]
+struct CliOptions {
+    device_name: Option<String>,
+    trust_file: Option<PathBuf>,
+    wait_secs: u64,
+    command: CliCommand,
+}
+
+/// 信任配置文件
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TrustFile {
+    #[serde(default)]
+    trusted_devices: Vec<TrustFileDevice>,
+    /// 接受所有设备的请求
+    #[serde(default)]
+    accept_all: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TrustFileDevice {
+    device_id: String,
+    #[serde(default)]
+    device_name: String,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<CliOptions, CliError> {
+    let mut device_name = None;
+    let mut trust_file = None;
+    let mut wait_secs = DEFAULT_WAIT_SECS;
+    let mut dir = None;
+    let mut accept_all = false;
+    let mut positional = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| CliError::Usage(format!("{} 缺少参数值", name)))
+        };
+
+        match arg.as_str() {
+            "--name" => device_name = Some(value("--name")?),
+            "--trust" => trust_file = Some(PathBuf::from(value("--trust")?)),
+            "--wait" => {
+                wait_secs = value("--wait")?
+                    .parse()
+                    .map_err(|_| CliError::Usage("--wait 必须是整数秒".to_string()))?;
+            }
+            "--dir" => dir = Some(PathBuf::from(value("--dir")?)),
+            "--accept-all" => accept_all = true,
+            "-h" | "--help" => return Err(CliError::Usage(String::new())),
+            flag if flag.starts_with("--") => {
+                return Err(CliError::Usage(format!("未知选项: {}", flag)));
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let command = match positional.next().as_deref() {
+        Some("list") => CliCommand::List,
+        Some("send") => {
+            let device = positional
+                .next()
+                .ok_or_else(|| CliError::Usage("send 需要指定设备".to_string()))?;
+            let files: Vec<String> = positional.by_ref().collect();
+            if files.is_empty() {
+                return Err(CliError::Usage("send 需要至少一个文件".to_string()));
+            }
+            CliCommand::Send { device, files }
+        }
+        Some("receive") => CliCommand::Receive {
+            dir: dir.ok_or_else(|| CliError::Usage("receive 需要 --dir <目录>".to_string()))?,
+            accept_all,
+        },
+        Some(other) => return Err(CliError::Usage(format!("未知命令: {}", other))),
+        None => return Err(CliError::Usage(String::new())),
+    };
+
+    if let Some(extra) = positional.next() {
+        return Err(CliError::Usage(format!("多余的参数: {}", extra)));
+    }
+
+    Ok(CliOptions {
+        device_name,
+        trust_file,
+        wait_secs,
+        command,
+    })
+}
+
+// ============================================================================
+// 入口
+// ============================================================================
+
+/// 运行命令行工具，返回进程退出码
+pub fn run() -> i32 {
+    // 日志写入文件，warn 及以上同时输出到标准错误（标准输出留给命令结果）
+    crate::logging::init_cli();
+
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(CliError::Usage(message)) => {
+            if !message.is_empty() {
+                eprintln!("{}\n", message);
+            }
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            return 2;
+        }
+    };
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("错误: 无法创建异步运行时: {}", e);
+            return 1;
+        }
+    };
+
+    match runtime.block_on(execute(options)) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            1
+        }
+    }
+}
+
+async fn execute(options: CliOptions) -> Result<(), CliError> {
+    let trust = match &options.trust_file {
+        Some(path) => load_trust_file(path)?,
+        None => TrustFile::default(),
+    };
+
+    if let CliCommand::Send { files, .. } = &options.command {
+        validate_files(files)?;
+    }
+
+    // 信任列表和保存目录只在内存中生效
+    let receive_dir = match &options.command {
+        CliCommand::Receive { dir, .. } => {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| CliError::InvalidFile(format!("{}: {}", dir.display(), e)))?;
+            Some(dir.canonicalize().unwrap_or_else(|_| dir.clone()))
+        }
+        _ => None,
+    };
+    let trusted_devices: Vec<TrustedDevice> = trust
+        .trusted_devices
+        .iter()
+        .map(|d| TrustedDevice {
+            device_id: d.device_id.clone(),
+            device_name: d.device_name.clone(),
+            added_at: Utc::now().to_rfc3339(),
+        })
+        .collect();
+    config::update_in_memory(|config| {
+        config.trusted_devices = trusted_devices;
+        config.auto_accept_trusted = true;
+        // 桌面应用配置的接收后处理（移动文件、执行命令）不应在命令行接收时生效
+        config.post_receive = PostReceiveActions::default();
+        if let Some(dir) = receive_dir {
+            config.save_directory = dir;
+            config.group_by_date = false;
+        }
+    });
+
+    let service = service::default_service();
+    service
+        .start(
+            CLI_USER_ID.to_string(),
+            CLI_USER_NICKNAME.to_string(),
+            options.device_name.clone(),
+        )
+        .await
+        .map_err(|e| CliError::Service(e.to_string()))?;
+
+    let wait = Duration::from_secs(options.wait_secs);
+    let result = match options.command {
+        CliCommand::List => list_devices(wait).await,
+        CliCommand::Send { device, files } => send_files(&device, files, wait).await,
+        CliCommand::Receive { dir, accept_all } => {
+            receive_files(&dir, accept_all || trust.accept_all).await
+        }
+    };
+
+    let _ = service.stop().await;
+    result
+}
+
+fn load_trust_file(path: &Path) -> Result<TrustFile, CliError> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| CliError::TrustFile(format!("{}: {}", path.display(), e)))?;
+    serde_json::from_str(&content)
+        .map_err(|e| CliError::TrustFile(format!("{}: {}", path.display(), e)))
+}
+
+fn validate_files(files: &[String]) -> Result<(), CliError> {
+    for file in files {
+        let path = Path::new(file);
+        if path.is_dir() {
+            return Err(CliError::InvalidFile(format!("{}（暂不支持发送文件夹，请先打包）", file)));
+        }
+        if !path.is_file() {
+            return Err(CliError::InvalidFile(format!("{}（文件不存在）", file)));
+        }
+    }
+    Ok(())
+}
+
+// ============================================================================
+// list
+// ============================================================================
+
+async fn list_devices(wait: Duration) -> Result<(), CliError> {
+    eprintln!("正在发现设备（{} 秒）...", wait.as_secs());
+    tokio::time::sleep(wait).await;
+
+    let mut devices: Vec<DiscoveredDevice> = get_lan_transfer_state()
+        .devices
+        .read()
+        .values()
+        .cloned()
+        .collect();
+    devices.sort_by(|a, b| a.device_name.cmp(&b.device_name));
+
+    if devices.is_empty() {
+        eprintln!("未发现设备");
+        return Ok(());
+    }
+
+    for device in devices {
+        println!(
+            "{}\t{}:{}\t{}\t{}",
+            device.device_name,
+            device.ip_address,
+            device.port,
+            device.user_nickname,
+            device.device_id
+        );
+    }
+    Ok(())
+}
+
+// ============================================================================
+// send
+// ============================================================================
+
+/// 按设备 ID、设备名（不区分大小写）或 IP 查找设备
+fn find_device(query: &str) -> Option<DiscoveredDevice> {
+    let devices = get_lan_transfer_state().devices.read().clone();
+    devices
+        .values()
+        .find(|d| d.device_id == query || d.ip_address == query)
+        .or_else(|| {
+            devices
+                .values()
+                .find(|d| d.device_name.eq_ignore_ascii_case(query))
+        })
+        .cloned()
+}
+
+async fn send_files(query: &str, files: Vec<String>, wait: Duration) -> Result<(), CliError> {
+    eprintln!("正在查找设备 {} ...", query);
+    let deadline = tokio::time::Instant::now() + wait;
+    let device = loop {
+        if let Some(device) = find_device(query) {
+            break device;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Err(CliError::DeviceNotFound(query.to_string()));
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    };
+
+    // 先订阅事件，避免错过快速完成的传输
+    let mut events = get_event_sender().subscribe();
+
+    let request_id = transfer::send_transfer_request(&device.device_id, files)
+        .await
+        .map_err(|e| CliError::Transfer(e.to_string()))?;
+    let session = transfer::get_transfer_session(&request_id)
+        .ok_or_else(|| CliError::Transfer("传输会话不存在".to_string()))?;
+    let file_ids: Vec<String> = session.files.iter().map(|f| f.file.file_id.clone()).collect();
+
+    eprintln!(
+        "已向 {} ({}) 发送传输请求，等待对方确认...",
+        device.device_name, device.ip_address
+    );
+
+    let mut failures: Vec<String> = Vec::new();
+    loop {
+        let event = tokio::select! {
+            event = events.recv() => event,
+            _ = tokio::signal::ctrl_c() => {
+                let _ = transfer::cancel_session(&request_id).await;
+                eprintln!();
+                return Err(CliError::Interrupted);
+            }
+        };
+
+        let event = match event {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => {
+                return Err(CliError::Transfer("事件通道已关闭".to_string()));
+            }
+        };
+
+        match event {
+            LanTransferEvent::TransferRequestResponse {
+                request_id: id,
+                accepted,
+                reject_reason,
+            } if id == request_id => {
+                if !accepted {
+                    return Err(CliError::Rejected(
+                        reject_reason.unwrap_or_else(|| "未说明原因".to_string()),
+                    ));
+                }
+                eprintln!("对方已接受，开始传输");
+            }
+            LanTransferEvent::BatchProgress { progress } if progress.session_id == session.session_id => {
+                print_progress(&progress);
+            }
+            LanTransferEvent::TransferFailed { task_id, error } if file_ids.contains(&task_id) => {
+                failures.push(error);
+            }
+            LanTransferEvent::BatchTransferCompleted { session_id, .. }
+                if session_id == session.session_id =>
+            {
+                eprintln!();
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    if failures.is_empty() {
+        println!("已发送 {} 个文件到 {}", file_ids.len(), device.device_name);
+        Ok(())
+    } else {
+        Err(CliError::Transfer(format!(
+            "{} 个文件发送失败: {}",
+            failures.len(),
+            failures.join("; ")
+        )))
+    }
+}
+
+// ============================================================================
+// receive
+// ============================================================================
+
+async fn receive_files(dir: &Path, accept_all: bool) -> Result<(), CliError> {
+    let mut events = get_event_sender().subscribe();
+
+    eprintln!("等待接收文件，保存到 {}（Ctrl+C 退出）", dir.display());
+    if !accept_all {
+        eprintln!("只接受信任设备的请求（--trust 文件中的 trustedDevices）");
+    }
+
+    loop {
+        let event = tokio::select! {
+            event = events.recv() => event,
+            _ = tokio::signal::ctrl_c() => {
+                eprintln!();
+                return Ok(());
+            }
+        };
+
+        let event = match event {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        };
+
+        match event {
+            LanTransferEvent::TransferRequestReceived { request } => {
+                let from = &request.from_device;
+                if accept_all {
+                    eprintln!(
+                        "接受来自 {} ({}) 的 {} 个文件",
+                        from.device_name,
+                        from.ip_address,
+                        request.files.len()
+                    );
+                } else {
+                    eprintln!(
+                        "拒绝未信任的设备 {} ({})，设备 ID: {}",
+                        from.device_name, from.ip_address, from.device_id
+                    );
+                }
+                if let Err(e) =
+                    transfer::answer_transfer_request(&request.request_id, accept_all, UNTRUSTED_REASON)
+                        .await
+                {
+                    eprintln!("响应传输请求失败: {}", e);
+                }
+            }
+            LanTransferEvent::PeerConnectionRequest { request } => {
+                let trusted =
+                    accept_all || config::is_device_trusted(&request.from_device.device_id);
+                let reason = (!trusted).then_some(UNTRUSTED_REASON);
+                if let Err(e) =
+                    transfer::answer_peer_connection(&request.connection_id, trusted, reason).await
+                {
+                    eprintln!("响应连接请求失败: {}", e);
+                }
+            }
+            LanTransferEvent::TransferRequestResponse { accepted: true, .. } => {
+                eprintln!("已自动接受信任设备的传输请求");
+            }
+            LanTransferEvent::BatchProgress { progress } => print_progress(&progress),
+            LanTransferEvent::TransferCompleted { saved_path, .. } => {
+                eprintln!();
+                println!("{}", saved_path);
+            }
+            LanTransferEvent::TransferFailed { error, .. } => {
+                eprintln!();
+                eprintln!("接收失败: {}", error);
+            }
+            _ => {}
+        }
+    }
+}
+
+// ============================================================================
+// 进度输出
+// ============================================================================
+
+/// 在同一行刷新进度（标准错误）
+fn print_progress(progress: &BatchTransferProgress) {
+    let percent = if progress.total_bytes > 0 {
+        progress.transferred_bytes as f64 * 100.0 / progress.total_bytes as f64
+    } else {
+        100.0
+    };
+    let eta = progress
+        .eta_seconds
+        .map(|s| format!("  剩余 {}:{:02}", s / 60, s % 60))
+        .unwrap_or_default();
+
+    let mut stderr = std::io::stderr();
+    let _ = write!(
+        stderr,
+        "\r{}/{} 个文件  {:5.1}%  {} / {}  {}/s{}    ",
+        progress.completed_files,
+        progress.total_files,
+        percent,
+        format_bytes(progress.transferred_bytes),
+        format_bytes(progress.total_bytes),
+        format_bytes(progress.speed),
+        eta
+    );
+    let _ = stderr.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_send_with_options_anywhere() {
+        let options = parse_args(args(&["send", "--wait", "10", "pc", "a.zip", "b.zip"])).unwrap();
+        assert_eq!(options.wait_secs, 10);
+        match options.command {
+            CliCommand::Send { device, files } => {
+                assert_eq!(device, "pc");
+                assert_eq!(files, vec!["a.zip", "b.zip"]);
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_receive_requires_dir() {
+        assert!(matches!(parse_args(args(&["receive"])), Err(CliError::Usage(_))));
+        assert!(matches!(
+            parse_args(args(&["receive", "--dir", "/tmp/in"])).map(|o| o.command),
+            Ok(CliCommand::Receive { accept_all: false, .. })
+        ));
+    }
+}
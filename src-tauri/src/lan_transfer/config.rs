@@ -6,14 +6,23 @@
  * - 临时文件目录（断点续传用）
  * - 信任设备列表
  * - 自动接受设置
+ * - 共享文件夹（供已信任设备浏览和拉取）
+ * - 点对点连接心跳（间隔、超时）
+ * - 未完成接收的最大保留时长（超时的临时文件和续传信息会被清理）
+ * - 接收后处理动作（按类型/发送方归类、自动解压、执行命令）
+ *
+ * 配置归属于服务实例（LanTransferService），从实例的数据目录加载，
+ * get_config_manager 等函数操作当前实例的配置
  */
 
+use super::service;
 use chrono::Utc;
+#[cfg(target_os = "android")]
 use once_cell::sync::OnceCell;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use thiserror::Error;
 
@@ -54,11 +63,11 @@ pub fn init_android_data_dir(path: PathBuf) -> Result<(), String> {
     let public_download = PathBuf::from("/storage/emulated/0/Download/HuanvaeChat");
     if !public_download.exists() && let Err(e) = fs::create_dir_all(&public_download) {
         // 如果无法创建公共目录，使用应用外部存储目录
-        eprintln!(
+        log::warn!(
             "[LanTransfer] 警告: 无法创建公共 Download 目录 {:?}: {}",
             public_download, e
         );
-        eprintln!("[LanTransfer] 将使用应用外部存储目录作为备选");
+        log::warn!("[LanTransfer] 将使用应用外部存储目录作为备选");
         // 备选: /storage/emulated/0/Android/data/{package}/files/LanTransfer
         let fallback = PathBuf::from("/storage/emulated/0/Android/data/com.github.huanwei520.huanvae_chat_app/files/LanTransfer");
         let _ = fs::create_dir_all(&fallback);
@@ -91,6 +100,8 @@ pub enum ConfigError {
     DirectoryCreationFailed(String),
     #[error("无效的路径: {0}")]
     InvalidPath(String),
+    #[error("无效的配置值: {0}")]
+    InvalidValue(String),
 }
 
 // ============================================================================
@@ -113,6 +124,21 @@ pub struct LanTransferConfig {
     pub trusted_devices: Vec<TrustedDevice>,
     /// 最大同时传输数
     pub max_concurrent_transfers: u32,
+    /// 共享文件夹
+    #[serde(default)]
+    pub shared_folders: Vec<SharedFolder>,
+    /// 点对点连接心跳间隔（秒）
+    #[serde(default = "default_heartbeat_interval_secs")]
+    pub heartbeat_interval_secs: u64,
+    /// 心跳超时（秒），超过该时间未收到对端响应则关闭连接
+    #[serde(default = "default_heartbeat_timeout_secs")]
+    pub heartbeat_timeout_secs: u64,
+    /// 未完成接收的最大保留时长（小时），超过后临时文件和续传信息会被清理
+    #[serde(default = "default_partial_max_age_hours")]
+    pub partial_max_age_hours: u64,
+    /// 接收后处理动作
+    #[serde(default)]
+    pub post_receive: PostReceiveActions,
     /// 配置版本
     pub version: String,
 }
@@ -129,9 +155,100 @@ pub struct TrustedDevice {
     pub added_at: String,
 }
 
+/// 接收后处理动作（文件保存后依次执行：归类移动 → 解压 → 执行命令）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostReceiveActions {
+    /// 按发送方归类（子文件夹以发送设备名命名）
+    #[serde(default)]
+    pub per_sender_folder: bool,
+    /// 按文件类型归类
+    #[serde(default)]
+    pub sort_by_type: bool,
+    /// 自定义类型归类规则（优先于内置规则）
+    #[serde(default)]
+    pub type_rules: Vec<MimeFolderRule>,
+    /// 自动解压 zip 压缩包（解压到同名文件夹，保留压缩包）
+    #[serde(default)]
+    pub extract_archives: bool,
+    /// 接收后执行的命令（如病毒扫描）
+    #[serde(default)]
+    pub command: Option<PostReceiveCommand>,
+}
+
+impl PostReceiveActions {
+    /// 是否配置了任何动作
+    pub fn is_empty(&self) -> bool {
+        !self.per_sender_folder && !self.sort_by_type && !self.extract_archives && self.command.is_none()
+    }
+}
+
+/// 类型归类规则：MIME 类型以 mime_prefix 开头的文件移动到 folder 子文件夹
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MimeFolderRule {
+    /// MIME 前缀（如 "image/"、"application/pdf"）
+    pub mime_prefix: String,
+    /// 子文件夹名
+    pub folder: String,
+}
+
+/// 接收后执行的命令（保存路径作为最后一个参数传入）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostReceiveCommand {
+    /// 可执行文件
+    pub program: String,
+    /// 参数（保存路径追加在最后）
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// 超时（秒）
+    #[serde(default = "default_command_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_command_timeout_secs() -> u64 {
+    300
+}
+
+/// 共享文件夹
+///
+/// 只有已信任的设备可以访问；`allowed_devices` 为空时允许所有已信任设备，
+/// 否则只允许列表中的设备
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedFolder {
+    /// 共享 ID
+    pub share_id: String,
+    /// 显示名称
+    pub name: String,
+    /// 本地目录
+    pub path: PathBuf,
+    /// 允许访问的设备 ID（为空表示所有已信任设备）
+    #[serde(default)]
+    pub allowed_devices: Vec<String>,
+    /// 添加时间
+    pub created_at: String,
+}
+
+impl SharedFolder {
+    /// 检查设备是否有权访问该共享（设备必须已信任）
+    pub fn is_accessible_by(&self, device_id: &str, trusted_devices: &[TrustedDevice]) -> bool {
+        trusted_devices.iter().any(|d| d.device_id == device_id)
+            && (self.allowed_devices.is_empty()
+                || self.allowed_devices.iter().any(|id| id == device_id))
+    }
+}
+
 impl Default for LanTransferConfig {
     fn default() -> Self {
-        let base_dir = get_base_directory();
+        Self::default_in(&get_base_directory())
+    }
+}
+
+impl LanTransferConfig {
+    /// 指定数据目录下的默认配置
+    fn default_in(base_dir: &Path) -> Self {
 
         // Android：使用公共 Download 目录保存接收的文件
         // 其他平台：使用应用数据目录
@@ -148,18 +265,32 @@ impl Default for LanTransferConfig {
             auto_accept_trusted: false,
             trusted_devices: vec![],
             max_concurrent_transfers: 3,
+            shared_folders: vec![],
+            heartbeat_interval_secs: default_heartbeat_interval_secs(),
+            heartbeat_timeout_secs: default_heartbeat_timeout_secs(),
+            partial_max_age_hours: default_partial_max_age_hours(),
+            post_receive: PostReceiveActions::default(),
             version: "1.0".to_string(),
         }
     }
 }
 
+fn default_heartbeat_interval_secs() -> u64 {
+    15
+}
+
+fn default_heartbeat_timeout_secs() -> u64 {
+    60
+}
+
+fn default_partial_max_age_hours() -> u64 {
+    7 * 24
+}
+
 // ============================================================================
 // 全局配置管理
 // ============================================================================
 
-/// 全局配置单例
-static CONFIG_MANAGER: OnceCell<Arc<RwLock<ConfigManager>>> = OnceCell::new();
-
 /// 配置管理器
 pub struct ConfigManager {
     config: LanTransferConfig,
@@ -167,35 +298,35 @@ pub struct ConfigManager {
 }
 
 impl ConfigManager {
-    /// 创建新的配置管理器
-    fn new() -> Self {
-        let config_path = get_config_file_path();
-        let config = Self::load_or_default(&config_path);
+    /// 从数据目录加载配置（每个服务实例一份，见 LanTransferService::config）
+    pub(crate) fn load(base_dir: &Path) -> Self {
+        let config_path = base_dir.join("config.json");
+        let config = Self::load_or_default(&config_path, base_dir);
 
         Self { config, config_path }
     }
 
     /// 加载配置或使用默认值
-    fn load_or_default(path: &PathBuf) -> LanTransferConfig {
+    fn load_or_default(path: &PathBuf, base_dir: &Path) -> LanTransferConfig {
         if path.exists() {
             match fs::read_to_string(path) {
                 Ok(content) => match serde_json::from_str(&content) {
                     Ok(config) => {
-                        println!("[LanTransfer] 配置已加载: {:?}", path);
+                        log::info!("[LanTransfer] 配置已加载: {:?}", path);
                         return config;
                     }
                     Err(e) => {
-                        eprintln!("[LanTransfer] 配置解析失败，使用默认配置: {}", e);
+                        log::warn!("[LanTransfer] 配置解析失败，使用默认配置: {}", e);
                     }
                 },
                 Err(e) => {
-                    eprintln!("[LanTransfer] 配置读取失败，使用默认配置: {}", e);
+                    log::warn!("[LanTransfer] 配置读取失败，使用默认配置: {}", e);
                 }
             }
         }
 
-        let config = LanTransferConfig::default();
-        println!("[LanTransfer] 使用默认配置");
+        let config = LanTransferConfig::default_in(base_dir);
+        log::info!("[LanTransfer] 使用默认配置");
         config
     }
 
@@ -213,7 +344,7 @@ impl ConfigManager {
         fs::write(&self.config_path, content)
             .map_err(|e| ConfigError::WriteFailed(e.to_string()))?;
 
-        println!("[LanTransfer] 配置已保存: {:?}", self.config_path);
+        log::info!("[LanTransfer] 配置已保存: {:?}", self.config_path);
         Ok(())
     }
 
@@ -283,6 +414,59 @@ impl ConfigManager {
             .any(|d| d.device_id == device_id)
     }
 
+    /// 添加共享文件夹
+    pub fn add_shared_folder(
+        &mut self,
+        name: String,
+        path: PathBuf,
+        allowed_devices: Vec<String>,
+    ) -> Result<SharedFolder, ConfigError> {
+        if !path.is_dir() {
+            return Err(ConfigError::InvalidPath(format!(
+                "目录不存在: {}",
+                path.display()
+            )));
+        }
+
+        let folder = SharedFolder {
+            share_id: uuid::Uuid::new_v4().to_string(),
+            name,
+            path,
+            allowed_devices,
+            created_at: Utc::now().to_rfc3339(),
+        };
+
+        self.config.shared_folders.push(folder.clone());
+        self.save()?;
+
+        Ok(folder)
+    }
+
+    /// 更新共享文件夹允许访问的设备
+    pub fn set_shared_folder_devices(
+        &mut self,
+        share_id: &str,
+        allowed_devices: Vec<String>,
+    ) -> Result<(), ConfigError> {
+        let folder = self
+            .config
+            .shared_folders
+            .iter_mut()
+            .find(|f| f.share_id == share_id)
+            .ok_or_else(|| ConfigError::InvalidPath(format!("共享不存在: {}", share_id)))?;
+
+        folder.allowed_devices = allowed_devices;
+        self.save()
+    }
+
+    /// 移除共享文件夹
+    pub fn remove_shared_folder(&mut self, share_id: &str) -> Result<(), ConfigError> {
+        self.config
+            .shared_folders
+            .retain(|f| f.share_id != share_id);
+        self.save()
+    }
+
     /// 获取保存目录（根据日期分组设置）
     pub fn get_save_path(&self, file_name: &str) -> PathBuf {
         let base_dir = &self.config.save_directory;
@@ -323,8 +507,13 @@ impl ConfigManager {
 // 公共函数
 // ============================================================================
 
-/// 获取基础目录
+/// 获取基础目录（当前服务实例的数据目录）
 fn get_base_directory() -> PathBuf {
+    service::current().data_directory()
+}
+
+/// 默认基础目录（未指定数据目录的实例使用）
+pub(crate) fn default_base_directory() -> PathBuf {
     // Android：使用 Tauri 提供的应用数据目录
     #[cfg(target_os = "android")]
     {
@@ -332,7 +521,7 @@ fn get_base_directory() -> PathBuf {
             .get()
             .cloned()
             .unwrap_or_else(|| {
-                eprintln!("[LanTransfer] 警告: Android 数据目录未初始化，使用临时目录");
+                log::warn!("[LanTransfer] 警告: Android 数据目录未初始化，使用临时目录");
                 PathBuf::from("/data/local/tmp/HuanvaeChat/LanTransfer")
             })
     }
@@ -347,16 +536,24 @@ fn get_base_directory() -> PathBuf {
     }
 }
 
-/// 获取配置文件路径
-fn get_config_file_path() -> PathBuf {
-    get_base_directory().join("config.json")
+/// 获取文本片段历史文件路径
+pub fn get_snippet_history_path() -> PathBuf {
+    get_base_directory().join("snippets.json")
+}
+
+/// 获取离线发送队列文件路径
+pub fn get_send_queue_path() -> PathBuf {
+    get_base_directory().join("send_queue.json")
 }
 
-/// 获取全局配置管理器
+/// 获取文件夹同步状态文件路径
+pub fn get_sync_state_path() -> PathBuf {
+    get_base_directory().join("sync_state.json")
+}
+
+/// 获取当前服务实例的配置管理器
 pub fn get_config_manager() -> Arc<RwLock<ConfigManager>> {
-    CONFIG_MANAGER
-        .get_or_init(|| Arc::new(RwLock::new(ConfigManager::new())))
-        .clone()
+    service::current().config()
 }
 
 /// 获取当前保存目录
@@ -436,6 +633,15 @@ pub fn get_full_config() -> LanTransferConfig {
     config.get_config().clone()
 }
 
+/// 仅修改内存中的配置（不写入配置文件）
+///
+/// 供命令行工具使用：保存目录、信任列表来自命令行参数，不影响桌面应用保存的配置
+pub fn update_in_memory(update: impl FnOnce(&mut LanTransferConfig)) {
+    let manager = get_config_manager();
+    let mut config = manager.write();
+    update(config.get_config_mut());
+}
+
 /// 设置自动接受信任设备
 pub fn set_auto_accept_trusted(enabled: bool) -> Result<(), ConfigError> {
     let manager = get_config_manager();
@@ -451,3 +657,120 @@ pub fn set_group_by_date(enabled: bool) -> Result<(), ConfigError> {
     config.get_config_mut().group_by_date = enabled;
     config.save()
 }
+
+/// 设置点对点连接心跳参数
+///
+/// 超时必须至少为间隔的两倍，否则一次丢包就会关闭连接
+pub fn set_heartbeat_settings(interval_secs: u64, timeout_secs: u64) -> Result<(), ConfigError> {
+    if interval_secs < 5 {
+        return Err(ConfigError::InvalidValue("心跳间隔不能小于 5 秒".to_string()));
+    }
+    if timeout_secs < interval_secs * 2 {
+        return Err(ConfigError::InvalidValue(
+            "心跳超时必须至少为间隔的两倍".to_string(),
+        ));
+    }
+
+    let manager = get_config_manager();
+    let mut config = manager.write();
+    let config_mut = config.get_config_mut();
+    config_mut.heartbeat_interval_secs = interval_secs;
+    config_mut.heartbeat_timeout_secs = timeout_secs;
+    config.save()
+}
+
+/// 设置未完成接收的最大保留时长（小时）
+pub fn set_partial_max_age_hours(hours: u64) -> Result<(), ConfigError> {
+    if hours == 0 {
+        return Err(ConfigError::InvalidValue("保留时长不能为 0".to_string()));
+    }
+
+    let manager = get_config_manager();
+    let mut config = manager.write();
+    config.get_config_mut().partial_max_age_hours = hours;
+    config.save()
+}
+
+/// 设置接收后处理动作
+pub fn set_post_receive_actions(actions: PostReceiveActions) -> Result<(), ConfigError> {
+    // 子文件夹名不能包含路径分隔符或上级目录
+    for rule in &actions.type_rules {
+        let folder = rule.folder.trim();
+        if folder.is_empty()
+            || folder == "."
+            || folder == ".."
+            || folder.contains(['/', '\\'])
+        {
+            return Err(ConfigError::InvalidValue(format!(
+                "无效的子文件夹名: {}",
+                rule.folder
+            )));
+        }
+    }
+
+    if let Some(command) = &actions.command
+        && command.program.trim().is_empty()
+    {
+        return Err(ConfigError::InvalidValue("命令不能为空".to_string()));
+    }
+
+    let manager = get_config_manager();
+    let mut config = manager.write();
+    config.get_config_mut().post_receive = actions;
+    config.save()
+}
+
+/// 获取临时文件目录
+pub fn get_temp_directory() -> PathBuf {
+    let manager = get_config_manager();
+    let config = manager.read();
+    config.get_config().temp_directory.clone()
+}
+
+/// 获取共享文件夹列表
+pub fn get_shared_folders() -> Vec<SharedFolder> {
+    let manager = get_config_manager();
+    let config = manager.read();
+    config.get_config().shared_folders.clone()
+}
+
+/// 添加共享文件夹
+pub fn add_shared_folder(
+    name: String,
+    path: PathBuf,
+    allowed_devices: Vec<String>,
+) -> Result<SharedFolder, ConfigError> {
+    let manager = get_config_manager();
+    let mut config = manager.write();
+    config.add_shared_folder(name, path, allowed_devices)
+}
+
+/// 更新共享文件夹允许访问的设备
+pub fn set_shared_folder_devices(
+    share_id: &str,
+    allowed_devices: Vec<String>,
+) -> Result<(), ConfigError> {
+    let manager = get_config_manager();
+    let mut config = manager.write();
+    config.set_shared_folder_devices(share_id, allowed_devices)
+}
+
+/// 移除共享文件夹
+pub fn remove_shared_folder(share_id: &str) -> Result<(), ConfigError> {
+    let manager = get_config_manager();
+    let mut config = manager.write();
+    config.remove_shared_folder(share_id)
+}
+
+/// 获取设备可访问的共享文件夹
+pub fn get_accessible_shared_folders(device_id: &str) -> Vec<SharedFolder> {
+    let manager = get_config_manager();
+    let config = manager.read();
+    let config = config.get_config();
+    config
+        .shared_folders
+        .iter()
+        .filter(|f| f.is_accessible_by(device_id, &config.trusted_devices))
+        .cloned()
+        .collect()
+}
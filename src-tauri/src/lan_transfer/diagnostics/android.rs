@@ -6,6 +6,7 @@
 //! - A3: 附近设备权限（Android 13+）
 //! - A4: MulticastLock 状态
 //! - A5: WiFi 连接状态
+//! - S1-S3: 本机服务自检（端口监听、本机接口访问、mDNS 自发现，见 self_test）
 //!
 //! # 注意
 //!
@@ -17,6 +18,7 @@
 //! - [Android NSD 文档](https://developer.android.com/develop/connectivity/wifi/use-nsd)
 //! - [Android WifiManager](https://developer.android.com/reference/android/net/wifi/WifiManager)
 
+use super::self_test::run_self_test;
 use super::types::*;
 
 /// Android 诊断器
@@ -42,7 +44,6 @@ impl AndroidDiagnostician {
                 status: DiagStatus::Unknown,
                 details: "需要通过前端检测".into(),
                 fix_suggestion: Some("在 AndroidManifest.xml 中声明权限".into()),
-                fix_command: None,
                 fix_steps: Some(vec![
                     "确保 AndroidManifest.xml 包含:".into(),
                     "<uses-permission android:name=\"android.permission.INTERNET\" />".into(),
@@ -54,6 +55,7 @@ impl AndroidDiagnostician {
                 doc_url: Some(
                     "https://developer.android.com/training/basics/network-ops/connecting".into(),
                 ),
+                ..Default::default()
             },
             DiagItem {
                 id: "A2".into(),
@@ -63,7 +65,6 @@ impl AndroidDiagnostician {
                 status: DiagStatus::Unknown,
                 details: "需要通过前端检测".into(),
                 fix_suggestion: Some("添加组播状态权限".into()),
-                fix_command: None,
                 fix_steps: Some(vec![
                     "在 AndroidManifest.xml 添加:".into(),
                     "<uses-permission android:name=\"android.permission.CHANGE_WIFI_MULTICAST_STATE\" />".into(),
@@ -71,6 +72,7 @@ impl AndroidDiagnostician {
                 doc_url: Some(
                     "https://developer.android.com/reference/android/net/wifi/WifiManager#createMulticastLock(java.lang.String)".into(),
                 ),
+                ..Default::default()
             },
             DiagItem {
                 id: "A3".into(),
@@ -80,7 +82,6 @@ impl AndroidDiagnostician {
                 status: DiagStatus::Unknown,
                 details: "需要通过前端检测".into(),
                 fix_suggestion: Some("添加附近设备权限（Android 13+）".into()),
-                fix_command: None,
                 fix_steps: Some(vec![
                     "在 AndroidManifest.xml 添加:".into(),
                     "<uses-permission android:name=\"android.permission.NEARBY_WIFI_DEVICES\" />"
@@ -90,6 +91,7 @@ impl AndroidDiagnostician {
                 doc_url: Some(
                     "https://developer.android.com/develop/connectivity/wifi/use-nsd".into(),
                 ),
+                ..Default::default()
             },
             DiagItem {
                 id: "A4".into(),
@@ -99,7 +101,6 @@ impl AndroidDiagnostician {
                 status: DiagStatus::Unknown,
                 details: "需要在应用代码中获取".into(),
                 fix_suggestion: Some("在代码中获取 MulticastLock".into()),
-                fix_command: None,
                 fix_steps: Some(vec![
                     "获取 WifiManager:".into(),
                     "WifiManager wifi = (WifiManager) getSystemService(WIFI_SERVICE);".into(),
@@ -110,6 +111,7 @@ impl AndroidDiagnostician {
                 doc_url: Some(
                     "https://developer.android.com/reference/android/net/wifi/WifiManager.MulticastLock".into(),
                 ),
+                ..Default::default()
             },
             DiagItem {
                 id: "A5".into(),
@@ -119,13 +121,12 @@ impl AndroidDiagnostician {
                 status: DiagStatus::Unknown,
                 details: "需要通过前端检测".into(),
                 fix_suggestion: Some("确保设备已连接到 WiFi".into()),
-                fix_command: None,
                 fix_steps: Some(vec![
                     "打开设置 → WiFi".into(),
                     "连接到与其他设备相同的 WiFi 网络".into(),
                     "确保路由器未开启 AP 隔离".into(),
                 ]),
-                doc_url: None,
+                ..Default::default()
             },
         ]
     }
@@ -141,7 +142,8 @@ impl Diagnostician for AndroidDiagnostician {
     async fn diagnose(&self) -> DiagReport {
         // Android 的实际检测需要在前端完成
         // 这里返回静态的检查项说明
-        let items = Self::get_check_items();
+        let mut items = Self::get_check_items();
+        items.extend(run_self_test().await);
 
         DiagReport::from_items("Android".into(), "需前端检测".into(), items)
     }
@@ -0,0 +1,307 @@
+//! 诊断包导出
+//!
+//! 用户反馈局域网传输问题时，一键把排查所需的信息打包成一个 zip 文件，
+//! 保存在局域网传输数据目录的 diagnostics 子目录中，由用户随工单发送。
+//!
+//! 包内文件（格式版本见 BUNDLE_VERSION）：
+//! - manifest.json: 格式名称、版本、生成时间、应用版本、操作系统
+//! - report.json: 完整诊断报告（diagnose_lan_transfer）
+//! - debug_info.json: get_lan_debug_info 的结果
+//! - state.json: 服务运行状态、本机设备信息、已发现设备列表
+//! - events.json: 事件日志中的最近事件
+//! - config.json: 局域网传输配置
+//! - interfaces.json: 网络接口列表
+//! - logs/*.log: 应用日志目录中最近日志文件的末尾部分
+//!
+//! 写入前对包内所有文件统一脱敏：信任设备 ID 替换为加盐摘要（见 Redactor），
+//! 盐值每个诊断包随机生成，同一包内同一设备的摘要相同，便于对照各文件。
+//!
+//! 版本变更时递增 BUNDLE_VERSION，解析工具按 manifest.json 中的版本处理。
+
+use super::diagnose_lan_transfer;
+use crate::lan_transfer::{get_lan_debug_info, get_lan_transfer_state, journal, service};
+use chrono::Local;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// 诊断包格式名称
+pub const BUNDLE_FORMAT: &str = "huanvae-lan-diagnostics";
+
+/// 诊断包格式版本
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// 最多打包的日志文件数（按修改时间取最新的）
+const MAX_LOG_FILES: usize = 3;
+
+/// 每个日志文件最多打包的字节数（取末尾）
+const MAX_LOG_BYTES: u64 = 512 * 1024;
+
+/// 生成诊断包，返回文件路径
+pub async fn export_bundle() -> Result<PathBuf, String> {
+    let report = diagnose_lan_transfer().await?;
+    let debug_info = get_lan_debug_info()?;
+    let svc = service::current();
+
+    let state = {
+        let state = get_lan_transfer_state();
+        let devices: Vec<_> = state.devices.read().values().cloned().collect();
+        let local_device = state.local_device.read().clone();
+        let is_running = *state.is_running.read();
+        json!({
+            "isRunning": is_running,
+            "port": svc.port(),
+            "mdnsEnabled": svc.options().enable_mdns,
+            "localDevice": local_device,
+            "devices": devices,
+        })
+    };
+
+    let events = journal::get_events_since(0);
+    let (config, redactor) = {
+        let config = svc.config();
+        let config = config.read();
+        let config = config.get_config();
+        let redactor = Redactor::new(config.trusted_devices.iter().map(|d| d.device_id.clone()));
+        (serde_json::to_value(config).map_err(|e| e.to_string())?, redactor)
+    };
+
+    let interfaces: Vec<Value> = local_ip_address::list_afinet_netifas()
+        .map(|list| {
+            list.into_iter()
+                .map(|(name, ip)| json!({ "name": name, "ip": ip.to_string() }))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let manifest = json!({
+        "format": BUNDLE_FORMAT,
+        "version": BUNDLE_VERSION,
+        "createdAt": chrono::Utc::now().to_rfc3339(),
+        "appVersion": env!("CARGO_PKG_VERSION"),
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+    });
+
+    let mut entries: Vec<(String, Vec<u8>)> = Vec::new();
+    for (name, value) in [
+        ("manifest.json", manifest),
+        ("report.json", serde_json::to_value(&report).map_err(|e| e.to_string())?),
+        ("debug_info.json", debug_info),
+        ("state.json", state),
+        ("events.json", serde_json::to_value(&events).map_err(|e| e.to_string())?),
+        ("config.json", config),
+        ("interfaces.json", Value::Array(interfaces)),
+    ] {
+        let bytes = serde_json::to_vec_pretty(&value).map_err(|e| e.to_string())?;
+        entries.push((name.to_string(), bytes));
+    }
+    entries.extend(collect_logs(&crate::user_data::get_app_root().join("logs")));
+
+    let dir = svc.data_directory().join("diagnostics");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("创建目录失败: {}", e))?;
+    let path = dir.join(format!(
+        "huanvae-lan-diagnostics-{}.zip",
+        Local::now().format("%Y%m%d-%H%M%S")
+    ));
+
+    service::spawn_blocking({
+        let path = path.clone();
+        move || {
+            for (_, content) in &mut entries {
+                *content = redactor.redact(content);
+            }
+            write_bundle(&path, &entries)
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    log::info!("[LanTransfer] 诊断包已导出: {}", path.display());
+    Ok(path)
+}
+
+/// 诊断包脱敏器
+///
+/// 把信任设备 ID 替换为 `redacted-` 加上 (盐值 + ID) 的 SHA-256 摘要前 16 位。
+/// 盐值每个诊断包随机生成，摘要无法通过枚举已知 ID 反查，也不能跨诊断包关联。
+struct Redactor {
+    /// (原始 ID, 替换文本)
+    replacements: Vec<(String, String)>,
+}
+
+impl Redactor {
+    fn new(ids: impl IntoIterator<Item = String>) -> Self {
+        let salt = uuid::Uuid::new_v4();
+        let mut replacements: Vec<(String, String)> = Vec::new();
+        for id in ids {
+            if id.is_empty() || replacements.iter().any(|(raw, _)| *raw == id) {
+                continue;
+            }
+            let digest = Sha256::new()
+                .chain_update(salt.as_bytes())
+                .chain_update(id.as_bytes())
+                .finalize();
+            let replacement = format!("redacted-{}", &hex::encode(digest)[..16]);
+            replacements.push((id, replacement));
+        }
+        // 较长的 ID 先替换，避免一个 ID 是另一个 ID 的一部分时只替换了一半
+        replacements.sort_by_key(|(raw, _)| std::cmp::Reverse(raw.len()));
+        Self { replacements }
+    }
+
+    /// 替换内容中出现的所有信任设备 ID（按字节匹配，日志末尾截断处不是完整 UTF-8 也能处理）
+    fn redact(&self, content: &[u8]) -> Vec<u8> {
+        let mut content = content.to_vec();
+        for (raw, replacement) in &self.replacements {
+            content = replace_bytes(&content, raw.as_bytes(), replacement.as_bytes());
+        }
+        content
+    }
+}
+
+/// 字节串替换
+fn replace_bytes(content: &[u8], from: &[u8], to: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(pos) = rest.windows(from.len()).position(|w| w == from) {
+        result.extend_from_slice(&rest[..pos]);
+        result.extend_from_slice(to);
+        rest = &rest[pos + from.len()..];
+    }
+    result.extend_from_slice(rest);
+    result
+}
+
+/// 读取日志目录中最新的日志文件（每个文件只取末尾 MAX_LOG_BYTES）
+fn collect_logs(dir: &Path) -> Vec<(String, Vec<u8>)> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let Ok(read_dir) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut files: Vec<(std::time::SystemTime, PathBuf)> = read_dir
+        .flatten()
+        .filter(|e| e.path().is_file())
+        .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.path())))
+        .collect();
+    files.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+
+    files
+        .into_iter()
+        .take(MAX_LOG_FILES)
+        .filter_map(|(_, path)| {
+            let mut file = std::fs::File::open(&path).ok()?;
+            let len = file.metadata().ok()?.len();
+            file.seek(SeekFrom::Start(len.saturating_sub(MAX_LOG_BYTES))).ok()?;
+            let mut content = Vec::new();
+            file.read_to_end(&mut content).ok()?;
+            let name = path.file_name()?.to_string_lossy().to_string();
+            Some((format!("logs/{}", name), content))
+        })
+        .collect()
+}
+
+/// 写入 zip 文件
+fn write_bundle(path: &Path, entries: &[(String, Vec<u8>)]) -> Result<(), String> {
+    let file = std::fs::File::create(path).map_err(|e| format!("创建诊断包失败: {}", e))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+
+    for (name, content) in entries {
+        zip.start_file(name.as_str(), options)
+            .map_err(|e| format!("写入 {} 失败: {}", name, e))?;
+        zip.write_all(content)
+            .map_err(|e| format!("写入 {} 失败: {}", name, e))?;
+    }
+
+    zip.finish().map_err(|e| format!("写入诊断包失败: {}", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trusted_device_ids_are_redacted_with_per_bundle_salt() {
+        let ids = || ["0123456789abcdef".to_string(), "0123456789abcdef".to_string()];
+        let redactor = Redactor::new(ids());
+        assert_eq!(redactor.replacements.len(), 1);
+
+        let content = b"from 0123456789abcdef to 0123456789abcdef";
+        let redacted = String::from_utf8(redactor.redact(content)).unwrap();
+        let replacement = &redactor.replacements[0].1;
+        assert!(replacement.starts_with("redacted-"));
+        assert_eq!(redacted, format!("from {} to {}", replacement, replacement));
+
+        // 另一个诊断包使用不同的盐值
+        assert_ne!(Redactor::new(ids()).replacements[0].1, *replacement);
+    }
+
+    #[test]
+    fn bundle_contains_no_raw_trusted_ids() {
+        let trusted = ["a1b2c3d4e5f60718", "ffeeddccbbaa9988"];
+        let dir = std::env::temp_dir().join(format!("huanvae-bundle-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("logs")).unwrap();
+        // 日志末尾截断处可能不是完整的 UTF-8
+        let mut log = format!("[LanTransfer] 收到 {} 的连接请求\n", trusted[0]).into_bytes();
+        log.push(0xff);
+        std::fs::write(dir.join("logs").join("app.log"), log).unwrap();
+
+        let redactor = Redactor::new(trusted.iter().map(|id| id.to_string()));
+        let mut entries: Vec<(String, Vec<u8>)> = [
+            ("state.json", json!({ "devices": [{ "deviceId": trusted[0] }] })),
+            ("events.json", json!([{ "payload": { "fromDevice": { "deviceId": trusted[1] } } }])),
+            ("debug_info.json", json!({ "connections": [format!("{}@10.0.0.2", trusted[1])] })),
+            ("config.json", json!({ "trustedDevices": [{ "deviceId": trusted[0] }, { "deviceId": trusted[1] }] })),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), serde_json::to_vec_pretty(&value).unwrap()))
+        .collect();
+        entries.extend(collect_logs(&dir.join("logs")));
+        for (_, content) in &mut entries {
+            *content = redactor.redact(content);
+        }
+
+        let path = dir.join("bundle.zip");
+        write_bundle(&path, &entries).unwrap();
+
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+        assert_eq!(archive.len(), 5);
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i).unwrap();
+            let mut content = Vec::new();
+            std::io::Read::read_to_end(&mut entry, &mut content).unwrap();
+            for id in trusted {
+                assert!(
+                    !content.windows(id.len()).any(|w| w == id.as_bytes()),
+                    "{} 中包含未脱敏的 ID",
+                    entry.name()
+                );
+            }
+            assert!(content.windows(9).any(|w| w == b"redacted-"), "{}", entry.name());
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn bundle_round_trips_through_zip() {
+        let dir = std::env::temp_dir().join(format!("huanvae-bundle-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("logs")).unwrap();
+        std::fs::write(dir.join("logs").join("app.log"), vec![b'x'; 600 * 1024]).unwrap();
+
+        let mut entries = vec![("manifest.json".to_string(), b"{}".to_vec())];
+        entries.extend(collect_logs(&dir.join("logs")));
+        let path = dir.join("bundle.zip");
+        write_bundle(&path, &entries).unwrap();
+
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+        assert_eq!(archive.len(), 2);
+        assert_eq!(archive.by_name("logs/app.log").unwrap().size(), MAX_LOG_BYTES);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
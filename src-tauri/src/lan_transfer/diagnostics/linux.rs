@@ -5,15 +5,28 @@
 //! - L2: avahi-daemon 服务状态
 //! - L3: UFW 防火墙规则
 //! - L4: firewalld 规则
+//! - L5: nftables / iptables 原始规则（未使用 UFW/firewalld 时）
+//! - L6: systemd-resolved mDNS 响应器（与 avahi 争用 5353）
+//! - L7: VPN / WireGuard 接管默认路由
+//! - L8: 对外公布的 IP 属于 Docker / 虚拟机网桥
+//! - S1-S3: 本机服务自检（端口监听、本机接口访问、mDNS 自发现，见 self_test）
+//!
+//! # 一键修复
+//!
+//! L2/L3/L4/L6 提供白名单修复动作（DiagFixAction），通过 pkexec 以 root 身份执行固定脚本，
+//! 执行后重新运行相关检查项确认修复结果。
 //!
 //! # 参考文档
 //!
 //! - [Avahi 官方文档](https://avahi.org/)
 //! - [UFW 文档](https://help.ubuntu.com/community/UFW)
 //! - [firewalld 文档](https://firewalld.org/documentation/)
+//! - [resolved.conf 文档](https://www.freedesktop.org/software/systemd/man/resolved.conf.html)
 
+use super::self_test::run_self_test;
 use super::types::*;
 use crate::lan_transfer::protocol::SERVICE_PORT;
+use crate::lan_transfer::service;
 use std::process::Command;
 
 /// Linux 诊断器
@@ -35,10 +48,7 @@ impl LinuxDiagnostician {
                 description: "检测本机局域网 IP 地址".into(),
                 status: DiagStatus::Ok,
                 details: format!("本机 IP: {}", ip),
-                fix_suggestion: None,
-                fix_command: None,
-                fix_steps: None,
-                doc_url: None,
+                ..Default::default()
             },
             Err(e) => DiagItem {
                 id: "L1".into(),
@@ -53,7 +63,7 @@ impl LinuxDiagnostician {
                     "检查网络连接状态".into(),
                     "运行 ip addr show 查看网络接口".into(),
                 ]),
-                doc_url: None,
+                ..Default::default()
             },
         }
     }
@@ -78,10 +88,8 @@ impl LinuxDiagnostician {
                         description: "mDNS/DNS-SD 服务发现守护进程".into(),
                         status: DiagStatus::Ok,
                         details: "avahi-daemon 服务正在运行".into(),
-                        fix_suggestion: None,
-                        fix_command: None,
-                        fix_steps: None,
                         doc_url: Some("https://avahi.org/".into()),
+                        ..Default::default()
                     }
                 } else {
                     DiagItem {
@@ -101,7 +109,9 @@ impl LinuxDiagnostician {
                             "启动: sudo systemctl start avahi-daemon".into(),
                             "开机启动: sudo systemctl enable avahi-daemon".into(),
                         ]),
+                        fix_action: Some(DiagFixAction::EnableAvahi),
                         doc_url: Some("https://avahi.org/".into()),
+                        ..Default::default()
                     }
                 }
             }
@@ -127,8 +137,8 @@ impl LinuxDiagnostician {
                     },
                     fix_suggestion: Some("安装 avahi-daemon".into()),
                     fix_command: Some("sudo apt install avahi-daemon".into()),
-                    fix_steps: None,
                     doc_url: Some("https://avahi.org/".into()),
+                    ..Default::default()
                 }
             }
         }
@@ -150,10 +160,7 @@ impl LinuxDiagnostician {
                         description: "Ubuntu/Debian 默认防火墙".into(),
                         status: DiagStatus::Ok,
                         details: "UFW 防火墙未启用，不会阻止连接".into(),
-                        fix_suggestion: None,
-                        fix_command: None,
-                        fix_steps: None,
-                        doc_url: None,
+                        ..Default::default()
                     }
                 } else {
                     // UFW 已启用，检查是否有相关规则
@@ -171,10 +178,7 @@ impl LinuxDiagnostician {
                                 "UFW 已允许 mDNS (5353) 和传输端口 ({})",
                                 SERVICE_PORT
                             ),
-                            fix_suggestion: None,
-                            fix_command: None,
-                            fix_steps: None,
-                            doc_url: None,
+                            ..Default::default()
                         }
                     } else {
                         let mut missing = Vec::new();
@@ -202,7 +206,9 @@ impl LinuxDiagnostician {
                                 format!("运行: sudo ufw allow {}/tcp", SERVICE_PORT),
                                 "重载: sudo ufw reload".into(),
                             ]),
+                            fix_action: Some(DiagFixAction::UfwAllowPorts),
                             doc_url: Some("https://help.ubuntu.com/community/UFW".into()),
+                            ..Default::default()
                         }
                     }
                 }
@@ -214,10 +220,7 @@ impl LinuxDiagnostician {
                 description: "Ubuntu/Debian 默认防火墙".into(),
                 status: DiagStatus::Skipped,
                 details: "UFW 未安装或无权限检测".into(),
-                fix_suggestion: None,
-                fix_command: None,
-                fix_steps: None,
-                doc_url: None,
+                ..Default::default()
             },
         }
     }
@@ -245,10 +248,7 @@ impl LinuxDiagnostician {
                         description: "RHEL/Fedora 防火墙".into(),
                         status: DiagStatus::Ok,
                         details: "firewalld 已允许 mDNS 服务".into(),
-                        fix_suggestion: None,
-                        fix_command: None,
-                        fix_steps: None,
-                        doc_url: None,
+                        ..Default::default()
                     }
                 } else {
                     DiagItem {
@@ -270,10 +270,12 @@ impl LinuxDiagnostician {
                             ),
                             "重载: sudo firewall-cmd --reload".into(),
                         ]),
+                        fix_action: Some(DiagFixAction::FirewalldAllowPorts),
                         doc_url: Some(
                             "https://firewalld.org/documentation/howto/open-a-port-or-service.html"
                                 .into(),
                         ),
+                        ..Default::default()
                     }
                 }
             }
@@ -284,14 +286,333 @@ impl LinuxDiagnostician {
                 description: "RHEL/Fedora 防火墙".into(),
                 status: DiagStatus::Skipped,
                 details: "firewalld 未安装或未运行".into(),
-                fix_suggestion: None,
-                fix_command: None,
-                fix_steps: None,
-                doc_url: None,
+                ..Default::default()
             },
         }
     }
 
+    /// L5: 检查 nftables / iptables 原始规则
+    ///
+    /// 未使用 UFW/firewalld 时，发行版或用户可能直接配置了默认丢弃的 input 链
+    async fn check_raw_firewall(&self) -> DiagItem {
+        let mut item = linux_item(
+            "L5",
+            "nftables / iptables",
+            DiagCategory::Firewall,
+            "检查未经 UFW/firewalld 管理的入站规则",
+        );
+
+        if let Some(manager) = ["ufw", "firewalld"].into_iter().find(|s| systemd_active(s)) {
+            item.status = DiagStatus::Skipped;
+            item.details = format!("防火墙由 {} 管理（见 L3/L4）", manager);
+            return item;
+        }
+
+        // 优先检查 nftables
+        if let Ok(output) = Command::new("nft").args(["list", "ruleset"]).output()
+            && output.status.success()
+        {
+            let ruleset = String::from_utf8_lossy(&output.stdout);
+            let blocking: Vec<NftInputChain> = parse_nft_input_chains(&ruleset)
+                .into_iter()
+                .filter(|c| c.policy_drop && !(c.allows_transfer && c.allows_mdns))
+                .collect();
+
+            if blocking.is_empty() {
+                item.details = "nftables 没有丢弃传输端口或 mDNS 的 input 链".into();
+                return item;
+            }
+
+            item.status = DiagStatus::Warning;
+            item.details = format!(
+                "input 链默认丢弃且未放行 {}/tcp 或 5353/udp: {}",
+                SERVICE_PORT,
+                blocking
+                    .iter()
+                    .map(|c| format!("{} {} {}", c.family, c.table, c.chain))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            item.fix_suggestion = Some("在 input 链中放行传输端口和 mDNS".into());
+            let mut commands = Vec::new();
+            for c in &blocking {
+                commands.push(DiagFixCommand::admin(
+                    format!("放行传输端口（{} {} {}）", c.family, c.table, c.chain),
+                    format!(
+                        "sudo nft insert rule {} {} {} tcp dport {} accept",
+                        c.family, c.table, c.chain, SERVICE_PORT
+                    ),
+                ));
+                commands.push(DiagFixCommand::admin(
+                    format!("放行 mDNS（{} {} {}）", c.family, c.table, c.chain),
+                    format!(
+                        "sudo nft insert rule {} {} {} udp dport 5353 accept",
+                        c.family, c.table, c.chain
+                    ),
+                ));
+            }
+            commands.push(DiagFixCommand::admin(
+                "持久化：将规则写入 /etc/nftables.conf（否则重启后失效）",
+                "sudo sh -c 'nft list ruleset > /etc/nftables.conf'",
+            ));
+            item.fix_command = Some(
+                commands
+                    .iter()
+                    .filter(|c| c.requires_admin)
+                    .map(|c| c.command.clone())
+                    .collect::<Vec<_>>()
+                    .join(" && "),
+            );
+            item.fix_commands = Some(commands);
+            return item;
+        }
+
+        // 回退到 iptables
+        match Command::new("iptables").args(["-S", "INPUT"]).output() {
+            Ok(output) if output.status.success() => {
+                let rules = String::from_utf8_lossy(&output.stdout);
+                let (policy_drop, allows_transfer, allows_mdns) = parse_iptables_input(&rules);
+
+                if !policy_drop || (allows_transfer && allows_mdns) {
+                    item.details = "iptables INPUT 链未阻止传输端口和 mDNS".into();
+                    return item;
+                }
+
+                item.status = DiagStatus::Warning;
+                item.details = format!(
+                    "iptables INPUT 链默认丢弃，且未放行 {}/tcp 或 5353/udp",
+                    SERVICE_PORT
+                );
+                item.fix_suggestion = Some("在 INPUT 链中放行传输端口和 mDNS".into());
+                let commands = vec![
+                    DiagFixCommand::admin(
+                        "放行传输端口",
+                        format!(
+                            "sudo iptables -I INPUT -p tcp --dport {} -j ACCEPT",
+                            SERVICE_PORT
+                        ),
+                    ),
+                    DiagFixCommand::admin(
+                        "放行 mDNS",
+                        "sudo iptables -I INPUT -p udp --dport 5353 -j ACCEPT",
+                    ),
+                    DiagFixCommand::admin(
+                        "持久化规则（Debian/Ubuntu 需安装 iptables-persistent）",
+                        "sudo netfilter-persistent save",
+                    ),
+                ];
+                item.fix_command = Some(
+                    commands[..2]
+                        .iter()
+                        .map(|c| c.command.clone())
+                        .collect::<Vec<_>>()
+                        .join(" && "),
+                );
+                item.fix_commands = Some(commands);
+            }
+            Ok(_) => {
+                item.status = DiagStatus::Unknown;
+                item.details = "读取 nftables/iptables 规则需要 root 权限".into();
+                item.fix_commands = Some(vec![
+                    DiagFixCommand::admin("查看 nftables 规则", "sudo nft list ruleset"),
+                    DiagFixCommand::admin("查看 iptables 规则", "sudo iptables -S INPUT"),
+                ]);
+            }
+            Err(_) => {
+                item.status = DiagStatus::Skipped;
+                item.details = "未安装 nft / iptables".into();
+            }
+        }
+
+        item
+    }
+
+    /// L6: 检查 systemd-resolved 的 mDNS 响应器
+    ///
+    /// systemd-resolved 开启 MulticastDNS 时会与 avahi / 本应用争用 5353 端口，
+    /// 导致部分响应被其吞掉，表现为设备时有时无
+    async fn check_resolved_mdns(&self) -> DiagItem {
+        let mut item = linux_item(
+            "L6",
+            "systemd-resolved mDNS",
+            DiagCategory::Service,
+            "检查 systemd-resolved 是否与 avahi 争用 5353 端口",
+        );
+
+        if !systemd_active("systemd-resolved") {
+            item.status = DiagStatus::Skipped;
+            item.details = "systemd-resolved 未运行".into();
+            return item;
+        }
+
+        let status = Command::new("resolvectl")
+            .arg("status")
+            .output()
+            .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+            .unwrap_or_default();
+
+        match parse_resolved_mdns(&status) {
+            Some(true) => {
+                let avahi = systemd_active("avahi-daemon");
+                item.status = DiagStatus::Warning;
+                item.details = if avahi {
+                    "systemd-resolved 与 avahi-daemon 同时响应 mDNS（5353/udp）".into()
+                } else {
+                    "systemd-resolved 的 mDNS 响应器已开启，可能与本应用争用 5353/udp".into()
+                };
+                item.fix_suggestion =
+                    Some("关闭 systemd-resolved 的 mDNS，由 avahi-daemon 统一负责".into());
+                let commands = vec![
+                    DiagFixCommand::admin(
+                        "关闭 systemd-resolved 的 MulticastDNS",
+                        "sudo mkdir -p /etc/systemd/resolved.conf.d && printf '[Resolve]\\nMulticastDNS=no\\n' | sudo tee /etc/systemd/resolved.conf.d/huanvae-no-mdns.conf",
+                    ),
+                    DiagFixCommand::admin(
+                        "重启 systemd-resolved",
+                        "sudo systemctl restart systemd-resolved",
+                    ),
+                    DiagFixCommand::user("查看 5353 端口占用", "ss -ulpn 'sport = :5353'"),
+                ];
+                item.fix_command =
+                    Some(format!("{} && {}", commands[0].command, commands[1].command));
+                item.fix_commands = Some(commands);
+                item.fix_action = Some(DiagFixAction::DisableResolvedMdns);
+                item.doc_url = Some(
+                    "https://www.freedesktop.org/software/systemd/man/resolved.conf.html".into(),
+                );
+            }
+            Some(false) => {
+                item.details = "systemd-resolved 未开启 mDNS 响应器".into();
+            }
+            None => {
+                item.status = DiagStatus::Unknown;
+                item.details = "无法读取 resolvectl status 输出".into();
+            }
+        }
+
+        item
+    }
+
+    /// L7: 检查 VPN / WireGuard 是否接管默认路由
+    async fn check_vpn_route(&self) -> DiagItem {
+        let mut item = linux_item(
+            "L7",
+            "VPN 路由",
+            DiagCategory::Network,
+            "检查 VPN / WireGuard 接口是否接管了默认路由",
+        );
+
+        // ip route get 能反映 wg-quick 等基于策略路由的接管
+        let route = Command::new("ip")
+            .args(["route", "get", "1.1.1.1"])
+            .output()
+            .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+            .unwrap_or_default();
+        let Some(dev) = parse_route_dev(&route) else {
+            item.status = DiagStatus::Unknown;
+            item.details = "无法获取默认路由".into();
+            return item;
+        };
+
+        let wireguard = Command::new("ip")
+            .args(["-o", "link", "show", "type", "wireguard"])
+            .output()
+            .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+            .unwrap_or_default();
+        let is_wireguard = wireguard.contains(&format!(": {}:", dev));
+
+        if !is_wireguard && !is_vpn_interface(&dev) {
+            item.details = format!("默认路由经由 {}", dev);
+            return item;
+        }
+
+        let local_on_vpn = selected_local_ip()
+            .and_then(|ip| interface_of(&ip))
+            .is_some_and(|name| name == dev);
+
+        item.status = if local_on_vpn {
+            DiagStatus::Error
+        } else {
+            DiagStatus::Warning
+        };
+        item.details = if local_on_vpn {
+            format!("默认路由经由 VPN 接口 {}，本机对外公布的 IP 也属于该接口", dev)
+        } else {
+            format!("默认路由经由 VPN 接口 {}，局域网流量和 mDNS 可能被 VPN 拦截", dev)
+        };
+        item.fix_suggestion =
+            Some("断开 VPN，或在 VPN 客户端中开启\"允许局域网访问\"（拆分隧道）".into());
+
+        let mut commands = Vec::new();
+        if is_wireguard || dev.starts_with("wg") {
+            commands.push(DiagFixCommand::admin(
+                format!("断开 WireGuard 接口 {}", dev),
+                format!("sudo wg-quick down {}", dev),
+            ));
+        }
+        if dev.starts_with("tailscale") {
+            commands.push(DiagFixCommand::user(
+                "使用出口节点时允许访问局域网",
+                "tailscale set --exit-node-allow-lan-access=true",
+            ));
+        }
+        item.fix_command = commands.first().map(|c| c.command.clone());
+        commands.push(DiagFixCommand::user("查看路由表", "ip route show table all"));
+        item.fix_commands = Some(commands);
+        item
+    }
+
+    /// L8: 检查本机 IP 是否选中了 Docker / 虚拟网桥
+    async fn check_virtual_bridge_ip(&self) -> DiagItem {
+        let mut item = linux_item(
+            "L8",
+            "虚拟网桥地址",
+            DiagCategory::Network,
+            "检查对外公布的 IP 是否属于 Docker / 虚拟机网桥",
+        );
+
+        let Some(ip) = selected_local_ip() else {
+            item.status = DiagStatus::Skipped;
+            item.details = "无法获取本机 IP（见 L1）".into();
+            return item;
+        };
+        let Some(name) = interface_of(&ip) else {
+            item.status = DiagStatus::Unknown;
+            item.details = format!("本机 IP {} 不属于任何已知网络接口", ip);
+            return item;
+        };
+
+        if !is_virtual_bridge(&name) {
+            item.details = format!("本机 IP {} 属于物理接口 {}", ip, name);
+            return item;
+        }
+
+        item.status = DiagStatus::Error;
+        item.details = format!(
+            "本机 IP {} 属于虚拟网桥 {}，局域网内其他设备无法访问该地址",
+            ip, name
+        );
+        item.fix_suggestion = Some(
+            "没有可用的局域网默认路由时会选中虚拟网桥，请确认已连接局域网后重新启动局域网传输".into(),
+        );
+        let mut commands = vec![
+            DiagFixCommand::user("查看各接口地址", "ip -4 -o addr show"),
+            DiagFixCommand::user("查看默认路由", "ip route show default"),
+        ];
+        if name.starts_with("docker") || name.starts_with("br-") {
+            commands.push(DiagFixCommand::admin(
+                format!("临时停用 Docker 网桥 {}（Docker 重启后恢复）", name),
+                format!("sudo ip link set {} down", name),
+            ));
+        }
+        item.fix_command = commands
+            .iter()
+            .find(|c| c.requires_admin)
+            .map(|c| c.command.clone());
+        item.fix_commands = Some(commands);
+        item
+    }
+
     /// 获取 Linux 发行版信息
     fn get_os_version() -> String {
         std::fs::read_to_string("/etc/os-release")
@@ -321,7 +642,355 @@ impl Diagnostician for LinuxDiagnostician {
         items.push(self.check_avahi_service().await);
         items.push(self.check_ufw_firewall().await);
         items.push(self.check_firewalld().await);
+        items.push(self.check_raw_firewall().await);
+        items.push(self.check_resolved_mdns().await);
+        items.push(self.check_vpn_route().await);
+        items.push(self.check_virtual_bridge_ip().await);
+        items.extend(run_self_test().await);
 
         DiagReport::from_items("Linux".into(), Self::get_os_version(), items)
     }
 }
+
+// ============================================================================
+// 一键修复
+// ============================================================================
+
+impl LinuxDiagnostician {
+    /// 通过 pkexec 执行白名单内的修复动作，并重新执行相关检查项确认结果
+    ///
+    /// pkexec 会弹出系统授权对话框；用户取消授权时返回 success = false
+    pub async fn apply_fix(&self, action: DiagFixAction) -> Result<DiagFixResult, String> {
+        let has_pkexec = Command::new("which")
+            .arg("pkexec")
+            .output()
+            .map(|o| o.status.success())
+            .unwrap_or(false);
+        if !has_pkexec {
+            return Err("未找到 pkexec，请安装 polkit 或手动执行修复命令".into());
+        }
+
+        let script = fix_script(action);
+        log::info!("[LanTransfer] 执行诊断修复 {:?}: {}", action, script);
+
+        let output = service::spawn_blocking(move || {
+            Command::new("pkexec").args(["/bin/sh", "-c", &script]).output()
+        })
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("启动 pkexec 失败: {}", e))?;
+
+        let exit_code = output.status.code();
+        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        let mut stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        match exit_code {
+            // pkexec: 126 = 用户取消授权对话框，127 = 未授权
+            Some(126) => stderr = format!("已取消授权\n{}", stderr).trim().to_string(),
+            Some(127) => stderr = format!("未获得授权\n{}", stderr).trim().to_string(),
+            _ => {}
+        }
+        log::info!(
+            "[LanTransfer] 诊断修复 {:?} 完成，退出码: {:?}",
+            action, exit_code
+        );
+
+        let mut items = Vec::new();
+        for id in affected_checks(action) {
+            items.push(match *id {
+                "L2" => self.check_avahi_service().await,
+                "L3" => self.check_ufw_firewall().await,
+                "L4" => self.check_firewalld().await,
+                "L5" => self.check_raw_firewall().await,
+                // L6
+                _ => self.check_resolved_mdns().await,
+            });
+        }
+
+        Ok(DiagFixResult {
+            action,
+            success: output.status.success(),
+            exit_code,
+            stdout,
+            stderr,
+            items,
+        })
+    }
+}
+
+/// 修复动作对应的固定脚本（以 root 身份执行，不拼接任何外部输入）
+fn fix_script(action: DiagFixAction) -> String {
+    match action {
+        DiagFixAction::UfwAllowPorts => format!(
+            "ufw allow 5353/udp && ufw allow {}/tcp && ufw reload",
+            SERVICE_PORT
+        ),
+        DiagFixAction::FirewalldAllowPorts => format!(
+            "firewall-cmd --permanent --add-service=mdns && firewall-cmd --permanent --add-port={}/tcp && firewall-cmd --reload",
+            SERVICE_PORT
+        ),
+        DiagFixAction::EnableAvahi => "systemctl enable --now avahi-daemon".into(),
+        DiagFixAction::DisableResolvedMdns => "mkdir -p /etc/systemd/resolved.conf.d && printf '[Resolve]\\nMulticastDNS=no\\n' > /etc/systemd/resolved.conf.d/huanvae-no-mdns.conf && systemctl restart systemd-resolved".into(),
+    }
+}
+
+/// 修复后需要重新执行的检查项
+fn affected_checks(action: DiagFixAction) -> &'static [&'static str] {
+    match action {
+        DiagFixAction::UfwAllowPorts => &["L3", "L5"],
+        DiagFixAction::FirewalldAllowPorts => &["L4", "L5"],
+        DiagFixAction::EnableAvahi => &["L2", "L6"],
+        DiagFixAction::DisableResolvedMdns => &["L6"],
+    }
+}
+
+// ============================================================================
+// 辅助函数
+// ============================================================================
+
+/// 构造检查项（默认状态为通过）
+fn linux_item(id: &str, name: &str, category: DiagCategory, description: &str) -> DiagItem {
+    DiagItem {
+        id: id.into(),
+        name: name.into(),
+        category,
+        description: description.into(),
+        status: DiagStatus::Ok,
+        details: String::new(),
+        ..Default::default()
+    }
+}
+
+/// systemd 服务是否处于 active 状态
+fn systemd_active(unit: &str) -> bool {
+    Command::new("systemctl")
+        .args(["is-active", "--quiet", unit])
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// 对外公布的本机 IP（服务运行时使用实际公布的地址，否则使用自动检测结果）
+fn selected_local_ip() -> Option<String> {
+    crate::lan_transfer::get_lan_transfer_state()
+        .local_device
+        .read()
+        .as_ref()
+        .map(|d| d.ip_address.clone())
+        .or_else(|| local_ip_address::local_ip().ok().map(|ip| ip.to_string()))
+}
+
+/// 查找 IP 所属的网络接口
+fn interface_of(ip: &str) -> Option<String> {
+    local_ip_address::list_afinet_netifas()
+        .ok()?
+        .into_iter()
+        .find(|(_, addr)| addr.to_string() == ip)
+        .map(|(name, _)| name)
+}
+
+/// 是否为常见 VPN 接口名
+fn is_vpn_interface(name: &str) -> bool {
+    const PREFIXES: [&str; 11] = [
+        "wg", "tun", "tap", "tailscale", "zt", "ppp", "nordlynx", "proton", "mullvad", "cscotun",
+        "ipsec",
+    ];
+    PREFIXES.iter().any(|p| name.starts_with(p))
+}
+
+/// 是否为容器 / 虚拟机网桥接口名
+fn is_virtual_bridge(name: &str) -> bool {
+    const PREFIXES: [&str; 12] = [
+        "docker", "br-", "veth", "virbr", "cni", "flannel", "podman", "lxcbr", "lxdbr", "vmnet",
+        "vboxnet", "kube",
+    ];
+    PREFIXES.iter().any(|p| name.starts_with(p))
+}
+
+/// 从 `ip route get` 输出中提取出口接口
+fn parse_route_dev(output: &str) -> Option<String> {
+    let mut fields = output.split_whitespace();
+    fields.find(|f| *f == "dev")?;
+    fields.next().map(str::to_string)
+}
+
+/// 解析 `resolvectl status` 输出，返回全局 mDNS 响应器是否开启
+fn parse_resolved_mdns(output: &str) -> Option<bool> {
+    for line in output.lines().map(str::trim) {
+        // 新版：Protocols: +LLMNR +mDNS -DNSOverTLS ...
+        if let Some(protocols) = line.strip_prefix("Protocols:") {
+            return Some(protocols.split_whitespace().any(|p| p == "+mDNS"));
+        }
+        // 旧版：MulticastDNS setting: yes
+        if let Some(setting) = line.strip_prefix("MulticastDNS setting:") {
+            return Some(setting.trim() == "yes");
+        }
+    }
+    None
+}
+
+/// nftables 中挂载在 input 钩子上的链
+#[derive(Debug, PartialEq)]
+struct NftInputChain {
+    family: String,
+    table: String,
+    chain: String,
+    /// 默认策略为 drop
+    policy_drop: bool,
+    /// 链中有放行传输端口的规则
+    allows_transfer: bool,
+    /// 链中有放行 mDNS 的规则
+    allows_mdns: bool,
+}
+
+/// 解析 `nft list ruleset` 输出中的 input 链
+fn parse_nft_input_chains(ruleset: &str) -> Vec<NftInputChain> {
+    let port = SERVICE_PORT.to_string();
+    let mut chains = Vec::new();
+    let (mut family, mut table) = (String::new(), String::new());
+    let mut current: Option<(String, Vec<&str>)> = None;
+
+    for line in ruleset.lines().map(str::trim) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match (fields.as_slice(), current.as_mut()) {
+            (["table", f, t, "{"], _) => {
+                family = f.to_string();
+                table = t.to_string();
+            }
+            (["chain", name, "{"], None) => current = Some((name.to_string(), Vec::new())),
+            (["}"], Some(_)) => {
+                let (chain, body) = current.take().unwrap_or_default();
+                if !body.iter().any(|l| l.contains("hook input")) {
+                    continue;
+                }
+                let accepts = |needle: &str| {
+                    body.iter()
+                        .any(|l| l.contains("dport") && l.contains(needle) && l.contains("accept"))
+                };
+                chains.push(NftInputChain {
+                    family: family.clone(),
+                    table: table.clone(),
+                    chain,
+                    policy_drop: body.iter().any(|l| l.contains("policy drop")),
+                    allows_transfer: accepts(&port),
+                    allows_mdns: accepts("5353"),
+                });
+            }
+            (_, Some((_, body))) => body.push(line),
+            _ => {}
+        }
+    }
+
+    chains
+}
+
+/// 解析 `iptables -S INPUT` 输出，返回（默认丢弃，放行传输端口，放行 mDNS）
+fn parse_iptables_input(rules: &str) -> (bool, bool, bool) {
+    let port = format!("--dport {}", SERVICE_PORT);
+    let accepts = |needle: &str| {
+        rules
+            .lines()
+            .any(|l| l.starts_with("-A INPUT") && l.contains(needle) && l.contains("-j ACCEPT"))
+    };
+    (
+        rules.lines().any(|l| l.trim() == "-P INPUT DROP"),
+        accepts(&port),
+        accepts("--dport 5353"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nft_input_chain_with_drop_policy() {
+        let ruleset = "table inet filter {
+	chain input {
+		type filter hook input priority filter; policy drop;
+		ct state established,related accept
+		udp dport 5353 accept
+	}
+	chain forward {
+		type filter hook forward priority filter; policy drop;
+	}
+}
+table ip nat {
+	chain prerouting {
+		type nat hook prerouting priority dstnat; policy accept;
+	}
+}
+";
+        let chains = parse_nft_input_chains(ruleset);
+        assert_eq!(
+            chains,
+            vec![NftInputChain {
+                family: "inet".into(),
+                table: "filter".into(),
+                chain: "input".into(),
+                policy_drop: true,
+                allows_transfer: false,
+                allows_mdns: true,
+            }]
+        );
+
+        let allowed = ruleset.replace(
+            "udp dport 5353 accept",
+            &format!("udp dport 5353 accept\n\t\ttcp dport {} accept", SERVICE_PORT),
+        );
+        assert!(parse_nft_input_chains(&allowed)[0].allows_transfer);
+    }
+
+    #[test]
+    fn iptables_input_policy_and_rules() {
+        let rules = format!(
+            "-P INPUT DROP\n-A INPUT -p udp -m udp --dport 5353 -j ACCEPT\n-A INPUT -p tcp -m tcp --dport {} -j ACCEPT\n",
+            SERVICE_PORT
+        );
+        assert_eq!(parse_iptables_input(&rules), (true, true, true));
+        assert_eq!(parse_iptables_input("-P INPUT ACCEPT\n"), (false, false, false));
+    }
+
+    #[test]
+    fn resolved_mdns_setting() {
+        assert_eq!(
+            parse_resolved_mdns("Global\n       Protocols: +LLMNR +mDNS -DNSOverTLS DNSSEC=no/unsupported\n"),
+            Some(true)
+        );
+        assert_eq!(
+            parse_resolved_mdns("Global\n       Protocols: -LLMNR -mDNS -DNSOverTLS\n"),
+            Some(false)
+        );
+        assert_eq!(parse_resolved_mdns("MulticastDNS setting: yes\n"), Some(true));
+        assert_eq!(parse_resolved_mdns(""), None);
+    }
+
+    #[test]
+    fn fix_scripts_are_fixed_and_rechecked() {
+        assert_eq!(
+            fix_script(DiagFixAction::UfwAllowPorts),
+            format!("ufw allow 5353/udp && ufw allow {}/tcp && ufw reload", SERVICE_PORT)
+        );
+        assert!(
+            fix_script(DiagFixAction::FirewalldAllowPorts)
+                .contains(&format!("--add-port={}/tcp", SERVICE_PORT))
+        );
+        assert_eq!(affected_checks(DiagFixAction::EnableAvahi), ["L2", "L6"]);
+        assert_eq!(
+            serde_json::to_string(&DiagFixAction::DisableResolvedMdns).unwrap(),
+            "\"disable_resolved_mdns\""
+        );
+    }
+
+    #[test]
+    fn route_and_interface_classification() {
+        assert_eq!(
+            parse_route_dev("1.1.1.1 dev wg0 table 51820 src 10.0.0.2 uid 1000\n    cache\n"),
+            Some("wg0".into())
+        );
+        assert!(is_vpn_interface("tailscale0"));
+        assert!(!is_vpn_interface("wlp2s0"));
+        assert!(is_virtual_bridge("docker0"));
+        assert!(is_virtual_bridge("br-3f2a9c"));
+        assert!(!is_virtual_bridge("enp3s0"));
+    }
+}
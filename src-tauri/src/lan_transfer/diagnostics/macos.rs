@@ -5,12 +5,14 @@
 //! - M2: 应用防火墙状态
 //! - M3: 阻止所有传入连接选项
 //! - M4: Bonjour 服务状态
+//! - S1-S3: 本机服务自检（端口监听、本机接口访问、mDNS 自发现，见 self_test）
 //!
 //! # 参考文档
 //!
 //! - [macOS 防火墙设置](https://support.apple.com/zh-cn/guide/mac-help/mh34041/mac)
 //! - [Bonjour 开发者文档](https://developer.apple.com/bonjour/)
 
+use super::self_test::run_self_test;
 use super::types::*;
 use std::process::Command;
 
@@ -33,10 +35,7 @@ impl MacOSDiagnostician {
                 description: "检测本机局域网 IP 地址".into(),
                 status: DiagStatus::Ok,
                 details: format!("本机 IP: {}", ip),
-                fix_suggestion: None,
-                fix_command: None,
-                fix_steps: None,
-                doc_url: None,
+                ..Default::default()
             },
             Err(e) => DiagItem {
                 id: "M1".into(),
@@ -46,12 +45,11 @@ impl MacOSDiagnostician {
                 status: DiagStatus::Error,
                 details: format!("无法获取本机 IP: {}", e),
                 fix_suggestion: Some("请检查网络连接".into()),
-                fix_command: None,
                 fix_steps: Some(vec![
                     "检查 WiFi 或有线网络连接".into(),
                     "打开「系统设置 → 网络」查看连接状态".into(),
                 ]),
-                doc_url: None,
+                ..Default::default()
             },
         }
     }
@@ -74,10 +72,7 @@ impl MacOSDiagnostician {
                         description: "macOS 应用防火墙状态".into(),
                         status: DiagStatus::Ok,
                         details: "应用防火墙已禁用".into(),
-                        fix_suggestion: None,
-                        fix_command: None,
-                        fix_steps: None,
-                        doc_url: None,
+                        ..Default::default()
                     }
                 } else {
                     DiagItem {
@@ -88,7 +83,6 @@ impl MacOSDiagnostician {
                         status: DiagStatus::Warning,
                         details: "应用防火墙已启用，请确保本应用被允许接收入站连接".into(),
                         fix_suggestion: Some("在防火墙设置中将本应用添加到允许列表".into()),
-                        fix_command: None,
                         fix_steps: Some(vec![
                             "打开「系统设置 → 网络 → 防火墙」".into(),
                             "点击「选项...」".into(),
@@ -98,6 +92,7 @@ impl MacOSDiagnostician {
                         doc_url: Some(
                             "https://support.apple.com/zh-cn/guide/mac-help/mh34041/mac".into(),
                         ),
+                        ..Default::default()
                     }
                 }
             }
@@ -108,10 +103,7 @@ impl MacOSDiagnostician {
                 description: "macOS 应用防火墙状态".into(),
                 status: DiagStatus::Unknown,
                 details: "无法检测防火墙状态".into(),
-                fix_suggestion: None,
-                fix_command: None,
-                fix_steps: None,
-                doc_url: None,
+                ..Default::default()
             },
         }
     }
@@ -136,10 +128,7 @@ impl MacOSDiagnostician {
                         description: "此选项会阻止所有非系统服务的入站连接".into(),
                         status: DiagStatus::Ok,
                         details: "「阻止所有传入连接」未启用".into(),
-                        fix_suggestion: None,
-                        fix_command: None,
-                        fix_steps: None,
-                        doc_url: None,
+                        ..Default::default()
                     }
                 } else {
                     DiagItem {
@@ -160,6 +149,7 @@ impl MacOSDiagnostician {
                         doc_url: Some(
                             "https://support.apple.com/zh-cn/guide/mac-help/mh34041/mac".into(),
                         ),
+                        ..Default::default()
                     }
                 }
             }
@@ -170,10 +160,7 @@ impl MacOSDiagnostician {
                 description: "此选项会阻止所有非系统服务的入站连接".into(),
                 status: DiagStatus::Unknown,
                 details: "无法检测设置状态".into(),
-                fix_suggestion: None,
-                fix_command: None,
-                fix_steps: None,
-                doc_url: None,
+                ..Default::default()
             },
         }
     }
@@ -194,10 +181,8 @@ impl MacOSDiagnostician {
                 description: "macOS 内置 mDNS 服务 (mDNSResponder)".into(),
                 status: DiagStatus::Ok,
                 details: "mDNSResponder 服务正在运行".into(),
-                fix_suggestion: None,
-                fix_command: None,
-                fix_steps: None,
                 doc_url: Some("https://developer.apple.com/bonjour/".into()),
+                ..Default::default()
             },
             _ => DiagItem {
                 id: "M4".into(),
@@ -215,6 +200,7 @@ impl MacOSDiagnostician {
                     "运行: sudo launchctl kickstart -k system/com.apple.mDNSResponder".into(),
                 ]),
                 doc_url: Some("https://developer.apple.com/bonjour/".into()),
+                ..Default::default()
             },
         }
     }
@@ -243,6 +229,7 @@ impl Diagnostician for MacOSDiagnostician {
         items.push(self.check_firewall_state().await);
         items.push(self.check_block_all().await);
         items.push(self.check_bonjour_service().await);
+        items.extend(run_self_test().await);
 
         DiagReport::from_items("macOS".into(), Self::get_os_version(), items)
     }
@@ -10,6 +10,26 @@
 //! - macOS: 检查应用防火墙、Bonjour 服务
 //! - Android: 提供权限检查项说明（需前端配合）
 //!
+//! # 本机自检
+//!
+//! 各平台诊断器最后执行本机服务自检（S1-S3）：端口监听（失败时定位占用进程）、
+//! 通过本机各网络接口访问自身服务、mDNS 自发现。
+//!
+//! # 一键修复
+//!
+//! Linux 上 `apply_lan_diagnostic_fix` 通过 pkexec 执行白名单修复动作（DiagFixAction），
+//! 并重新执行相关检查项。
+//!
+//! # 诊断包
+//!
+//! `export_lan_diagnostics_bundle` 把诊断报告、调试信息、最近事件、脱敏后的配置、
+//! 网络接口和最近日志打包为带版本号的 zip 文件，供用户随问题反馈发送。
+//!
+//! # 对端探测
+//!
+//! `probe_lan_peer` 对已发现的设备进行主动探测（TCP 连接、/api/info、
+//! 往返延迟、上下行吞吐量），用于排查"能看到设备但发送失败"的问题。
+//!
 //! # 使用示例
 //!
 //! ```rust,ignore
@@ -21,6 +41,9 @@
 //! }
 //! ```
 
+mod bundle;
+mod probe;
+pub(crate) mod self_test;
 mod types;
 
 #[cfg(target_os = "windows")]
@@ -35,6 +58,7 @@ mod macos;
 #[cfg(any(target_os = "android", target_os = "ios"))]
 mod android;
 
+pub use probe::PeerProbe;
 pub use types::*;
 
 #[cfg(target_os = "windows")]
@@ -105,3 +129,80 @@ pub async fn diagnose_lan_transfer() -> Result<DiagReport, String> {
         Err("不支持的操作系统".into())
     }
 }
+
+/// Tauri 命令：探测对端设备的连通性与吞吐量
+///
+/// 对设备列表中的设备执行 TCP 连接、/api/info、往返延迟和上下行吞吐量测试，
+/// 返回诊断报告（对端的设备、操作系统与协议版本见 peer 字段）。
+///
+/// # 参数
+///
+/// - `device_id`: 已发现设备的 ID
+///
+/// # 示例
+///
+/// 前端调用：
+/// ```typescript
+/// const report = await invoke<DiagReport>('probe_lan_peer', { deviceId });
+/// ```
+#[tauri::command]
+pub async fn probe_lan_peer(device_id: String) -> Result<DiagReport, String> {
+    let device = super::get_lan_transfer_state()
+        .devices
+        .read()
+        .get(&device_id)
+        .cloned()
+        .ok_or_else(|| format!("设备不存在: {}", device_id))?;
+
+    Ok(PeerProbe::new(device).probe().await)
+}
+
+/// Tauri 命令：执行诊断修复（仅 Linux）
+///
+/// 通过 pkexec 以管理员身份执行白名单内的修复动作（诊断项的 fixAction），
+/// 返回命令输出和重新执行的相关检查项。
+///
+/// # 示例
+///
+/// 前端调用：
+/// ```typescript
+/// const result = await invoke<DiagFixResult>('apply_lan_diagnostic_fix', {
+///     action: item.fixAction,
+/// });
+/// ```
+#[tauri::command]
+pub async fn apply_lan_diagnostic_fix(action: DiagFixAction) -> Result<DiagFixResult, String> {
+    #[cfg(target_os = "linux")]
+    {
+        LinuxDiagnostician::new().apply_fix(action).await
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = action;
+        Err("一键修复仅支持 Linux".into())
+    }
+}
+
+/// Tauri 命令：导出诊断包
+///
+/// 生成包含诊断报告、调试信息、最近事件、配置（信任设备已脱敏）、网络接口和
+/// 最近日志的 zip 文件，保存在局域网传输数据目录的 diagnostics 子目录中。
+///
+/// # 返回值
+///
+/// - `Ok(String)`: 诊断包文件路径
+///
+/// # 示例
+///
+/// 前端调用：
+/// ```typescript
+/// const path = await invoke<string>('export_lan_diagnostics_bundle');
+/// await revealItemInDir(path);
+/// ```
+#[tauri::command]
+pub async fn export_lan_diagnostics_bundle() -> Result<String, String> {
+    bundle::export_bundle()
+        .await
+        .map(|path| path.to_string_lossy().to_string())
+}
@@ -0,0 +1,454 @@
+//! 对端连通性探测
+//!
+//! 平台诊断只检查本机配置，无法回答"能看到设备但发送失败"的问题。
+//! 此模块对指定设备的实际网络路径进行主动探测。
+//!
+//! 检查项：
+//! - P1: TCP 连接（对端公布的 IP:端口）
+//! - P2: 设备信息接口（/api/info，校验设备 ID 与协议版本）
+//! - P3: 往返延迟（多次请求 /api/info）
+//! - P4: 上行吞吐量（POST /api/probe）
+//! - P5: 下行吞吐量（GET /api/probe?size=）
+//!
+//! TCP 连接失败时，后续检查项标记为跳过。
+
+use super::types::*;
+use crate::lan_transfer::protocol::{DeviceInfo, DiscoveredDevice, PROTOCOL_VERSION};
+use std::time::{Duration, Instant};
+
+/// TCP 连接超时
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// 单次 HTTP 请求超时
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 吞吐量测试超时
+const THROUGHPUT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// 延迟测试次数
+const LATENCY_SAMPLES: usize = 5;
+
+/// 平均延迟超过此值时警告（毫秒）
+const LATENCY_WARNING_MS: f64 = 200.0;
+
+/// 吞吐量测试数据量：2MB
+const THROUGHPUT_TEST_BYTES: usize = 2 * 1024 * 1024;
+
+/// 吞吐量低于此值时警告（MB/s）
+const THROUGHPUT_WARNING_MBPS: f64 = 1.0;
+
+/// 对端诊断器
+pub struct PeerProbe {
+    device: DiscoveredDevice,
+    client: reqwest::Client,
+}
+
+impl PeerProbe {
+    /// 创建对端诊断器
+    pub fn new(device: DiscoveredDevice) -> Self {
+        Self {
+            device,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn base_url(&self) -> String {
+        format!("http://{}:{}", self.device.ip_address, self.device.port)
+    }
+
+    fn address(&self) -> String {
+        format!("{}:{}", self.device.ip_address, self.device.port)
+    }
+
+    /// 构造检查项
+    fn item(
+        id: &str,
+        name: &str,
+        category: DiagCategory,
+        description: &str,
+        status: DiagStatus,
+        details: String,
+    ) -> DiagItem {
+        DiagItem {
+            id: id.into(),
+            name: name.into(),
+            category,
+            description: description.into(),
+            status,
+            details,
+            ..Default::default()
+        }
+    }
+
+    /// P1: TCP 连接
+    async fn check_tcp_connect(&self) -> DiagItem {
+        let address = self.address();
+        let started = Instant::now();
+        let result =
+            tokio::time::timeout(CONNECT_TIMEOUT, tokio::net::TcpStream::connect(&address)).await;
+        let elapsed = started.elapsed().as_secs_f64() * 1000.0;
+
+        let mut item = Self::item(
+            "P1",
+            "TCP 连接",
+            DiagCategory::Network,
+            "连接对端公布的传输端口",
+            DiagStatus::Ok,
+            String::new(),
+        );
+
+        match result {
+            Ok(Ok(_)) => {
+                item.details = format!("已连接 {}，耗时 {:.1} ms", address, elapsed);
+            }
+            Ok(Err(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                item.status = DiagStatus::Error;
+                item.details = format!("{} 拒绝连接: {}", address, e);
+                item.fix_suggestion =
+                    Some("对端未在该端口监听，或对端防火墙主动拒绝了连接".into());
+                item.fix_steps = Some(vec![
+                    "确认对端应用已打开并启用了局域网传输".into(),
+                    "在对端设备上运行诊断，检查防火墙与端口状态".into(),
+                    "对端重启应用后刷新设备列表（IP 或端口可能已变化）".into(),
+                ]);
+            }
+            Ok(Err(e)) => {
+                item.status = DiagStatus::Error;
+                item.details = format!("无法连接 {}: {}", address, e);
+                item.fix_suggestion = Some("本机到对端的网络不可达".into());
+                item.fix_steps = Some(vec![
+                    "确认两台设备连接到同一局域网".into(),
+                    "检查本机是否启用了 VPN 或代理，暂时关闭后重试".into(),
+                ]);
+            }
+            Err(_) => {
+                item.status = DiagStatus::Error;
+                item.details = format!(
+                    "连接 {} 超时（{} 秒）",
+                    address,
+                    CONNECT_TIMEOUT.as_secs()
+                );
+                item.fix_suggestion =
+                    Some("数据包被丢弃，通常是对端防火墙或路由器 AP 隔离导致".into());
+                item.fix_steps = Some(vec![
+                    "在对端设备上放行传输端口（运行对端诊断可获取命令）".into(),
+                    "检查路由器是否开启了 AP 隔离 / 客户端隔离（访客网络通常默认开启）".into(),
+                    "确认两台设备不在不同的子网或 VLAN 中".into(),
+                ]);
+            }
+        }
+
+        item
+    }
+
+    /// P2: 设备信息接口
+    ///
+    /// 返回检查项和对端设备信息（成功时）
+    async fn check_info(&self) -> (DiagItem, Option<DeviceInfo>) {
+        let mut item = Self::item(
+            "P2",
+            "设备信息接口",
+            DiagCategory::Service,
+            "请求对端 /api/info 并校验设备身份",
+            DiagStatus::Ok,
+            String::new(),
+        );
+
+        let response = self
+            .client
+            .get(format!("{}/api/info", self.base_url()))
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .await;
+
+        let info: DeviceInfo = match response {
+            Ok(resp) if resp.status().is_success() => match resp.json().await {
+                Ok(info) => info,
+                Err(e) => {
+                    item.status = DiagStatus::Error;
+                    item.details = format!("响应无法解析: {}", e);
+                    item.fix_suggestion =
+                        Some("该端口上运行的可能不是本应用，或中间有代理篡改了响应".into());
+                    return (item, None);
+                }
+            },
+            Ok(resp) => {
+                item.status = DiagStatus::Error;
+                item.details = format!("对端返回 HTTP {}", resp.status());
+                item.fix_suggestion = Some("该端口上运行的可能不是本应用".into());
+                return (item, None);
+            }
+            Err(e) => {
+                item.status = DiagStatus::Error;
+                item.details = format!("请求失败: {}", e);
+                item.fix_suggestion =
+                    Some("TCP 可以连接但 HTTP 请求失败，可能被安全软件或代理拦截".into());
+                item.fix_steps = Some(vec![
+                    "检查本机与对端是否有安全软件拦截 HTTP 流量".into(),
+                    "检查系统代理设置，局域网地址应绕过代理".into(),
+                ]);
+                return (item, None);
+            }
+        };
+
+        if info.device_id != self.device.device_id {
+            item.status = DiagStatus::Warning;
+            item.details = format!(
+                "该地址上的设备是 {}（{}），而不是 {}",
+                info.device_name, info.device_id, self.device.device_name
+            );
+            item.fix_suggestion =
+                Some("对端 IP 已变化，设备列表中的地址已过期，请刷新设备列表".into());
+        } else if info.version != PROTOCOL_VERSION {
+            item.status = DiagStatus::Warning;
+            item.details = format!(
+                "协议版本不一致：对端 {}，本机 {}",
+                info.version, PROTOCOL_VERSION
+            );
+            item.fix_suggestion = Some("将两台设备更新到相同版本的应用".into());
+        } else {
+            item.details = format!(
+                "{}（{}，协议版本 {}）",
+                info.device_name, info.os, info.version
+            );
+        }
+
+        (item, Some(info))
+    }
+
+    /// P3: 往返延迟
+    async fn check_latency(&self) -> DiagItem {
+        let mut item = Self::item(
+            "P3",
+            "往返延迟",
+            DiagCategory::Network,
+            "多次请求 /api/info 测量往返时间",
+            DiagStatus::Ok,
+            String::new(),
+        );
+
+        let url = format!("{}/api/info", self.base_url());
+        let mut samples = Vec::with_capacity(LATENCY_SAMPLES);
+        let mut failures = 0;
+
+        for _ in 0..LATENCY_SAMPLES {
+            let started = Instant::now();
+            let result = match self.client.get(&url).timeout(REQUEST_TIMEOUT).send().await {
+                Ok(resp) => match resp.error_for_status() {
+                    Ok(resp) => resp.bytes().await.map(|_| ()),
+                    Err(e) => Err(e),
+                },
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => samples.push(started.elapsed().as_secs_f64() * 1000.0),
+                Err(_) => failures += 1,
+            }
+        }
+
+        if samples.is_empty() {
+            item.status = DiagStatus::Error;
+            item.details = format!("{} 次请求全部失败", LATENCY_SAMPLES);
+            item.fix_suggestion = Some("连接不稳定，请检查 Wi-Fi 信号或网线连接".into());
+            return item;
+        }
+
+        let min = samples.iter().cloned().fold(f64::MAX, f64::min);
+        let max = samples.iter().cloned().fold(0.0, f64::max);
+        let avg = samples.iter().sum::<f64>() / samples.len() as f64;
+        item.details = format!(
+            "平均 {:.1} ms（最小 {:.1} ms，最大 {:.1} ms），失败 {}/{}",
+            avg, min, max, failures, LATENCY_SAMPLES
+        );
+
+        if failures > 0 {
+            item.status = DiagStatus::Warning;
+            item.fix_suggestion = Some("部分请求失败，连接不稳定，请检查 Wi-Fi 信号".into());
+        } else if avg > LATENCY_WARNING_MS {
+            item.status = DiagStatus::Warning;
+            item.fix_suggestion = Some(
+                "延迟偏高，流量可能经过 VPN 或远端路由，或无线信号较弱".into(),
+            );
+            item.fix_steps = Some(vec![
+                "关闭 VPN 后重试".into(),
+                "靠近路由器或改用 5GHz / 有线网络".into(),
+            ]);
+        }
+
+        item
+    }
+
+    /// P4: 上行吞吐量
+    async fn check_upload_throughput(&self) -> DiagItem {
+        let item = Self::item(
+            "P4",
+            "上行吞吐量",
+            DiagCategory::Network,
+            "向对端发送测试数据",
+            DiagStatus::Ok,
+            String::new(),
+        );
+
+        let started = Instant::now();
+        let result = self
+            .client
+            .post(format!("{}/api/probe", self.base_url()))
+            .header("Content-Type", "application/octet-stream")
+            .body(vec![0u8; THROUGHPUT_TEST_BYTES])
+            .timeout(THROUGHPUT_TIMEOUT)
+            .send()
+            .await;
+
+        let result = match result {
+            Ok(resp) => resp.error_for_status().map(|_| ()),
+            Err(e) => Err(e),
+        };
+        Self::throughput_result(item, result, started.elapsed())
+    }
+
+    /// P5: 下行吞吐量
+    async fn check_download_throughput(&self) -> DiagItem {
+        let item = Self::item(
+            "P5",
+            "下行吞吐量",
+            DiagCategory::Network,
+            "从对端接收测试数据",
+            DiagStatus::Ok,
+            String::new(),
+        );
+
+        let started = Instant::now();
+        let result = self
+            .client
+            .get(format!(
+                "{}/api/probe?size={}",
+                self.base_url(),
+                THROUGHPUT_TEST_BYTES
+            ))
+            .timeout(THROUGHPUT_TIMEOUT)
+            .send()
+            .await;
+
+        let result = match result {
+            Ok(resp) => match resp.error_for_status() {
+                Ok(resp) => resp.bytes().await.map(|_| ()),
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+        Self::throughput_result(item, result, started.elapsed())
+    }
+
+    /// 根据吞吐量测试结果填充检查项
+    fn throughput_result(
+        mut item: DiagItem,
+        result: Result<(), reqwest::Error>,
+        elapsed: Duration,
+    ) -> DiagItem {
+        match result {
+            Ok(()) => {
+                let mbps = THROUGHPUT_TEST_BYTES as f64
+                    / 1024.0
+                    / 1024.0
+                    / elapsed.as_secs_f64().max(0.001);
+                item.details = format!(
+                    "{} MB 用时 {:.2} 秒，约 {:.1} MB/s",
+                    THROUGHPUT_TEST_BYTES / 1024 / 1024,
+                    elapsed.as_secs_f64(),
+                    mbps
+                );
+                if mbps < THROUGHPUT_WARNING_MBPS {
+                    item.status = DiagStatus::Warning;
+                    item.fix_suggestion =
+                        Some("传输速度很低，大文件传输会很慢或超时".into());
+                    item.fix_steps = Some(vec![
+                        "靠近路由器或改用 5GHz / 有线网络".into(),
+                        "暂停其他占用带宽的下载或同步任务".into(),
+                        "关闭 VPN 后重试".into(),
+                    ]);
+                }
+            }
+            Err(e) if e.status() == Some(reqwest::StatusCode::NOT_FOUND) => {
+                item.status = DiagStatus::Skipped;
+                item.details = "对端版本不支持吞吐量测试".into();
+            }
+            Err(e) if e.is_timeout() => {
+                item.status = DiagStatus::Error;
+                item.details = format!(
+                    "{} 秒内未完成 {} MB 的测试",
+                    THROUGHPUT_TIMEOUT.as_secs(),
+                    THROUGHPUT_TEST_BYTES / 1024 / 1024
+                );
+                item.fix_suggestion = Some(
+                    "小请求正常但大数据量超时，常见于 MTU 不匹配、VPN 或网络严重拥塞".into(),
+                );
+                item.fix_steps = Some(vec![
+                    "关闭 VPN 后重试".into(),
+                    "检查路由器或网卡的 MTU 设置".into(),
+                ]);
+            }
+            Err(e) => {
+                item.status = DiagStatus::Error;
+                item.details = format!("测试失败: {}", e);
+                item.fix_suggestion =
+                    Some("传输数据时连接中断，可能被安全软件拦截或网络不稳定".into());
+            }
+        }
+
+        item
+    }
+
+    /// 执行探测，返回诊断报告
+    ///
+    /// 报告的 os 为本机系统，对端的操作系统与协议版本见 peer 字段
+    pub async fn probe(&self) -> DiagReport {
+        log::info!(
+            "[LanTransfer] 探测对端: {} ({})",
+            self.device.device_name,
+            self.address()
+        );
+
+        let mut items = Vec::new();
+
+        let connect = self.check_tcp_connect().await;
+        let connected = connect.status == DiagStatus::Ok;
+        items.push(connect);
+
+        let mut peer_info = None;
+        if connected {
+            let (info_item, info) = self.check_info().await;
+            items.push(info_item);
+            peer_info = info;
+            items.push(self.check_latency().await);
+            items.push(self.check_upload_throughput().await);
+            items.push(self.check_download_throughput().await);
+        } else {
+            for (id, name, category) in [
+                ("P2", "设备信息接口", DiagCategory::Service),
+                ("P3", "往返延迟", DiagCategory::Network),
+                ("P4", "上行吞吐量", DiagCategory::Network),
+                ("P5", "下行吞吐量", DiagCategory::Network),
+            ] {
+                items.push(Self::item(
+                    id,
+                    name,
+                    category,
+                    "需要 TCP 连接成功",
+                    DiagStatus::Skipped,
+                    "TCP 连接失败，已跳过".into(),
+                ));
+            }
+        }
+
+        let mut report =
+            DiagReport::from_items(std::env::consts::OS.into(), "未知".into(), items);
+        report.peer = Some(DiagPeerInfo {
+            device_id: self.device.device_id.clone(),
+            device_name: self.device.device_name.clone(),
+            address: self.address(),
+            os: peer_info.as_ref().map(|info| info.os.clone()),
+            protocol_version: peer_info.map(|info| info.version),
+        });
+        report
+    }
+}
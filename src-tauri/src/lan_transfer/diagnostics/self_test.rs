@@ -0,0 +1,403 @@
+//! 本机服务自检
+//!
+//! 平台诊断只检查防火墙与系统服务配置，不确认本机服务是否真的可用。
+//! 此模块对本机的 HTTP 服务和 mDNS 广播进行回环自检，各平台诊断器共用。
+//!
+//! 检查项：
+//! - S1: 端口监听（绑定失败时定位占用端口的进程）
+//! - S2: 本机各网络接口访问（逐个接口请求本机 /api/info）
+//! - S3: mDNS 自发现（浏览服务类型，确认能解析到本机广播）
+//!
+//! 说明：本机访问自身地址通常不经过防火墙，S2 通过不代表其他设备可以访问，
+//! 但 S2 失败说明服务本身或本机安全软件存在问题。
+
+use super::types::*;
+use crate::lan_transfer::protocol::{DeviceInfo, SERVICE_TYPE};
+use crate::lan_transfer::{get_lan_transfer_state, server, service};
+use std::time::{Duration, Instant};
+
+/// 单个接口的请求超时
+const INTERFACE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// mDNS 自发现超时
+const MDNS_BROWSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 执行本机服务自检
+pub async fn run_self_test() -> Vec<DiagItem> {
+    vec![
+        check_port_listening(),
+        check_interfaces().await,
+        check_mdns_self_discovery().await,
+    ]
+}
+
+/// 构造检查项
+fn item(id: &str, name: &str, category: DiagCategory, description: &str) -> DiagItem {
+    DiagItem {
+        id: id.into(),
+        name: name.into(),
+        category,
+        description: description.into(),
+        status: DiagStatus::Ok,
+        details: String::new(),
+        ..Default::default()
+    }
+}
+
+/// 服务是否正在运行，返回本机设备信息
+fn running_device() -> Option<DeviceInfo> {
+    let state = get_lan_transfer_state();
+    if !*state.is_running.read() {
+        return None;
+    }
+    state.local_device.read().clone()
+}
+
+// ============================================================================
+// S1: 端口监听
+// ============================================================================
+
+fn check_port_listening() -> DiagItem {
+    let mut item = item(
+        "S1",
+        "端口监听",
+        DiagCategory::Port,
+        "本机传输服务是否成功监听端口",
+    );
+    let port = service::current().port();
+
+    if let Some(error) = server::get_bind_error() {
+        item.status = DiagStatus::Error;
+        let owner = find_port_owner(port);
+        item.details = match &owner {
+            Some(owner) => format!("{}，端口 {} 被 {} 占用", error, port, owner),
+            None => error,
+        };
+        fill_port_in_use_fix(&mut item, port, owner.is_some());
+        return item;
+    }
+
+    if running_device().is_some() {
+        item.details = format!("正在监听 0.0.0.0:{}", port);
+        return item;
+    }
+
+    // 服务未启动：试探端口是否空闲
+    match std::net::TcpListener::bind(("0.0.0.0", port)) {
+        Ok(_) => {
+            item.status = DiagStatus::Skipped;
+            item.details = format!("服务未启动，端口 {} 空闲", port);
+        }
+        Err(e) => {
+            item.status = DiagStatus::Warning;
+            item.details = match find_port_owner(port) {
+                Some(owner) => format!("服务未启动，端口 {} 已被 {} 占用", port, owner),
+                None => format!("服务未启动，端口 {} 无法绑定: {}", port, e),
+            };
+            fill_port_in_use_fix(&mut item, port, true);
+        }
+    }
+
+    item
+}
+
+/// 端口被占用时的修复建议
+fn fill_port_in_use_fix(item: &mut DiagItem, port: u16, owner_known: bool) {
+    item.fix_suggestion = Some(if owner_known {
+        "关闭占用端口的进程（可能是另一个正在运行的本应用实例）后重新启动服务".into()
+    } else {
+        "检查是否有其他程序占用了传输端口".into()
+    });
+    item.fix_command = Some(port_owner_command(port));
+    item.fix_steps = Some(vec![
+        "确认没有同时运行多个本应用实例".into(),
+        "关闭占用端口的程序后，在应用中重新启动局域网传输".into(),
+    ]);
+}
+
+/// 查看端口占用的命令（可直接复制执行）
+fn port_owner_command(port: u16) -> String {
+    if cfg!(target_os = "windows") {
+        format!("netstat -ano | findstr :{}", port)
+    } else if cfg!(target_os = "linux") {
+        format!("sudo ss -ltnp 'sport = :{}'", port)
+    } else {
+        format!("sudo lsof -nP -iTCP:{} -sTCP:LISTEN", port)
+    }
+}
+
+/// 查找监听指定 TCP 端口的进程，返回 "名称 (PID x)"
+fn find_port_owner(port: u16) -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        // users:(("name",pid=123,fd=7))
+        let output = std::process::Command::new("ss")
+            .args(["-H", "-ltnp", &format!("sport = :{}", port)])
+            .output()
+            .ok()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let users = stdout.split("users:((").nth(1)?;
+        let name = users.split('"').nth(1)?;
+        let pid = users
+            .split("pid=")
+            .nth(1)
+            .and_then(|s| s.split(',').next())
+            .unwrap_or("?");
+        Some(format!("{} (PID {})", name, pid))
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        // COMMAND PID USER ...
+        let output = std::process::Command::new("lsof")
+            .args(["-nP", &format!("-iTCP:{}", port), "-sTCP:LISTEN"])
+            .output()
+            .ok()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut fields = stdout.lines().nth(1)?.split_whitespace();
+        let name = fields.next()?;
+        let pid = fields.next()?;
+        Some(format!("{} (PID {})", name, pid))
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        // TCP 0.0.0.0:53317 0.0.0.0:0 LISTENING 1234
+        let output = std::process::Command::new("netstat")
+            .args(["-ano", "-p", "TCP"])
+            .output()
+            .ok()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let suffix = format!(":{}", port);
+        let pid = stdout.lines().find_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            (fields.len() >= 5 && fields[1].ends_with(&suffix) && fields[3] == "LISTENING")
+                .then(|| fields[4].to_string())
+        })?;
+
+        // "name.exe","1234",...
+        let name = std::process::Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/FO", "CSV", "/NH"])
+            .output()
+            .ok()
+            .and_then(|o| {
+                String::from_utf8_lossy(&o.stdout)
+                    .split(',')
+                    .next()
+                    .map(|s| s.trim().trim_matches('"').to_string())
+            })
+            .filter(|s| !s.is_empty() && !s.starts_with("INFO"))
+            .unwrap_or_else(|| "未知进程".into());
+        Some(format!("{} (PID {})", name, pid))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    {
+        let _ = port;
+        None
+    }
+}
+
+// ============================================================================
+// S2: 本机各网络接口访问
+// ============================================================================
+
+async fn check_interfaces() -> DiagItem {
+    let mut item = item(
+        "S2",
+        "本机接口访问",
+        DiagCategory::Port,
+        "通过本机各网络接口地址访问自身传输服务",
+    );
+
+    let Some(local) = running_device() else {
+        item.status = DiagStatus::Skipped;
+        item.details = "服务未启动".into();
+        return item;
+    };
+    if server::get_bind_error().is_some() {
+        item.status = DiagStatus::Skipped;
+        item.details = "端口未监听".into();
+        return item;
+    }
+
+    let port = service::current().port();
+    let mut addresses: Vec<(String, String)> = local_ip_address::list_afinet_netifas()
+        .map(|list| {
+            list.into_iter()
+                .filter(|(_, ip)| ip.is_ipv4())
+                .map(|(name, ip)| (name, ip.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    if !addresses.iter().any(|(_, ip)| *ip == local.ip_address) {
+        addresses.insert(0, ("对外地址".into(), local.ip_address.clone()));
+    }
+
+    let client = reqwest::Client::new();
+    let mut lines = Vec::new();
+    let mut advertised_failed = false;
+    let mut other_failed = false;
+
+    for (name, ip) in &addresses {
+        let started = Instant::now();
+        let result = client
+            .get(format!("http://{}:{}/api/info", ip, port))
+            .timeout(INTERFACE_TIMEOUT)
+            .send()
+            .await;
+        let result = match result {
+            Ok(resp) => resp.json::<DeviceInfo>().await.map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+
+        let advertised = *ip == local.ip_address;
+        let marker = if advertised { "（对外公布）" } else { "" };
+        match result {
+            Ok(info) if info.device_id == local.device_id => {
+                lines.push(format!(
+                    "{} {}{}: 正常 {:.1} ms",
+                    name,
+                    ip,
+                    marker,
+                    started.elapsed().as_secs_f64() * 1000.0
+                ));
+            }
+            Ok(info) => {
+                lines.push(format!(
+                    "{} {}{}: 响应来自其他设备 {}",
+                    name, ip, marker, info.device_name
+                ));
+                advertised_failed |= advertised;
+                other_failed |= !advertised;
+            }
+            Err(e) => {
+                lines.push(format!("{} {}{}: 失败 {}", name, ip, marker, e));
+                advertised_failed |= advertised;
+                other_failed |= !advertised;
+            }
+        }
+    }
+
+    item.details = lines.join("\n");
+    if advertised_failed {
+        item.status = DiagStatus::Error;
+        item.fix_suggestion = Some(format!(
+            "对外公布的地址 {} 无法访问本机服务，其他设备将无法连接",
+            local.ip_address
+        ));
+        item.fix_steps = Some(vec![
+            "检查本机安全软件是否拦截了本应用的网络访问".into(),
+            "网络切换后在应用中重新启动局域网传输，以更新对外地址".into(),
+        ]);
+    } else if other_failed {
+        item.status = DiagStatus::Warning;
+        item.fix_suggestion =
+            Some("部分网络接口无法访问本机服务，通过这些网络连接的设备可能无法发送文件".into());
+    }
+
+    item
+}
+
+// ============================================================================
+// S3: mDNS 自发现
+// ============================================================================
+
+async fn check_mdns_self_discovery() -> DiagItem {
+    let mut item = item(
+        "S3",
+        "mDNS 自发现",
+        DiagCategory::Service,
+        "浏览局域网服务，确认能解析到本机的广播",
+    );
+
+    if !service::current().options().enable_mdns {
+        item.status = DiagStatus::Skipped;
+        item.details = "当前实例未启用 mDNS".into();
+        return item;
+    }
+    let Some(local) = running_device() else {
+        item.status = DiagStatus::Skipped;
+        item.details = "服务未启动".into();
+        return item;
+    };
+
+    let result = service::spawn_blocking(move || browse_for_self(&local))
+        .await
+        .unwrap_or_else(|e| Err(e.to_string()));
+
+    match result {
+        Ok(details) => item.details = details,
+        Err(e) => {
+            item.status = DiagStatus::Error;
+            item.details = e;
+            item.fix_suggestion = Some(
+                "本机无法解析自己的 mDNS 广播，其他设备也很可能无法自动发现本机".into(),
+            );
+            item.fix_steps = Some(vec![
+                "检查防火墙是否放行 5353/udp（mDNS）".into(),
+                "检查系统 mDNS 服务（Avahi / Bonjour / DNS Client）是否运行".into(),
+                "确认当前网络不是访客网络（通常禁止组播）".into(),
+                "无法发现时可在设备列表中手动添加对端 IP".into(),
+            ]);
+        }
+    }
+
+    item
+}
+
+/// 使用独立的 mDNS 守护进程浏览服务类型，等待本机实例被解析
+fn browse_for_self(local: &DeviceInfo) -> Result<String, String> {
+    let daemon =
+        mdns_sd::ServiceDaemon::new().map_err(|e| format!("创建 mDNS 服务失败: {}", e))?;
+    let receiver = daemon
+        .browse(SERVICE_TYPE)
+        .map_err(|e| format!("浏览 {} 失败: {}", SERVICE_TYPE, e))?;
+
+    let started = Instant::now();
+    let deadline = started + MDNS_BROWSE_TIMEOUT;
+    let mut result = Err(format!(
+        "{} 秒内未解析到本机的 {} 实例",
+        MDNS_BROWSE_TIMEOUT.as_secs(),
+        SERVICE_TYPE
+    ));
+
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        let Ok(event) = receiver.recv_timeout(remaining) else {
+            break;
+        };
+        let mdns_sd::ServiceEvent::ServiceResolved(info) = event else {
+            continue;
+        };
+        if info.get_property_val_str("device_id") != Some(local.device_id.as_str()) {
+            continue;
+        }
+
+        let addresses: Vec<String> = info.get_addresses().iter().map(|a| a.to_string()).collect();
+        result = if !addresses.contains(&local.ip_address) {
+            Err(format!(
+                "解析到本机实例，但地址 {:?} 不包含对外地址 {}",
+                addresses, local.ip_address
+            ))
+        } else if info.get_port() != local.port {
+            Err(format!(
+                "解析到本机实例，但端口 {} 与监听端口 {} 不一致",
+                info.get_port(),
+                local.port
+            ))
+        } else {
+            Ok(format!(
+                "{:.1} 秒内解析到本机实例 {}（{}:{}）",
+                started.elapsed().as_secs_f64(),
+                info.get_fullname(),
+                local.ip_address,
+                local.port
+            ))
+        };
+        break;
+    }
+
+    let _ = daemon.stop_browse(SERVICE_TYPE);
+    let _ = daemon.shutdown();
+    result
+}
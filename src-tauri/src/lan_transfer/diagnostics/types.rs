@@ -12,6 +12,7 @@
 //!     name: "网络接口".into(),
 //!     category: DiagCategory::Network,
 //!     // ...
+//!     ..Default::default()
 //! };
 //! ```
 
@@ -24,7 +25,7 @@ use serde::{Deserialize, Serialize};
 /// 诊断状态枚举
 ///
 /// 表示单个检查项的结果状态
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum DiagStatus {
     /// 检查通过，功能正常
@@ -34,6 +35,7 @@ pub enum DiagStatus {
     /// 错误，功能无法正常工作
     Error,
     /// 无法检测（权限不足或命令不存在）
+    #[default]
     Unknown,
     /// 跳过（不适用于当前环境）
     Skipped,
@@ -46,10 +48,11 @@ pub enum DiagStatus {
 /// 诊断项分类
 ///
 /// 用于对检查项进行分组显示
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum DiagCategory {
     /// 网络接口和连接
+    #[default]
     Network,
     /// 防火墙规则
     Firewall,
@@ -67,8 +70,9 @@ pub enum DiagCategory {
 
 /// 单项诊断结果
 ///
-/// 包含检查项的详细信息、状态和修复建议
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// 包含检查项的详细信息、状态和修复建议。
+/// 修复相关字段均为可选，构造时可用 `..Default::default()` 省略
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DiagItem {
     /// 检查项唯一标识（如 W1, L2, M3）
@@ -101,11 +105,100 @@ pub struct DiagItem {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub fix_steps: Option<Vec<String>>,
 
+    /// 结构化修复命令（逐条说明是否需要管理员权限）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix_commands: Option<Vec<DiagFixCommand>>,
+
+    /// 一键修复动作（仅白名单内的修复，见 DiagFixAction）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix_action: Option<DiagFixAction>,
+
     /// 官方文档链接
     #[serde(skip_serializing_if = "Option::is_none")]
     pub doc_url: Option<String>,
 }
 
+/// 诊断修复命令
+///
+/// 与媒体权限指南的 PermissionFixCommand 结构一致，前端可复用同一展示组件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagFixCommand {
+    /// 命令描述
+    pub description: String,
+    /// 要执行的命令
+    pub command: String,
+    /// 是否需要管理员权限
+    pub requires_admin: bool,
+    /// 执行后是否需要重启应用
+    pub requires_restart: bool,
+}
+
+impl DiagFixCommand {
+    /// 需要管理员权限的命令
+    pub fn admin(description: impl Into<String>, command: impl Into<String>) -> Self {
+        Self {
+            description: description.into(),
+            command: command.into(),
+            requires_admin: true,
+            requires_restart: false,
+        }
+    }
+
+    /// 普通用户即可执行的命令（通常用于查看状态）
+    pub fn user(description: impl Into<String>, command: impl Into<String>) -> Self {
+        Self {
+            description: description.into(),
+            command: command.into(),
+            requires_admin: false,
+            requires_restart: false,
+        }
+    }
+}
+
+// ============================================================================
+// 一键修复
+// ============================================================================
+
+/// 一键修复动作（白名单）
+///
+/// 前端只能选择动作，实际执行的命令由后端固定，不接受任意命令
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagFixAction {
+    /// UFW 放行 mDNS (5353/udp) 和传输端口
+    UfwAllowPorts,
+    /// firewalld 放行 mDNS 服务和传输端口
+    FirewalldAllowPorts,
+    /// 启用并启动 avahi-daemon
+    EnableAvahi,
+    /// 关闭 systemd-resolved 的 mDNS 响应器
+    DisableResolvedMdns,
+}
+
+/// 一键修复结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagFixResult {
+    /// 执行的修复动作
+    pub action: DiagFixAction,
+
+    /// 修复命令是否执行成功
+    pub success: bool,
+
+    /// 退出码（被信号终止时为空）
+    pub exit_code: Option<i32>,
+
+    /// 标准输出
+    pub stdout: String,
+
+    /// 标准错误（或失败原因）
+    pub stderr: String,
+
+    /// 修复后重新执行的相关检查项
+    pub items: Vec<DiagItem>,
+}
+
 // ============================================================================
 // 诊断报告
 // ============================================================================
@@ -139,6 +232,30 @@ pub struct DiagReport {
 
     /// 警告数量
     pub warning_count: usize,
+
+    /// 被探测的对端信息（仅对端探测报告包含）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer: Option<DiagPeerInfo>,
+}
+
+/// 对端探测报告中的对端信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagPeerInfo {
+    /// 设备 ID
+    pub device_id: String,
+
+    /// 设备名称
+    pub device_name: String,
+
+    /// 探测的地址（IP:端口）
+    pub address: String,
+
+    /// 对端操作系统（/api/info 不可用时为 None）
+    pub os: Option<String>,
+
+    /// 对端协议版本（/api/info 不可用时为 None）
+    pub protocol_version: Option<String>,
 }
 
 impl DiagReport {
@@ -170,6 +287,7 @@ impl DiagReport {
             quick_fix_commands,
             error_count,
             warning_count,
+            peer: None,
         }
     }
 }
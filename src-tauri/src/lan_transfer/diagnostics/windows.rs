@@ -6,12 +6,14 @@
 //! - W3: mDNS 防火墙规则 (UDP 5353)
 //! - W4: 传输端口防火墙规则 (TCP 53317)
 //! - W5: DNS Client 服务状态
+//! - S1-S3: 本机服务自检（端口监听、本机接口访问、mDNS 自发现，见 self_test）
 //!
 //! # 参考文档
 //!
 //! - [Windows 防火墙配置](https://learn.microsoft.com/zh-cn/windows/security/threat-protection/windows-firewall/)
 //! - [Windows mDNS 支持](https://techcommunity.microsoft.com/blog/networkingblog/mdns-in-the-enterprise/3275777)
 
+use super::self_test::run_self_test;
 use super::types::*;
 use crate::lan_transfer::protocol::SERVICE_PORT;
 use std::process::Command;
@@ -58,9 +60,7 @@ impl WindowsDiagnostician {
                     } else {
                         None
                     },
-                    fix_command: None,
-                    fix_steps: None,
-                    doc_url: None,
+                    ..Default::default()
                 }
             }
             Err(e) => DiagItem {
@@ -71,12 +71,11 @@ impl WindowsDiagnostician {
                 status: DiagStatus::Error,
                 details: format!("无法获取本机 IP: {}", e),
                 fix_suggestion: Some("请检查网络连接，确保已连接到局域网（WiFi 或有线）".into()),
-                fix_command: None,
                 fix_steps: Some(vec![
                     "检查网线是否连接或 WiFi 是否已连接".into(),
                     "打开「设置 → 网络和 Internet」查看连接状态".into(),
                 ]),
-                doc_url: None,
+                ..Default::default()
             },
         }
     }
@@ -104,10 +103,7 @@ impl WindowsDiagnostician {
                         description: "检测网络是否设置为专用网络".into(),
                         status: DiagStatus::Ok,
                         details: format!("当前网络类型: {}", stdout),
-                        fix_suggestion: None,
-                        fix_command: None,
-                        fix_steps: None,
-                        doc_url: None,
+                        ..Default::default()
                     }
                 } else {
                     DiagItem {
@@ -129,6 +125,7 @@ impl WindowsDiagnostician {
                         doc_url: Some(
                             "https://support.microsoft.com/zh-cn/windows/make-a-wi-fi-network-public-or-private-in-windows-0460117d-8d3e-a7ac-f003-7a0da607448d".into(),
                         ),
+                        ..Default::default()
                     }
                 }
             }
@@ -139,10 +136,7 @@ impl WindowsDiagnostician {
                 description: "检测网络是否设置为专用网络".into(),
                 status: DiagStatus::Unknown,
                 details: "无法检测网络类型".into(),
-                fix_suggestion: None,
-                fix_command: None,
-                fix_steps: None,
-                doc_url: None,
+                ..Default::default()
             },
         }
     }
@@ -208,10 +202,7 @@ impl WindowsDiagnostician {
                         description: description.into(),
                         status: DiagStatus::Ok,
                         details: format!("防火墙规则「{}」已启用", rule_name),
-                        fix_suggestion: None,
-                        fix_command: None,
-                        fix_steps: None,
-                        doc_url: None,
+                        ..Default::default()
                     }
                 } else {
                     let cmd = format!(
@@ -243,6 +234,7 @@ impl WindowsDiagnostician {
                         doc_url: Some(
                             "https://learn.microsoft.com/zh-cn/windows/security/threat-protection/windows-firewall/".into(),
                         ),
+                        ..Default::default()
                     }
                 }
             }
@@ -253,10 +245,7 @@ impl WindowsDiagnostician {
                 description: description.into(),
                 status: DiagStatus::Unknown,
                 details: format!("无法检查防火墙规则: {}", e),
-                fix_suggestion: None,
-                fix_command: None,
-                fix_steps: None,
-                doc_url: None,
+                ..Default::default()
             },
         }
     }
@@ -279,10 +268,7 @@ impl WindowsDiagnostician {
                         description: "Windows mDNS 支持依赖 DNS Client 服务".into(),
                         status: DiagStatus::Ok,
                         details: "DNS Client 服务正在运行".into(),
-                        fix_suggestion: None,
-                        fix_command: None,
-                        fix_steps: None,
-                        doc_url: None,
+                        ..Default::default()
                     }
                 } else {
                     DiagItem {
@@ -301,6 +287,7 @@ impl WindowsDiagnostician {
                         doc_url: Some(
                             "https://techcommunity.microsoft.com/blog/networkingblog/mdns-in-the-enterprise/3275777".into(),
                         ),
+                        ..Default::default()
                     }
                 }
             }
@@ -311,10 +298,7 @@ impl WindowsDiagnostician {
                 description: "Windows mDNS 支持依赖 DNS Client 服务".into(),
                 status: DiagStatus::Unknown,
                 details: "无法检查服务状态".into(),
-                fix_suggestion: None,
-                fix_command: None,
-                fix_steps: None,
-                doc_url: None,
+                ..Default::default()
             },
         }
     }
@@ -335,6 +319,7 @@ impl Diagnostician for WindowsDiagnostician {
         items.push(self.check_mdns_firewall().await);
         items.push(self.check_transfer_firewall().await);
         items.push(self.check_dns_client_service().await);
+        items.extend(run_self_test().await);
 
         // 获取 Windows 版本
         let os_version = Command::new("cmd")
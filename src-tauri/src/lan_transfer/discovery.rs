@@ -33,20 +33,34 @@
  * - 传输完成后清除标志，恢复正常的设备验证
  *
  * 更新日志：
+ * - 2026-10-18: 停止服务时写入尚未保存的传输统计
+ * - 2026-10-18: 发现状态归属于服务实例；支持指定端口/对外 IP/数据目录，可关闭 mDNS
+ * - 2026-10-18: 启动服务时清理过期的未完成接收
+ * - 2026-10-18: 启动/停止服务时启动/停止待处理请求过期清理任务
+ * - 2026-10-18: 启动/停止服务时启动/停止点对点连接心跳任务
+ * - 2026-10-18: 设备重新上线时投递排队的文件发送任务
+ * - 2026-10-18: 启动/停止服务时启动/停止文件夹同步任务
+ * - 2026-10-18: 停止服务时关闭网页分享
+ * - 2026-10-18: 好友设备上线/离线时发送 FriendDeviceJoined / FriendDeviceLeft 事件
+ * - 2026-10-18: 设备重新上线时投递排队的局域网聊天消息
  * - 2026-01-25: 添加 refresh_device() 函数，支持按需刷新单个设备信息
  * - 2026-01-25: 修复设备 IP 地址不更新问题，设备重新上线时也发送事件通知前端
  * - 2026-01-25: refresh_device() 改为清除缓存等待自动发现（不重启 browse）
  * - 2026-01-25: 添加活跃传输标志，传输期间暂停设备验证避免误判离线
  */
 
-use super::protocol::{DeviceInfo, DiscoveredDevice, LanTransferEvent, PROTOCOL_VERSION, SERVICE_PORT, SERVICE_TYPE};
-use super::{emit_lan_event, get_lan_transfer_state, server};
+use super::protocol::{DeviceInfo, DiscoveredDevice, LanTransferEvent, PROTOCOL_VERSION, SERVICE_TYPE};
+use super::{
+    chat, emit_lan_event, get_lan_transfer_state, heartbeat, presence, request_expiry, resume,
+    send_queue, server, service, stats, sync, web_share,
+};
 use chrono::Utc;
 use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
-use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
 use std::collections::HashMap;
+use std::net::IpAddr;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use thiserror::Error;
 use tokio::sync::broadcast;
@@ -72,15 +86,9 @@ pub enum DiscoveryError {
 }
 
 // ============================================================================
-// 全局单例
+// 实例状态
 // ============================================================================
 
-/// mDNS 服务守护进程
-static MDNS_DAEMON: OnceCell<Arc<Mutex<Option<ServiceDaemon>>>> = OnceCell::new();
-
-/// 验证任务运行标志
-static VERIFY_TASK_RUNNING: OnceCell<Arc<std::sync::atomic::AtomicBool>> = OnceCell::new();
-
 /// 设备验证间隔（秒）
 const DEVICE_VERIFY_INTERVAL_SECS: u64 = 5;
 
@@ -90,24 +98,33 @@ const DEVICE_VERIFY_TIMEOUT_SECS: u64 = 3;
 /// 最大验证失败次数，超过后主动移除设备
 const MAX_VERIFY_FAILURES: u32 = 3;
 
-/// mDNS fullname 到完整 device_id 的映射
-/// 由于 mDNS instance_name 限制为 15 字符，而 device_id 为 32 字符 UUID，
-/// 需要此映射表来正确处理 ServiceRemoved 事件
-static FULLNAME_TO_DEVICE_ID: OnceCell<Arc<Mutex<HashMap<String, String>>>> = OnceCell::new();
-
-/// 设备验证失败计数器
-/// key: device_id, value: 连续失败次数
-static VERIFY_FAILURE_COUNT: OnceCell<Arc<Mutex<HashMap<String, u32>>>> = OnceCell::new();
+/// 设备发现状态（每个服务实例一份，见 LanTransferService）
+#[derive(Default)]
+pub(crate) struct DiscoveryState {
+    /// mDNS 服务守护进程
+    mdns_daemon: Arc<Mutex<Option<ServiceDaemon>>>,
+    /// 验证任务运行标志
+    verify_task_running: Arc<AtomicBool>,
+    /// mDNS fullname 到完整 device_id 的映射
+    /// 由于 mDNS instance_name 限制为 15 字符，而 device_id 为 32 字符 UUID，
+    /// 需要此映射表来正确处理 ServiceRemoved 事件
+    fullname_to_device_id: Arc<Mutex<HashMap<String, String>>>,
+    /// 设备验证失败计数器
+    /// key: device_id, value: 连续失败次数
+    verify_failure_count: Arc<Mutex<HashMap<String, u32>>>,
+    /// 是否有活跃的传输任务
+    /// 传输期间暂停设备验证，避免高负载时误判设备离线
+    has_active_transfers: Arc<AtomicBool>,
+}
 
-/// 是否有活跃的传输任务
-/// 传输期间暂停设备验证，避免高负载时误判设备离线
-static HAS_ACTIVE_TRANSFERS: OnceCell<Arc<std::sync::atomic::AtomicBool>> = OnceCell::new();
+/// 获取 mDNS 守护进程
+fn get_mdns_daemon() -> Arc<Mutex<Option<ServiceDaemon>>> {
+    service::current().discovery.mdns_daemon.clone()
+}
 
 /// 获取活跃传输标志
-fn get_active_transfer_flag() -> Arc<std::sync::atomic::AtomicBool> {
-    HAS_ACTIVE_TRANSFERS
-        .get_or_init(|| Arc::new(std::sync::atomic::AtomicBool::new(false)))
-        .clone()
+fn get_active_transfer_flag() -> Arc<AtomicBool> {
+    service::current().discovery.has_active_transfers.clone()
 }
 
 /// 设置活跃传输状态
@@ -115,39 +132,32 @@ fn get_active_transfer_flag() -> Arc<std::sync::atomic::AtomicBool> {
 /// 在批量传输开始时设置为 true，结束时设置为 false。
 /// 传输期间设备验证任务会跳过验证，避免高负载时误判设备离线。
 pub fn set_active_transfer(active: bool) {
-    get_active_transfer_flag().store(active, std::sync::atomic::Ordering::SeqCst);
+    get_active_transfer_flag().store(active, Ordering::SeqCst);
     if active {
-        println!("[LanTransfer] 🔄 活跃传输标志已设置，暂停设备验证");
+        log::info!("[LanTransfer] 🔄 活跃传输标志已设置，暂停设备验证");
     } else {
-        println!("[LanTransfer] 🔄 活跃传输标志已清除，恢复设备验证");
+        log::info!("[LanTransfer] 🔄 活跃传输标志已清除，恢复设备验证");
     }
 }
 
+/// 是否有活跃的传输任务
+pub fn has_active_transfer() -> bool {
+    get_active_transfer_flag().load(Ordering::SeqCst)
+}
+
 /// 获取 fullname 到 device_id 的映射表
 fn get_fullname_to_device_id_map() -> Arc<Mutex<HashMap<String, String>>> {
-    FULLNAME_TO_DEVICE_ID
-        .get_or_init(|| Arc::new(Mutex::new(HashMap::new())))
-        .clone()
+    service::current().discovery.fullname_to_device_id.clone()
 }
 
 /// 获取验证失败计数器
 fn get_verify_failure_count_map() -> Arc<Mutex<HashMap<String, u32>>> {
-    VERIFY_FAILURE_COUNT
-        .get_or_init(|| Arc::new(Mutex::new(HashMap::new())))
-        .clone()
+    service::current().discovery.verify_failure_count.clone()
 }
 
-/// 事件广播通道
-static EVENT_SENDER: OnceCell<broadcast::Sender<LanTransferEvent>> = OnceCell::new();
-
-/// 获取事件发送器
+/// 获取事件发送器（当前实例的事件广播通道）
 pub fn get_event_sender() -> broadcast::Sender<LanTransferEvent> {
-    EVENT_SENDER
-        .get_or_init(|| {
-            let (tx, _) = broadcast::channel(100);
-            tx
-        })
-        .clone()
+    service::current().event_sender()
 }
 
 /// 订阅事件
@@ -157,10 +167,8 @@ pub fn subscribe_events() -> broadcast::Receiver<LanTransferEvent> {
 }
 
 /// 获取验证任务运行标志
-fn get_verify_task_flag() -> Arc<std::sync::atomic::AtomicBool> {
-    VERIFY_TASK_RUNNING
-        .get_or_init(|| Arc::new(std::sync::atomic::AtomicBool::new(false)))
-        .clone()
+fn get_verify_task_flag() -> Arc<AtomicBool> {
+    service::current().discovery.verify_task_running.clone()
 }
 
 // ============================================================================
@@ -175,8 +183,8 @@ pub async fn start_service(
 ) -> Result<(), DiscoveryError> {
     let state = get_lan_transfer_state();
 
-    println!("[LanTransfer] ========== 启动服务 ==========");
-    println!("[LanTransfer] 用户: {} ({})", user_nickname, user_id);
+    log::info!("[LanTransfer] ========== 启动服务 ==========");
+    log::info!("[LanTransfer] 用户: {} ({})", user_nickname, user_id);
 
     // 检查是否已在运行，如果是则先停止
     let was_running = {
@@ -185,32 +193,60 @@ pub async fn start_service(
     };
 
     if was_running {
-        println!("[LanTransfer] ⚠ 服务已在运行，正在重启...");
+        log::warn!("[LanTransfer] ⚠ 服务已在运行，正在重启...");
         let _ = stop_service().await; // 先停止服务
-        println!("[LanTransfer] ✓ 旧服务已停止");
+        log::info!("[LanTransfer] ✓ 旧服务已停止");
     }
 
-    // 获取本地 IP 地址
-    println!("[LanTransfer] 正在获取本地 IP 地址...");
-    let local_ip = local_ip_address::local_ip()
-        .map_err(|e| {
-            println!("[LanTransfer] ❌ 获取本地 IP 失败: {}", e);
-            DiscoveryError::LocalIpError(e.to_string())
-        })?;
-    println!("[LanTransfer] ✓ 本地 IP: {}", local_ip);
+    let svc = service::current();
+
+    // 获取本地 IP 地址（实例指定了对外 IP 时直接使用）
+    let local_ip = match svc.options().advertise_ip {
+        Some(ip) => {
+            log::info!("[LanTransfer] ✓ 使用指定的对外 IP: {}", ip);
+            ip
+        }
+        None => {
+            log::info!("[LanTransfer] 正在获取本地 IP 地址...");
+            let ip = local_ip_address::local_ip().map_err(|e| {
+                log::error!("[LanTransfer] ❌ 获取本地 IP 失败: {}", e);
+                DiscoveryError::LocalIpError(e.to_string())
+            })?;
+            log::info!("[LanTransfer] ✓ 本地 IP: {}", ip);
+            ip
+        }
+    };
 
     // 列出所有网络接口
     if let Ok(interfaces) = local_ip_address::list_afinet_netifas() {
-        println!("[LanTransfer] 所有网络接口:");
+        log::info!("[LanTransfer] 所有网络接口:");
         for (name, ip) in interfaces {
-            println!("[LanTransfer]   - {}: {}", name, ip);
+            log::info!("[LanTransfer]   - {}: {}", name, ip);
         }
     }
 
+    // 绑定 HTTP 服务器端口（端口为 0 时由系统分配，需在广播前确定实际端口）
+    log::info!("[LanTransfer] 正在启动 HTTP 服务器 (端口 {})...", svc.options().port);
+    let listener = match server::bind_server(svc.options().port).await {
+        Ok(listener) => {
+            if let Ok(addr) = listener.local_addr() {
+                svc.set_bound_port(addr.port());
+            }
+            server::set_bind_error(None);
+            Some(listener)
+        }
+        Err(e) => {
+            log::error!("[LanTransfer] ❌ HTTP 服务器启动失败: {}", e);
+            server::set_bind_error(Some(e.to_string()));
+            None
+        }
+    };
+    let port = svc.port();
+
     // 获取设备 ID（UUID）
-    println!("[LanTransfer] 正在获取设备 ID...");
+    log::info!("[LanTransfer] 正在获取设备 ID...");
     let device_id = get_device_id()?;
-    println!("[LanTransfer] ✓ 设备 ID: {}", device_id);
+    log::info!("[LanTransfer] ✓ 设备 ID: {}", device_id);
 
     // 获取设备名称（优先使用前端传入的，否则使用 hostname）
     let device_name = custom_device_name
@@ -220,11 +256,11 @@ pub async fn start_service(
                 .map(|h| h.to_string_lossy().to_string())
                 .unwrap_or_else(|_| "Unknown".to_string())
         });
-    println!("[LanTransfer] ✓ 设备名称: {}", device_name);
+    log::info!("[LanTransfer] ✓ 设备名称: {}", device_name);
 
     // 获取操作系统信息
     let os = std::env::consts::OS.to_string();
-    println!("[LanTransfer] ✓ 操作系统: {}", os);
+    log::info!("[LanTransfer] ✓ 操作系统: {}", os);
 
     // 构建本机设备信息
     let device_info = DeviceInfo {
@@ -233,7 +269,7 @@ pub async fn start_service(
         user_id: user_id.clone(),
         user_nickname: user_nickname.clone(),
         ip_address: local_ip.to_string(),
-        port: SERVICE_PORT,
+        port,
         version: PROTOCOL_VERSION.to_string(),
         os,
     };
@@ -244,20 +280,104 @@ pub async fn start_service(
         *local_device = Some(device_info.clone());
     }
 
+    // mDNS 广播与发现（实例关闭 mDNS 时跳过，设备需手动加入设备列表）
+    let browse_receiver = if svc.options().enable_mdns {
+        let (mdns, browse_receiver) =
+            start_mdns(&device_id, &device_name, &user_id, user_nickname, local_ip, port)?;
+
+        // 保存 mDNS 守护进程
+        let daemon_holder = get_mdns_daemon();
+        let mut daemon = daemon_holder.lock();
+        *daemon = Some(mdns);
+        Some(browse_receiver)
+    } else {
+        log::info!("[LanTransfer] ⏭️ 实例未启用 mDNS，跳过广播与发现");
+        None
+    };
+
+    // 启动 HTTP 服务器
+    if let Some(listener) = listener {
+        let server_device_info = device_info.clone();
+        service::spawn(server::run_server(listener, server_device_info));
+    }
+
+    if let Some(browse_receiver) = browse_receiver {
+        // 启动事件监听任务
+        let my_device_id = device_id.clone();
+        service::spawn(async move {
+            handle_mdns_events(browse_receiver, my_device_id).await;
+        });
+
+        // 启动设备验证任务（定期检测设备是否在线）
+        let verify_flag = get_verify_task_flag();
+        verify_flag.store(true, Ordering::SeqCst);
+        let verify_device_id = device_id.clone();
+        service::spawn(async move {
+            run_device_verify_task(verify_device_id).await;
+        });
+    }
+
+    // 启动文件夹同步任务
+    sync::start_sync_task();
+
+    // 启动点对点连接心跳任务
+    heartbeat::start_heartbeat_task();
+
+    // 启动待处理请求过期清理任务
+    request_expiry::start_request_expiry_task();
+
+    // 清理超过保留时长的未完成接收（临时文件、续传信息）
+    service::spawn_blocking(|| {
+        if let Err(e) = resume::cleanup_partial_transfers(None) {
+            log::warn!("[LanTransfer] 清理未完成接收失败: {}", e);
+        }
+    });
+
+    // 标记服务已启动
+    {
+        let mut is_running = state.is_running.write();
+        *is_running = true;
+    }
+
+    // 发送服务状态变化事件
+    let event = LanTransferEvent::ServiceStateChanged { is_running: true };
+    let _ = get_event_sender().send(event.clone());
+    emit_lan_event(&event);
+
+    log::info!("[LanTransfer] ========================================");
+    log::info!("[LanTransfer] ✅ 服务启动成功!");
+    log::info!("[LanTransfer]   设备: {} ({})", device_info.device_name, device_info.ip_address);
+    log::info!("[LanTransfer]   端口: {}", port);
+    log::info!("[LanTransfer]   设备验证间隔: {}秒", DEVICE_VERIFY_INTERVAL_SECS);
+    log::info!("[LanTransfer]   等待发现其他设备...");
+    log::info!("[LanTransfer] ========================================");
+
+    Ok(())
+}
+
+/// 创建 mDNS 守护进程，注册本机服务并开始浏览
+fn start_mdns(
+    device_id: &str,
+    device_name: &str,
+    user_id: &str,
+    user_nickname: String,
+    local_ip: IpAddr,
+    port: u16,
+) -> Result<(ServiceDaemon, mdns_sd::Receiver<ServiceEvent>), DiscoveryError> {
     // 创建 mDNS 服务守护进程
-    println!("[LanTransfer] 正在创建 mDNS 服务...");
+    log::info!("[LanTransfer] 正在创建 mDNS 服务...");
     let mdns = ServiceDaemon::new()
         .map_err(|e| {
-            println!("[LanTransfer] ❌ mDNS 服务创建失败: {}", e);
+            log::error!("[LanTransfer] ❌ mDNS 服务创建失败: {}", e);
             DiscoveryError::ServiceStartFailed(e.to_string())
         })?;
-    println!("[LanTransfer] ✓ mDNS 服务已创建");
+    log::info!("[LanTransfer] ✓ mDNS 服务已创建");
 
     // 创建服务信息
     let mut properties = HashMap::new();
-    properties.insert("device_id".to_string(), device_id.clone());
-    properties.insert("device_name".to_string(), device_name.clone());
-    properties.insert("user_id".to_string(), user_id.clone());
+    properties.insert("device_id".to_string(), device_id.to_string());
+    properties.insert("device_name".to_string(), device_name.to_string());
+    properties.insert("user_id".to_string(), user_id.to_string());
     properties.insert("user_nickname".to_string(), user_nickname);
     properties.insert("version".to_string(), PROTOCOL_VERSION.to_string());
 
@@ -274,12 +394,12 @@ pub async fn start_service(
     // 服务实例名称也需要限制在 15 字节以内
     let instance_name: String = device_id.chars().take(15).collect();
 
-    println!("[LanTransfer] mDNS 配置:");
-    println!("[LanTransfer]   服务类型: {}", SERVICE_TYPE);
-    println!("[LanTransfer]   实例名称: {} (原: {})", instance_name, device_id);
-    println!("[LanTransfer]   主机名: {} (原: {})", host_name, device_name);
-    println!("[LanTransfer]   端口: {}", SERVICE_PORT);
-    println!("[LanTransfer]   IP 地址: {}", local_ip);
+    log::info!("[LanTransfer] mDNS 配置:");
+    log::info!("[LanTransfer]   服务类型: {}", SERVICE_TYPE);
+    log::info!("[LanTransfer]   实例名称: {} (原: {})", instance_name, device_id);
+    log::info!("[LanTransfer]   主机名: {} (原: {})", host_name, device_name);
+    log::info!("[LanTransfer]   端口: {}", port);
+    log::info!("[LanTransfer]   IP 地址: {}", local_ip);
 
     // 直接使用检测到的本地 IP 地址注册服务
     let service_info = ServiceInfo::new(
@@ -287,44 +407,44 @@ pub async fn start_service(
         &instance_name,  // 使用截断后的实例名称
         &host_name,
         local_ip,
-        SERVICE_PORT,
+        port,
         properties,
     )
     .map_err(|e| {
-        println!("[LanTransfer] ❌ 创建 ServiceInfo 失败: {}", e);
+        log::error!("[LanTransfer] ❌ 创建 ServiceInfo 失败: {}", e);
         DiscoveryError::ServiceStartFailed(e.to_string())
     })?;
 
-    println!("[LanTransfer] ✓ ServiceInfo 已创建");
+    log::info!("[LanTransfer] ✓ ServiceInfo 已创建");
 
     // 监控服务注册状态
     let monitor_receiver = mdns.monitor()
         .map_err(|e| {
-            println!("[LanTransfer] ❌ 启动监控失败: {}", e);
+            log::error!("[LanTransfer] ❌ 启动监控失败: {}", e);
             DiscoveryError::ServiceStartFailed(e.to_string())
         })?;
 
     // 注册服务
-    println!("[LanTransfer] 正在注册 mDNS 服务...");
+    log::info!("[LanTransfer] 正在注册 mDNS 服务...");
     let fullname = service_info.get_fullname().to_string();
     mdns.register(service_info.clone())
         .map_err(|e| {
-            println!("[LanTransfer] ❌ 注册服务失败: {}", e);
+            log::error!("[LanTransfer] ❌ 注册服务失败: {}", e);
             DiscoveryError::ServiceStartFailed(e.to_string())
         })?;
-    println!("[LanTransfer] ✓ mDNS 服务注册请求已提交 (fullname: {})", fullname);
+    log::info!("[LanTransfer] ✓ mDNS 服务注册请求已提交 (fullname: {})", fullname);
 
     // 等待服务注册完成（最多 5 秒）
-    println!("[LanTransfer] 等待服务注册确认...");
+    log::info!("[LanTransfer] 等待服务注册确认...");
     let start = std::time::Instant::now();
     let mut registered = false;
     while start.elapsed() < std::time::Duration::from_secs(5) {
         match monitor_receiver.recv_timeout(std::time::Duration::from_millis(100)) {
             Ok(event) => {
-                println!("[LanTransfer] 📬 Monitor 事件: {:?}", event);
+                log::info!("[LanTransfer] 📬 Monitor 事件: {:?}", event);
                 // DaemonEvent::Announce 表示服务公告已发送
                 if format!("{:?}", event).contains("Announce") {
-                    println!("[LanTransfer] ✅ 检测到服务公告事件");
+                    log::info!("[LanTransfer] ✅ 检测到服务公告事件");
                     registered = true;
                     break;
                 }
@@ -340,71 +460,22 @@ pub async fn start_service(
     }
     
     if registered {
-        println!("[LanTransfer] ✓ mDNS 服务公告已发送");
+        log::info!("[LanTransfer] ✓ mDNS 服务公告已发送");
     } else {
-        println!("[LanTransfer] ⚠️ 服务公告确认超时（5秒），继续运行...");
+        log::warn!("[LanTransfer] ⚠️ 服务公告确认超时（5秒），继续运行...");
     }
 
     // 开始浏览服务
-    println!("[LanTransfer] 正在启动服务浏览...");
+    log::info!("[LanTransfer] 正在启动服务浏览...");
     let browse_receiver = mdns
         .browse(SERVICE_TYPE)
         .map_err(|e| {
-            println!("[LanTransfer] ❌ 启动浏览失败: {}", e);
+            log::error!("[LanTransfer] ❌ 启动浏览失败: {}", e);
             DiscoveryError::ServiceStartFailed(e.to_string())
         })?;
-    println!("[LanTransfer] ✓ 服务浏览已启动");
+    log::info!("[LanTransfer] ✓ 服务浏览已启动");
 
-    // 保存 mDNS 守护进程
-    let daemon_holder = MDNS_DAEMON.get_or_init(|| Arc::new(Mutex::new(None)));
-    {
-        let mut daemon = daemon_holder.lock();
-        *daemon = Some(mdns);
-    }
-
-    // 启动 HTTP 服务器
-    println!("[LanTransfer] 正在启动 HTTP 服务器 (端口 {})...", SERVICE_PORT);
-    let server_device_info = device_info.clone();
-    tokio::spawn(async move {
-        if let Err(e) = server::start_server(server_device_info).await {
-            eprintln!("[LanTransfer] ❌ HTTP 服务器启动失败: {}", e);
-        }
-    });
-
-    // 启动事件监听任务
-    let my_device_id = device_id.clone();
-    tokio::spawn(async move {
-        handle_mdns_events(browse_receiver, my_device_id).await;
-    });
-
-    // 启动设备验证任务（定期检测设备是否在线）
-    let verify_flag = get_verify_task_flag();
-    verify_flag.store(true, std::sync::atomic::Ordering::SeqCst);
-    let verify_device_id = device_id.clone();
-    tokio::spawn(async move {
-        run_device_verify_task(verify_device_id).await;
-    });
-
-    // 标记服务已启动
-    {
-        let mut is_running = state.is_running.write();
-        *is_running = true;
-    }
-
-    // 发送服务状态变化事件
-    let event = LanTransferEvent::ServiceStateChanged { is_running: true };
-    let _ = get_event_sender().send(event.clone());
-    emit_lan_event(&event);
-
-    println!("[LanTransfer] ========================================");
-    println!("[LanTransfer] ✅ 服务启动成功!");
-    println!("[LanTransfer]   设备: {} ({})", device_info.device_name, device_info.ip_address);
-    println!("[LanTransfer]   端口: {}", SERVICE_PORT);
-    println!("[LanTransfer]   设备验证间隔: {}秒", DEVICE_VERIFY_INTERVAL_SECS);
-    println!("[LanTransfer]   等待发现其他设备...");
-    println!("[LanTransfer] ========================================");
-
-    Ok(())
+    Ok((mdns, browse_receiver))
 }
 
 /// 停止局域网传输服务
@@ -429,8 +500,8 @@ pub async fn stop_service() -> Result<(), DiscoveryError> {
     // 停止设备验证任务
     {
         let verify_flag = get_verify_task_flag();
-        verify_flag.store(false, std::sync::atomic::Ordering::SeqCst);
-        println!("[LanTransfer] 设备验证任务已停止");
+        verify_flag.store(false, Ordering::SeqCst);
+        log::info!("[LanTransfer] 设备验证任务已停止");
     }
 
     // 断开所有活跃的点对点连接
@@ -442,7 +513,7 @@ pub async fn stop_service() -> Result<(), DiscoveryError> {
         };
 
         for conn_id in connection_ids {
-            println!("[LanTransfer] 断开连接: {}", conn_id);
+            log::info!("[LanTransfer] 断开连接: {}", conn_id);
             // 发送连接关闭事件
             let event = LanTransferEvent::PeerConnectionClosed {
                 connection_id: conn_id.clone(),
@@ -464,16 +535,32 @@ pub async fn stop_service() -> Result<(), DiscoveryError> {
     }
 
     // 停止 mDNS 服务
-    if let Some(daemon_holder) = MDNS_DAEMON.get() {
+    {
+        let daemon_holder = get_mdns_daemon();
         let mut daemon = daemon_holder.lock();
         if let Some(mdns) = daemon.take() {
             let _ = mdns.shutdown();
         }
     }
 
+    // 停止文件夹同步任务
+    sync::stop_sync_task();
+
+    // 停止点对点连接心跳任务
+    heartbeat::stop_heartbeat_task();
+
+    // 停止待处理请求过期清理任务
+    request_expiry::stop_request_expiry_task();
+
+    // 关闭网页分享
+    web_share::stop_web_share();
+
     // 停止 HTTP 服务器
     server::stop_server().await;
 
+    // 写入尚未保存的传输统计
+    stats::flush();
+
     // 清空设备列表
     {
         let mut devices = state.devices.write();
@@ -511,7 +598,7 @@ pub async fn stop_service() -> Result<(), DiscoveryError> {
     let _ = get_event_sender().send(event.clone());
     emit_lan_event(&event);
 
-    println!("[LanTransfer] 服务已停止");
+    log::info!("[LanTransfer] 服务已停止");
 
     Ok(())
 }
@@ -530,19 +617,19 @@ pub async fn stop_service() -> Result<(), DiscoveryError> {
 pub fn refresh_device(device_id: &str) -> Result<(), DiscoveryError> {
     let state = get_lan_transfer_state();
 
-    println!("[LanTransfer] 🔄 开始刷新设备: {}", device_id);
+    log::info!("[LanTransfer] 🔄 开始刷新设备: {}", device_id);
 
     // 1. 从设备列表中移除该设备
     let device_info = {
         let mut devices = state.devices.write();
         if let Some(device) = devices.remove(device_id) {
-            println!(
+            log::info!(
                 "[LanTransfer] 🔄 从列表中移除: {} ({}:{})",
                 device.device_name, device.ip_address, device.port
             );
-            Some((device.device_name.clone(), device.ip_address.clone()))
+            Some(device)
         } else {
-            println!(
+            log::info!(
                 "[LanTransfer] 🔄 设备不在列表中: {}",
                 device_id
             );
@@ -561,7 +648,7 @@ pub fn refresh_device(device_id: &str) -> Result<(), DiscoveryError> {
 
         if let Some(fullname) = fullname_to_remove {
             map.remove(&fullname);
-            println!("[LanTransfer] 🔄 清除映射: {}", fullname);
+            log::info!("[LanTransfer] 🔄 清除映射: {}", fullname);
         }
     }
 
@@ -573,17 +660,18 @@ pub fn refresh_device(device_id: &str) -> Result<(), DiscoveryError> {
     }
 
     // 4. 发送设备离线事件（让前端也移除）
-    if device_info.is_some() {
+    if let Some(device) = device_info {
         let event = LanTransferEvent::DeviceLeft {
             device_id: device_id.to_string(),
         };
         let _ = get_event_sender().send(event.clone());
         emit_lan_event(&event);
+        presence::notify_device_left(&device);
     }
 
     // 5. 不重启 browse，mDNS 会自动重新发现设备
     // 已有的 browse 任务会在设备重新广播时收到 ServiceResolved 事件
-    println!(
+    log::info!(
         "[LanTransfer] 🔄 设备已从缓存移除，等待 mDNS 自动重新发现: {}",
         device_id
     );
@@ -617,14 +705,14 @@ fn get_or_create_device_uuid() -> Result<String, DiscoveryError> {
     {
         let uuid = stored.trim().to_string();
         if !uuid.is_empty() {
-            println!("[LanTransfer] 使用已存储的设备 UUID: {}", &uuid[..8.min(uuid.len())]);
+            log::info!("[LanTransfer] 使用已存储的设备 UUID: {}", &uuid[..8.min(uuid.len())]);
             return Ok(uuid);
         }
     }
 
     // 生成新的 UUID
     let new_uuid = uuid::Uuid::new_v4().to_string().replace('-', "");
-    println!("[LanTransfer] 生成新的设备 UUID: {}", &new_uuid[..8]);
+    log::info!("[LanTransfer] 生成新的设备 UUID: {}", &new_uuid[..8]);
 
     // 确保父目录存在
     if let Some(parent) = uuid_file.parent() {
@@ -633,7 +721,7 @@ fn get_or_create_device_uuid() -> Result<String, DiscoveryError> {
 
     // 保存 UUID
     if let Err(e) = fs::write(&uuid_file, &new_uuid) {
-        println!("[LanTransfer] 警告: 保存设备 UUID 失败: {}", e);
+        log::warn!("[LanTransfer] 警告: 保存设备 UUID 失败: {}", e);
         // 即使保存失败，仍然返回生成的 UUID（本次运行有效）
     }
 
@@ -642,6 +730,11 @@ fn get_or_create_device_uuid() -> Result<String, DiscoveryError> {
 
 /// 获取 UUID 存储路径
 fn get_uuid_storage_path() -> PathBuf {
+    // 实例指定了数据目录时保存在其中（同一进程内的多个实例使用不同设备 ID）
+    if let Some(dir) = service::current().options().data_directory.clone() {
+        return dir.join(".lan_device_uuid");
+    }
+
     // Android：使用应用数据目录
     #[cfg(target_os = "android")]
     {
@@ -667,8 +760,8 @@ async fn handle_mdns_events(
     let state = get_lan_transfer_state();
     let event_sender = get_event_sender();
 
-    println!("[LanTransfer] mDNS 事件监听已启动，等待设备广播...");
-    println!("[LanTransfer] 本机设备 ID: {}", my_device_id);
+    log::info!("[LanTransfer] mDNS 事件监听已启动，等待设备广播...");
+    log::info!("[LanTransfer] 本机设备 ID: {}", my_device_id);
 
     let mut event_count = 0u64;
 
@@ -678,24 +771,24 @@ async fn handle_mdns_events(
                 event_count += 1;
                 match event {
                     ServiceEvent::ServiceResolved(info) => {
-                        println!("[LanTransfer] ────────────────────────────────");
-                        println!("[LanTransfer] 📡 收到 ServiceResolved 事件 #{}", event_count);
-                        println!("[LanTransfer]   全名: {}", info.get_fullname());
-                        println!("[LanTransfer]   主机: {}", info.get_hostname());
-                        println!("[LanTransfer]   端口: {}", info.get_port());
+                        log::info!("[LanTransfer] ────────────────────────────────");
+                        log::info!("[LanTransfer] 📡 收到 ServiceResolved 事件 #{}", event_count);
+                        log::info!("[LanTransfer]   全名: {}", info.get_fullname());
+                        log::info!("[LanTransfer]   主机: {}", info.get_hostname());
+                        log::info!("[LanTransfer]   端口: {}", info.get_port());
 
                         // 打印所有地址
                         let addresses: Vec<_> = info.get_addresses().iter().collect();
-                        println!("[LanTransfer]   地址数量: {}", addresses.len());
+                        log::info!("[LanTransfer]   地址数量: {}", addresses.len());
                         for (i, addr) in addresses.iter().enumerate() {
-                            println!("[LanTransfer]   地址[{}]: {}", i, addr);
+                            log::info!("[LanTransfer]   地址[{}]: {}", i, addr);
                         }
 
                         // 打印所有属性
                         let properties = info.get_properties();
-                        println!("[LanTransfer]   属性:");
+                        log::info!("[LanTransfer]   属性:");
                         for prop in properties.iter() {
-                            println!("[LanTransfer]     {}: {:?}", prop.key(), prop.val_str());
+                            log::info!("[LanTransfer]     {}: {:?}", prop.key(), prop.val_str());
                         }
 
                         let device_id = properties
@@ -705,12 +798,12 @@ async fn handle_mdns_events(
 
                         // 忽略自己
                         if device_id == my_device_id {
-                            println!("[LanTransfer]   ⏭️ 跳过：这是本机设备");
+                            log::info!("[LanTransfer]   ⏭️ 跳过：这是本机设备");
                             continue;
                         }
 
                         if device_id.is_empty() {
-                            println!("[LanTransfer]   ⚠️ 警告：device_id 为空，可能是其他 mDNS 服务");
+                            log::warn!("[LanTransfer]   ⚠️ 警告：device_id 为空，可能是其他 mDNS 服务");
                             continue;
                         }
 
@@ -758,7 +851,7 @@ async fn handle_mdns_events(
                             let map = get_fullname_to_device_id_map();
                             let mut map = map.lock();
                             map.insert(fullname.clone(), device_id.clone());
-                            println!("[LanTransfer]   📝 保存映射: {} -> {}", fullname, device_id);
+                            log::info!("[LanTransfer]   📝 保存映射: {} -> {}", fullname, device_id);
                         }
 
                         // 添加到设备列表
@@ -775,14 +868,14 @@ async fn handle_mdns_events(
                             }
 
                             if is_new {
-                                println!("[LanTransfer] ✅ 发现新设备!");
-                                println!("[LanTransfer]   名称: {}", device_name);
-                                println!("[LanTransfer]   用户: {} ({})", user_nickname, user_id);
-                                println!("[LanTransfer]   IP: {}:{}", ip_address, info.get_port());
+                                log::info!("[LanTransfer] ✅ 发现新设备!");
+                                log::info!("[LanTransfer]   名称: {}", device_name);
+                                log::info!("[LanTransfer]   用户: {} ({})", user_nickname, user_id);
+                                log::info!("[LanTransfer]   IP: {}:{}", ip_address, info.get_port());
                             } else {
                                 // 设备重新响应，更新设备信息（包括可能变化的 IP 地址）
-                                println!("[LanTransfer]   🔄 设备已存在，更新信息");
-                                println!("[LanTransfer]   IP: {}:{}", ip_address, info.get_port());
+                                log::info!("[LanTransfer]   🔄 设备已存在，更新信息");
+                                log::info!("[LanTransfer]   IP: {}:{}", ip_address, info.get_port());
                             }
 
                             // 无论新设备还是已存在设备，都发送事件通知前端
@@ -792,13 +885,29 @@ async fn handle_mdns_events(
                             };
                             let _ = event_sender.send(event.clone());
                             emit_lan_event(&event);
+
+                            if is_new {
+                                presence::notify_device_joined(&device);
+                            }
                         }
+
+                        // 设备重新上线：投递离线期间排队的聊天消息
+                        let flush_device_id = device_id.clone();
+                        service::spawn(async move {
+                            chat::flush_outbox(&flush_device_id).await;
+                        });
+
+                        // 设备重新上线：投递排队的文件发送任务
+                        let queue_device_id = device_id.clone();
+                        service::spawn(async move {
+                            send_queue::deliver_queued_transfers(&queue_device_id).await;
+                        });
                     }
                     ServiceEvent::ServiceRemoved(service_type, fullname) => {
-                        println!("[LanTransfer] ────────────────────────────────");
-                        println!("[LanTransfer] 📴 收到 ServiceRemoved 事件 #{}", event_count);
-                        println!("[LanTransfer]   类型: {}", service_type);
-                        println!("[LanTransfer]   全名: {}", fullname);
+                        log::info!("[LanTransfer] ────────────────────────────────");
+                        log::info!("[LanTransfer] 📴 收到 ServiceRemoved 事件 #{}", event_count);
+                        log::info!("[LanTransfer]   类型: {}", service_type);
+                        log::info!("[LanTransfer]   全名: {}", fullname);
 
                         // 使用映射表查找完整的 device_id
                         // 注意：mDNS fullname 使用截断后的 instance_name（最多15字符），
@@ -811,32 +920,32 @@ async fn handle_mdns_events(
 
                         let device_id = match device_id {
                             Some(id) => {
-                                println!("[LanTransfer]   📝 通过映射找到设备 ID: {}", id);
+                                log::info!("[LanTransfer]   📝 通过映射找到设备 ID: {}", id);
                                 id
                             }
                             None => {
                                 // 回退：尝试从 fullname 提取（可能是旧版本的设备）
                                 let fallback_id = fullname.split('.').next().unwrap_or("").to_string();
-                                println!("[LanTransfer]   ⚠️ 映射未找到，使用回退 ID: {}", fallback_id);
+                                log::warn!("[LanTransfer]   ⚠️ 映射未找到，使用回退 ID: {}", fallback_id);
                                 fallback_id
                             }
                         };
 
                         if device_id.is_empty() {
-                            println!("[LanTransfer]   ⚠️ 无法确定设备 ID，跳过");
+                            log::warn!("[LanTransfer]   ⚠️ 无法确定设备 ID，跳过");
                             continue;
                         }
 
                         if device_id == my_device_id {
-                            println!("[LanTransfer]   ⏭️ 跳过：这是本机设备");
+                            log::info!("[LanTransfer]   ⏭️ 跳过：这是本机设备");
                             continue;
                         }
 
                         // 从设备列表中移除
                         {
                             let mut devices = state.devices.write();
-                            if devices.remove(&device_id).is_some() {
-                                println!("[LanTransfer] ❌ 设备离线: {}", device_id);
+                            if let Some(removed) = devices.remove(&device_id) {
+                                log::warn!("[LanTransfer] ❌ 设备离线: {}", device_id);
                                 
                                 // 清理映射表
                                 {
@@ -857,19 +966,20 @@ async fn handle_mdns_events(
                                 };
                                 let _ = event_sender.send(event.clone());
                                 emit_lan_event(&event);
+                                presence::notify_device_left(&removed);
                             } else {
-                                println!("[LanTransfer]   ℹ️ 设备不在列表中");
+                                log::info!("[LanTransfer]   ℹ️ 设备不在列表中");
                             }
                         }
                     }
                     ServiceEvent::ServiceFound(service_type, fullname) => {
-                        println!("[LanTransfer] 🔍 ServiceFound: {} - {}", service_type, fullname);
+                        log::info!("[LanTransfer] 🔍 ServiceFound: {} - {}", service_type, fullname);
                     }
                     ServiceEvent::SearchStarted(service_type) => {
-                        println!("[LanTransfer] 🚀 SearchStarted: {}", service_type);
+                        log::info!("[LanTransfer] 🚀 SearchStarted: {}", service_type);
                     }
                     ServiceEvent::SearchStopped(service_type) => {
-                        println!("[LanTransfer] 🛑 SearchStopped: {}", service_type);
+                        log::info!("[LanTransfer] 🛑 SearchStopped: {}", service_type);
                     }
                     _ => {
                         // 其他事件类型（未来版本可能添加）
@@ -878,12 +988,12 @@ async fn handle_mdns_events(
             }
             Err(e) => {
                 // 通道关闭，退出循环
-                println!("[LanTransfer] ❌ mDNS 事件通道关闭: {}", e);
+                log::error!("[LanTransfer] ❌ mDNS 事件通道关闭: {}", e);
                 break;
             }
         }
     }
-    println!("[LanTransfer] mDNS 事件监听已结束，共处理 {} 个事件", event_count);
+    log::info!("[LanTransfer] mDNS 事件监听已结束，共处理 {} 个事件", event_count);
 }
 
 /// 设备验证任务
@@ -904,8 +1014,8 @@ async fn handle_mdns_events(
 async fn run_device_verify_task(my_device_id: String) {
     use std::time::Duration;
 
-    println!("[LanTransfer] 🔍 设备验证任务已启动");
-    println!("[LanTransfer] 🔍 验证间隔: {}s, 超时: {}s, 最大失败次数: {}",
+    log::info!("[LanTransfer] 🔍 设备验证任务已启动");
+    log::error!("[LanTransfer] 🔍 验证间隔: {}s, 超时: {}s, 最大失败次数: {}",
         DEVICE_VERIFY_INTERVAL_SECS, DEVICE_VERIFY_TIMEOUT_SECS, MAX_VERIFY_FAILURES);
 
     let verify_flag = get_verify_task_flag();
@@ -913,8 +1023,8 @@ async fn run_device_verify_task(my_device_id: String) {
 
     loop {
         // 检查是否应该停止
-        if !verify_flag.load(std::sync::atomic::Ordering::SeqCst) {
-            println!("[LanTransfer] 🔍 设备验证任务收到停止信号");
+        if !verify_flag.load(Ordering::SeqCst) {
+            log::info!("[LanTransfer] 🔍 设备验证任务收到停止信号");
             break;
         }
 
@@ -922,14 +1032,14 @@ async fn run_device_verify_task(my_device_id: String) {
         tokio::time::sleep(Duration::from_secs(DEVICE_VERIFY_INTERVAL_SECS)).await;
 
         // 再次检查是否应该停止（避免在 sleep 期间服务已停止）
-        if !verify_flag.load(std::sync::atomic::Ordering::SeqCst) {
-            println!("[LanTransfer] 🔍 设备验证任务收到停止信号");
+        if !verify_flag.load(Ordering::SeqCst) {
+            log::info!("[LanTransfer] 🔍 设备验证任务收到停止信号");
             break;
         }
 
         // 检查是否有活跃传输，如果有则跳过本次验证
-        if get_active_transfer_flag().load(std::sync::atomic::Ordering::SeqCst) {
-            println!("[LanTransfer] 🔍 有活跃传输，跳过本次设备验证");
+        if get_active_transfer_flag().load(Ordering::SeqCst) {
+            log::info!("[LanTransfer] 🔍 有活跃传输，跳过本次设备验证");
             continue;
         }
 
@@ -946,9 +1056,8 @@ async fn run_device_verify_task(my_device_id: String) {
 
         // 获取 mDNS daemon
         let mdns_opt = {
-            let daemon_guard = MDNS_DAEMON
-                .get_or_init(|| Arc::new(Mutex::new(None)))
-                .lock();
+            let daemon_holder = get_mdns_daemon();
+            let daemon_guard = daemon_holder.lock();
             daemon_guard.clone()
         };
 
@@ -956,7 +1065,7 @@ async fn run_device_verify_task(my_device_id: String) {
             Some(m) => m,
             None => {
                 // mDNS 服务未运行，退出验证任务
-                println!("[LanTransfer] 🔍 mDNS 服务未运行，验证任务退出");
+                log::info!("[LanTransfer] 🔍 mDNS 服务未运行，验证任务退出");
                 break;
             }
         };
@@ -1001,7 +1110,7 @@ async fn run_device_verify_task(my_device_id: String) {
                     let count_map = get_verify_failure_count_map();
                     let mut count_map = count_map.lock();
                     if count_map.remove(&device_id).is_some() {
-                        println!("[LanTransfer] 🔍 设备 {} 验证成功，重置失败计数", device_id);
+                        log::info!("[LanTransfer] 🔍 设备 {} 验证成功，重置失败计数", device_id);
                     }
                 }
                 Err(e) => {
@@ -1014,14 +1123,14 @@ async fn run_device_verify_task(my_device_id: String) {
                         *count
                     };
 
-                    println!(
+                    log::error!(
                         "[LanTransfer] 🔍 验证设备 {} 失败 ({}/{}): {}",
                         device_id, failure_count, MAX_VERIFY_FAILURES, e
                     );
 
                     // 如果连续失败次数超过阈值，主动移除设备
                     if failure_count >= MAX_VERIFY_FAILURES {
-                        println!(
+                        log::error!(
                             "[LanTransfer] 🔍 设备 {} 连续验证失败 {} 次，主动移除",
                             device_id, failure_count
                         );
@@ -1029,10 +1138,10 @@ async fn run_device_verify_task(my_device_id: String) {
                         // 从设备列表中移除
                         let removed = {
                             let mut devices = state.devices.write();
-                            devices.remove(&device_id).is_some()
+                            devices.remove(&device_id)
                         };
 
-                        if removed {
+                        if let Some(removed) = removed {
                             // 清理映射表
                             {
                                 let map = get_fullname_to_device_id_map();
@@ -1053,8 +1162,9 @@ async fn run_device_verify_task(my_device_id: String) {
                             };
                             let _ = event_sender.send(event.clone());
                             emit_lan_event(&event);
+                            presence::notify_device_left(&removed);
 
-                            println!("[LanTransfer] ❌ 设备已主动移除: {}", device_id);
+                            log::error!("[LanTransfer] ❌ 设备已主动移除: {}", device_id);
                         }
                     }
                 }
@@ -1062,5 +1172,5 @@ async fn run_device_verify_task(my_device_id: String) {
         }
     }
 
-    println!("[LanTransfer] 🔍 设备验证任务已结束");
+    log::info!("[LanTransfer] 🔍 设备验证任务已结束");
 }
\ No newline at end of file
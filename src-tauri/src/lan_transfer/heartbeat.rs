@@ -0,0 +1,433 @@
+/*!
+ * 点对点连接心跳模块
+ *
+ * 定期检测已建立的点对点连接是否仍然有效
+ * （对端应用被强制关闭、笔记本休眠后，连接不会再自动清理）
+ *
+ * 功能：
+ * - 每 heartbeat_interval_secs 秒向所有连接的对端发送 /api/peer-heartbeat
+ * - 连续两个间隔未收到响应：连接降级为 Degraded（PeerConnectionStatusChanged 事件）
+ * - 超过 heartbeat_timeout_secs 未收到响应：关闭连接（PeerConnectionClosed 事件）
+ * - 对端响应 alive=false（已重启、连接已不存在）或设备 ID 不一致：立即关闭连接
+ * - 降级连接恢复响应后回到 Connected
+ * - 重新连接时验证对端身份（verify_peer_identity：回访 /api/info 核对设备 ID 与用户 ID）
+ *
+ * 说明：
+ * - 双方都会发送心跳，收到对方的心跳同样视为对端存活
+ * - 心跳未经身份验证，收到心跳不会用来源 IP 更新连接地址（发送时优先使用最新发现的地址）
+ * - 传输进行中不关闭连接（高负载时心跳可能超时），只做降级
+ * - 间隔与超时可通过 set_heartbeat_settings 配置
+ */
+
+use super::config;
+use super::discovery::{get_event_sender, has_active_transfer};
+use super::protocol::*;
+use super::server::get_active_peer_connections_map;
+use super::service;
+use super::{emit_lan_event, get_lan_transfer_state};
+use futures::future::join_all;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// 单次心跳请求超时（秒）
+const HEARTBEAT_REQUEST_TIMEOUT_SECS: u64 = 5;
+
+// ============================================================================
+// 实例状态
+// ============================================================================
+
+/// 心跳状态（每个服务实例一份，见 LanTransferService）
+#[derive(Default)]
+pub(crate) struct HeartbeatState {
+    /// 每个连接最后一次确认对端存活的时间
+    last_contact: Arc<Mutex<HashMap<String, Instant>>>,
+    /// 心跳任务是否运行
+    task_running: Arc<AtomicBool>,
+}
+
+fn get_last_contact() -> Arc<Mutex<HashMap<String, Instant>>> {
+    service::current().heartbeat.last_contact.clone()
+}
+
+fn get_heartbeat_task_flag() -> Arc<AtomicBool> {
+    service::current().heartbeat.task_running.clone()
+}
+
+// ============================================================================
+// 心跳任务
+// ============================================================================
+
+/// 启动心跳任务（服务启动时调用）
+pub fn start_heartbeat_task() {
+    let flag = get_heartbeat_task_flag();
+    if flag.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    service::spawn(async move {
+        log::info!("[LanTransfer] 💓 连接心跳任务已启动");
+
+        while get_heartbeat_task_flag().load(Ordering::SeqCst) {
+            let interval = config::get_full_config().heartbeat_interval_secs;
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+
+            if !get_heartbeat_task_flag().load(Ordering::SeqCst) {
+                break;
+            }
+
+            run_heartbeat_round().await;
+        }
+
+        log::info!("[LanTransfer] 💓 连接心跳任务已停止");
+    });
+}
+
+/// 停止心跳任务
+pub fn stop_heartbeat_task() {
+    get_heartbeat_task_flag().store(false, Ordering::SeqCst);
+    get_last_contact().lock().clear();
+}
+
+/// 记录对端存活（收到对端心跳时由 server 调用）
+///
+/// 降级的连接会恢复为 Connected
+pub fn record_contact(connection_id: &str) {
+    get_last_contact()
+        .lock()
+        .insert(connection_id.to_string(), Instant::now());
+    set_status(connection_id, PeerConnectionStatus::Connected);
+}
+
+/// 立即检测单个连接（重新连接前调用）
+///
+/// 返回连接是否仍然有效；对端已不认识该连接时会关闭连接
+pub async fn probe_connection(connection_id: &str) -> bool {
+    let connection = {
+        let connections = get_active_peer_connections_map();
+        let connections = connections.lock();
+        connections.get(connection_id).cloned()
+    };
+
+    let Some(connection) = connection else {
+        return false;
+    };
+
+    match send_heartbeat(&connection).await {
+        Ok(true) => {
+            record_contact(connection_id);
+            true
+        }
+        Ok(false) => {
+            close_connection(connection_id, "对端已不存在该连接");
+            false
+        }
+        Err(_) => false,
+    }
+}
+
+/// 执行一轮心跳
+async fn run_heartbeat_round() {
+    let connections: Vec<PeerConnection> = {
+        let connections = get_active_peer_connections_map();
+        let connections = connections.lock();
+        connections.values().cloned().collect()
+    };
+
+    // 清理已不存在的连接记录；新连接以当前时间作为起点
+    {
+        let last_contact = get_last_contact();
+        let mut last_contact = last_contact.lock();
+        last_contact.retain(|id, _| connections.iter().any(|c| &c.connection_id == id));
+        for conn in &connections {
+            last_contact
+                .entry(conn.connection_id.clone())
+                .or_insert_with(Instant::now);
+        }
+    }
+
+    if connections.is_empty() {
+        return;
+    }
+
+    let results = join_all(connections.iter().map(send_heartbeat)).await;
+
+    let config = config::get_full_config();
+    let degrade_after = Duration::from_secs(config.heartbeat_interval_secs * 2);
+    let timeout = Duration::from_secs(config.heartbeat_timeout_secs);
+
+    for (conn, result) in connections.iter().zip(results) {
+        match result {
+            Ok(true) => record_contact(&conn.connection_id),
+            Ok(false) => close_connection(&conn.connection_id, "对端已不存在该连接"),
+            Err(e) => {
+                let elapsed = get_last_contact()
+                    .lock()
+                    .get(&conn.connection_id)
+                    .map(|t| t.elapsed())
+                    .unwrap_or_default();
+
+                log::warn!(
+                    "[LanTransfer] 💓 心跳失败: {} ({}), 已 {} 秒未响应",
+                    conn.peer_device.device_name,
+                    e,
+                    elapsed.as_secs()
+                );
+
+                if elapsed >= timeout && !has_active_transfer() {
+                    close_connection(&conn.connection_id, "心跳超时");
+                } else if elapsed >= degrade_after {
+                    set_status(&conn.connection_id, PeerConnectionStatus::Degraded);
+                }
+            }
+        }
+    }
+}
+
+/// 发送心跳请求
+///
+/// Ok(true): 对端存活且身份一致；Ok(false): 对端已不认识该连接或身份不一致
+async fn send_heartbeat(connection: &PeerConnection) -> Result<bool, String> {
+    let local_device_id = get_lan_transfer_state()
+        .local_device
+        .read()
+        .as_ref()
+        .map(|d| d.device_id.clone())
+        .ok_or_else(|| "本地服务未启动".to_string())?;
+
+    // 优先使用最新发现的地址（对端 IP 可能已变化）
+    let (ip, port) = get_lan_transfer_state()
+        .devices
+        .read()
+        .get(&connection.peer_device.device_id)
+        .map(|d| (d.ip_address.clone(), d.port))
+        .unwrap_or_else(|| {
+            (
+                connection.peer_device.ip_address.clone(),
+                connection.peer_device.port,
+            )
+        });
+
+    let url = format!("http://{}:{}/api/peer-heartbeat", ip, port);
+    let response = reqwest::Client::new()
+        .post(&url)
+        .json(&PeerHeartbeatRequest {
+            connection_id: connection.connection_id.clone(),
+            device_id: local_device_id,
+        })
+        .timeout(Duration::from_secs(HEARTBEAT_REQUEST_TIMEOUT_SECS))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let response: PeerHeartbeatResponse = response.json().await.map_err(|e| e.to_string())?;
+
+    Ok(response.alive && response.device_id == connection.peer_device.device_id)
+}
+
+// ============================================================================
+// 身份验证
+// ============================================================================
+
+/// 验证对端身份（重新连接时调用）
+///
+/// 回访请求来源 IP 的 /api/info，确认该地址上运行的确实是声称的设备，
+/// 且登录用户与已有连接记录一致，防止其他设备冒用 device_id 接管连接
+pub async fn verify_peer_identity(
+    claimed: &DiscoveredDevice,
+    source_ip: &str,
+    known: &DiscoveredDevice,
+) -> bool {
+    let url = format!("http://{}:{}/api/info", source_ip, claimed.port);
+    let info: DeviceInfo = match reqwest::Client::new()
+        .get(&url)
+        .timeout(Duration::from_secs(HEARTBEAT_REQUEST_TIMEOUT_SECS))
+        .send()
+        .await
+    {
+        Ok(resp) => match resp.json().await {
+            Ok(info) => info,
+            Err(e) => {
+                log::error!("[LanTransfer] 身份验证失败，/api/info 响应无效: {}", e);
+                return false;
+            }
+        },
+        Err(e) => {
+            log::error!("[LanTransfer] 身份验证失败，无法访问 {}: {}", url, e);
+            return false;
+        }
+    };
+
+    info.device_id == claimed.device_id
+        && info.device_id == known.device_id
+        && info.user_id == known.user_id
+}
+
+// ============================================================================
+// 内部函数
+// ============================================================================
+
+/// 更新连接状态（状态变化时发送事件）
+fn set_status(connection_id: &str, status: PeerConnectionStatus) {
+    {
+        let connections = get_active_peer_connections_map();
+        let mut connections = connections.lock();
+        let Some(conn) = connections.get_mut(connection_id) else {
+            return;
+        };
+        if conn.status == status {
+            return;
+        }
+        conn.status = status.clone();
+    }
+
+    log::info!(
+        "[LanTransfer] 💓 连接状态变化: {} -> {:?}",
+        connection_id, status
+    );
+
+    let event = LanTransferEvent::PeerConnectionStatusChanged {
+        connection_id: connection_id.to_string(),
+        status,
+    };
+    let _ = get_event_sender().send(event.clone());
+    emit_lan_event(&event);
+}
+
+/// 关闭连接
+pub fn close_connection(connection_id: &str, reason: &str) {
+    let removed = {
+        let connections = get_active_peer_connections_map();
+        let mut connections = connections.lock();
+        connections.remove(connection_id)
+    };
+    get_last_contact().lock().remove(connection_id);
+
+    let Some(conn) = removed else {
+        return;
+    };
+
+    log::info!(
+        "[LanTransfer] 💔 连接已关闭: {} ({}): {}",
+        conn.peer_device.device_name, connection_id, reason
+    );
+
+    let event = LanTransferEvent::PeerConnectionClosed {
+        connection_id: connection_id.to_string(),
+    };
+    let _ = get_event_sender().send(event.clone());
+    emit_lan_event(&event);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lan_transfer::service::{LanTransferService, ServiceOptions};
+    use tokio::net::TcpListener;
+
+    /// 返回一个已关闭的本地端口（心跳请求会立即失败）
+    async fn closed_port() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap().port()
+    }
+
+    fn connection(port: u16) -> PeerConnection {
+        let now = chrono::Utc::now().to_rfc3339();
+        PeerConnection {
+            connection_id: "conn".to_string(),
+            peer_device: DiscoveredDevice {
+                device_id: "peer".to_string(),
+                device_name: "Laptop".to_string(),
+                user_id: "me".to_string(),
+                user_nickname: "Me".to_string(),
+                ip_address: "127.0.0.1".to_string(),
+                port,
+                discovered_at: now.clone(),
+                last_seen: now.clone(),
+            },
+            established_at: now,
+            status: PeerConnectionStatus::Connected,
+            is_initiator: true,
+        }
+    }
+
+    /// 将连接的最后响应时间设为 `secs` 秒前
+    fn last_contact_ago(secs: u64) {
+        let at = Instant::now() - Duration::from_secs(secs);
+        get_last_contact().lock().insert("conn".to_string(), at);
+    }
+
+    fn status() -> Option<PeerConnectionStatus> {
+        get_active_peer_connections_map()
+            .lock()
+            .get("conn")
+            .map(|c| c.status.clone())
+    }
+
+    #[tokio::test]
+    async fn unresponsive_connections_degrade_then_close() {
+        let dir = std::env::temp_dir().join(format!("huanvae-heartbeat-{}", uuid::Uuid::new_v4()));
+        let service = LanTransferService::new(ServiceOptions {
+            enable_mdns: false,
+            data_directory: Some(dir.clone()),
+            ..Default::default()
+        });
+        let mut events = service.subscribe();
+        let port = closed_port().await;
+
+        service
+            .scope(async {
+                *get_lan_transfer_state().local_device.write() = Some(DeviceInfo {
+                    device_id: "me".to_string(),
+                    device_name: "Desktop".to_string(),
+                    user_id: "me".to_string(),
+                    user_nickname: "Me".to_string(),
+                    ip_address: "127.0.0.1".to_string(),
+                    port: 0,
+                    version: PROTOCOL_VERSION.to_string(),
+                    os: std::env::consts::OS.to_string(),
+                });
+                get_active_peer_connections_map()
+                    .lock()
+                    .insert("conn".to_string(), connection(port));
+                let config = config::get_full_config();
+
+                // 刚失去响应：仍为 Connected
+                last_contact_ago(1);
+                run_heartbeat_round().await;
+                assert_eq!(status(), Some(PeerConnectionStatus::Connected));
+
+                // 连续两个间隔未响应：降级
+                last_contact_ago(config.heartbeat_interval_secs * 2);
+                run_heartbeat_round().await;
+                assert_eq!(status(), Some(PeerConnectionStatus::Degraded));
+
+                // 收到对端心跳：恢复
+                record_contact("conn");
+                assert_eq!(status(), Some(PeerConnectionStatus::Connected));
+
+                // 超过超时时间：关闭
+                last_contact_ago(config.heartbeat_timeout_secs);
+                run_heartbeat_round().await;
+                assert_eq!(status(), None);
+                assert!(get_last_contact().lock().is_empty());
+            })
+            .await;
+
+        let mut seen = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            match event {
+                LanTransferEvent::PeerConnectionStatusChanged { status, .. } => {
+                    seen.push(format!("{:?}", status))
+                }
+                LanTransferEvent::PeerConnectionClosed { .. } => seen.push("Closed".to_string()),
+                _ => {}
+            }
+        }
+        assert_eq!(seen, ["Degraded", "Connected", "Closed"]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,227 @@
+/*!
+ * 局域网事件日志模块
+ *
+ * emit_lan_event 和广播通道都是"发出即忘"，网页视图重新加载或窗口隐藏期间的事件会丢失，
+ * 前端无法恢复设备列表、待处理请求和传输进度
+ *
+ * 功能：
+ * - 所有经过 emit_lan_event 的事件按顺序编号（seq 从 1 开始递增）并记录到内存日志
+ * - 日志有容量上限（JOURNAL_CAPACITY），超出时丢弃最旧的事件
+ * - 进度类事件（传输/批量/群发进度、哈希进度）只保留同一对象的最新一条，避免挤掉请求类事件
+ * - get_events_since 返回指定序号之后的事件；请求的序号已被丢弃时 truncated = true，
+ *   前端应改为全量刷新（get_discovered_devices、get_pending_transfer_requests 等）
+ *
+ * 说明：
+ * - 日志只保存在内存中，应用重启后从头编号
+ * - 进度类事件按合并键索引最新一条的序号，记录时无需扫描整个日志
+ * - 前端启动时先监听事件，再拉取 get_lan_events_since(0) 重放，重复事件由前端去重
+ * - 日志属于服务实例（LanTransferService），同一进程内的多个实例各自编号
+ */
+
+use super::protocol::LanTransferEvent;
+use super::service;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+/// 日志容量（条）
+const JOURNAL_CAPACITY: usize = 1000;
+
+// ============================================================================
+// 数据结构
+// ============================================================================
+
+/// 日志中的一条事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LanEventRecord {
+    /// 序号（递增，不重复）
+    pub seq: u64,
+    /// 事件时间
+    pub timestamp: String,
+    /// 事件内容（与 lan-transfer-event 的载荷相同）
+    pub event: LanTransferEvent,
+}
+
+/// 增量拉取结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LanEventsSince {
+    /// since 之后的事件（按序号升序）
+    pub events: Vec<LanEventRecord>,
+    /// 当前最新序号（下次拉取时作为 since）
+    pub latest_seq: u64,
+    /// since 之后有事件已被丢弃，需要全量刷新
+    pub truncated: bool,
+}
+
+/// 事件日志（每个服务实例一份，见 LanTransferService）
+pub(crate) struct Journal {
+    records: VecDeque<LanEventRecord>,
+    next_seq: u64,
+    /// 已丢弃（被容量淘汰）的最大序号
+    dropped_seq: u64,
+    /// 进度类事件的合并键 -> 日志中该键最新一条的序号
+    latest_by_key: HashMap<String, u64>,
+}
+
+impl Journal {
+    pub(crate) fn new() -> Self {
+        Self {
+            records: VecDeque::with_capacity(JOURNAL_CAPACITY),
+            next_seq: 1,
+            dropped_seq: 0,
+            latest_by_key: HashMap::new(),
+        }
+    }
+
+    /// 记录事件
+    pub(crate) fn record(&mut self, event: &LanTransferEvent) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+
+        // 进度类事件只保留最新一条（被替换的旧记录不计入 dropped_seq，最新状态仍在日志中）
+        if let Some(key) = coalesce_key(event)
+            && let Some(previous) = self.latest_by_key.insert(key, seq)
+            && let Ok(index) = self.records.binary_search_by_key(&previous, |r| r.seq)
+        {
+            self.records.remove(index);
+        }
+
+        if self.records.len() >= JOURNAL_CAPACITY
+            && let Some(oldest) = self.records.pop_front()
+        {
+            self.dropped_seq = oldest.seq;
+            if let Some(key) = coalesce_key(&oldest.event)
+                && self.latest_by_key.get(&key) == Some(&oldest.seq)
+            {
+                self.latest_by_key.remove(&key);
+            }
+        }
+
+        self.records.push_back(LanEventRecord {
+            seq,
+            timestamp: Utc::now().to_rfc3339(),
+            event: event.clone(),
+        });
+    }
+
+    /// 获取 since 之后的事件
+    pub(crate) fn events_since(&self, since: u64) -> LanEventsSince {
+        LanEventsSince {
+            events: self
+                .records
+                .iter()
+                .filter(|r| r.seq > since)
+                .cloned()
+                .collect(),
+            latest_seq: self.next_seq - 1,
+            truncated: since < self.dropped_seq,
+        }
+    }
+}
+
+// ============================================================================
+// 记录与查询
+// ============================================================================
+
+/// 记录事件到当前实例的日志（由 emit_lan_event 调用）
+pub fn record(event: &LanTransferEvent) {
+    service::current().journal.lock().record(event);
+}
+
+/// 获取当前实例 since 之后的事件
+pub fn get_events_since(since: u64) -> LanEventsSince {
+    service::current().journal.lock().events_since(since)
+}
+
+/// 进度类事件的合并键（同一键只保留最新一条）
+fn coalesce_key(event: &LanTransferEvent) -> Option<String> {
+    match event {
+        LanTransferEvent::TransferProgress { task } => Some(format!("transfer:{}", task.task_id)),
+        LanTransferEvent::BatchProgress { progress } => {
+            Some(format!("batch:{}", progress.session_id))
+        }
+        LanTransferEvent::MulticastProgress { progress } => {
+            Some(format!("multicast:{}", progress.multicast_id))
+        }
+        LanTransferEvent::HashingProgress { .. } => Some("hashing".to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_events_keep_only_latest() {
+        let start = get_events_since(0).latest_seq;
+        let hashing = |processed_bytes| LanTransferEvent::HashingProgress {
+            file_name: "a.bin".to_string(),
+            file_size: 100,
+            processed_bytes,
+            current_file: 1,
+            total_files: 1,
+        };
+
+        record(&hashing(10));
+        record(&LanTransferEvent::DeviceLeft {
+            device_id: "device".to_string(),
+        });
+        record(&hashing(50));
+
+        let since = get_events_since(start);
+        assert_eq!(since.latest_seq, start + 3);
+        assert!(!since.truncated);
+        assert_eq!(since.events.len(), 2);
+        assert!(matches!(
+            since.events[1].event,
+            LanTransferEvent::HashingProgress { processed_bytes: 50, .. }
+        ));
+    }
+
+    #[test]
+    fn coalesced_events_respect_capacity() {
+        let hashing = |processed_bytes| LanTransferEvent::HashingProgress {
+            file_name: "a.bin".to_string(),
+            file_size: 100,
+            processed_bytes,
+            current_file: 1,
+            total_files: 1,
+        };
+        let left = LanTransferEvent::DeviceLeft {
+            device_id: "device".to_string(),
+        };
+
+        let mut journal = Journal::new();
+        journal.record(&hashing(10));
+        for _ in 0..JOURNAL_CAPACITY {
+            journal.record(&left);
+        }
+        // 旧的进度事件已被容量淘汰
+        assert_eq!(journal.records.len(), JOURNAL_CAPACITY);
+        assert_eq!(journal.dropped_seq, 1);
+        assert!(journal.latest_by_key.is_empty());
+
+        // 新的进度事件不会误删其他记录
+        journal.record(&hashing(20));
+        journal.record(&hashing(30));
+        assert_eq!(journal.records.len(), JOURNAL_CAPACITY);
+        assert_eq!(journal.dropped_seq, 2);
+        let last = journal.records.back().unwrap();
+        assert_eq!(last.seq, JOURNAL_CAPACITY as u64 + 3);
+        assert!(matches!(
+            last.event,
+            LanTransferEvent::HashingProgress { processed_bytes: 30, .. }
+        ));
+        assert_eq!(
+            journal
+                .records
+                .iter()
+                .filter(|r| coalesce_key(&r.event).is_some())
+                .count(),
+            1
+        );
+    }
+}
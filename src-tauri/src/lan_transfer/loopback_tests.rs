@@ -0,0 +1,762 @@
+/*!
+ * 局域网传输回环集成测试
+ *
+ * 在同一进程内启动两个服务实例（127.0.0.1、系统分配端口、独立临时数据目录、关闭 mDNS），
+ * 通过真实的 HTTP 协议端到端验证发送与接收
+ *
+ * 覆盖场景：
+ * - 传输请求的接受与拒绝
+ * - 信任设备自动接受、多文件并行发送（含空文件和分块边界大小），发送方记录传输统计
+ * - 点对点连接发送文件夹（保留相对路径和修改时间；处理连接请求后发送 PeerConnectionRequestResolved）
+ * - 多设备群发（已连接与未连接的接收设备混合，汇总结果分别报告）
+ * - 传输中途取消
+ * - 接收方崩溃后重启续传
+ * - 传输前文件被修改导致的哈希不匹配
+ * - 恶意相对路径的过滤（不能写出接收目录）
+ * - 诊断模块的对端探测（连接、设备信息、延迟、吞吐量；对端停止后连接失败）
+ * - 诊断模块的本机自检（端口监听、本机接口访问；端口被占用时报告错误）
+ *
+ * 说明：
+ * - 设备通过直接写入对方设备列表互相"发现"
+ * - 异常场景（崩溃续传、恶意路径）直接调用接收方 HTTP 接口模拟发送方
+ */
+
+use super::config::TrustedDevice;
+use super::diagnostics::{self_test, DiagCategory, DiagStatus, PeerProbe};
+use super::multicast;
+use super::protocol::*;
+use super::server;
+use super::service::{LanTransferService, ServiceOptions};
+use super::stats;
+use super::transfer;
+use chrono::Utc;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// 等待单个事件的超时
+const EVENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+// ============================================================================
+// 测试工具
+// ============================================================================
+
+/// 临时数据目录（测试结束时删除）
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("huanvae-lan-{}-{}", name, Uuid::new_v4()));
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// 测试用服务实例
+struct TestPeer {
+    service: Arc<LanTransferService>,
+    events: broadcast::Receiver<LanTransferEvent>,
+}
+
+impl TestPeer {
+    async fn start(name: &str, dir: &TempDir) -> Self {
+        let service = LanTransferService::new(ServiceOptions {
+            port: 0,
+            advertise_ip: Some(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+            data_directory: Some(dir.path().to_path_buf()),
+            enable_mdns: false,
+        });
+        service
+            .config()
+            .write()
+            .get_config_mut()
+            .group_by_date = false;
+
+        let events = service.subscribe();
+        service
+            .start("loopback-user".to_string(), name.to_string(), Some(name.to_string()))
+            .await
+            .unwrap();
+
+        Self { service, events }
+    }
+
+    fn device(&self) -> DiscoveredDevice {
+        let local = self.service.state().local_device.read().clone().unwrap();
+        let now = Utc::now().to_rfc3339();
+        DiscoveredDevice {
+            device_id: local.device_id,
+            device_name: local.device_name,
+            user_id: local.user_id,
+            user_nickname: local.user_nickname,
+            ip_address: local.ip_address,
+            port: local.port,
+            discovered_at: now.clone(),
+            last_seen: now,
+        }
+    }
+
+    fn id(&self) -> String {
+        self.device().device_id
+    }
+
+    fn base_url(&self) -> String {
+        format!("http://127.0.0.1:{}", self.service.port())
+    }
+
+    fn received_dir(&self) -> PathBuf {
+        self.service.config().read().get_config().save_directory.clone()
+    }
+
+    fn trust(&self, other: &TestPeer) {
+        let device = other.device();
+        self.service
+            .config()
+            .write()
+            .get_config_mut()
+            .trusted_devices
+            .push(TrustedDevice {
+                device_id: device.device_id,
+                device_name: device.device_name,
+                added_at: Utc::now().to_rfc3339(),
+            });
+    }
+
+    /// 等待满足条件的事件
+    async fn wait_for<T>(&mut self, mut pick: impl FnMut(&LanTransferEvent) -> Option<T>) -> T {
+        let deadline = tokio::time::Instant::now() + EVENT_TIMEOUT;
+        loop {
+            let event = tokio::time::timeout_at(deadline, self.events.recv())
+                .await
+                .expect("等待事件超时");
+            match event {
+                Ok(event) => {
+                    if let Some(value) = pick(&event) {
+                        return value;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => panic!("事件通道已关闭"),
+            }
+        }
+    }
+}
+
+/// 两个实例互相加入对方的设备列表
+fn link(a: &TestPeer, b: &TestPeer) {
+    a.service.state().devices.write().insert(b.id(), b.device());
+    b.service.state().devices.write().insert(a.id(), a.device());
+}
+
+/// a 向 b 发起点对点连接，b 接受后返回连接 ID
+async fn connect(a: &mut TestPeer, b: &mut TestPeer) -> String {
+    let connection_id = a
+        .service
+        .scope(transfer::request_peer_connection(&b.id()))
+        .await
+        .unwrap();
+    b.wait_for(|e| match e {
+        LanTransferEvent::PeerConnectionRequest { request } => {
+            (request.connection_id == connection_id).then_some(())
+        }
+        _ => None,
+    })
+    .await;
+    b.service
+        .scope(transfer::answer_peer_connection(&connection_id, true, None))
+        .await
+        .unwrap();
+    let accepted = b
+        .wait_for(|e| match e {
+            LanTransferEvent::PeerConnectionRequestResolved {
+                connection_id: id,
+                accepted,
+            } if *id == connection_id => Some(*accepted),
+            _ => None,
+        })
+        .await;
+    assert!(accepted);
+    a.wait_for(|e| match e {
+        LanTransferEvent::PeerConnectionEstablished { connection } => {
+            (connection.connection_id == connection_id).then_some(())
+        }
+        _ => None,
+    })
+    .await;
+    connection_id
+}
+
+/// 生成确定性的测试数据
+fn pattern(len: usize, seed: u8) -> Vec<u8> {
+    (0..len).map(|i| (i as u8).wrapping_mul(31).wrapping_add(seed)).collect()
+}
+
+fn write_file(dir: &Path, name: &str, content: &[u8]) -> String {
+    let path = dir.join("outbox").join(name);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, content).unwrap();
+    path.to_string_lossy().to_string()
+}
+
+fn file_meta(file_name: &str, content: &[u8]) -> FileMetadata {
+    FileMetadata {
+        file_id: Uuid::new_v4().to_string(),
+        file_name: file_name.to_string(),
+        file_size: content.len() as u64,
+        mime_type: "application/octet-stream".to_string(),
+        sha256: format!("{:08x}", crc32fast::hash(content)),
+        ..Default::default()
+    }
+}
+
+// ============================================================================
+// 直接调用接收方接口（模拟发送方）
+// ============================================================================
+
+async fn prepare_upload(peer: &TestPeer, session_id: &str, file: &FileMetadata) -> PrepareUploadResponse {
+    reqwest::Client::new()
+        .post(format!("{}/api/prepare-upload", peer.base_url()))
+        .json(&PrepareUploadRequest {
+            session_id: session_id.to_string(),
+            file: file.clone(),
+            resume: true,
+            target_path: None,
+        })
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+async fn upload_chunk(peer: &TestPeer, session_id: &str, file_id: &str, chunk: &[u8]) {
+    let response = reqwest::Client::new()
+        .post(format!(
+            "{}/api/upload?sessionId={}&fileId={}",
+            peer.base_url(),
+            session_id,
+            file_id
+        ))
+        .body(chunk.to_vec())
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+}
+
+async fn finish_upload(peer: &TestPeer, session_id: &str, file_id: &str) -> FinishUploadResponse {
+    reqwest::Client::new()
+        .post(format!("{}/api/finish", peer.base_url()))
+        .json(&FinishUploadRequest {
+            session_id: session_id.to_string(),
+            file_id: file_id.to_string(),
+        })
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+/// 完整上传一个文件，返回完成响应
+async fn upload_file(peer: &TestPeer, file: &FileMetadata, content: &[u8]) -> FinishUploadResponse {
+    let session_id = Uuid::new_v4().to_string();
+    let prepared = prepare_upload(peer, &session_id, file).await;
+    assert!(prepared.accepted);
+    for chunk in content[prepared.resume_offset as usize..].chunks(CHUNK_SIZE) {
+        upload_chunk(peer, &session_id, &file.file_id, chunk).await;
+    }
+    finish_upload(peer, &session_id, &file.file_id).await
+}
+
+// ============================================================================
+// 传输请求
+// ============================================================================
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_transfer_request_accepted() {
+    let (dir_a, dir_b) = (TempDir::new("a"), TempDir::new("b"));
+    let mut a = TestPeer::start("sender", &dir_a).await;
+    let mut b = TestPeer::start("receiver", &dir_b).await;
+    link(&a, &b);
+
+    let content = b"hello loopback".to_vec();
+    let path = write_file(dir_a.path(), "hello.txt", &content);
+    let request_id = a
+        .service
+        .scope(transfer::send_transfer_request(&b.id(), vec![path]))
+        .await
+        .unwrap();
+
+    let incoming = b
+        .wait_for(|e| match e {
+            LanTransferEvent::TransferRequestReceived { request } => Some(request.clone()),
+            _ => None,
+        })
+        .await;
+    assert_eq!(incoming.request_id, request_id);
+    assert_eq!(incoming.from_device.device_id, a.id());
+    assert_eq!(incoming.files.len(), 1);
+
+    b.service
+        .scope(transfer::respond_to_transfer_request(&request_id, true))
+        .await
+        .unwrap();
+
+    let session = a
+        .service
+        .enter(|| transfer::get_transfer_session(&request_id))
+        .unwrap();
+    a.wait_for(|e| match e {
+        LanTransferEvent::BatchTransferCompleted { session_id, .. } => {
+            (*session_id == session.session_id).then_some(())
+        }
+        _ => None,
+    })
+    .await;
+
+    let session = a
+        .service
+        .enter(|| transfer::get_transfer_session(&request_id))
+        .unwrap();
+    assert!(session.files.iter().all(|f| f.status == TransferStatus::Completed));
+    assert_eq!(std::fs::read(b.received_dir().join("hello.txt")).unwrap(), content);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_transfer_request_rejected() {
+    let (dir_a, dir_b) = (TempDir::new("a"), TempDir::new("b"));
+    let mut a = TestPeer::start("sender", &dir_a).await;
+    let mut b = TestPeer::start("receiver", &dir_b).await;
+    link(&a, &b);
+
+    let path = write_file(dir_a.path(), "secret.txt", b"not wanted");
+    let request_id = a
+        .service
+        .scope(transfer::send_transfer_request(&b.id(), vec![path]))
+        .await
+        .unwrap();
+
+    b.wait_for(|e| matches!(e, LanTransferEvent::TransferRequestReceived { .. }).then_some(()))
+        .await;
+    b.service
+        .scope(transfer::respond_to_transfer_request(&request_id, false))
+        .await
+        .unwrap();
+
+    let reason = a
+        .wait_for(|e| match e {
+            LanTransferEvent::TransferRequestResponse {
+                request_id: id,
+                accepted,
+                reject_reason,
+            } if *id == request_id => Some((*accepted, reject_reason.clone())),
+            _ => None,
+        })
+        .await;
+    assert_eq!(reason, (false, Some("用户拒绝".to_string())));
+
+    assert!(b.service.enter(|| server::get_pending_transfer_requests_map().lock().is_empty()));
+    assert!(!b.received_dir().join("secret.txt").exists());
+}
+
+// ============================================================================
+// 多文件与文件夹
+// ============================================================================
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_trusted_multi_file_parallel_send() {
+    let (dir_a, dir_b) = (TempDir::new("a"), TempDir::new("b"));
+    let mut a = TestPeer::start("sender", &dir_a).await;
+    let b = TestPeer::start("receiver", &dir_b).await;
+    link(&a, &b);
+    b.trust(&a);
+
+    // 空文件、分块边界前后、多块文件
+    let sizes = [0, 1, CHUNK_SIZE - 1, CHUNK_SIZE, CHUNK_SIZE + 1, 3 * CHUNK_SIZE + 123];
+    let files: Vec<(String, Vec<u8>)> = sizes
+        .iter()
+        .enumerate()
+        .map(|(i, size)| (format!("file-{}.bin", i), pattern(*size, i as u8)))
+        .collect();
+    let paths: Vec<String> = files
+        .iter()
+        .map(|(name, content)| write_file(dir_a.path(), name, content))
+        .collect();
+
+    let request_id = a
+        .service
+        .scope(transfer::send_transfer_request(&b.id(), paths))
+        .await
+        .unwrap();
+    let session = a
+        .service
+        .enter(|| transfer::get_transfer_session(&request_id))
+        .unwrap();
+    let file_ids: Vec<String> = session.files.iter().map(|f| f.file.file_id.clone()).collect();
+
+    let mut failures = Vec::new();
+    a.wait_for(|e| match e {
+        LanTransferEvent::TransferFailed { task_id, error } if file_ids.contains(task_id) => {
+            failures.push(error.clone());
+            None
+        }
+        LanTransferEvent::BatchTransferCompleted { session_id, .. } => {
+            (*session_id == session.session_id).then_some(())
+        }
+        _ => None,
+    })
+    .await;
+    assert!(failures.is_empty(), "传输失败: {:?}", failures);
+
+    for (name, content) in &files {
+        let received = std::fs::read(b.received_dir().join(name)).unwrap();
+        assert_eq!(received.len(), content.len(), "{}", name);
+        assert!(received == *content, "{} 内容不一致", name);
+    }
+
+    // 发送方为每个文件记录了传输统计，经回环接口发送
+    let history = a.service.enter(|| stats::get_history(Some(&b.id()), 100));
+    assert_eq!(history.len(), files.len());
+    assert!(history.iter().all(|m| m.success && m.retry_count == 0));
+    assert!(history.iter().all(|m| m.local_interface.as_deref() == Some("loopback")));
+    let peers = a.service.enter(stats::get_peer_stats);
+    assert_eq!(peers.len(), 1);
+    assert_eq!(peers[0].transfer_count, files.len() as u32);
+    assert_eq!(peers[0].total_bytes, sizes.iter().sum::<usize>() as u64);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_peer_connection_sends_folder_tree() {
+    let (dir_a, dir_b) = (TempDir::new("a"), TempDir::new("b"));
+    let mut a = TestPeer::start("sender", &dir_a).await;
+    let mut b = TestPeer::start("receiver", &dir_b).await;
+    link(&a, &b);
+    let connection_id = connect(&mut a, &mut b).await;
+
+    let first = write_file(dir_a.path(), "album/a.txt", b"first");
+    let second = write_file(dir_a.path(), "album/sub/b.txt", b"second");
+    let modified_at = std::fs::metadata(&second).unwrap().modified().unwrap();
+
+    let session_id = a
+        .service
+        .scope(transfer::send_tree_to_peer(
+            &connection_id,
+            vec![first, second],
+            vec!["album/a.txt".to_string(), "album/sub/b.txt".to_string()],
+        ))
+        .await
+        .unwrap();
+    a.wait_for(|e| match e {
+        LanTransferEvent::BatchTransferCompleted { session_id: id, .. } => (*id == session_id).then_some(()),
+        _ => None,
+    })
+    .await;
+
+    let received = b.received_dir().join("album");
+    assert_eq!(std::fs::read(received.join("a.txt")).unwrap(), b"first");
+    let saved = received.join("sub").join("b.txt");
+    assert_eq!(std::fs::read(&saved).unwrap(), b"second");
+
+    // 修改时间按毫秒精度恢复
+    let saved_modified = std::fs::metadata(&saved).unwrap().modified().unwrap();
+    let millis = |t: std::time::SystemTime| {
+        t.duration_since(std::time::UNIX_EPOCH).unwrap().as_millis()
+    };
+    assert_eq!(millis(saved_modified), millis(modified_at));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_multicast_reports_connected_and_unconnected_recipients() {
+    let (dir_a, dir_b) = (TempDir::new("a"), TempDir::new("b"));
+    let mut a = TestPeer::start("sender", &dir_a).await;
+    let mut b = TestPeer::start("receiver", &dir_b).await;
+    link(&a, &b);
+    connect(&mut a, &mut b).await;
+
+    let small = pattern(1000, 3);
+    let large = pattern(CHUNK_SIZE + 17, 4);
+    let paths = vec![
+        write_file(dir_a.path(), "small.bin", &small),
+        write_file(dir_a.path(), "large.bin", &large),
+    ];
+
+    let multicast_id = a
+        .service
+        .scope(multicast::send_files_to_devices(
+            vec![b.id(), "offline-device".to_string(), b.id()],
+            paths,
+        ))
+        .await
+        .unwrap();
+    let progress = a
+        .wait_for(|e| match e {
+            LanTransferEvent::MulticastCompleted { progress } => {
+                (progress.multicast_id == multicast_id).then(|| progress.clone())
+            }
+            _ => None,
+        })
+        .await;
+
+    // 重复的设备 ID 只发送一次
+    assert_eq!(progress.total_files, 2);
+    assert_eq!(progress.total_bytes, (small.len() + large.len()) as u64);
+    assert_eq!(progress.recipients.len(), 2);
+
+    let connected = &progress.recipients[0];
+    assert_eq!(connected.device_id, b.id());
+    assert_eq!(connected.status, TransferStatus::Completed);
+    assert_eq!(connected.completed_files, 2);
+    assert_eq!(connected.transferred_bytes, progress.total_bytes);
+    assert!(connected.error.is_none());
+
+    let offline = &progress.recipients[1];
+    assert_eq!(offline.device_id, "offline-device");
+    assert_eq!(offline.status, TransferStatus::Failed);
+    assert!(offline.session_id.is_none());
+    assert_eq!(offline.error.as_deref(), Some("未建立连接"));
+
+    assert_eq!(std::fs::read(b.received_dir().join("small.bin")).unwrap(), small);
+    assert_eq!(std::fs::read(b.received_dir().join("large.bin")).unwrap(), large);
+}
+
+// ============================================================================
+// 取消、续传与校验
+// ============================================================================
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_cancel_mid_transfer() {
+    let (dir_a, dir_b) = (TempDir::new("a"), TempDir::new("b"));
+    let mut a = TestPeer::start("sender", &dir_a).await;
+    let mut b = TestPeer::start("receiver", &dir_b).await;
+    link(&a, &b);
+    b.trust(&a);
+
+    let path = write_file(dir_a.path(), "large.bin", &pattern(64 * CHUNK_SIZE, 7));
+    let request_id = a
+        .service
+        .scope(transfer::send_transfer_request(&b.id(), vec![path]))
+        .await
+        .unwrap();
+    let session = a
+        .service
+        .enter(|| transfer::get_transfer_session(&request_id))
+        .unwrap();
+    let file_id = session.files[0].file.file_id.clone();
+
+    // 接收方开始接收（prepare-upload 发出初始进度）后立即取消
+    b.wait_for(|e| matches!(e, LanTransferEvent::BatchProgress { .. }).then_some(()))
+        .await;
+    a.service
+        .scope(transfer::cancel_session(&request_id))
+        .await
+        .unwrap();
+
+    let error = a
+        .wait_for(|e| match e {
+            LanTransferEvent::TransferFailed { task_id, error } if *task_id == file_id => {
+                Some(error.clone())
+            }
+            _ => None,
+        })
+        .await;
+    assert!(error.contains("取消"), "{}", error);
+
+    a.wait_for(|e| match e {
+        LanTransferEvent::BatchTransferCompleted { session_id, .. } => {
+            (*session_id == session.session_id).then_some(())
+        }
+        _ => None,
+    })
+    .await;
+    assert!(!b.received_dir().join("large.bin").exists());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_resume_after_receiver_restart() {
+    let dir_b = TempDir::new("b");
+    let content = pattern(3 * CHUNK_SIZE + 777, 3);
+    let file = file_meta("resume.bin", &content);
+
+    // 第一个实例接收第一块后"崩溃"（丢弃内存中的上传会话）
+    let first = TestPeer::start("receiver", &dir_b).await;
+    let prepared = prepare_upload(&first, "before-crash", &file).await;
+    assert_eq!(prepared.resume_offset, 0);
+    upload_chunk(&first, "before-crash", &file.file_id, &content[..CHUNK_SIZE]).await;
+    first.service.stop().await.unwrap();
+    drop(first);
+
+    // 使用同一数据目录重新启动，从已接收的位置继续
+    let second = TestPeer::start("receiver", &dir_b).await;
+    let prepared = prepare_upload(&second, "after-crash", &file).await;
+    assert_eq!(prepared.resume_offset, CHUNK_SIZE as u64);
+    for chunk in content[CHUNK_SIZE..].chunks(CHUNK_SIZE) {
+        upload_chunk(&second, "after-crash", &file.file_id, chunk).await;
+    }
+
+    let finished = finish_upload(&second, "after-crash", &file.file_id).await;
+    assert!(finished.success, "{:?}", finished.error);
+    assert!(finished.sha256_match);
+    assert!(std::fs::read(second.received_dir().join("resume.bin")).unwrap() == content);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_hash_mismatch_is_rejected() {
+    let (dir_a, dir_b) = (TempDir::new("a"), TempDir::new("b"));
+    let mut a = TestPeer::start("sender", &dir_a).await;
+    let mut b = TestPeer::start("receiver", &dir_b).await;
+    link(&a, &b);
+
+    let path = write_file(dir_a.path(), "changing.bin", &pattern(2 * CHUNK_SIZE, 1));
+    let request_id = a
+        .service
+        .scope(transfer::send_transfer_request(&b.id(), vec![path.clone()]))
+        .await
+        .unwrap();
+    let session = a
+        .service
+        .enter(|| transfer::get_transfer_session(&request_id))
+        .unwrap();
+    let file_id = session.files[0].file.file_id.clone();
+
+    // 请求发出后（哈希已计算）、对方接受前修改文件内容
+    std::fs::write(&path, pattern(2 * CHUNK_SIZE, 2)).unwrap();
+    b.wait_for(|e| matches!(e, LanTransferEvent::TransferRequestReceived { .. }).then_some(()))
+        .await;
+    b.service
+        .scope(transfer::respond_to_transfer_request(&request_id, true))
+        .await
+        .unwrap();
+
+    let receiver_error = b
+        .wait_for(|e| match e {
+            LanTransferEvent::TransferFailed { task_id, error } if *task_id == file_id => {
+                Some(error.clone())
+            }
+            _ => None,
+        })
+        .await;
+    assert!(receiver_error.contains("校验失败"), "{}", receiver_error);
+
+    a.wait_for(|e| match e {
+        LanTransferEvent::TransferFailed { task_id, .. } if *task_id == file_id => Some(()),
+        _ => None,
+    })
+    .await;
+    assert!(!b.received_dir().join("changing.bin").exists());
+
+    // 临时文件和续传信息已清理
+    let temp_dir = b.service.config().read().get_config().temp_directory.clone();
+    assert!(!temp_dir.join(format!("{}.part", file_id)).exists());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_malicious_paths_stay_inside_save_directory() {
+    let dir_b = TempDir::new("b");
+    let b = TestPeer::start("receiver", &dir_b).await;
+    let received = b.received_dir();
+
+    let cases = [
+        ("../../escape.txt", None, "escape.txt"),
+        ("a.txt", Some("../../outside/../evil.txt"), "outside/evil.txt"),
+        ("b.txt", Some("/etc/absolute.txt"), "etc/absolute.txt"),
+        ("c.txt", Some("C:\\Windows\\drive.txt"), "C_/Windows/drive.txt"),
+        ("..", Some("./../."), "unnamed"),
+    ];
+
+    for (file_name, relative_path, expected) in cases {
+        let content = pattern(100, 9);
+        let file = FileMetadata {
+            relative_path: relative_path.map(str::to_string),
+            ..file_meta(file_name, &content)
+        };
+
+        let finished = upload_file(&b, &file, &content).await;
+        assert!(finished.success, "{:?}", finished.error);
+
+        let saved = PathBuf::from(finished.saved_path.unwrap());
+        assert_eq!(saved, received.join(expected), "{} / {:?}", file_name, relative_path);
+        assert!(std::fs::read(&saved).unwrap() == content);
+    }
+
+    // 数据目录之外没有写入任何文件
+    let parent = dir_b.path().parent().unwrap();
+    assert!(!parent.join("escape.txt").exists());
+    assert!(!parent.join("evil.txt").exists());
+    assert!(!dir_b.path().join("outside").exists());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_peer_probe() {
+    let (dir_a, dir_b) = (TempDir::new("a"), TempDir::new("b"));
+    let a = TestPeer::start("prober", &dir_a).await;
+    let b = TestPeer::start("target", &dir_b).await;
+    link(&a, &b);
+
+    let report = a.service.scope(PeerProbe::new(b.device()).probe()).await;
+    let ids: Vec<&str> = report.items.iter().map(|i| i.id.as_str()).collect();
+    assert_eq!(ids, ["P1", "P2", "P3", "P4", "P5"]);
+    for item in &report.items {
+        assert_eq!(item.status, DiagStatus::Ok, "{}: {}", item.id, item.details);
+    }
+    let peer = report.peer.as_ref().unwrap();
+    assert_eq!(peer.device_id, b.id());
+    assert_eq!(peer.protocol_version.as_deref(), Some(PROTOCOL_VERSION));
+    assert_eq!(peer.os.as_deref(), Some(std::env::consts::OS));
+
+    // 对端停止后 TCP 连接失败，后续检查项跳过
+    let target = b.device();
+    b.service.stop().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let report = a.service.scope(PeerProbe::new(target).probe()).await;
+    assert_eq!(report.items[0].status, DiagStatus::Error);
+    assert!(report.items[0].fix_suggestion.is_some());
+    assert!(report.items[1..].iter().all(|i| i.status == DiagStatus::Skipped));
+    let peer = report.peer.unwrap();
+    assert!(peer.os.is_none() && peer.protocol_version.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_self_test() {
+    let (dir_a, dir_b) = (TempDir::new("a"), TempDir::new("b"));
+    let a = TestPeer::start("self", &dir_a).await;
+
+    let items = a.service.scope(self_test::run_self_test()).await;
+    let ids: Vec<&str> = items.iter().map(|i| i.id.as_str()).collect();
+    assert_eq!(ids, ["S1", "S2", "S3"]);
+    assert_eq!(items[0].category, DiagCategory::Port);
+    assert_eq!(items[0].status, DiagStatus::Ok, "{}", items[0].details);
+    assert_ne!(items[1].status, DiagStatus::Error, "{}", items[1].details);
+    assert!(items[1].details.contains("127.0.0.1"));
+    assert_eq!(items[2].status, DiagStatus::Skipped);
+
+    // 第二个实例使用同一端口：绑定失败，S1 报告错误
+    let b = LanTransferService::new(ServiceOptions {
+        port: a.service.port(),
+        advertise_ip: Some(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+        data_directory: Some(dir_b.path().to_path_buf()),
+        enable_mdns: false,
+    });
+    b.start("loopback-user".into(), "clash".into(), None).await.unwrap();
+
+    let items = b.scope(self_test::run_self_test()).await;
+    assert_eq!(items[0].status, DiagStatus::Error);
+    assert!(items[0].fix_command.is_some());
+    assert_eq!(items[1].status, DiagStatus::Skipped);
+}
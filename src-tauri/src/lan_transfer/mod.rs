@@ -9,13 +9,47 @@
  * - 连接确认：双向确认机制确保安全
  * - 文件传输：支持大文件分块传输、校验、断点续传
  * - 并行传输：多文件同时传输（默认并行度 3）
+ * - 多设备群发：同一批文件同时发送到多个已连接设备（只计算一次哈希、共享读取缓存）
  * - 单文件取消：使用 CancellationToken 支持取消正在传输的单个文件
+ * - 连接心跳：定期检测点对点连接，超时降级/关闭，重新连接时验证对端身份
+ * - 未完成接收清理：服务启动时清理过期的临时文件和续传信息，可查看占用空间并手动清理
+ * - 请求过期：未处理的传输/连接请求超时自动拒绝并通知发送方，限制单设备待处理请求数
+ * - 文本片段：向已连接设备快速分享文本（URL、密码、代码片段）
+ * - 局域网聊天：服务器不可用时通过点对点连接收发消息（送达回执、离线队列）
+ * - 联系人关联：将局域网设备与本地好友、群组关联（"同一网络"标识）
+ * - 附件直传：聊天附件优先从同一局域网内的好友设备获取，失败回退服务器
+ * - 网页分享：未安装应用的设备通过浏览器收发文件（一次性 PIN + 逐次确认）
+ * - 共享文件夹：发布命名共享，已信任的对端可浏览并拉取文件/文件夹
+ * - 文件夹同步：本账号两台设备之间持续双向同步一个文件夹（冲突保留副本）
+ * - 离线发送队列：目标设备不在线时排队，设备重新上线后自动发送
+ * - 命令行工具：无图形界面的机器通过 huanvae-lan 命令发送/接收文件
+ * - 事件日志：事件按序号记录在内存中，前端重新加载后可拉取并重放
+ * - 接收后处理：按类型/发送方归类、自动解压 zip、执行用户命令（如病毒扫描）
+ * - 服务实例：状态、配置、端口归属于 LanTransferService，事件通过可插拔的事件输出发送
+ * - 传输统计：按对端设备汇总历史吞吐，按网络接口对比速度，标记慢速链路
  *
  * 模块结构：
+ * - attachments: 聊天附件局域网直传（按 file_hash 获取、按聊天关系授权）
+ * - cli: 命令行工具（无界面机器上列出设备、发送、接收，仅桌面端）
+ * - chat: 局域网聊天（消息存入本地数据库、投递队列）
  * - discovery: mDNS 设备发现
+ * - heartbeat: 点对点连接心跳（保活检测、超时关闭、身份验证）
+ * - journal: 局域网事件日志（有序编号、容量上限、增量拉取）
+ * - loopback_tests: 回环集成测试（同一进程内两个实例通过 127.0.0.1 端到端收发）
+ * - multicast: 多设备群发（每设备独立会话、汇总进度）
+ * - post_receive: 接收后处理动作（归类移动、解压、执行命令）
+ * - presence: 局域网设备与好友/群组的关联
  * - protocol: 协议定义（消息类型、数据结构）
+ * - request_expiry: 待处理传输/连接请求的超时自动拒绝
+ * - send_queue: 离线设备的发送队列（持久化、上线自动投递、过期清理）
  * - server: HTTP 服务器（接收文件）
+ * - service: 服务实例（持有运行状态与端口、可插拔事件输出，同一进程可运行多个实例）
+ * - shares: 共享文件夹（浏览、拉取、按设备授权）
+ * - snippets: 已接收文本片段的本地历史
+ * - stats: 传输统计（每个文件的速度、重试、哈希/发送耗时、网络接口，按对端设备汇总）
+ * - sync: 本账号设备之间的文件夹同步（清单比较、增量传输、状态持久化）
  * - transfer: 文件传输逻辑（并行传输、取消机制）
+ * - web_share: 浏览器网页分享（PIN 登录、访客上传/下载）
  *
  * 并行传输：
  * - 使用 Semaphore 限制最大并发数（默认 3）
@@ -34,23 +68,41 @@
  * @see https://docs.rs/crc32fast/ CRC32fast 文档
  */
 
+pub mod attachments;
+pub mod chat;
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+pub mod cli;
 pub mod config;
 pub mod diagnostics;
 pub mod discovery;
+pub mod heartbeat;
+pub mod journal;
+#[cfg(test)]
+mod loopback_tests;
+pub mod multicast;
+pub mod post_receive;
+pub mod presence;
 pub mod protocol;
+pub mod request_expiry;
 pub mod resume;
+pub mod send_queue;
 pub mod server;
+pub mod service;
+pub mod shares;
+pub mod snippets;
+pub mod stats;
+pub mod sync;
 pub mod transfer;
+pub mod web_share;
 
 use once_cell::sync::OnceCell;
 use parking_lot::RwLock;
 use std::collections::HashMap;
 use std::sync::Arc;
-use tauri::Emitter;
 
 pub use protocol::{
-    ConnectionRequest, DiscoveredDevice, DeviceInfo,
-    PeerConnection, PeerConnectionRequest,
+    ConnectionRequest, DiscoveredDevice, DeviceInfo, PartialCleanupResult, PartialTransferReport,
+    PeerConnection, PeerConnectionRequest, QueuedTransfer, SyncSummary, TextSnippet,
     TransferRequest, TransferSession, TransferTask,
 };
 
@@ -58,12 +110,14 @@ pub use protocol::{
 // 全局 AppHandle 管理
 // ============================================================================
 
-/// 全局 AppHandle 用于发送事件到前端
+/// 全局 AppHandle（默认实例的 Tauri 事件输出）
 static APP_HANDLE: OnceCell<tauri::AppHandle> = OnceCell::new();
 
-/// 设置全局 AppHandle
+/// 设置全局 AppHandle（首次设置时为默认实例挂载 Tauri 事件输出）
 pub fn set_app_handle(handle: tauri::AppHandle) {
-    let _ = APP_HANDLE.set(handle);
+    if APP_HANDLE.set(handle.clone()).is_ok() {
+        service::default_service().add_event_sink(Arc::new(service::TauriEventSink::new(handle)));
+    }
 }
 
 /// 获取全局 AppHandle
@@ -71,13 +125,9 @@ pub fn get_app_handle() -> Option<&'static tauri::AppHandle> {
     APP_HANDLE.get()
 }
 
-/// 向前端发送局域网传输事件
+/// 发送局域网传输事件到当前实例的事件输出（同时记录到事件日志，供前端重新加载后重放）
 pub fn emit_lan_event(event: &protocol::LanTransferEvent) {
-    if let Some(handle) = get_app_handle()
-        && let Err(e) = handle.emit("lan-transfer-event", event)
-    {
-        eprintln!("[LanTransfer] 发送事件失败: {}", e);
-    }
+    service::current().emit(event);
 }
 
 // ============================================================================
@@ -116,14 +166,9 @@ impl Default for LanTransferState {
     }
 }
 
-/// 全局状态单例
-static LAN_TRANSFER_STATE: OnceCell<Arc<LanTransferState>> = OnceCell::new();
-
-/// 获取全局状态
+/// 获取当前实例的状态
 pub fn get_lan_transfer_state() -> Arc<LanTransferState> {
-    LAN_TRANSFER_STATE
-        .get_or_init(|| Arc::new(LanTransferState::new()))
-        .clone()
+    service::current().state()
 }
 
 // ============================================================================
@@ -145,7 +190,8 @@ pub async fn start_lan_transfer_service(
 ) -> Result<(), String> {
     set_app_handle(app_handle);
 
-    discovery::start_service(user_id, user_nickname, device_name)
+    service::default_service()
+        .start(user_id, user_nickname, device_name)
         .await
         .map_err(|e| e.to_string())
 }
@@ -153,7 +199,10 @@ pub async fn start_lan_transfer_service(
 /// 停止局域网传输服务
 #[tauri::command]
 pub async fn stop_lan_transfer_service() -> Result<(), String> {
-    discovery::stop_service().await.map_err(|e| e.to_string())
+    service::default_service()
+        .stop()
+        .await
+        .map_err(|e| e.to_string())
 }
 
 /// 获取发现的设备列表
@@ -211,6 +260,12 @@ pub fn get_active_transfers() -> Vec<TransferTask> {
     transfers.values().cloned().collect()
 }
 
+/// 获取指定序号之后的局域网事件（前端重新加载后重放，恢复状态）
+#[tauri::command]
+pub fn get_lan_events_since(since: u64) -> journal::LanEventsSince {
+    journal::get_events_since(since)
+}
+
 /// 取消传输任务
 #[tauri::command]
 pub async fn cancel_transfer(transfer_id: String) -> Result<(), String> {
@@ -283,6 +338,283 @@ pub async fn send_files_to_peer(
         .map_err(|e| e.to_string())
 }
 
+/// 将一批文件同时发送到多个已连接设备（群发）
+///
+/// 返回群发 ID，进度通过 MulticastProgress / MulticastCompleted 事件通知
+#[tauri::command]
+pub async fn send_files_to_devices(
+    device_ids: Vec<String>,
+    file_paths: Vec<String>,
+) -> Result<String, String> {
+    multicast::send_files_to_devices(device_ids, file_paths)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// ============================================================================
+// 文本片段命令
+// ============================================================================
+
+/// 向已连接的设备发送文本片段
+#[tauri::command]
+pub async fn send_text_snippet(
+    connection_id: String,
+    content: String,
+    format: protocol::SnippetFormat,
+) -> Result<String, String> {
+    transfer::send_text_snippet(&connection_id, content, format)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 获取已接收的文本片段历史
+#[tauri::command]
+pub fn get_received_snippets() -> Vec<TextSnippet> {
+    snippets::get_received_snippets()
+}
+
+/// 删除单条文本片段记录
+#[tauri::command]
+pub fn delete_received_snippet(snippet_id: String) -> Result<(), String> {
+    snippets::delete_snippet(&snippet_id).map_err(|e| e.to_string())
+}
+
+/// 清空文本片段历史
+#[tauri::command]
+pub fn clear_received_snippets() -> Result<(), String> {
+    snippets::clear_snippet_history().map_err(|e| e.to_string())
+}
+
+// ============================================================================
+// 局域网聊天命令
+// ============================================================================
+
+/// 向设备发送聊天消息（对方离线或未连接时加入投递队列，重新上线后自动投递）
+#[tauri::command]
+pub async fn send_lan_chat_message(
+    device_id: String,
+    content: String,
+) -> Result<crate::db::LocalMessage, String> {
+    chat::send_chat_message(&device_id, content)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 立即重试投递发往指定设备的排队消息
+#[tauri::command]
+pub async fn retry_lan_chat_messages(device_id: String) -> Result<(), String> {
+    chat::flush_outbox(&device_id).await;
+    Ok(())
+}
+
+/// 获取局域网消息的投递状态
+#[tauri::command]
+pub fn get_lan_message_delivery(
+    message_uuid: String,
+) -> Result<Option<crate::db::LocalLanDelivery>, String> {
+    crate::db::get_lan_delivery(&message_uuid)
+}
+
+// ============================================================================
+// 联系人关联命令
+// ============================================================================
+
+/// 获取局域网设备及其关联的好友、共同群组
+#[tauri::command]
+pub fn get_lan_contacts() -> Vec<presence::LanContact> {
+    presence::get_lan_contacts()
+}
+
+/// 获取好友当前在局域网中的设备
+#[tauri::command]
+pub fn get_friend_lan_devices(friend_id: String) -> Vec<DiscoveredDevice> {
+    presence::get_friend_lan_devices(&friend_id)
+}
+
+// ============================================================================
+// 离线发送队列命令
+// ============================================================================
+
+/// 为设备排队发送文件（设备在线时立即发送，否则上线后自动发送）
+///
+/// - `expires_in_hours`: 过期时间（小时），默认 7 天
+#[tauri::command]
+pub async fn queue_transfer_for_device(
+    device_id: String,
+    file_paths: Vec<String>,
+    expires_in_hours: Option<u32>,
+) -> Result<QueuedTransfer, String> {
+    send_queue::queue_transfer(&device_id, file_paths, expires_in_hours)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 获取排队中的发送任务
+#[tauri::command]
+pub fn get_queued_transfers() -> Vec<QueuedTransfer> {
+    send_queue::get_queued_transfers()
+}
+
+/// 取消排队中的发送任务
+#[tauri::command]
+pub fn cancel_queued_transfer(queue_id: String) -> Result<(), String> {
+    send_queue::cancel_queued_transfer(&queue_id).map_err(|e| e.to_string())
+}
+
+// ============================================================================
+// 共享文件夹命令
+// ============================================================================
+
+/// 获取本机的共享文件夹
+#[tauri::command]
+pub fn get_shared_folders() -> Vec<config::SharedFolder> {
+    config::get_shared_folders()
+}
+
+/// 添加共享文件夹
+///
+/// - `allowed_devices`: 允许访问的设备 ID（为空表示所有已信任设备）
+#[tauri::command]
+pub fn add_shared_folder(
+    name: String,
+    path: String,
+    allowed_devices: Vec<String>,
+) -> Result<config::SharedFolder, String> {
+    config::add_shared_folder(name, std::path::PathBuf::from(path), allowed_devices)
+        .map_err(|e| e.to_string())
+}
+
+/// 更新共享文件夹允许访问的设备
+#[tauri::command]
+pub fn set_shared_folder_devices(
+    share_id: String,
+    allowed_devices: Vec<String>,
+) -> Result<(), String> {
+    config::set_shared_folder_devices(&share_id, allowed_devices).map_err(|e| e.to_string())
+}
+
+/// 移除共享文件夹
+#[tauri::command]
+pub fn remove_shared_folder(share_id: String) -> Result<(), String> {
+    config::remove_shared_folder(&share_id).map_err(|e| e.to_string())
+}
+
+/// 获取已连接设备的共享文件夹列表
+#[tauri::command]
+pub async fn browse_peer_shares(
+    connection_id: String,
+) -> Result<Vec<protocol::SharedFolderInfo>, String> {
+    shares::browse_peer_shares(&connection_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 浏览已连接设备的共享文件夹目录
+///
+/// - `path`: 相对于共享根目录的路径（空字符串表示根目录）
+#[tauri::command]
+pub async fn browse_peer_share(
+    connection_id: String,
+    share_id: String,
+    path: String,
+) -> Result<Vec<protocol::ShareEntry>, String> {
+    shares::browse_peer_share(&connection_id, &share_id, path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 从已连接设备的共享文件夹拉取文件或文件夹
+#[tauri::command]
+pub async fn pull_from_peer_share(
+    connection_id: String,
+    share_id: String,
+    paths: Vec<String>,
+) -> Result<shares::PullResponse, String> {
+    shares::pull_from_peer(&connection_id, &share_id, paths)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// ============================================================================
+// 文件夹同步命令
+// ============================================================================
+
+/// 创建同步配对（向本账号的另一台设备发送邀请）
+#[tauri::command]
+pub async fn create_sync_pair(
+    device_id: String,
+    local_dir: String,
+) -> Result<sync::SyncPair, String> {
+    sync::create_sync_pair(&device_id, std::path::PathBuf::from(local_dir))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 接受同步邀请
+#[tauri::command]
+pub fn join_sync_pair(
+    pair_id: String,
+    device_id: String,
+    local_dir: String,
+) -> Result<sync::SyncPair, String> {
+    sync::join_sync_pair(&pair_id, &device_id, std::path::PathBuf::from(local_dir))
+        .map_err(|e| e.to_string())
+}
+
+/// 获取同步配对列表
+#[tauri::command]
+pub fn get_sync_pairs() -> Vec<sync::SyncPair> {
+    sync::get_sync_pairs()
+}
+
+/// 启用/暂停同步配对
+#[tauri::command]
+pub fn set_sync_pair_enabled(pair_id: String, enabled: bool) -> Result<(), String> {
+    sync::set_sync_pair_enabled(&pair_id, enabled).map_err(|e| e.to_string())
+}
+
+/// 移除同步配对（不删除文件）
+#[tauri::command]
+pub fn remove_sync_pair(pair_id: String) -> Result<(), String> {
+    sync::remove_sync_pair(&pair_id).map_err(|e| e.to_string())
+}
+
+/// 立即同步
+#[tauri::command]
+pub async fn sync_folder_now(pair_id: String) -> Result<SyncSummary, String> {
+    sync::sync_now(&pair_id).await.map_err(|e| e.to_string())
+}
+
+// ============================================================================
+// 网页分享命令
+// ============================================================================
+
+/// 开启网页分享（返回访问地址和一次性 PIN）
+///
+/// - `file_paths`: 允许访客下载的文件（可为空，仅接收访客上传）
+#[tauri::command]
+pub fn start_web_share(file_paths: Vec<String>) -> Result<web_share::WebShareInfo, String> {
+    web_share::start_web_share(file_paths).map_err(|e| e.to_string())
+}
+
+/// 关闭网页分享
+#[tauri::command]
+pub fn stop_web_share() {
+    web_share::stop_web_share();
+}
+
+/// 重新生成网页分享 PIN
+#[tauri::command]
+pub fn regenerate_web_share_pin() -> Result<String, String> {
+    web_share::regenerate_pin().map_err(|e| e.to_string())
+}
+
+/// 获取网页分享状态（未开启时返回 None）
+#[tauri::command]
+pub fn get_web_share_info() -> Option<web_share::WebShareInfo> {
+    web_share::get_web_share_info()
+}
+
 // ============================================================================
 // 传输命令（旧版兼容）
 // ============================================================================
@@ -419,12 +751,77 @@ pub fn set_auto_accept_trusted(enabled: bool) -> Result<(), String> {
     config::set_auto_accept_trusted(enabled).map_err(|e| e.to_string())
 }
 
+/// 获取未完成的接收（时长、大小、来源设备）及占用空间
+#[tauri::command]
+pub async fn get_partial_transfers() -> Result<PartialTransferReport, String> {
+    service::spawn_blocking(resume::list_partial_transfers)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 清理未完成的接收
+///
+/// max_age_hours 为空时使用配置的保留时长，只清理超过该时长未写入的接收；不能为 0
+#[tauri::command]
+pub async fn cleanup_partial_transfers(
+    max_age_hours: Option<u64>,
+) -> Result<PartialCleanupResult, String> {
+    service::spawn_blocking(move || resume::cleanup_partial_transfers(max_age_hours))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// 设置未完成接收的最大保留时长（小时）
+#[tauri::command]
+pub fn set_partial_max_age_hours(hours: u64) -> Result<(), String> {
+    config::set_partial_max_age_hours(hours).map_err(|e| e.to_string())
+}
+
+/// 设置接收后处理动作（归类移动、自动解压、执行命令）
+#[tauri::command]
+pub fn set_post_receive_actions(actions: config::PostReceiveActions) -> Result<(), String> {
+    config::set_post_receive_actions(actions).map_err(|e| e.to_string())
+}
+
+/// 设置点对点连接心跳间隔与超时（秒）
+#[tauri::command]
+pub fn set_peer_heartbeat_settings(interval_secs: u64, timeout_secs: u64) -> Result<(), String> {
+    config::set_heartbeat_settings(interval_secs, timeout_secs).map_err(|e| e.to_string())
+}
+
 /// 设置按日期分组
 #[tauri::command]
 pub fn set_group_by_date(enabled: bool) -> Result<(), String> {
     config::set_group_by_date(enabled).map_err(|e| e.to_string())
 }
 
+// ============================================================================
+// 传输统计命令
+// ============================================================================
+
+/// 获取按对端设备汇总的传输统计（最近传输的设备在前）
+#[tauri::command]
+pub fn get_lan_transfer_stats() -> Vec<stats::PeerTransferStats> {
+    stats::get_peer_stats()
+}
+
+/// 获取最近的传输记录（最新的在前，用于绘制历史吞吐曲线）
+///
+/// device_id 为空时返回所有设备的记录，limit 默认 100
+#[tauri::command]
+pub fn get_lan_transfer_history(
+    device_id: Option<String>,
+    limit: Option<usize>,
+) -> Vec<stats::TransferMetrics> {
+    stats::get_history(device_id.as_deref(), limit.unwrap_or(100))
+}
+
+/// 清空传输统计（指定设备时只清空该设备）
+#[tauri::command]
+pub fn clear_lan_transfer_stats(device_id: Option<String>) -> Result<(), String> {
+    stats::clear(device_id.as_deref()).map_err(|e| e.to_string())
+}
+
 // ============================================================================
 // 调试命令
 // ============================================================================
@@ -0,0 +1,294 @@
+/*!
+ * 多设备群发模块
+ *
+ * 将同一批文件同时发送到多个已连接设备（例如同时发给手机和笔记本）
+ *
+ * 功能：
+ * - 文件只计算一次哈希，所有接收设备共用同一份文件元数据
+ * - 每个接收设备独立会话、并发传输，一个设备失败不影响其他设备
+ * - 共享读取缓存（ChunkCache）：速度相近的接收方共用已读取的文件块，减少重复读盘
+ * - 汇总进度：MulticastProgress 事件包含每个设备的进度与状态
+ * - 结束时发送 MulticastCompleted 事件，包含每个设备的最终结果
+ *
+ * 说明：
+ * - 接收设备必须已建立点对点连接（与 send_files_to_peer 相同）
+ * - 未连接的设备直接标记为失败，不影响其他设备
+ * - 每个设备的会话仍会发送常规的 TransferProgress/BatchProgress 事件
+ */
+
+use super::discovery::get_event_sender;
+use super::emit_lan_event;
+use super::protocol::*;
+use super::server::get_active_peer_connections_map;
+use super::service;
+use super::transfer::{self, ChunkCache, ParallelProgress, TransferError};
+use futures::future::join_all;
+use parking_lot::Mutex;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// 共享读取缓存容量（块数，每块 CHUNK_SIZE）
+const MULTICAST_CACHE_CHUNKS: usize = 32;
+
+/// 汇总进度事件间隔（毫秒）
+const MULTICAST_PROGRESS_INTERVAL_MS: u64 = 500;
+
+/// 单个接收设备的运行状态
+struct Recipient {
+    connection_id: Option<String>,
+    device: Option<DiscoveredDevice>,
+    progress: Option<Arc<ParallelProgress>>,
+    report: MulticastRecipientProgress,
+}
+
+// ============================================================================
+// 公共接口
+// ============================================================================
+
+/// 将一批文件同时发送到多个设备
+///
+/// 哈希计算完成后立即返回群发 ID，传输在后台进行
+pub async fn send_files_to_devices(
+    device_ids: Vec<String>,
+    file_paths: Vec<String>,
+) -> Result<String, TransferError> {
+    if file_paths.is_empty() {
+        return Err(TransferError::FileReadFailed("文件列表为空".to_string()));
+    }
+
+    let mut seen = HashSet::new();
+    let recipients: Vec<Recipient> = device_ids
+        .iter()
+        .filter(|id| !id.is_empty() && seen.insert(id.as_str()))
+        .map(|id| resolve_recipient(id))
+        .collect();
+
+    if !recipients.iter().any(|r| r.connection_id.is_some()) {
+        return Err(TransferError::ConnectionFailed(
+            "没有已连接的目标设备".to_string(),
+        ));
+    }
+
+    // 只计算一次哈希
+    let (files, total_size) = transfer::collect_file_metadata(&file_paths)?;
+    let multicast_id = Uuid::new_v4().to_string();
+
+    log::info!(
+        "[LanTransfer] 📡 开始群发: {} 个文件 -> {} 个设备",
+        files.len(),
+        recipients.len()
+    );
+
+    let recipients = Arc::new(Mutex::new(recipients));
+    let multicast_id_clone = multicast_id.clone();
+    service::spawn(async move {
+        run_multicast(multicast_id_clone, recipients, files, total_size, file_paths).await;
+    });
+
+    Ok(multicast_id)
+}
+
+// ============================================================================
+// 内部函数
+// ============================================================================
+
+/// 查找设备的已连接点对点连接
+fn resolve_recipient(device_id: &str) -> Recipient {
+    let connection = {
+        let connections = get_active_peer_connections_map();
+        let connections = connections.lock();
+        connections
+            .values()
+            .find(|c| {
+                c.peer_device.device_id == device_id
+                    && c.status == PeerConnectionStatus::Connected
+            })
+            .cloned()
+    };
+
+    let mut report = MulticastRecipientProgress {
+        device_id: device_id.to_string(),
+        device_name: device_id.to_string(),
+        session_id: None,
+        status: TransferStatus::Pending,
+        completed_files: 0,
+        transferred_bytes: 0,
+        error: None,
+    };
+
+    match connection {
+        Some(conn) => {
+            report.device_name = conn.peer_device.device_name.clone();
+            Recipient {
+                connection_id: Some(conn.connection_id),
+                device: Some(conn.peer_device),
+                progress: None,
+                report,
+            }
+        }
+        None => {
+            report.status = TransferStatus::Failed;
+            report.error = Some("未建立连接".to_string());
+            Recipient {
+                connection_id: None,
+                device: None,
+                progress: None,
+                report,
+            }
+        }
+    }
+}
+
+/// 执行群发：每个设备一个会话，并发传输
+async fn run_multicast(
+    multicast_id: String,
+    recipients: Arc<Mutex<Vec<Recipient>>>,
+    files: Vec<FileMetadata>,
+    total_size: u64,
+    file_paths: Vec<String>,
+) {
+    let chunk_cache = Arc::new(ChunkCache::new(MULTICAST_CACHE_CHUNKS));
+    let total_files = files.len() as u32;
+
+    let targets: Vec<(usize, String, DiscoveredDevice)> = recipients
+        .lock()
+        .iter()
+        .enumerate()
+        .filter_map(|(i, r)| Some((i, r.connection_id.clone()?, r.device.clone()?)))
+        .collect();
+
+    // 定时发送汇总进度
+    let ticker = {
+        let recipients = recipients.clone();
+        let multicast_id = multicast_id.clone();
+        service::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_millis(MULTICAST_PROGRESS_INTERVAL_MS)).await;
+                let progress = snapshot(&multicast_id, &recipients, total_files, total_size);
+                let event = LanTransferEvent::MulticastProgress { progress };
+                let _ = get_event_sender().send(event.clone());
+                emit_lan_event(&event);
+            }
+        })
+    };
+
+    let handles = targets.into_iter().map(|(index, connection_id, device)| {
+        let recipients = recipients.clone();
+        let chunk_cache = chunk_cache.clone();
+        let file_paths = file_paths.clone();
+        // 每个设备使用独立的 file_id，避免取消令牌和活跃传输表冲突
+        let device_files: Vec<FileMetadata> = files
+            .iter()
+            .map(|f| FileMetadata {
+                file_id: Uuid::new_v4().to_string(),
+                ..f.clone()
+            })
+            .collect();
+
+        async move {
+            let result = async {
+                let session_id = Uuid::new_v4().to_string();
+                transfer::open_direct_session(
+                    &session_id,
+                    &connection_id,
+                    &device,
+                    &device_files,
+                    &file_paths,
+                )
+                .await?;
+                let progress = Arc::new(ParallelProgress::new(&session_id, &device_files));
+
+                {
+                    let mut recipients = recipients.lock();
+                    let r = &mut recipients[index];
+                    r.report.session_id = Some(session_id.clone());
+                    r.report.status = TransferStatus::Transferring;
+                    r.progress = Some(progress.clone());
+                }
+
+                transfer::run_batch_transfer(
+                    &session_id,
+                    file_paths,
+                    Some(progress.clone()),
+                    Some(chunk_cache),
+                )
+                .await?;
+
+                // 部分文件失败时 run_batch_transfer 仍返回 Ok，这里按完成数判断
+                if progress.completed_files() < total_files {
+                    return Err(TransferError::TransferFailed(format!(
+                        "{}/{} 个文件传输失败",
+                        total_files - progress.completed_files(),
+                        total_files
+                    )));
+                }
+                Ok(())
+            }
+            .await;
+
+            let mut recipients = recipients.lock();
+            let r = &mut recipients[index];
+            match result {
+                Ok(()) => r.report.status = TransferStatus::Completed,
+                Err(e) => {
+                    log::error!(
+                        "[LanTransfer] 群发到 {} 失败: {}",
+                        r.report.device_name, e
+                    );
+                    r.report.status = TransferStatus::Failed;
+                    r.report.error = Some(e.to_string());
+                }
+            }
+        }
+    });
+
+    join_all(handles).await;
+    ticker.abort();
+
+    let progress = snapshot(&multicast_id, &recipients, total_files, total_size);
+    let succeeded = progress
+        .recipients
+        .iter()
+        .filter(|r| r.status == TransferStatus::Completed)
+        .count();
+
+    log::info!(
+        "[LanTransfer] 📡 群发结束: {}/{} 个设备成功",
+        succeeded,
+        progress.recipients.len()
+    );
+
+    let event = LanTransferEvent::MulticastCompleted { progress };
+    let _ = get_event_sender().send(event.clone());
+    emit_lan_event(&event);
+}
+
+/// 生成当前汇总进度
+fn snapshot(
+    multicast_id: &str,
+    recipients: &Mutex<Vec<Recipient>>,
+    total_files: u32,
+    total_bytes: u64,
+) -> MulticastProgress {
+    let recipients = recipients
+        .lock()
+        .iter()
+        .map(|r| {
+            let mut report = r.report.clone();
+            if let Some(progress) = &r.progress {
+                report.completed_files = progress.completed_files();
+                report.transferred_bytes = progress.transferred_bytes();
+            }
+            report
+        })
+        .collect();
+
+    MulticastProgress {
+        multicast_id: multicast_id.to_string(),
+        total_files,
+        total_bytes,
+        recipients,
+    }
+}
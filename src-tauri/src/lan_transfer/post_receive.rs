@@ -0,0 +1,466 @@
+/*!
+ * 接收后处理模块
+ *
+ * 文件接收完成（finalize_transfer 之后）按配置依次执行处理动作
+ *
+ * 功能：
+ * - 归类移动：按发送方（设备名子文件夹）和/或文件类型（MIME 规则子文件夹）移动
+ * - 自动解压：zip 压缩包解压到同名文件夹（保留压缩包）
+ * - 执行命令：以最终保存路径作为最后一个参数运行用户命令（如病毒扫描），带超时
+ * - 结果通过 PostReceiveCompleted 事件报告（每个动作的成功/失败与说明）
+ *
+ * 说明：
+ * - 未配置任何动作时不做处理、不发送事件
+ * - 处理在后台任务中进行，不阻塞 /api/finish 响应
+ * - 某个动作失败不影响后续动作（移动失败时后续动作使用原路径）
+ * - 解压时拒绝越界路径（zip-slip），并限制条目数和解压总大小
+ *   （按实际写入的字节数计算，不信任压缩包声明的大小；超限时删除已解压的目录）
+ *
+ * 更新日志：
+ * - 2026-10-18: 解压大小上限按实际写入字节数计算，超限或失败时删除解压目录
+ */
+
+use super::config::{self, MimeFolderRule, PostReceiveActions, PostReceiveCommand};
+use super::discovery::get_event_sender;
+use super::emit_lan_event;
+use super::protocol::*;
+use super::resume::ResumeManager;
+use super::service;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// 解压条目数上限
+const MAX_EXTRACT_ENTRIES: usize = 10_000;
+
+/// 解压总大小上限（8 GB，防止压缩炸弹）
+const MAX_EXTRACT_BYTES: u64 = 8 * 1024 * 1024 * 1024;
+
+/// 命令输出保留长度（字符）
+const MAX_COMMAND_OUTPUT_CHARS: usize = 2000;
+
+/// 内置类型归类规则（MIME 前缀 -> 子文件夹）
+const BUILTIN_TYPE_RULES: &[(&str, &str)] = &[
+    ("image/", "图片"),
+    ("video/", "视频"),
+    ("audio/", "音频"),
+    ("text/", "文档"),
+    ("application/pdf", "文档"),
+    ("application/msword", "文档"),
+    ("application/vnd.ms-", "文档"),
+    ("application/vnd.openxmlformats-officedocument", "文档"),
+    ("application/vnd.oasis.opendocument", "文档"),
+    ("application/zip", "压缩包"),
+    ("application/x-7z-compressed", "压缩包"),
+    ("application/vnd.rar", "压缩包"),
+    ("application/x-rar-compressed", "压缩包"),
+    ("application/x-tar", "压缩包"),
+    ("application/gzip", "压缩包"),
+    ("application/x-bzip2", "压缩包"),
+    ("application/x-xz", "压缩包"),
+    ("application/vnd.android.package-archive", "安装包"),
+    ("application/x-msdownload", "安装包"),
+    ("application/x-msi", "安装包"),
+    ("application/x-apple-diskimage", "安装包"),
+];
+
+/// 未匹配任何规则时的子文件夹
+const FALLBACK_TYPE_FOLDER: &str = "其他";
+
+// ============================================================================
+// 入口
+// ============================================================================
+
+/// 执行接收后处理（在后台任务中运行）
+///
+/// - task_id: 文件 ID（与 TransferCompleted 事件一致）
+/// - saved_path: finalize_transfer 返回的保存路径
+/// - sender_name: 发送方名称（用于按发送方归类）
+pub fn spawn_post_receive(
+    task_id: String,
+    saved_path: PathBuf,
+    file_meta: &FileMetadata,
+    sender_name: String,
+) {
+    let actions = config::get_full_config().post_receive;
+    if actions.is_empty() {
+        return;
+    }
+
+    let mime_type = resolve_mime_type(file_meta);
+
+    service::spawn(async move {
+        let (final_path, results) =
+            run_post_receive(&actions, saved_path.clone(), &mime_type, &sender_name).await;
+
+        for result in &results {
+            log::info!(
+                "[LanTransfer] 📂 接收后处理 {:?}: {} - {}",
+                result.action,
+                if result.success { "成功" } else { "失败" },
+                result.message
+            );
+        }
+
+        let event = LanTransferEvent::PostReceiveCompleted {
+            task_id,
+            saved_path: saved_path.to_string_lossy().to_string(),
+            final_path: final_path.to_string_lossy().to_string(),
+            results,
+        };
+        let _ = get_event_sender().send(event.clone());
+        emit_lan_event(&event);
+    });
+}
+
+/// 依次执行配置的动作，返回最终路径和每个动作的结果
+async fn run_post_receive(
+    actions: &PostReceiveActions,
+    saved_path: PathBuf,
+    mime_type: &str,
+    sender_name: &str,
+) -> (PathBuf, Vec<PostReceiveActionResult>) {
+    let mut results = Vec::new();
+    let mut current_path = saved_path;
+
+    // 1. 归类移动
+    if actions.per_sender_folder || actions.sort_by_type {
+        let mut target_dir = current_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        if actions.per_sender_folder {
+            target_dir.push(sanitize_folder_name(sender_name));
+        }
+        if actions.sort_by_type {
+            target_dir.push(type_folder(mime_type, &actions.type_rules));
+        }
+
+        let source = current_path.clone();
+        let moved = service::spawn_blocking(move || move_into(&source, &target_dir))
+            .await
+            .unwrap_or_else(|e| Err(io::Error::other(e.to_string())));
+
+        match moved {
+            Ok(new_path) => {
+                results.push(action_result(
+                    PostReceiveActionKind::Move,
+                    true,
+                    new_path.to_string_lossy().to_string(),
+                ));
+                current_path = new_path;
+            }
+            Err(e) => results.push(action_result(
+                PostReceiveActionKind::Move,
+                false,
+                format!("移动失败: {}", e),
+            )),
+        }
+    }
+
+    // 2. 解压
+    if actions.extract_archives && is_zip(&current_path, mime_type) {
+        let archive = current_path.clone();
+        let extracted = service::spawn_blocking(move || extract_zip(&archive))
+            .await
+            .unwrap_or_else(|e| Err(io::Error::other(e.to_string())));
+
+        results.push(match extracted {
+            Ok((dir, count)) => action_result(
+                PostReceiveActionKind::Extract,
+                true,
+                format!("已解压 {} 个文件到 {}", count, dir.to_string_lossy()),
+            ),
+            Err(e) => action_result(
+                PostReceiveActionKind::Extract,
+                false,
+                format!("解压失败: {}", e),
+            ),
+        });
+    }
+
+    // 3. 执行命令
+    if let Some(command) = &actions.command {
+        results.push(run_command(command, &current_path).await);
+    }
+
+    (current_path, results)
+}
+
+// ============================================================================
+// 归类移动
+// ============================================================================
+
+/// 文件 MIME 类型（发送方未提供时按文件名推断）
+fn resolve_mime_type(file_meta: &FileMetadata) -> String {
+    if file_meta.mime_type.is_empty() || file_meta.mime_type == "application/octet-stream" {
+        mime_guess::from_path(&file_meta.file_name)
+            .first_or_octet_stream()
+            .to_string()
+    } else {
+        file_meta.mime_type.clone()
+    }
+}
+
+/// 根据 MIME 类型选择子文件夹（自定义规则优先）
+fn type_folder(mime_type: &str, rules: &[MimeFolderRule]) -> String {
+    let mime_type = mime_type.to_ascii_lowercase();
+
+    rules
+        .iter()
+        .find(|rule| mime_type.starts_with(&rule.mime_prefix.to_ascii_lowercase()))
+        .map(|rule| rule.folder.trim().to_string())
+        .or_else(|| {
+            BUILTIN_TYPE_RULES
+                .iter()
+                .find(|(prefix, _)| mime_type.starts_with(prefix))
+                .map(|(_, folder)| folder.to_string())
+        })
+        .unwrap_or_else(|| FALLBACK_TYPE_FOLDER.to_string())
+}
+
+/// 将发送方名称转换为安全的文件夹名
+fn sanitize_folder_name(name: &str) -> String {
+    let sanitized: String = name
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let sanitized = sanitized.trim_matches(|c| c == '.' || c == ' ');
+
+    if sanitized.is_empty() {
+        "未知设备".to_string()
+    } else {
+        sanitized.to_string()
+    }
+}
+
+/// 移动文件到目标目录（文件名冲突时自动重命名）
+fn move_into(source: &Path, target_dir: &Path) -> io::Result<PathBuf> {
+    if source.parent() == Some(target_dir) {
+        return Ok(source.to_path_buf());
+    }
+
+    let file_name = source
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "无效的文件路径"))?;
+
+    fs::create_dir_all(target_dir)?;
+    let target = ResumeManager::resolve_filename_conflict(&target_dir.join(file_name));
+
+    // 跨文件系统时 rename 失败，回退为复制后删除
+    if fs::rename(source, &target).is_err() {
+        fs::copy(source, &target)?;
+        fs::remove_file(source)?;
+    }
+
+    Ok(target)
+}
+
+// ============================================================================
+// 解压
+// ============================================================================
+
+/// 是否为 zip 压缩包
+fn is_zip(path: &Path, mime_type: &str) -> bool {
+    mime_type == "application/zip"
+        || path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("zip"))
+}
+
+/// 解压 zip 到同名文件夹，返回解压目录和文件数
+fn extract_zip(archive_path: &Path) -> io::Result<(PathBuf, usize)> {
+    extract_zip_limited(archive_path, MAX_EXTRACT_BYTES)
+}
+
+/// 解压 zip 到同名文件夹，解压总大小不超过 max_bytes；失败时删除解压目录
+fn extract_zip_limited(archive_path: &Path, max_bytes: u64) -> io::Result<(PathBuf, usize)> {
+    let file = fs::File::open(archive_path)?;
+    let mut archive = zip::ZipArchive::new(file).map_err(io::Error::other)?;
+
+    if archive.len() > MAX_EXTRACT_ENTRIES {
+        return Err(io::Error::other(format!(
+            "条目过多 ({} > {})",
+            archive.len(),
+            MAX_EXTRACT_ENTRIES
+        )));
+    }
+
+    let stem = archive_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("archive");
+    let parent = archive_path.parent().unwrap_or(Path::new("."));
+    let target_dir = ResumeManager::resolve_filename_conflict(&parent.join(stem));
+    fs::create_dir_all(&target_dir)?;
+
+    match extract_entries(&mut archive, &target_dir, max_bytes) {
+        Ok(file_count) => Ok((target_dir, file_count)),
+        Err(e) => {
+            let _ = fs::remove_dir_all(&target_dir);
+            Err(e)
+        }
+    }
+}
+
+/// 解压所有条目到目标目录，返回文件数
+fn extract_entries(
+    archive: &mut zip::ZipArchive<fs::File>,
+    target_dir: &Path,
+    max_bytes: u64,
+) -> io::Result<usize> {
+    let mut total_bytes = 0u64;
+    let mut file_count = 0usize;
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(io::Error::other)?;
+
+        // enclosed_name 会拒绝绝对路径和 ".." 越界路径
+        let Some(relative) = entry.enclosed_name() else {
+            log::warn!("[LanTransfer] ⚠️ 跳过不安全的压缩包条目: {}", entry.name());
+            continue;
+        };
+        let out_path = target_dir.join(relative);
+
+        if entry.is_dir() {
+            fs::create_dir_all(&out_path)?;
+            continue;
+        }
+
+        if let Some(dir) = out_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut out_file = fs::File::create(&out_path)?;
+
+        // 条目声明的大小可能是伪造的：最多写入剩余额度 + 1 字节，按实际写入量判断是否超限
+        let remaining = max_bytes - total_bytes;
+        total_bytes += io::copy(&mut (&mut entry).take(remaining + 1), &mut out_file)?;
+        if total_bytes > max_bytes {
+            return Err(io::Error::other("解压后大小超过上限"));
+        }
+        file_count += 1;
+    }
+
+    Ok(file_count)
+}
+
+// ============================================================================
+// 执行命令
+// ============================================================================
+
+/// 运行用户命令（保存路径作为最后一个参数）
+async fn run_command(command: &PostReceiveCommand, path: &Path) -> PostReceiveActionResult {
+    let child = tokio::process::Command::new(&command.program)
+        .args(&command.args)
+        .arg(path)
+        .stdin(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .output();
+
+    let output = match tokio::time::timeout(Duration::from_secs(command.timeout_secs), child).await
+    {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => {
+            return action_result(
+                PostReceiveActionKind::Command,
+                false,
+                format!("无法执行 {}: {}", command.program, e),
+            );
+        }
+        Err(_) => {
+            return action_result(
+                PostReceiveActionKind::Command,
+                false,
+                format!("命令超时（{} 秒）", command.timeout_secs),
+            );
+        }
+    };
+
+    let mut message = match output.status.code() {
+        Some(code) => format!("退出码 {}", code),
+        None => "被信号终止".to_string(),
+    };
+    for stream in [&output.stdout, &output.stderr] {
+        let text = String::from_utf8_lossy(stream);
+        let text = text.trim();
+        if !text.is_empty() {
+            message.push('\n');
+            message.extend(text.chars().take(MAX_COMMAND_OUTPUT_CHARS));
+        }
+    }
+
+    action_result(PostReceiveActionKind::Command, output.status.success(), message)
+}
+
+fn action_result(
+    action: PostReceiveActionKind,
+    success: bool,
+    message: String,
+) -> PostReceiveActionResult {
+    PostReceiveActionResult {
+        action,
+        success,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// 在临时目录中创建包含一个文件的 zip
+    fn create_zip(dir: &Path, content: &[u8]) -> PathBuf {
+        let path = dir.join("archive.zip");
+        let mut writer = zip::ZipWriter::new(fs::File::create(&path).unwrap());
+        writer
+            .start_file("nested/data.bin", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(content).unwrap();
+        writer.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn extract_aborts_and_cleans_up_over_size_limit() {
+        let dir = std::env::temp_dir().join(format!("huanvae-unzip-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let archive = create_zip(&dir, &[7u8; 4096]);
+
+        assert!(extract_zip_limited(&archive, 4095).is_err());
+        assert!(!dir.join("archive").exists());
+
+        let (target, count) = extract_zip_limited(&archive, 4096).unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(target, dir.join("archive"));
+        assert_eq!(fs::read(target.join("nested").join("data.bin")).unwrap().len(), 4096);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+
+    #[test]
+    fn type_folder_prefers_custom_rules() {
+        let rules = vec![MimeFolderRule {
+            mime_prefix: "image/png".to_string(),
+            folder: "截图".to_string(),
+        }];
+
+        assert_eq!(type_folder("image/png", &rules), "截图");
+        assert_eq!(type_folder("image/jpeg", &rules), "图片");
+        assert_eq!(type_folder("application/x-unknown", &rules), "其他");
+    }
+
+    #[test]
+    fn sanitize_folder_name_strips_separators() {
+        assert_eq!(sanitize_folder_name("../Alice/PC"), "_Alice_PC");
+        assert_eq!(sanitize_folder_name(" .. "), "未知设备");
+    }
+}
@@ -0,0 +1,167 @@
+/*!
+ * 局域网联系人关联模块
+ *
+ * 将 mDNS 发现的设备（TXT 记录中的 user_id）与本地好友、群组关联
+ *
+ * 功能：
+ * - 查询局域网设备对应的好友和共同群组（用于"同一网络"标识）
+ * - 查询某个好友当前在局域网中的设备
+ * - 好友设备上线/离线时发送 FriendDeviceJoined / FriendDeviceLeft 事件
+ *
+ * 说明：
+ * - 数据库未初始化（未登录）或查询失败时不做关联，也不发送好友事件
+ * - 共同群组的判断规则见 db::get_groups_shared_with
+ */
+
+use super::discovery::get_event_sender;
+use super::protocol::{DiscoveredDevice, LanTransferEvent};
+use super::{emit_lan_event, get_lan_transfer_state};
+use crate::db::{self, LocalFriend, LocalGroup};
+use serde::Serialize;
+
+/// 局域网设备与联系人的关联信息
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LanContact {
+    /// 发现的设备
+    pub device: DiscoveredDevice,
+    /// 是否为当前账号的其他设备
+    pub is_own_device: bool,
+    /// 对应的好友（不是好友时为 None）
+    pub friend: Option<LocalFriend>,
+    /// 共同群组
+    pub shared_groups: Vec<LocalGroup>,
+}
+
+/// 获取所有局域网设备及其关联的联系人
+///
+/// 数据库查询失败（如未登录）时设备仍然返回，只是没有关联的好友和群组
+pub fn get_lan_contacts() -> Vec<LanContact> {
+    let state = get_lan_transfer_state();
+    let devices: Vec<DiscoveredDevice> = state.devices.read().values().cloned().collect();
+    let my_user_id = state
+        .local_device
+        .read()
+        .as_ref()
+        .map(|d| d.user_id.clone())
+        .unwrap_or_default();
+
+    let mut contacts = Vec::with_capacity(devices.len());
+    for device in devices {
+        let (friend, shared_groups) = if device.user_id.is_empty() {
+            (None, Vec::new())
+        } else {
+            (
+                db::get_friend(&device.user_id).ok().flatten(),
+                db::get_groups_shared_with(&device.user_id).unwrap_or_default(),
+            )
+        };
+
+        contacts.push(LanContact {
+            is_own_device: !my_user_id.is_empty() && device.user_id == my_user_id,
+            device,
+            friend,
+            shared_groups,
+        });
+    }
+
+    contacts
+}
+
+/// 获取好友当前在局域网中的设备
+pub fn get_friend_lan_devices(friend_id: &str) -> Vec<DiscoveredDevice> {
+    let state = get_lan_transfer_state();
+    let devices = state.devices.read();
+    devices
+        .values()
+        .filter(|d| d.user_id == friend_id)
+        .cloned()
+        .collect()
+}
+
+/// 设备上线：如果是好友的设备，发送 FriendDeviceJoined 事件
+pub fn notify_device_joined(device: &DiscoveredDevice) {
+    if !is_friend(&device.user_id) {
+        return;
+    }
+
+    log::info!(
+        "[LanTransfer] 好友设备上线: {} ({})",
+        device.user_nickname, device.device_name
+    );
+
+    let event = LanTransferEvent::FriendDeviceJoined {
+        friend_id: device.user_id.clone(),
+        device: device.clone(),
+    };
+    let _ = get_event_sender().send(event.clone());
+    emit_lan_event(&event);
+}
+
+/// 设备离线：如果是好友的设备，发送 FriendDeviceLeft 事件
+pub fn notify_device_left(device: &DiscoveredDevice) {
+    if !is_friend(&device.user_id) {
+        return;
+    }
+
+    log::info!(
+        "[LanTransfer] 好友设备离线: {} ({})",
+        device.user_nickname, device.device_name
+    );
+
+    let event = LanTransferEvent::FriendDeviceLeft {
+        friend_id: device.user_id.clone(),
+        device_id: device.device_id.clone(),
+    };
+    let _ = get_event_sender().send(event.clone());
+    emit_lan_event(&event);
+}
+
+/// 判断用户是否为好友（数据库未初始化时视为否）
+fn is_friend(user_id: &str) -> bool {
+    if user_id.is_empty() {
+        return false;
+    }
+
+    matches!(db::get_friend(user_id), Ok(Some(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lan_transfer::service::{LanTransferService, ServiceOptions};
+
+    #[test]
+    fn contacts_without_database_have_no_association() {
+        // 其他测试可能临时初始化全局数据库
+        let _db = crate::db::TEST_DB_LOCK.lock();
+        let service = LanTransferService::new(ServiceOptions {
+            enable_mdns: false,
+            ..Default::default()
+        });
+        let now = chrono::Utc::now().to_rfc3339();
+        service.state().devices.write().insert(
+            "device-1".to_string(),
+            DiscoveredDevice {
+                device_id: "device-1".to_string(),
+                device_name: "Laptop".to_string(),
+                user_id: "friend-user".to_string(),
+                user_nickname: "Alice".to_string(),
+                ip_address: "192.168.1.20".to_string(),
+                port: 53317,
+                discovered_at: now.clone(),
+                last_seen: now,
+            },
+        );
+
+        // 测试中不会初始化数据库，查询好友和群组都会失败
+        assert!(db::get_friend("friend-user").is_err());
+
+        let contacts = service.enter(get_lan_contacts);
+        assert_eq!(contacts.len(), 1);
+        assert_eq!(contacts[0].device.device_id, "device-1");
+        assert!(contacts[0].friend.is_none());
+        assert!(contacts[0].shared_groups.is_empty());
+        assert!(!contacts[0].is_own_device);
+    }
+}
@@ -30,6 +30,31 @@ pub const CHUNK_SIZE: usize = 1024 * 1024;
 /// 协议版本
 pub const PROTOCOL_VERSION: &str = "1.0";
 
+/// 文本片段最大长度：64KB（超出部分应通过文件传输）
+pub const MAX_SNIPPET_BYTES: usize = 64 * 1024;
+
+/// 局域网聊天消息最大长度：16KB
+pub const MAX_CHAT_MESSAGE_BYTES: usize = 16 * 1024;
+
+/// 待处理请求（传输请求、连接请求）有效期：2 分钟，超时自动拒绝
+pub const PENDING_REQUEST_TTL_SECS: i64 = 120;
+
+/// 每个来源设备最多同时存在的待处理传输请求数
+pub const MAX_PENDING_REQUESTS_PER_DEVICE: usize = 5;
+
+/// 每个来源 IP 最多同时存在的待处理传输请求数（防止同一主机伪造不同设备 ID 绕过设备上限）
+pub const MAX_PENDING_REQUESTS_PER_IP: usize = 10;
+
+/// 连通性探测（/api/probe）单次最大数据量：4MB
+pub const PROBE_MAX_BYTES: usize = 4 * 1024 * 1024;
+
+/// 连通性探测速率限制：每个来源 IP 在 PROBE_RATE_WINDOW_SECS 内最多 MAX_PROBES_PER_WINDOW 次
+/// （一次完整探测包含上行、下行各一次请求）
+pub const MAX_PROBES_PER_WINDOW: u32 = 10;
+
+/// 连通性探测速率限制的时间窗口（秒）
+pub const PROBE_RATE_WINDOW_SECS: u64 = 60;
+
 // ============================================================================
 // 设备信息
 // ============================================================================
@@ -133,6 +158,8 @@ pub struct ConnectionResponse {
 pub enum PeerConnectionStatus {
     /// 已连接
     Connected,
+    /// 心跳未响应（对端可能休眠或网络波动），恢复响应后回到 Connected
+    Degraded,
     /// 已断开
     Disconnected,
 }
@@ -153,6 +180,26 @@ pub struct PeerConnection {
     pub is_initiator: bool,
 }
 
+/// 心跳请求（点对点连接保活）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerHeartbeatRequest {
+    /// 连接 ID
+    pub connection_id: String,
+    /// 发送方设备 ID
+    pub device_id: String,
+}
+
+/// 心跳响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerHeartbeatResponse {
+    /// 对端是否仍保留该连接
+    pub alive: bool,
+    /// 响应方设备 ID（用于确认对端身份未变化）
+    pub device_id: String,
+}
+
 /// 连接请求（用于建立点对点连接）
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -178,12 +225,137 @@ pub struct PeerConnectionResponse {
     pub from_device: Option<DiscoveredDevice>,
 }
 
+// ============================================================================
+// 文本片段（剪贴板共享）
+// ============================================================================
+
+/// 文本片段格式
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SnippetFormat {
+    /// 纯文本（URL、密码、代码片段等）
+    Plain,
+    /// 富文本（HTML，来自剪贴板）
+    Html,
+}
+
+/// 文本片段（通过已建立的点对点连接发送）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextSnippet {
+    /// 片段 ID
+    pub snippet_id: String,
+    /// 关联的连接 ID
+    pub connection_id: String,
+    /// 发送方设备信息
+    pub from_device: DiscoveredDevice,
+    /// 片段内容
+    pub content: String,
+    /// 片段格式
+    pub format: SnippetFormat,
+    /// 发送时间
+    pub sent_at: String,
+    /// 接收时间（接收方填写）
+    #[serde(default)]
+    pub received_at: String,
+}
+
+// ============================================================================
+// 局域网聊天（服务器不可用时的离线消息）
+// ============================================================================
+
+/// 局域网聊天消息（通过已建立的点对点连接发送）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LanChatMessage {
+    /// 消息 ID（与本地 messages 表的 message_uuid 一致，用于去重）
+    pub message_id: String,
+    /// 关联的连接 ID
+    pub connection_id: String,
+    /// 发送方设备信息
+    pub from_device: DiscoveredDevice,
+    /// 消息内容
+    pub content: String,
+    /// 内容类型（目前仅支持 text）
+    pub content_type: String,
+    /// 发送时间
+    pub sent_at: String,
+}
+
+// ============================================================================
+// 共享文件夹（对端浏览并拉取）
+// ============================================================================
+
+/// 单次拉取的最大文件数（防止误选超大目录）
+pub const MAX_SHARE_PULL_FILES: usize = 1000;
+
+/// 共享文件夹概要（返回给对端，不含本地路径）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedFolderInfo {
+    /// 共享 ID
+    pub share_id: String,
+    /// 显示名称
+    pub name: String,
+}
+
+/// 共享文件夹中的条目
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareEntry {
+    /// 文件/目录名
+    pub name: String,
+    /// 相对于共享根目录的路径（使用 / 分隔）
+    pub relative_path: String,
+    /// 是否为目录
+    pub is_dir: bool,
+    /// 文件大小（目录为 0）
+    pub size: u64,
+    /// 修改时间
+    pub modified_at: Option<String>,
+}
+
+// ============================================================================
+// 文件夹同步（本账号设备之间）
+// ============================================================================
+
+/// 同步清单中的文件条目
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncEntry {
+    /// 相对于同步目录的路径（使用 / 分隔）
+    pub relative_path: String,
+    /// 文件大小
+    pub size: u64,
+    /// 修改时间（Unix 毫秒）
+    pub modified_at: i64,
+    /// 文件哈希 (CRC32)
+    pub hash: String,
+}
+
+/// 一次同步的结果统计
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncSummary {
+    /// 发送到对端的文件数
+    pub uploaded: u32,
+    /// 从对端接收的文件数
+    pub downloaded: u32,
+    /// 删除的文件数（两端合计）
+    pub deleted: u32,
+    /// 冲突数（保留了冲突副本）
+    pub conflicts: u32,
+}
+
 // ============================================================================
 // 文件传输
 // ============================================================================
 
 /// 文件元信息
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// modified_at / created_at / mode / relative_path 为可选属性，
+/// 旧版本发送方不提供时接收方按普通文件处理
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FileMetadata {
     /// 文件 ID
@@ -197,6 +369,49 @@ pub struct FileMetadata {
     /// 文件哈希 (CRC32，8字符十六进制)
     /// 用于传输完整性验证，采用高性能 crc32fast 库
     pub sha256: String,  // 字段名保持不变以兼容现有协议
+    /// 修改时间（Unix 毫秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_at: Option<i64>,
+    /// 创建时间（Unix 毫秒，仅 Windows / macOS 可恢复）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<i64>,
+    /// Unix 权限位（如 0o755，仅 Unix 发送方提供、Unix 接收方恢复）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+    /// 相对路径（发送文件夹时使用 / 分隔，接收方据此建立子目录）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relative_path: Option<String>,
+}
+
+impl FileMetadata {
+    /// 相对于保存目录的安全路径（使用 / 分隔）
+    ///
+    /// 优先使用 relative_path，丢弃空段、`.`、`..` 和盘符，防止写到保存目录之外
+    pub fn target_relative_path(&self) -> String {
+        let sanitize = |raw: &str| -> Vec<String> {
+            raw.split(['/', '\\'])
+                .map(|part| part.replace(':', "_").trim().to_string())
+                .filter(|part| !part.is_empty() && part != "." && part != "..")
+                .collect()
+        };
+
+        let mut parts = self
+            .relative_path
+            .as_deref()
+            .map(sanitize)
+            .unwrap_or_default();
+        if parts.is_empty() {
+            parts = sanitize(&self.file_name);
+            // 文件名本身不应包含目录
+            parts.drain(..parts.len().saturating_sub(1));
+        }
+
+        if parts.is_empty() {
+            "unnamed".to_string()
+        } else {
+            parts.join("/")
+        }
+    }
 }
 
 // ============================================================================
@@ -249,6 +464,32 @@ pub struct TransferRequestResponse {
     pub save_directory: Option<String>,
 }
 
+// ============================================================================
+// 离线发送队列
+// ============================================================================
+
+/// 排队中的发送任务（目标设备上线后自动投递）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedTransfer {
+    /// 队列任务 ID
+    pub queue_id: String,
+    /// 目标设备 ID
+    pub device_id: String,
+    /// 目标设备名称
+    pub device_name: String,
+    /// 要发送的文件路径
+    pub file_paths: Vec<String>,
+    /// 总大小
+    pub total_size: u64,
+    /// 加入队列时间
+    pub queued_at: String,
+    /// 过期时间
+    pub expires_at: String,
+    /// 投递失败次数
+    pub attempts: u32,
+}
+
 // ============================================================================
 // 断点续传
 // ============================================================================
@@ -270,6 +511,87 @@ pub struct ResumeInfo {
     pub chunk_hashes: Vec<String>,
     /// 最后更新时间
     pub last_updated: String,
+    /// 文件名（用于未完成传输列表展示）
+    #[serde(default)]
+    pub file_name: String,
+    /// 文件总大小
+    #[serde(default)]
+    pub file_size: u64,
+    /// 来源设备（设备名或 IP）
+    #[serde(default)]
+    pub origin_device: String,
+}
+
+/// 未完成的接收（临时文件 + 续传信息）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PartialTransfer {
+    /// 文件 ID
+    pub file_id: String,
+    /// 文件名（未知时为空）
+    pub file_name: String,
+    /// 来源设备（未知时为空）
+    pub origin_device: String,
+    /// 已占用磁盘空间（临时文件 + 续传信息）
+    pub size_on_disk: u64,
+    /// 文件总大小（未知时为 0）
+    pub file_size: u64,
+    /// 最后写入时间
+    pub last_modified: String,
+    /// 距最后写入的时长（秒）
+    pub age_secs: u64,
+    /// 是否超过最大保留时长（会被清理）
+    pub stale: bool,
+}
+
+/// 未完成接收的汇总
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PartialTransferReport {
+    /// 未完成的接收（按最后写入时间从旧到新）
+    pub partials: Vec<PartialTransfer>,
+    /// 占用的总空间
+    pub total_bytes: u64,
+    /// 过期部分占用的空间
+    pub stale_bytes: u64,
+    /// 最大保留时长（小时）
+    pub max_age_hours: u64,
+}
+
+/// 未完成接收的清理结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PartialCleanupResult {
+    /// 删除的未完成接收数
+    pub removed_count: u32,
+    /// 释放的空间
+    pub freed_bytes: u64,
+}
+
+// ============================================================================
+// 接收后处理
+// ============================================================================
+
+/// 接收后处理动作类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PostReceiveActionKind {
+    /// 按发送方/类型归类移动
+    Move,
+    /// 解压压缩包
+    Extract,
+    /// 执行用户命令
+    Command,
+}
+
+/// 单个接收后处理动作的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostReceiveActionResult {
+    pub action: PostReceiveActionKind,
+    pub success: bool,
+    /// 结果说明（目标路径、命令输出或错误信息）
+    pub message: String,
 }
 
 // ============================================================================
@@ -360,6 +682,40 @@ pub struct BatchTransferProgress {
     pub eta_seconds: Option<u64>,
 }
 
+/// 群发中单个接收设备的进度
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MulticastRecipientProgress {
+    /// 接收设备 ID
+    pub device_id: String,
+    /// 接收设备名称
+    pub device_name: String,
+    /// 该设备对应的传输会话 ID（会话建立前为空）
+    pub session_id: Option<String>,
+    /// 传输状态
+    pub status: TransferStatus,
+    /// 已完成文件数
+    pub completed_files: u32,
+    /// 已传输字节数
+    pub transferred_bytes: u64,
+    /// 失败原因
+    pub error: Option<String>,
+}
+
+/// 群发（一批文件同时发送到多个设备）进度
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MulticastProgress {
+    /// 群发 ID
+    pub multicast_id: String,
+    /// 每个设备的文件数
+    pub total_files: u32,
+    /// 每个设备的总字节数
+    pub total_bytes: u64,
+    /// 各接收设备进度
+    pub recipients: Vec<MulticastRecipientProgress>,
+}
+
 // ============================================================================
 // 文件传输 API
 // ============================================================================
@@ -426,7 +782,6 @@ pub struct ChunkResponse {
 }
 
 /// 传输完成请求
-#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FinishUploadRequest {
@@ -521,6 +876,13 @@ pub enum LanTransferEvent {
     DeviceDiscovered { device: DiscoveredDevice },
     /// 设备离线
     DeviceLeft { device_id: String },
+    /// 好友的设备出现在局域网中
+    FriendDeviceJoined {
+        friend_id: String,
+        device: DiscoveredDevice,
+    },
+    /// 好友的设备离开局域网
+    FriendDeviceLeft { friend_id: String, device_id: String },
 
     // ========== 点对点连接事件 ==========
     /// 收到连接请求（点对点连接）
@@ -529,6 +891,64 @@ pub enum LanTransferEvent {
     PeerConnectionEstablished { connection: PeerConnection },
     /// 连接已关闭
     PeerConnectionClosed { connection_id: String },
+    /// 连接请求超时未处理，已自动拒绝
+    PeerConnectionRequestExpired { connection_id: String },
+    /// 连接请求已被本机处理（接受或拒绝），前端移除对应的确认弹窗
+    PeerConnectionRequestResolved {
+        connection_id: String,
+        accepted: bool,
+    },
+    /// 点对点连接状态变化（心跳超时降级 / 恢复）
+    PeerConnectionStatusChanged {
+        connection_id: String,
+        status: PeerConnectionStatus,
+    },
+    /// 收到文本片段（剪贴板共享）
+    TextSnippetReceived { snippet: TextSnippet },
+    /// 对端从共享文件夹拉取文件（本机开始发送）
+    SharePullStarted {
+        connection_id: String,
+        share_id: String,
+        session_id: String,
+        file_count: u32,
+    },
+
+    // ========== 局域网聊天事件 ==========
+    /// 收到局域网聊天消息（已保存到本地数据库）
+    LanChatMessageReceived {
+        conversation_id: String,
+        message: LanChatMessage,
+    },
+    /// 消息对方暂时无法接收，已加入投递队列
+    LanChatMessageQueued {
+        conversation_id: String,
+        message_id: String,
+    },
+    /// 消息已送达（收到对方回执）
+    LanChatMessageDelivered {
+        conversation_id: String,
+        message_id: String,
+    },
+
+    // ========== 文件夹同步事件 ==========
+    /// 本账号的其他设备邀请同步文件夹（调用 join_sync_pair 接受）
+    SyncPairInvited {
+        pair_id: String,
+        device: DiscoveredDevice,
+        folder_name: String,
+        /// 邀请方是否为信任设备（user_id 来自 mDNS 广播，未信任时应提示未验证）
+        trusted: bool,
+    },
+    /// 一次同步完成（有文件变化时发送）
+    SyncCompleted { pair_id: String, summary: SyncSummary },
+    /// 检测到冲突，本地版本已保留为冲突副本
+    SyncConflict {
+        pair_id: String,
+        relative_path: String,
+        conflict_copy: String,
+    },
+    /// 同步失败
+    SyncFailed { pair_id: String, error: String },
 
     // ========== 旧版连接事件（保留兼容） ==========
     /// 收到连接请求（旧版，保留兼容）
@@ -545,6 +965,8 @@ pub enum LanTransferEvent {
         accepted: bool,
         reject_reason: Option<String>,
     },
+    /// 传输请求超时未处理，已自动拒绝
+    TransferRequestExpired { request_id: String },
     /// 单文件传输进度更新
     TransferProgress { task: TransferTask },
     /// 批量传输进度更新
@@ -559,6 +981,36 @@ pub enum LanTransferEvent {
     },
     /// 传输失败
     TransferFailed { task_id: String, error: String },
+    /// 接收后处理完成（文件被移动时 final_path 与 saved_path 不同）
+    PostReceiveCompleted {
+        task_id: String,
+        saved_path: String,
+        final_path: String,
+        results: Vec<PostReceiveActionResult>,
+    },
+
+    // ========== 多设备群发事件 ==========
+    /// 群发进度更新（包含每个接收设备的进度）
+    MulticastProgress { progress: MulticastProgress },
+    /// 群发结束（所有接收设备均已完成或失败）
+    MulticastCompleted { progress: MulticastProgress },
+
+    // ========== 离线发送队列事件 ==========
+    /// 发送任务已加入队列（目标设备不在线）
+    TransferQueued { queued: QueuedTransfer },
+    /// 目标设备上线，排队任务已发出传输请求
+    QueuedTransferStarted {
+        queue_id: String,
+        device_id: String,
+        request_id: String,
+    },
+    /// 排队任务过期或无法投递，已移除
+    QueuedTransferExpired {
+        queue_id: String,
+        device_id: String,
+        reason: String,
+    },
+
     /// 服务状态变化
     ServiceStateChanged { is_running: bool },
 
@@ -0,0 +1,208 @@
+/*!
+ * 待处理请求过期清理模块
+ *
+ * 传输请求（PENDING_TRANSFER_REQUESTS）和连接请求（PENDING_PEER_CONNECTION_REQUESTS）
+ * 原本只在用户响应时移除，被忽略的请求会一直堆积，发送方也会一直等待
+ *
+ * 功能：
+ * - 请求超过 PENDING_REQUEST_TTL_SECS 未处理时自动拒绝
+ * - 拒绝时向发送方说明原因（"请求已超时"），发送方不再等待
+ * - 网页访客的请求直接标记为拒绝（浏览器轮询状态时得知）
+ * - 发送 TransferRequestExpired / PeerConnectionRequestExpired 事件，前端移除对应的确认弹窗
+ *
+ * 说明：
+ * - 同一来源设备的待处理传输请求数上限见 server::insert_pending_transfer_request
+ * - 同一设备同时只会有一个待处理连接请求（见 server 连接请求去重）
+ */
+
+use super::discovery::get_event_sender;
+use super::emit_lan_event;
+use super::protocol::*;
+use super::server::{get_pending_peer_connection_requests_map, get_pending_transfer_requests_map};
+use super::service;
+use super::transfer;
+use chrono::{DateTime, Duration, Utc};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// 检查间隔（秒）
+const EXPIRY_CHECK_INTERVAL_SECS: u64 = 10;
+
+/// 超时拒绝原因
+const EXPIRED_REASON: &str = "请求已超时";
+
+/// 清理任务是否运行（当前实例）
+fn get_expiry_task_flag() -> Arc<AtomicBool> {
+    service::current().request_expiry_running.clone()
+}
+
+// ============================================================================
+// 清理任务
+// ============================================================================
+
+/// 启动过期清理任务（服务启动时调用）
+pub fn start_request_expiry_task() {
+    let flag = get_expiry_task_flag();
+    if flag.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    service::spawn(async move {
+        while get_expiry_task_flag().load(Ordering::SeqCst) {
+            tokio::time::sleep(std::time::Duration::from_secs(EXPIRY_CHECK_INTERVAL_SECS)).await;
+
+            if !get_expiry_task_flag().load(Ordering::SeqCst) {
+                break;
+            }
+
+            expire_pending_requests().await;
+        }
+    });
+}
+
+/// 停止过期清理任务
+pub fn stop_request_expiry_task() {
+    get_expiry_task_flag().store(false, Ordering::SeqCst);
+}
+
+/// 自动拒绝所有已过期的待处理请求
+pub async fn expire_pending_requests() {
+    let now = Utc::now();
+
+    let expired_transfers: Vec<String> = {
+        let requests = get_pending_transfer_requests_map();
+        let requests = requests.lock();
+        requests
+            .values()
+            .filter(|r| is_expired(&r.requested_at, now))
+            .map(|r| r.request_id.clone())
+            .collect()
+    };
+
+    let expired_connections: Vec<String> = {
+        let requests = get_pending_peer_connection_requests_map();
+        let requests = requests.lock();
+        requests
+            .values()
+            .filter(|r| is_expired(&r.requested_at, now))
+            .map(|r| r.connection_id.clone())
+            .collect()
+    };
+
+    for request_id in expired_transfers {
+        log::info!("[LanTransfer] ⏰ 传输请求超时，自动拒绝: {}", request_id);
+
+        // 请求可能刚被用户处理（RequestNotFound），此时不再发送过期事件
+        match transfer::answer_transfer_request(&request_id, false, EXPIRED_REASON).await {
+            Err(transfer::TransferError::RequestNotFound(_)) => continue,
+            Err(e) => log::error!("[LanTransfer] 通知发送方失败: {}", e),
+            Ok(()) => {}
+        }
+
+        let event = LanTransferEvent::TransferRequestExpired { request_id };
+        let _ = get_event_sender().send(event.clone());
+        emit_lan_event(&event);
+    }
+
+    for connection_id in expired_connections {
+        log::info!("[LanTransfer] ⏰ 连接请求超时，自动拒绝: {}", connection_id);
+
+        match transfer::answer_peer_connection(&connection_id, false, Some(EXPIRED_REASON)).await {
+            Err(transfer::TransferError::RequestNotFound(_)) => continue,
+            Err(e) => log::error!("[LanTransfer] 通知发起方失败: {}", e),
+            Ok(()) => {}
+        }
+
+        let event = LanTransferEvent::PeerConnectionRequestExpired { connection_id };
+        let _ = get_event_sender().send(event.clone());
+        emit_lan_event(&event);
+    }
+}
+
+/// 请求是否已过期（无法解析时间的请求视为已过期）
+fn is_expired(requested_at: &str, now: DateTime<Utc>) -> bool {
+    DateTime::parse_from_rfc3339(requested_at)
+        .map_or(true, |t| now - t.with_timezone(&Utc) > Duration::seconds(PENDING_REQUEST_TTL_SECS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lan_transfer::server::insert_pending_transfer_request;
+    use crate::lan_transfer::service::{LanTransferService, ServiceOptions};
+
+    #[test]
+    fn requests_expire_after_ttl() {
+        let now = Utc::now();
+        let ago = |secs| (now - Duration::seconds(secs)).to_rfc3339();
+
+        assert!(!is_expired(&ago(0), now));
+        assert!(!is_expired(&ago(PENDING_REQUEST_TTL_SECS), now));
+        assert!(is_expired(&ago(PENDING_REQUEST_TTL_SECS + 1), now));
+        // 时钟偏差导致的未来时间不算过期
+        assert!(!is_expired(&ago(-60), now));
+        // 其他时区的时间按 UTC 比较
+        let offset = (now - Duration::seconds(PENDING_REQUEST_TTL_SECS + 1))
+            .with_timezone(&chrono::FixedOffset::east_opt(8 * 3600).unwrap())
+            .to_rfc3339();
+        assert!(is_expired(&offset, now));
+    }
+
+    #[test]
+    fn unparsable_timestamps_are_expired() {
+        let now = Utc::now();
+        assert!(is_expired("", now));
+        assert!(is_expired("yesterday", now));
+        assert!(is_expired("2026-10-18 12:00:00", now));
+    }
+
+    #[test]
+    fn pending_requests_are_capped_per_device_and_ip() {
+        let request = |device_id: &str, ip: &str| {
+            let now = Utc::now().to_rfc3339();
+            TransferRequest {
+                request_id: uuid::Uuid::new_v4().to_string(),
+                from_device: DiscoveredDevice {
+                    device_id: device_id.to_string(),
+                    device_name: device_id.to_string(),
+                    user_id: "user".to_string(),
+                    user_nickname: "User".to_string(),
+                    ip_address: ip.to_string(),
+                    port: 0,
+                    discovered_at: now.clone(),
+                    last_seen: now.clone(),
+                },
+                files: vec![],
+                total_size: 0,
+                requested_at: now,
+                status: TransferRequestStatus::Pending,
+            }
+        };
+
+        let service = LanTransferService::new(ServiceOptions {
+            enable_mdns: false,
+            ..Default::default()
+        });
+        service.enter(|| {
+            // 同一设备
+            for _ in 0..MAX_PENDING_REQUESTS_PER_DEVICE {
+                assert!(insert_pending_transfer_request(&request("a", "10.0.0.1")));
+            }
+            assert!(!insert_pending_transfer_request(&request("a", "10.0.0.2")));
+
+            // 同一 IP 换用不同的设备 ID
+            for i in MAX_PENDING_REQUESTS_PER_DEVICE..MAX_PENDING_REQUESTS_PER_IP {
+                let device_id = format!("b{}", i);
+                assert!(insert_pending_transfer_request(&request(&device_id, "10.0.0.1")));
+            }
+            assert!(!insert_pending_transfer_request(&request("c", "10.0.0.1")));
+
+            // 其他设备和 IP 不受影响
+            assert!(insert_pending_transfer_request(&request("c", "10.0.0.3")));
+            assert_eq!(
+                get_pending_transfer_requests_map().lock().len(),
+                MAX_PENDING_REQUESTS_PER_IP + 1
+            );
+        });
+    }
+}
@@ -6,15 +6,27 @@
  * - 加载传输进度信息
  * - 管理临时文件
  * - 清理过期的断点信息
+ *
+ * 未完成接收清理：
+ * - 发送方不再回来时，临时文件（.part）和续传信息（.resume）会一直保留，可能占用数 GB
+ * - 服务启动时清理超过 partial_max_age_hours 未写入的未完成接收
+ * - list_partial_transfers 列出所有未完成接收（时长、大小、来源设备）及占用空间
+ * - 正在接收的文件（HTTP 上传会话和文件夹同步）不会被清理
+ *
+ * 更新日志：
+ * - 2026-10-18: 清理时跳过正在接收的文件夹同步临时文件，拒绝 0 小时的保留时长
+ * - 2026-10-18: 续传信息记录文件名、大小和来源设备；添加未完成接收的列表与清理
  */
 
 use super::config;
-use super::protocol::ResumeInfo;
-use chrono::Utc;
+use super::protocol::{PartialCleanupResult, PartialTransfer, PartialTransferReport, ResumeInfo};
+use chrono::{DateTime, Utc};
 use crc32fast::Hasher as Crc32Hasher;
+use std::collections::{BTreeMap, HashSet};
 use std::fs::{self, File};
 use std::io::{Read, Seek, SeekFrom};
 use std::path::PathBuf;
+use std::time::SystemTime;
 use thiserror::Error;
 
 // ============================================================================
@@ -63,7 +75,7 @@ impl ResumeManager {
             .map_err(|e| ResumeError::SerializeError(e.to_string()))?;
 
         fs::write(&path, content)?;
-        println!(
+        log::info!(
             "[ResumeManager] 保存续传信息: {} (已传输: {} 字节)",
             info.file_id, info.transferred_bytes
         );
@@ -83,7 +95,7 @@ impl ResumeManager {
         let info: ResumeInfo = serde_json::from_str(&content)
             .map_err(|e| ResumeError::DeserializeError(e.to_string()))?;
 
-        println!(
+        log::info!(
             "[ResumeManager] 加载续传信息: {} (已传输: {} 字节)",
             file_id, info.transferred_bytes
         );
@@ -99,13 +111,13 @@ impl ResumeManager {
         // 删除续传信息文件
         if resume_path.exists() {
             fs::remove_file(&resume_path)?;
-            println!("[ResumeManager] 删除续传信息: {}", file_id);
+            log::info!("[ResumeManager] 删除续传信息: {}", file_id);
         }
 
         // 删除临时文件
         if temp_path.exists() {
             fs::remove_file(&temp_path)?;
-            println!("[ResumeManager] 删除临时文件: {}", file_id);
+            log::info!("[ResumeManager] 删除临时文件: {}", file_id);
         }
 
         Ok(())
@@ -131,7 +143,7 @@ impl ResumeManager {
 
         // 检查文件 SHA256 是否匹配
         if info.file_sha256 != expected_sha256 {
-            println!(
+            log::info!(
                 "[ResumeManager] 文件哈希不匹配，需要重新传输: {}",
                 file_id
             );
@@ -142,7 +154,7 @@ impl ResumeManager {
         // 检查临时文件是否存在
         let temp_path = self.get_temp_file_path(file_id);
         if !temp_path.exists() {
-            println!("[ResumeManager] 临时文件不存在，需要重新传输: {}", file_id);
+            log::info!("[ResumeManager] 临时文件不存在，需要重新传输: {}", file_id);
             self.clear_resume_info(file_id)?;
             return Ok(None);
         }
@@ -150,7 +162,7 @@ impl ResumeManager {
         // 验证临时文件大小
         let temp_size = fs::metadata(&temp_path)?.len();
         if temp_size != info.transferred_bytes {
-            println!(
+            log::info!(
                 "[ResumeManager] 临时文件大小不匹配（期望 {} 字节，实际 {} 字节），需要重新传输",
                 info.transferred_bytes, temp_size
             );
@@ -164,12 +176,12 @@ impl ResumeManager {
                 .verify_temp_file_hash(&temp_path, &info.chunk_hashes)
                 .is_err()
         {
-            println!("[ResumeManager] 临时文件哈希校验失败，需要重新传输");
+            log::warn!("[ResumeManager] 临时文件哈希校验失败，需要重新传输");
             self.clear_resume_info(file_id)?;
             return Ok(None);
         }
 
-        println!(
+        log::info!(
             "[ResumeManager] 可以续传: {} (从 {} 字节开始)",
             file_id, info.transferred_bytes
         );
@@ -189,7 +201,7 @@ impl ResumeManager {
         }
 
         let file = File::create(&path)?;
-        println!("[ResumeManager] 创建临时文件: {:?}", path);
+        log::info!("[ResumeManager] 创建临时文件: {:?}", path);
 
         Ok(file)
     }
@@ -209,7 +221,7 @@ impl ResumeManager {
 
         // 定位到续传位置
         file.seek(SeekFrom::Start(offset))?;
-        println!("[ResumeManager] 打开临时文件续传: {:?} (offset: {})", path, offset);
+        log::info!("[ResumeManager] 打开临时文件续传: {:?} (offset: {})", path, offset);
 
         Ok(file)
     }
@@ -233,7 +245,7 @@ impl ResumeManager {
 
         // 移动文件
         fs::rename(&temp_path, &final_path)?;
-        println!(
+        log::info!(
             "[ResumeManager] 传输完成，文件保存到: {:?}",
             final_path
         );
@@ -245,7 +257,7 @@ impl ResumeManager {
     }
 
     /// 处理文件名冲突
-    fn resolve_filename_conflict(path: &std::path::Path) -> PathBuf {
+    pub(crate) fn resolve_filename_conflict(path: &std::path::Path) -> PathBuf {
         if !path.exists() {
             return path.to_path_buf();
         }
@@ -295,7 +307,7 @@ impl ResumeManager {
             let actual_hash = format!("{:08x}", hasher.finalize());
 
             if &actual_hash != expected_hash {
-                println!(
+                log::info!(
                     "[ResumeManager] 块 {} 哈希不匹配: 期望 {}, 实际 {}",
                     index, expected_hash, actual_hash
                 );
@@ -306,6 +318,28 @@ impl ResumeManager {
         Ok(())
     }
 
+    /// 登记新的接收（记录文件名、大小和来源设备，用于未完成接收列表）
+    pub fn register_transfer(
+        &self,
+        file_id: &str,
+        file_sha256: &str,
+        file_name: &str,
+        file_size: u64,
+        origin_device: &str,
+    ) -> Result<(), ResumeError> {
+        self.save_resume_info(&ResumeInfo {
+            file_id: file_id.to_string(),
+            file_sha256: file_sha256.to_string(),
+            temp_file_path: self.get_temp_file_path(file_id).to_string_lossy().to_string(),
+            transferred_bytes: 0,
+            chunk_hashes: vec![],
+            last_updated: Utc::now().to_rfc3339(),
+            file_name: file_name.to_string(),
+            file_size,
+            origin_device: origin_device.to_string(),
+        })
+    }
+
     /// 更新续传信息（传输过程中调用）
     pub fn update_progress(
         &self,
@@ -321,6 +355,9 @@ impl ResumeManager {
             transferred_bytes: 0,
             chunk_hashes: vec![],
             last_updated: Utc::now().to_rfc3339(),
+            file_name: String::new(),
+            file_size: 0,
+            origin_device: String::new(),
         });
 
         info.transferred_bytes = transferred_bytes;
@@ -348,3 +385,246 @@ impl Default for ResumeManager {
 pub fn get_resume_manager() -> ResumeManager {
     ResumeManager::new()
 }
+
+// ============================================================================
+// 未完成接收清理
+// ============================================================================
+
+/// 扫描到的未完成接收（含其全部文件路径）
+struct PartialEntry {
+    partial: PartialTransfer,
+    paths: Vec<PathBuf>,
+}
+
+/// 列出未完成的接收及其占用空间
+pub fn list_partial_transfers() -> PartialTransferReport {
+    let max_age_hours = config::get_full_config().partial_max_age_hours;
+    let partials: Vec<PartialTransfer> = scan_partials(max_age_hours)
+        .into_iter()
+        .map(|e| e.partial)
+        .collect();
+
+    PartialTransferReport {
+        total_bytes: partials.iter().map(|p| p.size_on_disk).sum(),
+        stale_bytes: partials
+            .iter()
+            .filter(|p| p.stale)
+            .map(|p| p.size_on_disk)
+            .sum(),
+        partials,
+        max_age_hours,
+    }
+}
+
+/// 清理超过保留时长的未完成接收
+///
+/// max_age_hours 为空时使用配置中的 partial_max_age_hours，为 0 时返回错误
+pub fn cleanup_partial_transfers(
+    max_age_hours: Option<u64>,
+) -> Result<PartialCleanupResult, String> {
+    let max_age_hours =
+        max_age_hours.unwrap_or_else(|| config::get_full_config().partial_max_age_hours);
+    if max_age_hours == 0 {
+        return Err("保留时长不能为 0".to_string());
+    }
+
+    let mut receiving = super::server::get_receiving_file_ids();
+    receiving.extend(super::sync::get_receiving_file_keys());
+
+    Ok(cleanup_stale_partials(max_age_hours, &receiving))
+}
+
+/// 删除过期且不在接收中的未完成接收
+fn cleanup_stale_partials(max_age_hours: u64, receiving: &HashSet<String>) -> PartialCleanupResult {
+    let mut result = PartialCleanupResult {
+        removed_count: 0,
+        freed_bytes: 0,
+    };
+
+    for entry in scan_partials(max_age_hours) {
+        if !entry.partial.stale || receiving.contains(&entry.partial.file_id) {
+            continue;
+        }
+
+        let mut removed_all = true;
+        for path in &entry.paths {
+            if let Err(e) = fs::remove_file(path) {
+                log::error!("[ResumeManager] 删除 {:?} 失败: {}", path, e);
+                removed_all = false;
+            }
+        }
+
+        if removed_all {
+            log::info!(
+                "[ResumeManager] 清理未完成接收: {} ({} 字节, 来源: {})",
+                if entry.partial.file_name.is_empty() {
+                    &entry.partial.file_id
+                } else {
+                    &entry.partial.file_name
+                },
+                entry.partial.size_on_disk,
+                if entry.partial.origin_device.is_empty() {
+                    "未知"
+                } else {
+                    &entry.partial.origin_device
+                }
+            );
+            result.removed_count += 1;
+            result.freed_bytes += entry.partial.size_on_disk;
+        }
+    }
+
+    if result.removed_count > 0 {
+        log::info!(
+            "[ResumeManager] 共清理 {} 个未完成接收，释放 {} 字节",
+            result.removed_count, result.freed_bytes
+        );
+    }
+
+    result
+}
+
+/// 扫描临时目录，按 file_id 合并 .part 与 .resume 文件
+fn scan_partials(max_age_hours: u64) -> Vec<PartialEntry> {
+    let temp_dir = config::get_temp_directory();
+    let Ok(entries) = fs::read_dir(&temp_dir) else {
+        return Vec::new();
+    };
+
+    let mut grouped: BTreeMap<String, (Vec<PathBuf>, u64, SystemTime)> = BTreeMap::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_partial = matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("part") | Some("resume")
+        );
+        let Some(file_id) = path.file_stem().and_then(|s| s.to_str()).map(str::to_string) else {
+            continue;
+        };
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if !is_partial || !metadata.is_file() {
+            continue;
+        }
+
+        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        let group = grouped
+            .entry(file_id)
+            .or_insert_with(|| (Vec::new(), 0, SystemTime::UNIX_EPOCH));
+        group.0.push(path);
+        group.1 += metadata.len();
+        group.2 = group.2.max(modified);
+    }
+
+    let manager = get_resume_manager();
+    let now = SystemTime::now();
+    let max_age_secs = max_age_hours.saturating_mul(3600);
+
+    let mut partials: Vec<PartialEntry> = grouped
+        .into_iter()
+        .map(|(file_id, (paths, size_on_disk, modified))| {
+            let info = manager.load_resume_info(&file_id).ok();
+            let age_secs = now
+                .duration_since(modified)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+
+            // 文件夹同步的临时文件没有续传信息，按 file_key 前缀识别
+            let origin_device = match &info {
+                Some(info) if !info.origin_device.is_empty() => info.origin_device.clone(),
+                _ if file_id.starts_with("sync-") => "文件夹同步".to_string(),
+                _ => String::new(),
+            };
+
+            PartialEntry {
+                partial: PartialTransfer {
+                    file_name: info.as_ref().map(|i| i.file_name.clone()).unwrap_or_default(),
+                    file_size: info.as_ref().map(|i| i.file_size).unwrap_or(0),
+                    origin_device,
+                    size_on_disk,
+                    last_modified: DateTime::<Utc>::from(modified).to_rfc3339(),
+                    age_secs,
+                    stale: age_secs >= max_age_secs,
+                    file_id,
+                },
+                paths,
+            }
+        })
+        .collect();
+
+    partials.sort_by(|a, b| b.partial.age_secs.cmp(&a.partial.age_secs));
+    partials
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lan_transfer::service::{LanTransferService, ServiceOptions};
+    use std::time::Duration;
+
+    /// 在临时目录写入文件，并将修改时间设为 `age_hours` 小时前
+    fn write_partial(name: &str, age_hours: u64) {
+        let path = config::get_temp_directory().join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, b"partial").unwrap();
+        let modified = SystemTime::now() - Duration::from_secs(age_hours * 3600);
+        File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|f| f.set_modified(modified))
+            .unwrap();
+    }
+
+    #[test]
+    fn only_stale_partials_that_are_not_receiving_are_removed() {
+        let dir = std::env::temp_dir().join(format!("huanvae-resume-{}", uuid::Uuid::new_v4()));
+        let service = LanTransferService::new(ServiceOptions {
+            enable_mdns: false,
+            data_directory: Some(dir.clone()),
+            ..Default::default()
+        });
+
+        service.enter(|| {
+            write_partial("stale.part", 10 * 24);
+            write_partial("stale.resume", 10 * 24);
+            write_partial("fresh.part", 1);
+            write_partial("busy.part", 10 * 24);
+            write_partial("sync-p-0000abcd.part", 10 * 24);
+
+            let report = list_partial_transfers();
+            let stale: Vec<(&str, bool)> = report
+                .partials
+                .iter()
+                .map(|p| (p.file_id.as_str(), p.stale))
+                .collect();
+            assert_eq!(stale.len(), 4);
+            assert!(stale.contains(&("stale", true)));
+            assert!(stale.contains(&("fresh", false)));
+            assert!(stale.contains(&("busy", true)));
+            assert!(stale.contains(&("sync-p-0000abcd", true)));
+            assert_eq!(report.stale_bytes, 7 * 4);
+
+            assert!(cleanup_partial_transfers(Some(0)).is_err());
+
+            let receiving: HashSet<String> = ["busy", "sync-p-0000abcd"]
+                .into_iter()
+                .map(str::to_string)
+                .collect();
+            let max_age_hours = config::get_full_config().partial_max_age_hours;
+            let result = cleanup_stale_partials(max_age_hours, &receiving);
+            assert_eq!(result.removed_count, 1);
+            assert_eq!(result.freed_bytes, 7 * 2);
+
+            let remaining: Vec<String> = list_partial_transfers()
+                .partials
+                .into_iter()
+                .map(|p| p.file_id)
+                .collect();
+            assert_eq!(remaining.len(), 3);
+            assert!(!remaining.contains(&"stale".to_string()));
+        });
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
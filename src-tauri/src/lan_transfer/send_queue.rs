@@ -0,0 +1,588 @@
+/*!
+ * 离线发送队列模块
+ *
+ * 目标设备不在线时，将发送任务排队，设备重新出现在局域网中时自动投递
+ * （"回家后发到手机"）
+ *
+ * 功能：
+ * - 只能为已知设备排队：已信任设备，或当前已发现的设备
+ * - 队列持久化到 send_queue.json，应用重启后仍然有效
+ * - discovery 解析到设备（ServiceResolved）时调用 deliver_queued_transfers 自动投递
+ * - 投递使用普通的传输请求流程（send_transfer_request），接收方按常规确认或自动接受
+ * - 每个任务有过期时间（默认 DEFAULT_EXPIRY_HOURS 小时），过期或文件已不存在时移除
+ * - 同一设备同时只有一个投递流程；结束前重新检查队列，投递期间新加入的任务和
+ *   投递失败的任务（间隔 DELIVERY_RETRY_DELAY_SECS 秒，最多 DELIVERY_ATTEMPTS 次）在同一流程中处理
+ *
+ * 事件：
+ * - TransferQueued: 任务已加入队列
+ * - QueuedTransferStarted: 设备上线，已发送传输请求
+ * - QueuedTransferExpired: 任务过期或无法投递，已移除
+ */
+
+use super::config;
+use super::discovery::get_event_sender;
+use super::protocol::*;
+use super::service;
+use super::transfer;
+use super::{emit_lan_event, get_lan_transfer_state};
+use chrono::{DateTime, Duration, Utc};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// 默认过期时间（小时）
+const DEFAULT_EXPIRY_HOURS: u32 = 7 * 24;
+
+/// 一次投递流程中每个任务的最多尝试次数（仍失败则等待设备下次上线）
+const DELIVERY_ATTEMPTS: u32 = 3;
+
+/// 投递失败后重试的间隔（秒）
+const DELIVERY_RETRY_DELAY_SECS: u64 = 2;
+
+// ============================================================================
+// 错误类型
+// ============================================================================
+
+#[derive(Error, Debug)]
+pub enum SendQueueError {
+    #[error("未知设备（需要已信任或已发现的设备）: {0}")]
+    UnknownDevice(String),
+    #[error("文件不存在: {0}")]
+    FileNotFound(String),
+    #[error("文件列表为空")]
+    EmptyFileList,
+    #[error("队列任务不存在: {0}")]
+    NotFound(String),
+    #[error("队列保存失败: {0}")]
+    WriteFailed(String),
+}
+
+// ============================================================================
+// 队列存储
+// ============================================================================
+
+/// 发送队列状态（每个服务实例一份，见 LanTransferService）
+#[derive(Default)]
+pub(crate) struct SendQueueState {
+    /// 队列存储（首次访问时从实例数据目录加载）
+    store: OnceCell<Arc<Mutex<SendQueueStore>>>,
+    /// 正在投递的设备（防止同一设备重复投递）
+    delivering_devices: Arc<Mutex<HashSet<String>>>,
+}
+
+/// 发送队列存储
+struct SendQueueStore {
+    items: Vec<QueuedTransfer>,
+    queue_path: PathBuf,
+}
+
+impl SendQueueStore {
+    fn new() -> Self {
+        let queue_path = config::get_send_queue_path();
+        let items = Self::load_or_default(&queue_path);
+
+        Self { items, queue_path }
+    }
+
+    fn load_or_default(path: &PathBuf) -> Vec<QueuedTransfer> {
+        if !path.exists() {
+            return Vec::new();
+        }
+
+        match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                log::warn!("[LanTransfer] 发送队列解析失败，使用空队列: {}", e);
+                Vec::new()
+            }),
+            Err(e) => {
+                log::warn!("[LanTransfer] 发送队列读取失败，使用空队列: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    fn save(&self) -> Result<(), SendQueueError> {
+        if let Some(parent) = self.queue_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| SendQueueError::WriteFailed(e.to_string()))?;
+        }
+
+        let content = serde_json::to_string_pretty(&self.items)
+            .map_err(|e| SendQueueError::WriteFailed(e.to_string()))?;
+
+        fs::write(&self.queue_path, content).map_err(|e| SendQueueError::WriteFailed(e.to_string()))
+    }
+}
+
+fn get_send_queue() -> Arc<Mutex<SendQueueStore>> {
+    service::current()
+        .send_queue
+        .store
+        .get_or_init(|| Arc::new(Mutex::new(SendQueueStore::new())))
+        .clone()
+}
+
+fn get_delivering_devices() -> Arc<Mutex<HashSet<String>>> {
+    service::current().send_queue.delivering_devices.clone()
+}
+
+// ============================================================================
+// 公共接口
+// ============================================================================
+
+/// 为设备排队发送文件
+///
+/// 设备当前在线时立即尝试投递
+pub async fn queue_transfer(
+    device_id: &str,
+    file_paths: Vec<String>,
+    expires_in_hours: Option<u32>,
+) -> Result<QueuedTransfer, SendQueueError> {
+    if file_paths.is_empty() {
+        return Err(SendQueueError::EmptyFileList);
+    }
+
+    let device_name = resolve_device_name(device_id)
+        .ok_or_else(|| SendQueueError::UnknownDevice(device_id.to_string()))?;
+
+    let mut total_size = 0;
+    for file_path in &file_paths {
+        let metadata = fs::metadata(file_path)
+            .ok()
+            .filter(|m| m.is_file())
+            .ok_or_else(|| SendQueueError::FileNotFound(file_path.clone()))?;
+        total_size += metadata.len();
+    }
+
+    let now = Utc::now();
+    let hours = expires_in_hours.unwrap_or(DEFAULT_EXPIRY_HOURS);
+    let queued = QueuedTransfer {
+        queue_id: Uuid::new_v4().to_string(),
+        device_id: device_id.to_string(),
+        device_name,
+        file_paths,
+        total_size,
+        queued_at: now.to_rfc3339(),
+        expires_at: (now + Duration::hours(hours as i64)).to_rfc3339(),
+        attempts: 0,
+    };
+
+    {
+        let queue = get_send_queue();
+        let mut queue = queue.lock();
+        queue.items.push(queued.clone());
+        queue.save()?;
+    }
+
+    log::info!(
+        "[LanTransfer] 📥 已为 {} 排队 {} 个文件",
+        queued.device_name,
+        queued.file_paths.len()
+    );
+
+    let event = LanTransferEvent::TransferQueued {
+        queued: queued.clone(),
+    };
+    let _ = get_event_sender().send(event.clone());
+    emit_lan_event(&event);
+
+    // 设备在线时立即投递
+    if get_lan_transfer_state()
+        .devices
+        .read()
+        .contains_key(device_id)
+    {
+        deliver_queued_transfers(device_id).await;
+    }
+
+    Ok(queued)
+}
+
+/// 获取排队中的任务（同时清理过期任务）
+pub fn get_queued_transfers() -> Vec<QueuedTransfer> {
+    remove_expired();
+    get_send_queue().lock().items.clone()
+}
+
+/// 取消排队中的任务
+pub fn cancel_queued_transfer(queue_id: &str) -> Result<(), SendQueueError> {
+    let queue = get_send_queue();
+    let mut queue = queue.lock();
+
+    let before = queue.items.len();
+    queue.items.retain(|q| q.queue_id != queue_id);
+    if queue.items.len() == before {
+        return Err(SendQueueError::NotFound(queue_id.to_string()));
+    }
+
+    queue.save()
+}
+
+/// 投递设备的排队任务（设备上线时由 discovery 调用）
+///
+/// 设备已在投递中时直接返回，由正在进行的投递流程处理新任务
+pub async fn deliver_queued_transfers(device_id: &str) {
+    remove_expired();
+
+    {
+        let delivering = get_delivering_devices();
+        let mut delivering = delivering.lock();
+        if !delivering.insert(device_id.to_string()) {
+            return;
+        }
+    }
+
+    // 本次投递流程中每个任务的失败次数
+    let mut failures: HashMap<String, u32> = HashMap::new();
+
+    while let Some(pending) = next_pending(device_id, &failures) {
+        // 只剩失败过的任务：等待后重试，设备已离线则结束
+        if pending.iter().all(|q| failures.contains_key(&q.queue_id)) {
+            tokio::time::sleep(std::time::Duration::from_secs(DELIVERY_RETRY_DELAY_SECS)).await;
+            if !get_lan_transfer_state()
+                .devices
+                .read()
+                .contains_key(device_id)
+            {
+                get_delivering_devices().lock().remove(device_id);
+                break;
+            }
+        }
+
+        log::info!(
+            "[LanTransfer] 📤 设备 {} 已上线，投递 {} 个排队任务",
+            device_id,
+            pending.len()
+        );
+
+        for queued in pending {
+            if !deliver_item(device_id, &queued).await {
+                *failures.entry(queued.queue_id.clone()).or_default() += 1;
+            }
+        }
+    }
+}
+
+// ============================================================================
+// 内部函数
+// ============================================================================
+
+/// 取出设备待投递的任务；没有时清除投递标记并返回 None
+///
+/// 检查队列与清除标记在投递标记锁内完成，保证在此之前加入队列的任务不会被遗漏
+fn next_pending(device_id: &str, failures: &HashMap<String, u32>) -> Option<Vec<QueuedTransfer>> {
+    let delivering = get_delivering_devices();
+    let mut delivering = delivering.lock();
+
+    let pending: Vec<QueuedTransfer> = {
+        let queue = get_send_queue();
+        let queue = queue.lock();
+        queue
+            .items
+            .iter()
+            .filter(|q| q.device_id == device_id)
+            .filter(|q| failures.get(&q.queue_id).copied().unwrap_or(0) < DELIVERY_ATTEMPTS)
+            .cloned()
+            .collect()
+    };
+
+    if pending.is_empty() {
+        delivering.remove(device_id);
+        return None;
+    }
+    Some(pending)
+}
+
+/// 投递单个排队任务，返回是否已从队列移除（已发出或无法投递）
+async fn deliver_item(device_id: &str, queued: &QueuedTransfer) -> bool {
+    // 文件已被移动或删除：无法投递，直接移除
+    if let Some(missing) = queued.file_paths.iter().find(|p| !Path::new(p).is_file()) {
+        remove_item(&queued.queue_id);
+        emit_expired(queued, format!("文件不存在: {}", missing));
+        return true;
+    }
+
+    match transfer::send_transfer_request(device_id, queued.file_paths.clone()).await {
+        Ok(request_id) => {
+            remove_item(&queued.queue_id);
+
+            log::info!(
+                "[LanTransfer] 📤 排队任务已发出: {} -> {}",
+                queued.queue_id, queued.device_name
+            );
+
+            let event = LanTransferEvent::QueuedTransferStarted {
+                queue_id: queued.queue_id.clone(),
+                device_id: device_id.to_string(),
+                request_id,
+            };
+            let _ = get_event_sender().send(event.clone());
+            emit_lan_event(&event);
+            true
+        }
+        Err(e) => {
+            log::error!(
+                "[LanTransfer] 排队任务投递失败，保留在队列中: {} ({})",
+                queued.queue_id, e
+            );
+
+            let queue = get_send_queue();
+            let mut queue = queue.lock();
+            if let Some(item) = queue.items.iter_mut().find(|q| q.queue_id == queued.queue_id) {
+                item.attempts += 1;
+            }
+            let _ = queue.save();
+            false
+        }
+    }
+}
+
+/// 获取已知设备名称（已发现的设备或已信任设备）
+fn resolve_device_name(device_id: &str) -> Option<String> {
+    let discovered = get_lan_transfer_state()
+        .devices
+        .read()
+        .get(device_id)
+        .map(|d| d.device_name.clone());
+
+    discovered.or_else(|| {
+        config::get_trusted_devices()
+            .into_iter()
+            .find(|d| d.device_id == device_id)
+            .map(|d| d.device_name)
+    })
+}
+
+/// 移除队列任务
+fn remove_item(queue_id: &str) {
+    let queue = get_send_queue();
+    let mut queue = queue.lock();
+    queue.items.retain(|q| q.queue_id != queue_id);
+    if let Err(e) = queue.save() {
+        log::error!("[LanTransfer] 发送队列保存失败: {}", e);
+    }
+}
+
+/// 移除过期任务
+fn remove_expired() {
+    let now = Utc::now();
+    let expired: Vec<QueuedTransfer> = {
+        let queue = get_send_queue();
+        let mut queue = queue.lock();
+
+        let (expired, remaining): (Vec<_>, Vec<_>) =
+            queue.items.drain(..).partition(|q| {
+                DateTime::parse_from_rfc3339(&q.expires_at).is_ok_and(|t| t < now)
+            });
+        queue.items = remaining;
+
+        if !expired.is_empty() && let Err(e) = queue.save() {
+            log::error!("[LanTransfer] 发送队列保存失败: {}", e);
+        }
+        expired
+    };
+
+    for queued in expired {
+        emit_expired(&queued, "已过期".to_string());
+    }
+}
+
+/// 发送任务过期事件
+fn emit_expired(queued: &QueuedTransfer, reason: String) {
+    log::info!(
+        "[LanTransfer] 排队任务已移除: {} ({})",
+        queued.queue_id, reason
+    );
+
+    let event = LanTransferEvent::QueuedTransferExpired {
+        queue_id: queued.queue_id.clone(),
+        device_id: queued.device_id.clone(),
+        reason,
+    };
+    let _ = get_event_sender().send(event.clone());
+    emit_lan_event(&event);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lan_transfer::service::{LanTransferService, ServiceOptions};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn service(dir: &Path) -> Arc<LanTransferService> {
+        LanTransferService::new(ServiceOptions {
+            enable_mdns: false,
+            data_directory: Some(dir.to_path_buf()),
+            ..Default::default()
+        })
+    }
+
+    fn item(queue_id: &str, file_path: &Path, expires_at: String) -> QueuedTransfer {
+        QueuedTransfer {
+            queue_id: queue_id.to_string(),
+            device_id: "peer".to_string(),
+            device_name: "Phone".to_string(),
+            file_paths: vec![file_path.to_string_lossy().to_string()],
+            total_size: 4,
+            queued_at: Utc::now().to_rfc3339(),
+            expires_at,
+            attempts: 0,
+        }
+    }
+
+    fn in_hours(hours: i64) -> String {
+        (Utc::now() + Duration::hours(hours)).to_rfc3339()
+    }
+
+    fn queue_ids() -> Vec<String> {
+        get_send_queue()
+            .lock()
+            .items
+            .iter()
+            .map(|q| q.queue_id.clone())
+            .collect()
+    }
+
+    /// 启动接收传输请求的 HTTP 服务器：第一个请求返回 500，之后返回请求 ID
+    async fn serve_requests() -> (u16, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = [0u8; 8192];
+                let _ = socket.read(&mut request).await;
+                let response = if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+                    "HTTP/1.1 500 X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+                } else {
+                    let body = format!(r#"{{"requestId":"{}"}}"#, Uuid::new_v4());
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                };
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        (port, hits)
+    }
+
+    #[test]
+    fn expired_items_are_removed() {
+        let dir = std::env::temp_dir().join(format!("huanvae-queue-{}", Uuid::new_v4()));
+        let file = dir.join("a.txt");
+        let service = service(&dir);
+        let mut events = service.subscribe();
+
+        service.enter(|| {
+            get_send_queue().lock().items = vec![
+                item("expired", &file, in_hours(-1)),
+                item("valid", &file, in_hours(1)),
+            ];
+
+            let queued = get_queued_transfers();
+            assert_eq!(queued.len(), 1);
+            assert_eq!(queued[0].queue_id, "valid");
+
+            // 移除结果已持久化
+            let saved = SendQueueStore::load_or_default(&config::get_send_queue_path());
+            assert_eq!(saved.len(), 1);
+        });
+
+        match events.try_recv().unwrap() {
+            LanTransferEvent::QueuedTransferExpired { queue_id, .. } => {
+                assert_eq!(queue_id, "expired")
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+        assert!(events.try_recv().is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn delivery_retries_failures_and_clears_the_delivering_flag() {
+        let dir = std::env::temp_dir().join(format!("huanvae-queue-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("a.txt");
+        fs::write(&file, b"data").unwrap();
+        let (port, hits) = serve_requests().await;
+        let service = service(&dir);
+        let mut events = service.subscribe();
+
+        service
+            .scope(async {
+                let now = Utc::now().to_rfc3339();
+                let state = get_lan_transfer_state();
+                *state.local_device.write() = Some(DeviceInfo {
+                    device_id: "me".to_string(),
+                    device_name: "Desktop".to_string(),
+                    user_id: "me".to_string(),
+                    user_nickname: "Me".to_string(),
+                    ip_address: "127.0.0.1".to_string(),
+                    port: 0,
+                    version: PROTOCOL_VERSION.to_string(),
+                    os: std::env::consts::OS.to_string(),
+                });
+                state.devices.write().insert(
+                    "peer".to_string(),
+                    DiscoveredDevice {
+                        device_id: "peer".to_string(),
+                        device_name: "Phone".to_string(),
+                        user_id: "other".to_string(),
+                        user_nickname: "Other".to_string(),
+                        ip_address: "127.0.0.1".to_string(),
+                        port,
+                        discovered_at: now.clone(),
+                        last_seen: now,
+                    },
+                );
+                get_send_queue().lock().items = vec![
+                    item("first", &file, in_hours(1)),
+                    item("second", &file, in_hours(1)),
+                    item("missing", &dir.join("gone.txt"), in_hours(1)),
+                ];
+
+                // 已有投递流程时直接返回，任务留给该流程处理
+                get_delivering_devices().lock().insert("peer".to_string());
+                deliver_queued_transfers("peer").await;
+                assert_eq!(queue_ids().len(), 3);
+                assert_eq!(hits.load(Ordering::SeqCst), 0);
+                get_delivering_devices().lock().remove("peer");
+
+                // 第一个任务首次失败后在同一流程中重试
+                deliver_queued_transfers("peer").await;
+                assert!(queue_ids().is_empty());
+                assert_eq!(hits.load(Ordering::SeqCst), 3);
+                assert!(get_delivering_devices().lock().is_empty());
+            })
+            .await;
+
+        let mut outcomes = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            match event {
+                LanTransferEvent::QueuedTransferStarted { queue_id, .. } => {
+                    outcomes.push(format!("started:{}", queue_id))
+                }
+                LanTransferEvent::QueuedTransferExpired { queue_id, .. } => {
+                    outcomes.push(format!("expired:{}", queue_id))
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(
+            outcomes,
+            ["started:second", "expired:missing", "started:first"]
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -5,11 +5,40 @@
  *
  * API 端点：
  * - GET /api/info: 获取设备信息
+ * - POST /api/probe: 连通性探测（上行吞吐量，丢弃请求体，返回收到的字节数）
+ * - GET /api/probe?size=: 连通性探测（下行吞吐量，返回指定大小的数据）
+ *   （仅限已发现或已连接的设备，按来源 IP 限速）
  *
  * 点对点连接（新版）：
  * - POST /api/peer-connection-request: 请求建立点对点连接
  * - POST /api/peer-connection-response: 响应连接请求
  * - POST /api/peer-disconnect: 断开连接
+ * - POST /api/peer-heartbeat: 连接心跳（响应 alive=false 表示连接已不存在）
+ * - POST /api/text-snippet: 发送文本片段（需已建立连接）
+ * - POST /api/chat-message: 局域网聊天消息（需已建立连接，响应即送达回执）
+ * - GET /api/attachment?fileHash=&deviceId=: 聊天附件直传（按聊天关系授权）
+ * - POST /api/shares: 浏览共享文件夹（需已建立连接且设备已信任）
+ * - POST /api/shares/pull: 拉取共享文件夹中的文件（本机通过普通传输流程发送）
+ *
+ * 文件夹同步（仅限本账号设备，见 sync 模块）：
+ * - POST /api/sync/invite: 同步配对邀请
+ * - POST /api/sync/manifest: 获取文件清单
+ * - POST /api/sync/trigger: 请求协调方立即同步
+ * - POST /api/sync/request-push: 请求推送指定文件
+ * - POST /api/sync/prepare: 准备接收文件（返回续传偏移量）
+ * - POST /api/sync/chunk?pairId=&deviceId=&fileKey=&offset=: 上传数据块
+ * - POST /api/sync/commit: 校验并保存文件
+ * - POST /api/sync/delete: 删除文件
+ *
+ * 网页分享（需手动开启，见 web_share 模块）：
+ * - GET /web: 分享页面
+ * - POST /web/login: 使用一次性 PIN 登录，返回访问令牌
+ * - GET /web/files: 分享的文件列表
+ * - POST /web/upload-request: 请求上传文件（需本机确认）
+ * - POST /web/download-request: 请求下载文件（需本机确认）
+ * - GET /web/status?requestId=: 查询请求状态
+ * - POST /web/upload?requestId=&fileId=&offset=: 上传文件块
+ * - GET /web/download?requestId=&fileId=&token=: 下载文件
  *
  * 旧版兼容：
  * - POST /api/connect: 连接请求（旧版兼容）
@@ -32,23 +61,51 @@
  * - 所有响应添加 `Connection: close` 头，防止客户端复用已关闭的连接
  *
  * 更新日志：
+ * - 2026-10-18: 同步文件的准备、数据块写入和删除在阻塞线程池中执行，不阻塞异步运行时
+ * - 2026-10-18: 连通性探测仅响应已发现或已连接的设备，并按来源 IP 限速（超出返回 429）
+ * - 2026-10-18: 待处理传输请求同时按来源 IP 限制数量（MAX_PENDING_REQUESTS_PER_IP）
+ * - 2026-10-18: 记录绑定端口失败的原因（供诊断自检定位占用端口的进程）
+ * - 2026-10-18: 添加连通性探测接口（/api/probe），供诊断模块测量延迟与吞吐量
+ * - 2026-10-18: 服务器状态归属于服务实例，拆分为绑定端口与运行两步（支持系统分配端口）
+ * - 2026-10-18: 按发送方提供的相对路径保存（过滤越界路径），完成后恢复修改时间、创建时间和权限位
+ * - 2026-10-18: 接收完成后执行接收后处理动作（post_receive）
+ * - 2026-10-18: 新接收登记来源设备（用于未完成接收列表与清理）
+ * - 2026-10-18: 限制同一设备的待处理传输请求数（超出返回 429），连接拒绝响应支持附带原因
+ * - 2026-10-18: 添加连接心跳接口（/api/peer-heartbeat），重新连接时验证对端身份
+ * - 2026-10-18: 并行发送：同一会话的多个文件加入同一上传会话，/api/finish 支持通过 JSON 请求体传递会话 ID 和文件 ID
+ * - 2026-10-18: 添加文件夹同步接口（/api/sync）
+ * - 2026-10-18: 添加共享文件夹浏览/拉取接口（/api/shares）
+ * - 2026-10-18: 添加网页分享接口（/web）
+ * - 2026-10-18: 添加聊天附件直传接口（/api/attachment）
+ * - 2026-10-18: 添加局域网聊天消息接口（/api/chat-message）
+ * - 2026-10-18: 添加文本片段接收接口（/api/text-snippet）
  * - 2026-01-21: 添加 Connection: close 头修复跨平台传输连接重用问题
  * - 2026-01-21: 添加接收方进度显示（初始进度、实时速度、完成事件）
  */
 
+use super::attachments::{self, AttachmentError};
+use super::chat;
 use super::config;
 use super::discovery::get_event_sender;
+use super::heartbeat;
+use super::post_receive;
 use super::protocol::*;
 use super::resume::get_resume_manager;
+use super::service;
+use super::shares::{self, ShareError};
+use super::snippets;
+use super::sync::{self, SyncError};
+use super::web_share::{self, WebShareError};
 use super::{emit_lan_event, get_lan_transfer_state};
 use chrono::Utc;
-use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
 use crc32fast::Hasher as Crc32Hasher;
 use std::collections::HashMap;
 use std::io::{Seek, SeekFrom, Write};
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant, UNIX_EPOCH};
 use thiserror::Error;
 use tokio::sync::oneshot;
 use uuid::Uuid;
@@ -74,51 +131,97 @@ pub enum ServerError {
 // 服务器状态
 // ============================================================================
 
-/// 服务器关闭信号
-static SERVER_SHUTDOWN: OnceCell<Arc<Mutex<Option<oneshot::Sender<()>>>>> = OnceCell::new();
-
-/// 活跃的上传会话
-static UPLOAD_SESSIONS: OnceCell<Arc<Mutex<HashMap<String, UploadSession>>>> = OnceCell::new();
-
-/// 待处理的传输请求
-static PENDING_TRANSFER_REQUESTS: OnceCell<Arc<Mutex<HashMap<String, TransferRequest>>>> =
-    OnceCell::new();
+/// 服务器状态（每个服务实例一份，见 LanTransferService）
+#[derive(Default)]
+pub(crate) struct ServerState {
+    /// 服务器关闭信号
+    shutdown: Arc<Mutex<Option<oneshot::Sender<()>>>>,
+    /// 活跃的上传会话
+    upload_sessions: Arc<Mutex<HashMap<String, UploadSession>>>,
+    /// 待处理的传输请求
+    pending_transfer_requests: Arc<Mutex<HashMap<String, TransferRequest>>>,
+    /// 活跃的点对点连接
+    active_peer_connections: Arc<Mutex<HashMap<String, PeerConnection>>>,
+    /// 待处理的连接请求
+    pending_peer_connection_requests: Arc<Mutex<HashMap<String, PeerConnectionRequest>>>,
+    /// 最近一次绑定端口失败的原因（绑定成功时清除）
+    bind_error: Arc<Mutex<Option<String>>>,
+    /// 各来源 IP 的连通性探测计数（窗口开始时间, 窗口内次数）
+    probe_requests: Arc<Mutex<HashMap<IpAddr, (Instant, u32)>>>,
+}
 
-/// 活跃的点对点连接
-static ACTIVE_PEER_CONNECTIONS: OnceCell<Arc<Mutex<HashMap<String, PeerConnection>>>> =
-    OnceCell::new();
+/// 最近一次启动服务时绑定端口失败的原因（诊断使用）
+pub fn get_bind_error() -> Option<String> {
+    service::current().server.bind_error.lock().clone()
+}
 
-/// 待处理的连接请求
-static PENDING_PEER_CONNECTION_REQUESTS: OnceCell<
-    Arc<Mutex<HashMap<String, PeerConnectionRequest>>>,
-> = OnceCell::new();
+/// 记录绑定端口的结果（None 表示绑定成功）
+pub(crate) fn set_bind_error(error: Option<String>) {
+    *service::current().server.bind_error.lock() = error;
+}
 
 fn get_upload_sessions() -> Arc<Mutex<HashMap<String, UploadSession>>> {
-    UPLOAD_SESSIONS
-        .get_or_init(|| Arc::new(Mutex::new(HashMap::new())))
-        .clone()
+    service::current().server.upload_sessions.clone()
+}
+
+/// 获取正在接收的文件 ID（清理未完成接收时跳过）
+pub fn get_receiving_file_ids() -> std::collections::HashSet<String> {
+    let sessions = get_upload_sessions();
+    let sessions = sessions.lock();
+    sessions
+        .values()
+        .flat_map(|s| s.files.keys().cloned())
+        .collect()
 }
 
 /// 获取待处理的传输请求
 pub fn get_pending_transfer_requests_map() -> Arc<Mutex<HashMap<String, TransferRequest>>> {
-    PENDING_TRANSFER_REQUESTS
-        .get_or_init(|| Arc::new(Mutex::new(HashMap::new())))
-        .clone()
+    service::current().server.pending_transfer_requests.clone()
+}
+
+/// 保存待处理的传输请求
+///
+/// 同一来源设备的待处理请求已达 MAX_PENDING_REQUESTS_PER_DEVICE 个，
+/// 或同一来源 IP 已达 MAX_PENDING_REQUESTS_PER_IP 个时不保存（防止刷屏），返回 false。
+/// from_device.ip_address 由调用方设置为 TCP 连接的来源 IP
+pub fn insert_pending_transfer_request(request: &TransferRequest) -> bool {
+    let requests = get_pending_transfer_requests_map();
+    let mut requests = requests.lock();
+
+    let from_same_device = requests
+        .values()
+        .filter(|r| r.from_device.device_id == request.from_device.device_id)
+        .count();
+    let from_same_ip = requests
+        .values()
+        .filter(|r| r.from_device.ip_address == request.from_device.ip_address)
+        .count();
+    if from_same_device >= MAX_PENDING_REQUESTS_PER_DEVICE
+        || from_same_ip >= MAX_PENDING_REQUESTS_PER_IP
+    {
+        log::warn!(
+            "[LanTransfer] ⚠️ 来自 {} ({}) 的待处理请求过多 (设备 {} 个, IP {} 个)，拒绝新请求",
+            request.from_device.device_name,
+            request.from_device.ip_address,
+            from_same_device,
+            from_same_ip
+        );
+        return false;
+    }
+
+    requests.insert(request.request_id.clone(), request.clone());
+    true
 }
 
 /// 获取活跃的点对点连接
 pub fn get_active_peer_connections_map() -> Arc<Mutex<HashMap<String, PeerConnection>>> {
-    ACTIVE_PEER_CONNECTIONS
-        .get_or_init(|| Arc::new(Mutex::new(HashMap::new())))
-        .clone()
+    service::current().server.active_peer_connections.clone()
 }
 
 /// 获取待处理的连接请求
 pub fn get_pending_peer_connection_requests_map(
 ) -> Arc<Mutex<HashMap<String, PeerConnectionRequest>>> {
-    PENDING_PEER_CONNECTION_REQUESTS
-        .get_or_init(|| Arc::new(Mutex::new(HashMap::new())))
-        .clone()
+    service::current().server.pending_peer_connection_requests.clone()
 }
 
 /// 上传会话（支持断点续传）
@@ -149,11 +252,11 @@ struct UploadSession {
 // 服务器管理
 // ============================================================================
 
-/// 启动 HTTP 服务器
-pub async fn start_server(device_info: DeviceInfo) -> Result<(), ServerError> {
+/// 绑定 HTTP 服务器端口（端口为 0 时由系统分配，实际端口见 listener.local_addr()）
+pub async fn bind_server(port: u16) -> Result<tokio::net::TcpListener, ServerError> {
     use tokio::net::TcpSocket;
     
-    let addr = SocketAddr::from(([0, 0, 0, 0], SERVICE_PORT));
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
 
     // 使用 TcpSocket 来设置 SO_REUSEADDR，避免 TIME_WAIT 导致端口占用
     let socket = TcpSocket::new_v4()
@@ -164,17 +267,22 @@ pub async fn start_server(device_info: DeviceInfo) -> Result<(), ServerError> {
         .map_err(|e| ServerError::StartFailed(format!("设置 SO_REUSEADDR 失败: {}", e)))?;
     
     socket.bind(addr)
-        .map_err(|e| ServerError::StartFailed(format!("绑定端口 {} 失败: {}", SERVICE_PORT, e)))?;
+        .map_err(|e| ServerError::StartFailed(format!("绑定端口 {} 失败: {}", port, e)))?;
     
-    let listener = socket.listen(128)
-        .map_err(|e| ServerError::StartFailed(format!("监听失败: {}", e)))?;
+    socket.listen(128)
+        .map_err(|e| ServerError::StartFailed(format!("监听失败: {}", e)))
+}
 
-    println!("[LanTransfer] HTTP 服务器启动: {} (SO_REUSEADDR 已启用)", addr);
+/// 运行 HTTP 服务器（直到 stop_server）
+pub async fn run_server(listener: tokio::net::TcpListener, device_info: DeviceInfo) {
+    if let Ok(addr) = listener.local_addr() {
+        log::info!("[LanTransfer] HTTP 服务器启动: {} (SO_REUSEADDR 已启用)", addr);
+    }
 
     // 创建关闭信号
     let (shutdown_tx, mut shutdown_rx) = oneshot::channel::<()>();
-    let shutdown_holder = SERVER_SHUTDOWN.get_or_init(|| Arc::new(Mutex::new(None)));
     {
+        let shutdown_holder = service::current().server.shutdown.clone();
         let mut holder = shutdown_holder.lock();
         *holder = Some(shutdown_tx);
     }
@@ -185,36 +293,33 @@ pub async fn start_server(device_info: DeviceInfo) -> Result<(), ServerError> {
             result = listener.accept() => {
                 match result {
                     Ok((stream, peer_addr)) => {
-                        println!("[LanTransfer] 📥 收到 TCP 连接: 来自 {}", peer_addr);
+                        log::info!("[LanTransfer] 📥 收到 TCP 连接: 来自 {}", peer_addr);
                         let device_info = device_info.clone();
-                        tokio::spawn(async move {
+                        service::spawn(async move {
                             if let Err(e) = handle_connection(stream, peer_addr, device_info).await {
-                                eprintln!("[LanTransfer] ❌ 处理连接失败 (来自 {}): {}", peer_addr, e);
+                                log::error!("[LanTransfer] ❌ 处理连接失败 (来自 {}): {}", peer_addr, e);
                             }
                         });
                     }
                     Err(e) => {
-                        eprintln!("[LanTransfer] ❌ 接受连接失败: {}", e);
+                        log::error!("[LanTransfer] ❌ 接受连接失败: {}", e);
                     }
                 }
             }
             _ = &mut shutdown_rx => {
-                println!("[LanTransfer] HTTP 服务器关闭");
+                log::info!("[LanTransfer] HTTP 服务器关闭");
                 break;
             }
         }
     }
-
-    Ok(())
 }
 
 /// 停止 HTTP 服务器
 pub async fn stop_server() {
-    if let Some(shutdown_holder) = SERVER_SHUTDOWN.get() {
-        let mut holder = shutdown_holder.lock();
-        if let Some(tx) = holder.take() {
-            let _ = tx.send(());
-        }
+    let shutdown_holder = service::current().server.shutdown.clone();
+    let mut holder = shutdown_holder.lock();
+    if let Some(tx) = holder.take() {
+        let _ = tx.send(());
     }
 }
 
@@ -287,6 +392,12 @@ async fn handle_connection(
         ("GET", "/api/info") => {
             handle_info(&mut writer, &device_info).await
         }
+        ("POST", "/api/probe") => {
+            handle_probe_upload(&mut writer, &body, peer_addr).await
+        }
+        ("GET", path) if path.starts_with("/api/probe") => {
+            handle_probe_download(&mut writer, path, peer_addr).await
+        }
         // ========== 点对点连接 API ==========
         ("POST", "/api/peer-connection-request") => {
             handle_peer_connection_request(&mut writer, &body, peer_addr).await
@@ -297,6 +408,74 @@ async fn handle_connection(
         ("POST", "/api/peer-disconnect") => {
             handle_peer_disconnect(&mut writer, &body).await
         }
+        ("POST", "/api/peer-heartbeat") => {
+            handle_peer_heartbeat(&mut writer, &body, &device_info).await
+        }
+        ("POST", "/api/text-snippet") => {
+            handle_text_snippet(&mut writer, &body, peer_addr).await
+        }
+        ("POST", "/api/chat-message") => {
+            handle_chat_message(&mut writer, &body, peer_addr).await
+        }
+        ("GET", path) if path.starts_with("/api/attachment") => {
+            handle_attachment(&mut writer, path, peer_addr).await
+        }
+        ("POST", "/api/shares") => {
+            handle_browse_shares(&mut writer, &body, peer_addr).await
+        }
+        ("POST", "/api/shares/pull") => {
+            handle_share_pull(&mut writer, &body, peer_addr).await
+        }
+        // ========== 文件夹同步 ==========
+        ("POST", "/api/sync/invite") => {
+            handle_sync_invite(&mut writer, &body, peer_addr).await
+        }
+        ("POST", "/api/sync/manifest") => {
+            handle_sync_manifest(&mut writer, &body, peer_addr).await
+        }
+        ("POST", "/api/sync/trigger") => {
+            handle_sync_trigger(&mut writer, &body, peer_addr).await
+        }
+        ("POST", "/api/sync/request-push") => {
+            handle_sync_push_request(&mut writer, &body, peer_addr).await
+        }
+        ("POST", "/api/sync/prepare") => {
+            handle_sync_prepare(&mut writer, &body, peer_addr).await
+        }
+        ("POST", path) if path.starts_with("/api/sync/chunk") => {
+            handle_sync_chunk(&mut writer, &body, path, peer_addr).await
+        }
+        ("POST", "/api/sync/commit") => {
+            handle_sync_commit(&mut writer, &body, peer_addr).await
+        }
+        ("POST", "/api/sync/delete") => {
+            handle_sync_delete(&mut writer, &body, peer_addr).await
+        }
+        // ========== 网页分享 ==========
+        ("GET", "/web") | ("GET", "/web/") => {
+            handle_web_page(&mut writer).await
+        }
+        ("POST", "/web/login") => {
+            handle_web_login(&mut writer, &body, &headers, peer_addr).await
+        }
+        ("GET", "/web/files") => {
+            handle_web_files(&mut writer, &headers, peer_addr).await
+        }
+        ("POST", "/web/upload-request") => {
+            handle_web_upload_request(&mut writer, &body, &headers, peer_addr).await
+        }
+        ("POST", "/web/download-request") => {
+            handle_web_download_request(&mut writer, &body, &headers, peer_addr).await
+        }
+        ("GET", path) if path.starts_with("/web/status") => {
+            handle_web_status(&mut writer, path, &headers, peer_addr).await
+        }
+        ("POST", path) if path.starts_with("/web/upload?") => {
+            handle_web_upload(&mut writer, &body, path, &headers, peer_addr).await
+        }
+        ("GET", path) if path.starts_with("/web/download?") => {
+            handle_web_download(&mut writer, path, peer_addr).await
+        }
         // ========== 旧版兼容 API ==========
         ("POST", "/api/connect") => {
             handle_connect(&mut writer, &body, peer_addr).await
@@ -309,13 +488,13 @@ async fn handle_connection(
         }
         // ========== 文件传输 API ==========
         ("POST", "/api/prepare-upload") => {
-            handle_prepare_upload(&mut writer, &body).await
+            handle_prepare_upload(&mut writer, &body, peer_addr).await
         }
         ("POST", path) if path.starts_with("/api/upload") => {
             handle_upload(&mut writer, &body, path, &headers).await
         }
         ("POST", path) if path.starts_with("/api/finish") => {
-            handle_finish(&mut writer, path).await
+            handle_finish(&mut writer, &body, path, peer_addr).await
         }
         ("POST", "/api/cancel") => {
             handle_cancel(&mut writer, &body).await
@@ -409,6 +588,8 @@ struct PeerConnectionResponseBody {
     connection_id: String,
     accepted: bool,
     from_device: Option<DiscoveredDevice>,
+    #[serde(default)]
+    reject_reason: Option<String>,
 }
 
 /// 请求体：断开连接
@@ -426,49 +607,64 @@ async fn handle_peer_connection_request(
     body: &[u8],
     peer_addr: SocketAddr,
 ) -> Result<(), ServerError> {
-    println!("[LanTransfer] ========== 收到连接请求 ==========");
-    println!("[LanTransfer] 来源 TCP 地址: {}", peer_addr);
+    log::info!("[LanTransfer] ========== 收到连接请求 ==========");
+    log::info!("[LanTransfer] 来源 TCP 地址: {}", peer_addr);
     
     let req_body: PeerConnectionRequestBody =
         serde_json::from_slice(body).map_err(|e| {
-            println!("[LanTransfer] ❌ 解析请求 JSON 失败: {}", e);
+            log::error!("[LanTransfer] ❌ 解析请求 JSON 失败: {}", e);
             ServerError::RequestFailed(e.to_string())
         })?;
 
     let from_device_id = req_body.from_device.device_id.clone();
     
-    println!("[LanTransfer] 请求来自:");
-    println!("[LanTransfer]   设备 ID: {}", from_device_id);
-    println!("[LanTransfer]   设备名: {}", req_body.from_device.device_name);
-    println!("[LanTransfer]   声称 IP: {}:{}", req_body.from_device.ip_address, req_body.from_device.port);
-    println!("[LanTransfer]   实际 TCP 来源: {}", peer_addr);
+    log::info!("[LanTransfer] 请求来自:");
+    log::info!("[LanTransfer]   设备 ID: {}", from_device_id);
+    log::info!("[LanTransfer]   设备名: {}", req_body.from_device.device_name);
+    log::info!("[LanTransfer]   声称 IP: {}:{}", req_body.from_device.ip_address, req_body.from_device.port);
+    log::info!("[LanTransfer]   实际 TCP 来源: {}", peer_addr);
 
     // ========== 检查是否已存在与该设备的连接（去重）==========
     // 注意：先提取数据，释放锁，再调用 async 函数
-    let existing_connection_id: Option<String> = {
+    let existing_connection: Option<PeerConnection> = {
         let connections = get_active_peer_connections_map();
         let connections = connections.lock();
         connections
-            .iter()
-            .find(|(_, conn)| {
+            .values()
+            .find(|conn| {
                 conn.peer_device.device_id == from_device_id
-                    && conn.status == PeerConnectionStatus::Connected
+                    && conn.status != PeerConnectionStatus::Disconnected
             })
-            .map(|(conn_id, _)| conn_id.clone())
+            .cloned()
     };
 
-    if let Some(conn_id) = existing_connection_id {
-        println!(
-            "[LanTransfer] 已存在与 {} 的连接: {}，返回现有连接",
+    if let Some(existing) = existing_connection {
+        let conn_id = existing.connection_id.clone();
+        log::info!(
+            "[LanTransfer] 已存在与 {} 的连接: {}，验证身份后返回现有连接",
             from_device_id, conn_id
         );
 
-        // 重新发送连接建立事件，确保前端知道这个连接
+        // 重新连接：回访来源地址验证对端身份，防止冒用 device_id 接管已有连接
+        let source_ip = peer_addr.ip().to_string();
+        if !heartbeat::verify_peer_identity(&req_body.from_device, &source_ip, &existing.peer_device)
+            .await
+        {
+            log::error!("[LanTransfer] ❌ 对端身份验证失败，拒绝复用连接: {}", conn_id);
+            return send_error_response(writer, 403, "Forbidden").await;
+        }
+
+        // 更新对端地址（IP 可能已变化）并恢复连接状态
         let connection: Option<PeerConnection> = {
             let connections = get_active_peer_connections_map();
-            let connections = connections.lock();
-            connections.get(&conn_id).cloned()
+            let mut connections = connections.lock();
+            connections.get_mut(&conn_id).map(|conn| {
+                conn.peer_device.ip_address = source_ip.clone();
+                conn.peer_device.port = req_body.from_device.port;
+                conn.clone()
+            })
         };
+        heartbeat::record_contact(&conn_id);
 
         if let Some(conn) = connection {
             let event = LanTransferEvent::PeerConnectionEstablished { connection: conn };
@@ -504,7 +700,7 @@ async fn handle_peer_connection_request(
     };
 
     if let Some(request) = existing_request {
-        println!(
+        log::info!(
             "[LanTransfer] 已存在来自 {} 的待处理请求: {}，重新发送事件",
             from_device_id, request.connection_id
         );
@@ -516,186 +712,1163 @@ async fn handle_peer_connection_request(
         let _ = get_event_sender().send(event.clone());
         emit_lan_event(&event);
 
-        #[derive(serde::Serialize)]
-        #[serde(rename_all = "camelCase")]
-        struct Response {
-            connection_id: String,
-            status: String,
-        }
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Response {
+            connection_id: String,
+            status: String,
+        }
+
+        return send_json_response(
+            writer,
+            &Response {
+                connection_id: request.connection_id,
+                status: "pending".to_string(),
+            },
+        )
+        .await;
+    }
+
+    let connection_id = Uuid::new_v4().to_string();
+    let now = Utc::now().to_rfc3339();
+
+    // 创建连接请求
+    let request = PeerConnectionRequest {
+        connection_id: connection_id.clone(),
+        from_device: DiscoveredDevice {
+            ip_address: peer_addr.ip().to_string(),
+            ..req_body.from_device
+        },
+        requested_at: now,
+    };
+
+    log::info!("[LanTransfer] ✓ 创建新连接请求: {}", connection_id);
+    log::info!("[LanTransfer]   修正后的 IP: {} (使用 TCP 来源地址)", peer_addr.ip());
+
+    // 保存到待处理请求
+    {
+        let requests = get_pending_peer_connection_requests_map();
+        let mut requests = requests.lock();
+        requests.insert(connection_id.clone(), request.clone());
+        log::info!("[LanTransfer] ✓ 已保存到待处理请求列表 (共 {} 个)", requests.len());
+    }
+
+    // 发送事件通知前端
+    let event = LanTransferEvent::PeerConnectionRequest { request };
+    let _ = get_event_sender().send(event.clone());
+    emit_lan_event(&event);
+
+    log::info!("[LanTransfer] ✓ 已发送 PeerConnectionRequest 事件到前端");
+    log::info!("[LanTransfer] ========== 等待用户响应 ==========");
+
+    // 返回连接 ID
+    #[derive(serde::Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Response {
+        connection_id: String,
+        status: String,
+    }
+
+    send_json_response(
+        writer,
+        &Response {
+            connection_id,
+            status: "pending".to_string(),
+        },
+    )
+    .await
+}
+
+/// 处理点对点连接响应（发起方收到接收方的响应）
+async fn handle_peer_connection_response(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    body: &[u8],
+    peer_addr: SocketAddr,
+) -> Result<(), ServerError> {
+    log::info!("[LanTransfer] ========== 收到连接响应 ==========");
+    log::info!("[LanTransfer] 来源 TCP 地址: {}", peer_addr);
+    
+    let req_body: PeerConnectionResponseBody =
+        serde_json::from_slice(body).map_err(|e| {
+            log::error!("[LanTransfer] ❌ 解析响应 JSON 失败: {}", e);
+            ServerError::RequestFailed(e.to_string())
+        })?;
+
+    let connection_id = req_body.connection_id.clone();
+    let now = Utc::now().to_rfc3339();
+    
+    log::info!("[LanTransfer] 连接 ID: {}", connection_id);
+    log::info!("[LanTransfer] 接受连接: {}", req_body.accepted);
+    if let Some(ref from_device) = req_body.from_device {
+        log::info!("[LanTransfer] 响应设备: {} @ {}:{}", 
+            from_device.device_name, from_device.ip_address, from_device.port);
+    }
+
+    if req_body.accepted {
+        // 接收方接受了连接，创建连接对象
+        if let Some(from_device) = req_body.from_device {
+            let connection = PeerConnection {
+                connection_id: connection_id.clone(),
+                peer_device: DiscoveredDevice {
+                    ip_address: peer_addr.ip().to_string(),
+                    ..from_device
+                },
+                established_at: now,
+                status: PeerConnectionStatus::Connected,
+                is_initiator: true, // 发起方收到此响应
+            };
+
+            // 保存连接
+            {
+                let connections = get_active_peer_connections_map();
+                let mut connections = connections.lock();
+                connections.insert(connection_id.clone(), connection.clone());
+            }
+
+            // 投递之前离线时排队的聊天消息
+            let peer_device_id = connection.peer_device.device_id.clone();
+            service::spawn(async move {
+                chat::flush_outbox(&peer_device_id).await;
+            });
+
+            // 发送事件通知前端
+            let event = LanTransferEvent::PeerConnectionEstablished { connection };
+            let _ = get_event_sender().send(event.clone());
+            emit_lan_event(&event);
+
+            log::info!("[LanTransfer] 连接已建立: {}", connection_id);
+        }
+    } else {
+        // 连接被拒绝，从发起方的活跃连接中移除
+        {
+            let connections = get_active_peer_connections_map();
+            let mut connections = connections.lock();
+            connections.remove(&connection_id);
+        }
+
+        // 发送连接关闭事件通知前端
+        let event = LanTransferEvent::PeerConnectionClosed {
+            connection_id: connection_id.clone(),
+        };
+        let _ = get_event_sender().send(event.clone());
+        emit_lan_event(&event);
+
+        log::info!(
+            "[LanTransfer] 连接请求被拒绝: {} ({})，已清理连接记录",
+            connection_id,
+            req_body.reject_reason.as_deref().unwrap_or("无原因")
+        );
+    }
+
+    // 返回确认
+    #[derive(serde::Serialize)]
+    struct AckResponse {
+        success: bool,
+    }
+
+    send_json_response(writer, &AckResponse { success: true }).await
+}
+
+/// 处理断开连接请求
+async fn handle_peer_disconnect(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    body: &[u8],
+) -> Result<(), ServerError> {
+    let req_body: PeerDisconnectBody =
+        serde_json::from_slice(body).map_err(|e| ServerError::RequestFailed(e.to_string()))?;
+
+    let connection_id = req_body.connection_id.clone();
+
+    // 从活跃连接中移除
+    {
+        let connections = get_active_peer_connections_map();
+        let mut connections = connections.lock();
+        connections.remove(&connection_id);
+    }
+
+    // 发送事件通知前端
+    let event = LanTransferEvent::PeerConnectionClosed {
+        connection_id: connection_id.clone(),
+    };
+    let _ = get_event_sender().send(event.clone());
+    emit_lan_event(&event);
+
+    log::info!("[LanTransfer] 连接已断开: {}", connection_id);
+
+    // 返回确认
+    #[derive(serde::Serialize)]
+    struct AckResponse {
+        success: bool,
+    }
+
+    send_json_response(writer, &AckResponse { success: true }).await
+}
+
+/// 处理连接心跳
+///
+/// 连接存在且属于发送方设备时响应 alive=true，并记录对端存活；
+/// 否则响应 alive=false，对端会关闭该连接
+async fn handle_peer_heartbeat(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    body: &[u8],
+    device_info: &DeviceInfo,
+) -> Result<(), ServerError> {
+    let req_body: PeerHeartbeatRequest = match serde_json::from_slice(body) {
+        Ok(req) => req,
+        Err(_) => return send_error_response(writer, 400, "Bad Request").await,
+    };
+
+    let alive = {
+        let connections = get_active_peer_connections_map();
+        let connections = connections.lock();
+        // 不根据来源 IP 更新连接地址：心跳未经身份验证，发送心跳时会优先使用最新发现的地址
+        connections
+            .get(&req_body.connection_id)
+            .is_some_and(|conn| conn.peer_device.device_id == req_body.device_id)
+    };
+
+    if alive {
+        heartbeat::record_contact(&req_body.connection_id);
+    }
+
+    send_json_response(
+        writer,
+        &PeerHeartbeatResponse {
+            alive,
+            device_id: device_info.device_id.clone(),
+        },
+    )
+    .await
+}
+
+/// 处理文本片段（接收方收到）
+///
+/// 只接受来自已建立连接的设备，收到后记录到本地历史并通知前端
+async fn handle_text_snippet(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    body: &[u8],
+    peer_addr: SocketAddr,
+) -> Result<(), ServerError> {
+    let snippet: TextSnippet =
+        serde_json::from_slice(body).map_err(|e| ServerError::RequestFailed(e.to_string()))?;
+
+    if snippet.content.len() > MAX_SNIPPET_BYTES {
+        log::info!(
+            "[LanTransfer] 文本片段过大，拒绝接收: {} 字节",
+            snippet.content.len()
+        );
+        return send_error_response(writer, 413, "Payload Too Large").await;
+    }
+
+    // 校验连接：连接必须存在、处于已连接状态，且属于发送方设备
+    let is_authorized = {
+        let connections = get_active_peer_connections_map();
+        let connections = connections.lock();
+        connections.get(&snippet.connection_id).is_some_and(|conn| {
+            conn.status == PeerConnectionStatus::Connected
+                && conn.peer_device.device_id == snippet.from_device.device_id
+        })
+    };
+
+    if !is_authorized {
+        log::info!(
+            "[LanTransfer] 拒绝来自未连接设备的文本片段: {} (连接: {})",
+            snippet.from_device.device_id, snippet.connection_id
+        );
+        return send_error_response(writer, 403, "Forbidden").await;
+    }
+
+    let snippet = TextSnippet {
+        from_device: DiscoveredDevice {
+            ip_address: peer_addr.ip().to_string(),
+            ..snippet.from_device
+        },
+        received_at: Utc::now().to_rfc3339(),
+        ..snippet
+    };
+
+    let record = snippet.clone();
+    match service::spawn_blocking(move || snippets::record_received_snippet(record)).await {
+        Ok(Err(e)) => log::error!("[LanTransfer] 保存文本片段历史失败: {}", e),
+        Err(e) => log::error!("[LanTransfer] 保存文本片段历史失败: {}", e),
+        Ok(Ok(())) => {}
+    }
+
+    log::info!(
+        "[LanTransfer] 收到文本片段: {} 来自 {} ({} 字节)",
+        snippet.snippet_id,
+        snippet.from_device.device_name,
+        snippet.content.len()
+    );
+
+    // 发送事件通知前端
+    let event = LanTransferEvent::TextSnippetReceived { snippet };
+    let _ = get_event_sender().send(event.clone());
+    emit_lan_event(&event);
+
+    // 返回确认
+    #[derive(serde::Serialize)]
+    struct AckResponse {
+        success: bool,
+    }
+
+    send_json_response(writer, &AckResponse { success: true }).await
+}
+
+/// 处理局域网聊天消息（接收方收到）
+///
+/// 消息保存成功后返回 200 作为送达回执；发送方收到回执前会保留在投递队列中
+async fn handle_chat_message(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    body: &[u8],
+    peer_addr: SocketAddr,
+) -> Result<(), ServerError> {
+    let message: LanChatMessage =
+        serde_json::from_slice(body).map_err(|e| ServerError::RequestFailed(e.to_string()))?;
+
+    // 校验连接：连接必须存在、处于已连接状态，且属于发送方设备
+    let is_authorized = {
+        let connections = get_active_peer_connections_map();
+        let connections = connections.lock();
+        connections.get(&message.connection_id).is_some_and(|conn| {
+            conn.status == PeerConnectionStatus::Connected
+                && conn.peer_device.device_id == message.from_device.device_id
+        })
+    };
+
+    if !is_authorized {
+        log::info!(
+            "[LanTransfer] 拒绝来自未连接设备的聊天消息: {} (连接: {})",
+            message.from_device.device_id, message.connection_id
+        );
+        return send_error_response(writer, 403, "Forbidden").await;
+    }
+
+    let message = LanChatMessage {
+        from_device: DiscoveredDevice {
+            ip_address: peer_addr.ip().to_string(),
+            ..message.from_device
+        },
+        ..message
+    };
+
+    if let Err(e) = chat::handle_incoming_message(message) {
+        log::error!("[LanTransfer] 保存聊天消息失败: {}", e);
+        return send_error_response(writer, 500, "Internal Server Error").await;
+    }
+
+    #[derive(serde::Serialize)]
+    struct AckResponse {
+        success: bool,
+    }
+
+    send_json_response(writer, &AckResponse { success: true }).await
+}
+
+/// 处理聊天附件请求（持有方提供文件）
+///
+/// 按 file_hash 查找本地文件，校验请求设备与聊天关系后流式返回文件内容
+async fn handle_attachment(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    path: &str,
+    peer_addr: SocketAddr,
+) -> Result<(), ServerError> {
+    use tokio::io::AsyncWriteExt;
+
+    let query = path.split('?').nth(1).unwrap_or("");
+    let params: HashMap<&str, &str> = query
+        .split('&')
+        .filter_map(|s| s.split_once('='))
+        .collect();
+
+    let file_hash = params.get("fileHash").unwrap_or(&"").to_string();
+    let device_id = params.get("deviceId").unwrap_or(&"").to_string();
+
+    if file_hash.is_empty() || device_id.is_empty() {
+        return send_error_response(writer, 400, "Bad Request").await;
+    }
+
+    let attachment = match attachments::resolve_shared_attachment(
+        &file_hash,
+        &device_id,
+        &peer_addr.ip().to_string(),
+    ) {
+        Ok(attachment) => attachment,
+        Err(e) => {
+            log::info!(
+                "[LanTransfer] 拒绝附件请求: {} 来自 {} ({})",
+                file_hash, device_id, e
+            );
+            return match e {
+                AttachmentError::NotFound => send_error_response(writer, 404, "Not Found").await,
+                AttachmentError::Database(_) => {
+                    send_error_response(writer, 500, "Internal Server Error").await
+                }
+                _ => send_error_response(writer, 403, "Forbidden").await,
+            };
+        }
+    };
+
+    let mut file = match tokio::fs::File::open(&attachment.path).await {
+        Ok(file) => file,
+        Err(_) => return send_error_response(writer, 404, "Not Found").await,
+    };
+
+    log::info!(
+        "[LanTransfer] 📤 提供附件: {} -> {} ({} bytes)",
+        file_hash, peer_addr, attachment.file_size
+    );
+
+    let header = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nConnection: close\r\nContent-Length: {}\r\n\r\n",
+        attachment.content_type, attachment.file_size
+    );
+
+    writer
+        .write_all(header.as_bytes())
+        .await
+        .map_err(|e| ServerError::RequestFailed(e.to_string()))?;
+
+    tokio::io::copy(&mut file, writer)
+        .await
+        .map_err(|e| ServerError::RequestFailed(e.to_string()))?;
+
+    Ok(())
+}
+
+/// 将共享文件夹错误转换为 HTTP 错误响应
+async fn send_share_error(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    error: &ShareError,
+) -> Result<(), ServerError> {
+    match error {
+        ShareError::NotConnected | ShareError::Forbidden => {
+            send_error_response(writer, 403, "Forbidden").await
+        }
+        ShareError::ShareNotFound(_) => send_error_response(writer, 404, "Not Found").await,
+        ShareError::InvalidPath(_) | ShareError::TooManyFiles(_) => {
+            send_error_response(writer, 400, "Bad Request").await
+        }
+        _ => send_error_response(writer, 500, "Internal Server Error").await,
+    }
+}
+
+/// 处理共享文件夹浏览请求
+async fn handle_browse_shares(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    body: &[u8],
+    peer_addr: SocketAddr,
+) -> Result<(), ServerError> {
+    let request: shares::BrowseSharesRequest = match serde_json::from_slice(body) {
+        Ok(r) => r,
+        Err(_) => return send_error_response(writer, 400, "Bad Request").await,
+    };
+
+    let result = shares::authorize_peer(
+        &request.connection_id,
+        &request.device_id,
+        &peer_addr.ip().to_string(),
+    )
+    .and_then(|_| shares::browse(&request));
+
+    match result {
+        Ok(response) => send_json_response(writer, &response).await,
+        Err(e) => {
+            log::info!(
+                "[LanTransfer] 拒绝共享浏览请求: {} ({})",
+                request.device_id, e
+            );
+            send_share_error(writer, &e).await
+        }
+    }
+}
+
+/// 处理共享文件夹拉取请求
+async fn handle_share_pull(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    body: &[u8],
+    peer_addr: SocketAddr,
+) -> Result<(), ServerError> {
+    let request: shares::PullRequest = match serde_json::from_slice(body) {
+        Ok(r) => r,
+        Err(_) => return send_error_response(writer, 400, "Bad Request").await,
+    };
+
+    if let Err(e) = shares::authorize_peer(
+        &request.connection_id,
+        &request.device_id,
+        &peer_addr.ip().to_string(),
+    ) {
+        return send_share_error(writer, &e).await;
+    }
+
+    match shares::serve_pull(&request).await {
+        Ok(response) => send_json_response(writer, &response).await,
+        Err(e) => {
+            log::info!(
+                "[LanTransfer] 拒绝共享拉取请求: {} ({})",
+                request.device_id, e
+            );
+            send_share_error(writer, &e).await
+        }
+    }
+}
+
+// ============================================================================
+// 连通性探测
+// ============================================================================
+
+/// 来源 IP 是否属于已发现的设备或已建立连接的对端
+fn is_known_peer_ip(ip: IpAddr) -> bool {
+    let ip = ip.to_string();
+    let discovered = get_lan_transfer_state()
+        .devices
+        .read()
+        .values()
+        .any(|d| d.ip_address == ip);
+
+    discovered
+        || get_active_peer_connections_map()
+            .lock()
+            .values()
+            .any(|c| c.peer_device.ip_address == ip)
+}
+
+/// 记录一次探测请求，同一 IP 在时间窗口内超过 MAX_PROBES_PER_WINDOW 次时返回 false
+fn allow_probe(ip: IpAddr) -> bool {
+    let now = Instant::now();
+    let window = Duration::from_secs(PROBE_RATE_WINDOW_SECS);
+
+    let probes = service::current().server.probe_requests.clone();
+    let mut probes = probes.lock();
+    probes.retain(|_, (start, _)| now.duration_since(*start) < window);
+
+    let (_, count) = probes.entry(ip).or_insert((now, 0));
+    *count += 1;
+    *count <= MAX_PROBES_PER_WINDOW
+}
+
+/// 探测请求的准入检查：未知来源返回 403，超出速率限制返回 429
+///
+/// 返回 Ok(true) 表示已发送拒绝响应，调用方应直接结束
+async fn reject_probe(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    peer_addr: SocketAddr,
+) -> Result<bool, ServerError> {
+    if !is_known_peer_ip(peer_addr.ip()) {
+        log::warn!("[LanTransfer] 拒绝来自未知设备的连通性探测: {}", peer_addr);
+        send_error_response(writer, 403, "Forbidden").await?;
+        return Ok(true);
+    }
+
+    if !allow_probe(peer_addr.ip()) {
+        log::warn!("[LanTransfer] ⚠️ 来自 {} 的连通性探测过于频繁，已拒绝", peer_addr);
+        send_error_response(writer, 429, "Too Many Requests").await?;
+        return Ok(true);
+    }
+
+    Ok(false)
+}
+
+/// 处理上行探测：丢弃请求体，返回收到的字节数
+async fn handle_probe_upload(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    body: &[u8],
+    peer_addr: SocketAddr,
+) -> Result<(), ServerError> {
+    if reject_probe(writer, peer_addr).await? {
+        return Ok(());
+    }
+
+    if body.len() > PROBE_MAX_BYTES {
+        return send_error_response(writer, 413, "Payload Too Large").await;
+    }
+
+    log::info!(
+        "[LanTransfer] 连通性探测（上行）: {} bytes 来自 {}",
+        body.len(),
+        peer_addr
+    );
+    send_json_response(writer, &serde_json::json!({ "receivedBytes": body.len() })).await
+}
+
+/// 处理下行探测：返回 size 指定大小的数据（不超过 PROBE_MAX_BYTES）
+async fn handle_probe_download(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    path: &str,
+    peer_addr: SocketAddr,
+) -> Result<(), ServerError> {
+    use tokio::io::AsyncWriteExt;
+
+    if reject_probe(writer, peer_addr).await? {
+        return Ok(());
+    }
+
+    let size = match parse_query(path).get("size").map(|s| s.parse::<usize>()) {
+        Some(Ok(size)) if size <= PROBE_MAX_BYTES => size,
+        Some(Ok(_)) => return send_error_response(writer, 413, "Payload Too Large").await,
+        _ => return send_error_response(writer, 400, "Bad Request").await,
+    };
+
+    log::info!(
+        "[LanTransfer] 连通性探测（下行）: {} bytes 发往 {}",
+        size, peer_addr
+    );
+
+    let header = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\nConnection: close\r\nContent-Length: {}\r\n\r\n",
+        size
+    );
+    writer
+        .write_all(header.as_bytes())
+        .await
+        .map_err(|e| ServerError::RequestFailed(e.to_string()))?;
+
+    let block = vec![0u8; size.min(64 * 1024)];
+    let mut remaining = size;
+    while remaining > 0 {
+        let n = remaining.min(block.len());
+        writer
+            .write_all(&block[..n])
+            .await
+            .map_err(|e| ServerError::RequestFailed(e.to_string()))?;
+        remaining -= n;
+    }
+
+    Ok(())
+}
+
+// ============================================================================
+// 文件夹同步
+// ============================================================================
+
+/// 同步接口的确认响应
+#[derive(serde::Serialize)]
+struct SyncAckResponse {
+    success: bool,
+}
+
+/// 将同步错误转换为 HTTP 错误响应
+async fn send_sync_error(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    error: &SyncError,
+) -> Result<(), ServerError> {
+    log::error!("[LanTransfer] 🔁 同步请求失败: {}", error);
+
+    match error {
+        SyncError::NotOwnDevice | SyncError::DeviceOffline => {
+            send_error_response(writer, 403, "Forbidden").await
+        }
+        SyncError::PairNotFound(_) => send_error_response(writer, 404, "Not Found").await,
+        SyncError::Modified(_) => send_error_response(writer, 409, "Conflict").await,
+        SyncError::InvalidPath(_) | SyncError::HashMismatch(_) => {
+            send_error_response(writer, 400, "Bad Request").await
+        }
+        _ => send_error_response(writer, 500, "Internal Server Error").await,
+    }
+}
+
+/// 处理同步配对邀请
+async fn handle_sync_invite(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    body: &[u8],
+    peer_addr: SocketAddr,
+) -> Result<(), ServerError> {
+    let request: sync::SyncInviteRequest = match serde_json::from_slice(body) {
+        Ok(r) => r,
+        Err(_) => return send_error_response(writer, 400, "Bad Request").await,
+    };
+
+    match sync::handle_invite(&request, &peer_addr.ip().to_string()) {
+        Ok(()) => send_json_response(writer, &SyncAckResponse { success: true }).await,
+        Err(e) => send_sync_error(writer, &e).await,
+    }
+}
+
+/// 处理同步清单请求
+async fn handle_sync_manifest(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    body: &[u8],
+    peer_addr: SocketAddr,
+) -> Result<(), ServerError> {
+    let request: sync::SyncPairRequest = match serde_json::from_slice(body) {
+        Ok(r) => r,
+        Err(_) => return send_error_response(writer, 400, "Bad Request").await,
+    };
+
+    match sync::handle_manifest(&request, &peer_addr.ip().to_string()).await {
+        Ok(entries) => send_json_response(writer, &entries).await,
+        Err(e) => send_sync_error(writer, &e).await,
+    }
+}
+
+/// 处理立即同步请求（本机为协调方）
+async fn handle_sync_trigger(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    body: &[u8],
+    peer_addr: SocketAddr,
+) -> Result<(), ServerError> {
+    let request: sync::SyncPairRequest = match serde_json::from_slice(body) {
+        Ok(r) => r,
+        Err(_) => return send_error_response(writer, 400, "Bad Request").await,
+    };
+
+    match sync::handle_trigger(&request, &peer_addr.ip().to_string()).await {
+        Ok(summary) => send_json_response(writer, &summary).await,
+        Err(e) => send_sync_error(writer, &e).await,
+    }
+}
+
+/// 处理推送请求（推送完成后才响应）
+async fn handle_sync_push_request(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    body: &[u8],
+    peer_addr: SocketAddr,
+) -> Result<(), ServerError> {
+    let request: sync::SyncPushRequest = match serde_json::from_slice(body) {
+        Ok(r) => r,
+        Err(_) => return send_error_response(writer, 400, "Bad Request").await,
+    };
+
+    match sync::handle_push_request(&request, &peer_addr.ip().to_string()).await {
+        Ok(response) => send_json_response(writer, &response).await,
+        Err(e) => send_sync_error(writer, &e).await,
+    }
+}
+
+/// 处理同步文件准备请求
+async fn handle_sync_prepare(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    body: &[u8],
+    peer_addr: SocketAddr,
+) -> Result<(), ServerError> {
+    let header: sync::SyncFileHeader = match serde_json::from_slice(body) {
+        Ok(h) => h,
+        Err(_) => return send_error_response(writer, 400, "Bad Request").await,
+    };
+
+    let peer_ip = peer_addr.ip().to_string();
+    let result = service::spawn_blocking(move || sync::handle_prepare(&header, &peer_ip))
+        .await
+        .map_err(|e| ServerError::RequestFailed(e.to_string()))?;
+
+    match result {
+        Ok(response) => send_json_response(writer, &response).await,
+        Err(e) => send_sync_error(writer, &e).await,
+    }
+}
+
+/// 处理同步文件数据块
+async fn handle_sync_chunk(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    body: &[u8],
+    path: &str,
+    peer_addr: SocketAddr,
+) -> Result<(), ServerError> {
+    let params = parse_query(path);
+    let pair_id = params.get("pairId").copied().unwrap_or("").to_string();
+    let device_id = params.get("deviceId").copied().unwrap_or("").to_string();
+    let file_key = params.get("fileKey").copied().unwrap_or("").to_string();
+    let offset: u64 = match params.get("offset").and_then(|s| s.parse().ok()) {
+        Some(offset) => offset,
+        None => return send_error_response(writer, 400, "Bad Request").await,
+    };
+
+    let data = body.to_vec();
+    let peer_ip = peer_addr.ip().to_string();
+    let result = service::spawn_blocking(move || {
+        sync::handle_chunk(&pair_id, &device_id, &file_key, offset, &data, &peer_ip)
+    })
+    .await
+    .map_err(|e| ServerError::RequestFailed(e.to_string()))?;
+
+    match result {
+        Ok(()) => send_json_response(writer, &SyncAckResponse { success: true }).await,
+        Err(e) => send_sync_error(writer, &e).await,
+    }
+}
+
+/// 处理同步文件提交
+async fn handle_sync_commit(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    body: &[u8],
+    peer_addr: SocketAddr,
+) -> Result<(), ServerError> {
+    let header: sync::SyncFileHeader = match serde_json::from_slice(body) {
+        Ok(h) => h,
+        Err(_) => return send_error_response(writer, 400, "Bad Request").await,
+    };
+
+    let peer_ip = peer_addr.ip().to_string();
+    let result = service::spawn_blocking(move || sync::handle_commit(&header, &peer_ip))
+        .await
+        .map_err(|e| ServerError::RequestFailed(e.to_string()))?;
+
+    match result {
+        Ok(()) => send_json_response(writer, &SyncAckResponse { success: true }).await,
+        Err(e) => send_sync_error(writer, &e).await,
+    }
+}
+
+/// 处理同步删除请求
+async fn handle_sync_delete(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    body: &[u8],
+    peer_addr: SocketAddr,
+) -> Result<(), ServerError> {
+    let request: sync::SyncDeleteRequest = match serde_json::from_slice(body) {
+        Ok(r) => r,
+        Err(_) => return send_error_response(writer, 400, "Bad Request").await,
+    };
+
+    let peer_ip = peer_addr.ip().to_string();
+    let result = service::spawn_blocking(move || sync::handle_delete(&request, &peer_ip))
+        .await
+        .map_err(|e| ServerError::RequestFailed(e.to_string()))?;
+
+    match result {
+        Ok(()) => send_json_response(writer, &SyncAckResponse { success: true }).await,
+        Err(e) => send_sync_error(writer, &e).await,
+    }
+}
+
+// ============================================================================
+// 网页分享
+// ============================================================================
+
+/// 解析查询参数
+fn parse_query(path: &str) -> HashMap<&str, &str> {
+    path.split('?')
+        .nth(1)
+        .unwrap_or("")
+        .split('&')
+        .filter_map(|s| s.split_once('='))
+        .collect()
+}
+
+/// 读取网页访客令牌（X-Web-Token 头）
+fn web_token(headers: &HashMap<String, String>) -> &str {
+    headers.get("x-web-token").map(|s| s.as_str()).unwrap_or("")
+}
+
+/// 将网页分享错误转换为 HTTP 错误响应
+async fn send_web_share_error(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    error: &WebShareError,
+) -> Result<(), ServerError> {
+    match error {
+        WebShareError::Disabled | WebShareError::ServiceNotRunning => {
+            send_error_response(writer, 404, "Not Found").await
+        }
+        WebShareError::InvalidPin | WebShareError::Unauthorized => {
+            send_error_response(writer, 401, "Unauthorized").await
+        }
+        WebShareError::NotAccepted => send_error_response(writer, 403, "Forbidden").await,
+        WebShareError::RequestNotFound(_) | WebShareError::FileNotFound(_) => {
+            send_error_response(writer, 404, "Not Found").await
+        }
+        WebShareError::InvalidRequest(_) => send_error_response(writer, 400, "Bad Request").await,
+        WebShareError::Io(_) => send_error_response(writer, 500, "Internal Server Error").await,
+        WebShareError::TooManyRequests => {
+            send_error_response(writer, 429, "Too Many Requests").await
+        }
+    }
+}
+
+/// 处理网页分享页面请求
+async fn handle_web_page(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+) -> Result<(), ServerError> {
+    use tokio::io::AsyncWriteExt;
+
+    if !web_share::is_enabled() {
+        return send_error_response(writer, 404, "Not Found").await;
+    }
+
+    let body = web_share::WEB_SHARE_PAGE;
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nCache-Control: no-store\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    );
+
+    writer
+        .write_all(response.as_bytes())
+        .await
+        .map_err(|e| ServerError::RequestFailed(e.to_string()))?;
+
+    Ok(())
+}
 
-        return send_json_response(
-            writer,
-            &Response {
-                connection_id: request.connection_id,
-                status: "pending".to_string(),
-            },
-        )
-        .await;
+/// 处理网页访客登录
+async fn handle_web_login(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    body: &[u8],
+    headers: &HashMap<String, String>,
+    peer_addr: SocketAddr,
+) -> Result<(), ServerError> {
+    #[derive(serde::Deserialize)]
+    struct LoginRequest {
+        pin: String,
     }
 
-    let connection_id = Uuid::new_v4().to_string();
-    let now = Utc::now().to_rfc3339();
+    #[derive(serde::Serialize)]
+    struct LoginResponse {
+        token: String,
+    }
 
-    // 创建连接请求
-    let request = PeerConnectionRequest {
-        connection_id: connection_id.clone(),
-        from_device: DiscoveredDevice {
-            ip_address: peer_addr.ip().to_string(),
-            ..req_body.from_device
-        },
-        requested_at: now,
+    let request: LoginRequest = match serde_json::from_slice(body) {
+        Ok(r) => r,
+        Err(_) => return send_error_response(writer, 400, "Bad Request").await,
     };
 
-    println!("[LanTransfer] ✓ 创建新连接请求: {}", connection_id);
-    println!("[LanTransfer]   修正后的 IP: {} (使用 TCP 来源地址)", peer_addr.ip());
+    let user_agent = headers.get("user-agent").map(|s| s.as_str()).unwrap_or("");
 
-    // 保存到待处理请求
-    {
-        let requests = get_pending_peer_connection_requests_map();
-        let mut requests = requests.lock();
-        requests.insert(connection_id.clone(), request.clone());
-        println!("[LanTransfer] ✓ 已保存到待处理请求列表 (共 {} 个)", requests.len());
+    match web_share::login(&request.pin, &peer_addr.ip().to_string(), user_agent) {
+        Ok(token) => send_json_response(writer, &LoginResponse { token }).await,
+        Err(e) => {
+            log::error!("[LanTransfer] 🌐 网页访客登录失败: {} ({})", peer_addr, e);
+            send_web_share_error(writer, &e).await
+        }
     }
+}
 
-    // 发送事件通知前端
-    let event = LanTransferEvent::PeerConnectionRequest { request };
-    let _ = get_event_sender().send(event.clone());
-    emit_lan_event(&event);
-
-    println!("[LanTransfer] ✓ 已发送 PeerConnectionRequest 事件到前端");
-    println!("[LanTransfer] ========== 等待用户响应 ==========");
-
-    // 返回连接 ID
+/// 处理网页分享文件列表请求
+async fn handle_web_files(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    headers: &HashMap<String, String>,
+    peer_addr: SocketAddr,
+) -> Result<(), ServerError> {
     #[derive(serde::Serialize)]
-    #[serde(rename_all = "camelCase")]
-    struct Response {
-        connection_id: String,
-        status: String,
+    struct FilesResponse {
+        files: Vec<web_share::WebSharedFile>,
     }
 
-    send_json_response(
-        writer,
-        &Response {
-            connection_id,
-            status: "pending".to_string(),
-        },
-    )
-    .await
+    match web_share::list_shared_files(web_token(headers), &peer_addr.ip().to_string()) {
+        Ok(files) => send_json_response(writer, &FilesResponse { files }).await,
+        Err(e) => send_web_share_error(writer, &e).await,
+    }
 }
 
-/// 处理点对点连接响应（发起方收到接收方的响应）
-async fn handle_peer_connection_response(
+/// 网页访客请求的响应
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WebRequestResponse {
+    request_id: String,
+    files: Vec<FileMetadata>,
+}
+
+/// 处理网页访客上传请求（等待本机确认）
+async fn handle_web_upload_request(
     writer: &mut tokio::net::tcp::WriteHalf<'_>,
     body: &[u8],
+    headers: &HashMap<String, String>,
     peer_addr: SocketAddr,
 ) -> Result<(), ServerError> {
-    println!("[LanTransfer] ========== 收到连接响应 ==========");
-    println!("[LanTransfer] 来源 TCP 地址: {}", peer_addr);
-    
-    let req_body: PeerConnectionResponseBody =
-        serde_json::from_slice(body).map_err(|e| {
-            println!("[LanTransfer] ❌ 解析响应 JSON 失败: {}", e);
-            ServerError::RequestFailed(e.to_string())
-        })?;
-
-    let connection_id = req_body.connection_id.clone();
-    let now = Utc::now().to_rfc3339();
-    
-    println!("[LanTransfer] 连接 ID: {}", connection_id);
-    println!("[LanTransfer] 接受连接: {}", req_body.accepted);
-    if let Some(ref from_device) = req_body.from_device {
-        println!("[LanTransfer] 响应设备: {} @ {}:{}", 
-            from_device.device_name, from_device.ip_address, from_device.port);
+    #[derive(serde::Deserialize)]
+    struct UploadRequest {
+        files: Vec<web_share::WebUploadEntry>,
     }
 
-    if req_body.accepted {
-        // 接收方接受了连接，创建连接对象
-        if let Some(from_device) = req_body.from_device {
-            let connection = PeerConnection {
-                connection_id: connection_id.clone(),
-                peer_device: DiscoveredDevice {
-                    ip_address: peer_addr.ip().to_string(),
-                    ..from_device
+    let request: UploadRequest = match serde_json::from_slice(body) {
+        Ok(r) => r,
+        Err(_) => return send_error_response(writer, 400, "Bad Request").await,
+    };
+
+    match web_share::create_upload_request(
+        web_token(headers),
+        &peer_addr.ip().to_string(),
+        request.files,
+    ) {
+        Ok(request) => {
+            log::info!(
+                "[LanTransfer] 🌐 网页访客请求发送 {} 个文件: {}",
+                request.files.len(),
+                peer_addr
+            );
+            send_json_response(
+                writer,
+                &WebRequestResponse {
+                    request_id: request.request_id,
+                    files: request.files,
                 },
-                established_at: now,
-                status: PeerConnectionStatus::Connected,
-                is_initiator: true, // 发起方收到此响应
-            };
+            )
+            .await
+        }
+        Err(e) => send_web_share_error(writer, &e).await,
+    }
+}
 
-            // 保存连接
-            {
-                let connections = get_active_peer_connections_map();
-                let mut connections = connections.lock();
-                connections.insert(connection_id.clone(), connection.clone());
-            }
+/// 处理网页访客下载请求（等待本机确认）
+async fn handle_web_download_request(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    body: &[u8],
+    headers: &HashMap<String, String>,
+    peer_addr: SocketAddr,
+) -> Result<(), ServerError> {
+    #[derive(serde::Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct DownloadRequest {
+        file_ids: Vec<String>,
+    }
 
-            // 发送事件通知前端
-            let event = LanTransferEvent::PeerConnectionEstablished { connection };
-            let _ = get_event_sender().send(event.clone());
-            emit_lan_event(&event);
+    let request: DownloadRequest = match serde_json::from_slice(body) {
+        Ok(r) => r,
+        Err(_) => return send_error_response(writer, 400, "Bad Request").await,
+    };
 
-            println!("[LanTransfer] 连接已建立: {}", connection_id);
-        }
-    } else {
-        // 连接被拒绝，从发起方的活跃连接中移除
-        {
-            let connections = get_active_peer_connections_map();
-            let mut connections = connections.lock();
-            connections.remove(&connection_id);
+    match web_share::create_download_request(
+        web_token(headers),
+        &peer_addr.ip().to_string(),
+        request.file_ids,
+    ) {
+        Ok(request) => {
+            log::info!(
+                "[LanTransfer] 🌐 网页访客请求下载 {} 个文件: {}",
+                request.files.len(),
+                peer_addr
+            );
+            send_json_response(
+                writer,
+                &WebRequestResponse {
+                    request_id: request.request_id,
+                    files: request.files,
+                },
+            )
+            .await
         }
+        Err(e) => send_web_share_error(writer, &e).await,
+    }
+}
 
-        // 发送连接关闭事件通知前端
-        let event = LanTransferEvent::PeerConnectionClosed {
-            connection_id: connection_id.clone(),
-        };
-        let _ = get_event_sender().send(event.clone());
-        emit_lan_event(&event);
+/// 处理网页访客请求状态查询
+async fn handle_web_status(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    path: &str,
+    headers: &HashMap<String, String>,
+    peer_addr: SocketAddr,
+) -> Result<(), ServerError> {
+    #[derive(serde::Serialize)]
+    struct StatusResponse {
+        status: TransferRequestStatus,
+        files: Vec<FileMetadata>,
+    }
+
+    let params = parse_query(path);
+    let request_id = params.get("requestId").copied().unwrap_or("");
 
-        println!("[LanTransfer] 连接请求被拒绝: {}，已清理连接记录", connection_id);
+    match web_share::get_request_status(
+        web_token(headers),
+        &peer_addr.ip().to_string(),
+        request_id,
+    ) {
+        Ok((status, files)) => send_json_response(writer, &StatusResponse { status, files }).await,
+        Err(e) => send_web_share_error(writer, &e).await,
     }
+}
 
-    // 返回确认
+/// 处理网页访客上传的文件块
+async fn handle_web_upload(
+    writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    body: &[u8],
+    path: &str,
+    headers: &HashMap<String, String>,
+    peer_addr: SocketAddr,
+) -> Result<(), ServerError> {
     #[derive(serde::Serialize)]
     struct AckResponse {
         success: bool,
     }
 
-    send_json_response(writer, &AckResponse { success: true }).await
+    let params = parse_query(path);
+    let request_id = params.get("requestId").copied().unwrap_or("");
+    let file_id = params.get("fileId").copied().unwrap_or("");
+    let offset: u64 = match params.get("offset").and_then(|s| s.parse().ok()) {
+        Some(offset) => offset,
+        None => return send_error_response(writer, 400, "Bad Request").await,
+    };
+
+    match web_share::write_upload_chunk(
+        web_token(headers),
+        &peer_addr.ip().to_string(),
+        request_id,
+        file_id,
+        offset,
+        body,
+    ) {
+        Ok(_) => send_json_response(writer, &AckResponse { success: true }).await,
+        Err(e) => {
+            log::error!("[LanTransfer] 🌐 网页访客上传失败: {}", e);
+            send_web_share_error(writer, &e).await
+        }
+    }
 }
 
-/// 处理断开连接请求
-async fn handle_peer_disconnect(
+/// 处理网页访客下载文件
+///
+/// 浏览器通过链接直接下载，令牌通过 token 查询参数携带
+async fn handle_web_download(
     writer: &mut tokio::net::tcp::WriteHalf<'_>,
-    body: &[u8],
+    path: &str,
+    peer_addr: SocketAddr,
 ) -> Result<(), ServerError> {
-    let req_body: PeerDisconnectBody =
-        serde_json::from_slice(body).map_err(|e| ServerError::RequestFailed(e.to_string()))?;
-
-    let connection_id = req_body.connection_id.clone();
+    use tokio::io::AsyncWriteExt;
 
-    // 从活跃连接中移除
-    {
-        let connections = get_active_peer_connections_map();
-        let mut connections = connections.lock();
-        connections.remove(&connection_id);
-    }
+    let params = parse_query(path);
+    let request_id = params.get("requestId").copied().unwrap_or("");
+    let file_id = params.get("fileId").copied().unwrap_or("");
+    let token = params.get("token").copied().unwrap_or("");
+
+    let shared = match web_share::get_download_file(
+        token,
+        &peer_addr.ip().to_string(),
+        request_id,
+        file_id,
+    ) {
+        Ok(shared) => shared,
+        Err(e) => return send_web_share_error(writer, &e).await,
+    };
 
-    // 发送事件通知前端
-    let event = LanTransferEvent::PeerConnectionClosed {
-        connection_id: connection_id.clone(),
+    let mut file = match tokio::fs::File::open(&shared.path).await {
+        Ok(file) => file,
+        Err(_) => return send_error_response(writer, 404, "Not Found").await,
     };
-    let _ = get_event_sender().send(event.clone());
-    emit_lan_event(&event);
 
-    println!("[LanTransfer] 连接已断开: {}", connection_id);
+    log::info!(
+        "[LanTransfer] 🌐 网页访客下载: {} -> {}",
+        shared.file_name, peer_addr
+    );
 
-    // 返回确认
-    #[derive(serde::Serialize)]
-    struct AckResponse {
-        success: bool,
-    }
+    let header = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Disposition: attachment; filename*=UTF-8''{}\r\nConnection: close\r\nContent-Length: {}\r\n\r\n",
+        mime_guess::from_path(&shared.path).first_or_octet_stream(),
+        percent_encode(&shared.file_name),
+        shared.file_size
+    );
 
-    send_json_response(writer, &AckResponse { success: true }).await
+    writer
+        .write_all(header.as_bytes())
+        .await
+        .map_err(|e| ServerError::RequestFailed(e.to_string()))?;
+
+    tokio::io::copy(&mut file, writer)
+        .await
+        .map_err(|e| ServerError::RequestFailed(e.to_string()))?;
+
+    Ok(())
+}
+
+/// 百分号编码（RFC 5987，用于 Content-Disposition 文件名）
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
 }
 
 // ============================================================================
@@ -819,11 +1992,9 @@ async fn handle_transfer_request(
 
         send_json_response(writer, &response).await
     } else {
-        // 保存到待处理请求
-        {
-            let requests = get_pending_transfer_requests_map();
-            let mut requests = requests.lock();
-            requests.insert(request_id.clone(), request.clone());
+        // 保存到待处理请求（同一设备的待处理请求数有上限）
+        if !insert_pending_transfer_request(&request) {
+            return send_error_response(writer, 429, "Too Many Requests").await;
         }
 
         // 发送事件通知前端
@@ -890,7 +2061,7 @@ async fn handle_transfer_response(
             let file_paths = session.file_paths.clone();
 
             if !file_paths.is_empty() {
-                println!(
+                log::info!(
                     "[LanTransfer] 传输请求已被接受，开始传输: {} ({} 个文件)",
                     request_id,
                     file_paths.len()
@@ -898,19 +2069,19 @@ async fn handle_transfer_response(
 
                 // 在后台启动批量传输
                 let request_id_clone = request_id.clone();
-                tokio::spawn(async move {
+                service::spawn(async move {
                     if let Err(e) = transfer::start_batch_transfer(&request_id_clone, file_paths).await {
-                        eprintln!("[LanTransfer] 批量传输失败: {}", e);
+                        log::error!("[LanTransfer] 批量传输失败: {}", e);
                     }
                 });
             } else {
-                println!("[LanTransfer] 传输请求已被接受，但没有文件路径: {}", request_id);
+                log::info!("[LanTransfer] 传输请求已被接受，但没有文件路径: {}", request_id);
             }
         } else {
-            println!("[LanTransfer] 传输请求已被接受，但找不到会话: {}", request_id);
+            log::info!("[LanTransfer] 传输请求已被接受，但找不到会话: {}", request_id);
         }
     } else {
-        println!(
+        log::info!(
             "[LanTransfer] 传输请求被拒绝: {} ({})",
             request_id,
             req_body.reject_reason.as_deref().unwrap_or("无原因")
@@ -926,10 +2097,31 @@ async fn handle_transfer_response(
     send_json_response(writer, &AckResponse { success: true }).await
 }
 
+/// 根据来源 IP 描述对端设备（未发现的设备返回 IP）
+///
+/// with_ip 为 true 时已发现的设备显示为 "设备名 (IP)"，否则只返回设备名（按发送方归类使用）
+fn describe_peer(peer_addr: SocketAddr, with_ip: bool) -> String {
+    let ip = peer_addr.ip().to_string();
+    get_lan_transfer_state()
+        .devices
+        .read()
+        .values()
+        .find(|d| d.ip_address == ip)
+        .map(|d| {
+            if with_ip {
+                format!("{} ({})", d.device_name, ip)
+            } else {
+                d.device_name.clone()
+            }
+        })
+        .unwrap_or(ip)
+}
+
 /// 处理准备上传请求（支持断点续传）
 async fn handle_prepare_upload(
     writer: &mut tokio::net::tcp::WriteHalf<'_>,
     body: &[u8],
+    #[cfg_attr(target_os = "android", allow(unused_variables))] peer_addr: SocketAddr,
 ) -> Result<(), ServerError> {
     // 解析请求
     let request: PrepareUploadRequest = serde_json::from_slice(body)
@@ -954,7 +2146,7 @@ async fn handle_prepare_upload(
             Ok(Some(offset)) => offset,
             Ok(None) => 0,
             Err(e) => {
-                println!("[LanTransfer] 检查续传状态失败: {}", e);
+                log::error!("[LanTransfer] 检查续传状态失败: {}", e);
                 0
             }
         }
@@ -999,7 +2191,7 @@ async fn handle_prepare_upload(
         f.seek(SeekFrom::Start(resume_offset))
             .map_err(|e| ServerError::FileWriteFailed(e.to_string()))?;
 
-        println!(
+        log::info!(
             "[LanTransfer] 断点续传: {} 从 {} 字节继续",
             file.file_name, resume_offset
         );
@@ -1011,7 +2203,7 @@ async fn handle_prepare_upload(
         #[cfg(target_os = "android")]
         {
             // 获取最终保存路径
-            let final_path = config::get_file_save_path(&file.file_name);
+            let final_path = config::get_file_save_path(&file.target_relative_path());
 
             // 确保目标目录存在
             if let Some(parent) = final_path.parent() {
@@ -1023,7 +2215,7 @@ async fn handle_prepare_upload(
                 .map_err(|e| ServerError::FileWriteFailed(format!("创建目标文件失败: {}", e)))?;
             let hasher = Crc32Hasher::new();
 
-            println!(
+            log::info!(
                 "[LanTransfer] 新传输 (Android 直接写入): {} -> {:?} (大小: {} 字节)",
                 file.file_name, final_path, file.file_size
             );
@@ -1039,46 +2231,48 @@ async fn handle_prepare_upload(
                 .map_err(|e| ServerError::FileWriteFailed(e.to_string()))?;
             let hasher = Crc32Hasher::new();
 
-            println!("[LanTransfer] 新传输 (临时文件): {} (大小: {} 字节)", file.file_name, file.file_size);
+            // 记录来源设备，便于清理未完成接收时展示
+            let _ = resume_manager.register_transfer(
+                file_id,
+                &file.sha256,
+                &file.file_name,
+                file.file_size,
+                &describe_peer(peer_addr, true),
+            );
+
+            log::info!("[LanTransfer] 新传输 (临时文件): {} (大小: {} 字节)", file.file_name, file.file_size);
 
             (f, hasher, None)
         }
     };
 
-    // 创建上传会话
-    let mut files = HashMap::new();
-    let mut writers = HashMap::new();
-    let mut hashers = HashMap::new();
-    let mut received_bytes = HashMap::new();
-    let mut target_paths = HashMap::new();
-
-    files.insert(file_id.clone(), file.clone());
-    writers.insert(file_id.clone(), writer_file);
-    hashers.insert(file_id.clone(), hasher);
-    received_bytes.insert(file_id.clone(), resume_offset);
-
-    // 保存目标路径（Android 直接写入模式）
-    if let Some(ref target_path) = direct_target_path {
-        target_paths.insert(file_id.clone(), target_path.clone());
-    }
-
-    let session = UploadSession {
-        session_id: request.session_id.clone(),
-        files,
-        writers,
-        hashers,
-        received_bytes,
-        last_progress_time: std::time::Instant::now(),
-        start_time: std::time::Instant::now(),
-        resume_offset,
-        target_paths,
-    };
-
-    // 保存会话
+    // 创建或加入上传会话（并行发送时同一会话的多个文件分别 prepare-upload，不能互相覆盖）
     let sessions = get_upload_sessions();
     {
         let mut sessions = sessions.lock();
-        sessions.insert(request.session_id.clone(), session);
+        let session = sessions
+            .entry(request.session_id.clone())
+            .or_insert_with(|| UploadSession {
+                session_id: request.session_id.clone(),
+                files: HashMap::new(),
+                writers: HashMap::new(),
+                hashers: HashMap::new(),
+                received_bytes: HashMap::new(),
+                last_progress_time: std::time::Instant::now(),
+                start_time: std::time::Instant::now(),
+                resume_offset,
+                target_paths: HashMap::new(),
+            });
+
+        session.files.insert(file_id.clone(), file.clone());
+        session.writers.insert(file_id.clone(), writer_file);
+        session.hashers.insert(file_id.clone(), hasher);
+        session.received_bytes.insert(file_id.clone(), resume_offset);
+
+        // 保存目标路径（Android 直接写入模式）
+        if let Some(ref target_path) = direct_target_path {
+            session.target_paths.insert(file_id.clone(), target_path.clone());
+        }
     }
 
     // 发送初始进度事件（让用户知道传输已开始）
@@ -1233,9 +2427,14 @@ async fn handle_upload(
 }
 
 /// 处理上传完成
+///
+/// 会话 ID 和文件 ID 可通过查询参数或 JSON 请求体（FinishUploadRequest）传递，
+/// 并行发送使用请求体，单文件发送使用查询参数
 async fn handle_finish(
     writer: &mut tokio::net::tcp::WriteHalf<'_>,
+    body: &[u8],
     path: &str,
+    peer_addr: SocketAddr,
 ) -> Result<(), ServerError> {
     // 解析查询参数
     let query = path.split('?').nth(1).unwrap_or("");
@@ -1244,8 +2443,14 @@ async fn handle_finish(
         .filter_map(|s| s.split_once('='))
         .collect();
 
-    let session_id = params.get("sessionId").unwrap_or(&"").to_string();
-    let file_id = params.get("fileId").unwrap_or(&"").to_string();
+    let (session_id, file_id) = match (params.get("sessionId"), params.get("fileId")) {
+        (Some(session_id), Some(file_id)) => (session_id.to_string(), file_id.to_string()),
+        _ => {
+            let request: FinishUploadRequest = serde_json::from_slice(body)
+                .map_err(|e| ServerError::RequestFailed(e.to_string()))?;
+            (request.session_id, request.file_id)
+        }
+    };
 
     // 在锁的作用域内完成所有同步操作
     let (file_meta, computed_hash, hash_match, target_path) = {
@@ -1289,7 +2494,7 @@ async fn handle_finish(
         // 检查是否使用了直接写入模式（有 target_path）
         if let Some(ref direct_path) = target_path {
             // 直接写入模式：文件已在目标位置，无需移动
-            println!(
+            log::info!(
                 "[LanTransfer] ✅ 接收完成 (直接写入): {} -> {}",
                 file_meta.file_name, direct_path
             );
@@ -1306,7 +2511,7 @@ async fn handle_finish(
             (response, direct_path.clone())
         } else {
             // 临时文件模式：移动文件到最终位置
-            match resume_manager.finalize_transfer(&file_id, &file_meta.file_name) {
+            match resume_manager.finalize_transfer(&file_id, &file_meta.target_relative_path()) {
                 Ok(final_path) => {
                     let saved_path_str = final_path.to_string_lossy().to_string();
                     let response = FinishUploadResponse {
@@ -1330,7 +2535,7 @@ async fn handle_finish(
         }
     } else {
         // 哈希不匹配
-        println!(
+        log::error!(
             "[LanTransfer] 文件校验失败: {} (期望: {}, 实际: {})",
             file_meta.file_name, file_meta.sha256, computed_hash
         );
@@ -1357,6 +2562,17 @@ async fn handle_finish(
         let _ = get_event_sender().send(event.clone());
         emit_lan_event(&event);
 
+        // 恢复发送方的时间戳和权限位（需在接收后处理移动文件之前）
+        apply_file_attributes(Path::new(&saved_path_str), &file_meta);
+
+        // 接收后处理（归类、解压、执行命令）
+        post_receive::spawn_post_receive(
+            file_id.clone(),
+            PathBuf::from(&saved_path_str),
+            &file_meta,
+            describe_peer(peer_addr, false),
+        );
+
         // 发送批量传输完成事件（清除前端进度显示）
         let batch_event = LanTransferEvent::BatchTransferCompleted {
             session_id: session_id.clone(),
@@ -1366,7 +2582,7 @@ async fn handle_finish(
         let _ = get_event_sender().send(batch_event.clone());
         emit_lan_event(&batch_event);
 
-        println!(
+        log::info!(
             "[LanTransfer] ✅ 接收完成: {} (会话: {})",
             file_meta.file_name, session_id
         );
@@ -1382,6 +2598,48 @@ async fn handle_finish(
     send_json_response(writer, &response).await
 }
 
+/// 恢复文件的修改时间、创建时间和权限位（发送方未提供的属性保持不变）
+fn apply_file_attributes(path: &Path, file_meta: &FileMetadata) {
+    let to_time = |millis: i64| UNIX_EPOCH + Duration::from_millis(millis.max(0) as u64);
+
+    if file_meta.modified_at.is_some() || file_meta.created_at.is_some() {
+        let mut times = std::fs::FileTimes::new();
+        if let Some(modified_at) = file_meta.modified_at {
+            times = times.set_modified(to_time(modified_at));
+        }
+
+        // 创建时间只有 Windows 和 macOS 支持设置
+        #[cfg(windows)]
+        if let Some(created_at) = file_meta.created_at {
+            use std::os::windows::fs::FileTimesExt;
+            times = times.set_created(to_time(created_at));
+        }
+        #[cfg(target_os = "macos")]
+        if let Some(created_at) = file_meta.created_at {
+            use std::os::macos::fs::FileTimesExt;
+            times = times.set_created(to_time(created_at));
+        }
+
+        if let Err(e) = std::fs::File::options()
+            .write(true)
+            .open(path)
+            .and_then(|f| f.set_times(times))
+        {
+            log::error!("[LanTransfer] 设置文件时间失败 {:?}: {}", path, e);
+        }
+    }
+
+    // 权限位只在 Unix 上恢复（只保留 rwx 位，忽略 setuid/setgid/sticky）
+    #[cfg(unix)]
+    if let Some(mode) = file_meta.mode {
+        use std::os::unix::fs::PermissionsExt;
+        let permissions = std::fs::Permissions::from_mode(mode & 0o777);
+        if let Err(e) = std::fs::set_permissions(path, permissions) {
+            log::error!("[LanTransfer] 设置文件权限失败 {:?}: {}", path, e);
+        }
+    }
+}
+
 /// 取消传输请求体
 #[derive(serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -1416,7 +2674,7 @@ async fn handle_cancel(
                     let _ = resume_manager.clear_resume_info(file_id);
                 }
 
-                println!("[LanTransfer] 取消文件传输: {}", file_id);
+                log::info!("[LanTransfer] 取消文件传输: {}", file_id);
             } else {
                 // 取消整个会话
                 let file_ids: Vec<String> = session.files.keys().cloned().collect();
@@ -1427,7 +2685,7 @@ async fn handle_cancel(
                 }
                 sessions.remove(&request.session_id);
 
-                println!("[LanTransfer] 取消传输会话: {}", request.session_id);
+                log::info!("[LanTransfer] 取消传输会话: {}", request.session_id);
             }
         }
     } // 锁在这里释放
@@ -1439,3 +2697,87 @@ async fn handle_cancel(
 
     send_json_response(writer, &CancelResponse { success: true }).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lan_transfer::service::{LanTransferService, ServiceOptions};
+
+    fn request_from(device_id: &str, ip: &str) -> TransferRequest {
+        let now = Utc::now().to_rfc3339();
+        TransferRequest {
+            request_id: Uuid::new_v4().to_string(),
+            from_device: DiscoveredDevice {
+                device_id: device_id.to_string(),
+                device_name: device_id.to_string(),
+                user_id: String::new(),
+                user_nickname: String::new(),
+                ip_address: ip.to_string(),
+                port: 0,
+                discovered_at: now.clone(),
+                last_seen: now.clone(),
+            },
+            files: Vec::new(),
+            total_size: 0,
+            requested_at: now,
+            status: TransferRequestStatus::Pending,
+        }
+    }
+
+    #[test]
+    fn pending_requests_are_capped_per_device_and_per_ip() {
+        let service = LanTransferService::new(ServiceOptions {
+            enable_mdns: false,
+            ..Default::default()
+        });
+
+        service.enter(|| {
+            for _ in 0..MAX_PENDING_REQUESTS_PER_DEVICE {
+                assert!(insert_pending_transfer_request(&request_from("device-a", "10.0.0.1")));
+            }
+            assert!(!insert_pending_transfer_request(&request_from("device-a", "10.0.0.9")));
+
+            // 同一 IP 更换设备 ID 也受 IP 上限限制
+            let mut index = 0;
+            while get_pending_transfer_requests_map().lock().len() < MAX_PENDING_REQUESTS_PER_IP {
+                index += 1;
+                let device_id = format!("device-{}", index);
+                assert!(insert_pending_transfer_request(&request_from(&device_id, "10.0.0.1")));
+            }
+            assert!(!insert_pending_transfer_request(&request_from("device-new", "10.0.0.1")));
+
+            // 其他 IP 不受影响
+            assert!(insert_pending_transfer_request(&request_from("device-new", "10.0.0.2")));
+        });
+    }
+
+    #[test]
+    fn probes_require_known_peer_and_are_rate_limited_per_ip() {
+        let service = LanTransferService::new(ServiceOptions {
+            enable_mdns: false,
+            ..Default::default()
+        });
+
+        service.enter(|| {
+            let known: IpAddr = "10.0.0.1".parse().unwrap();
+            let other: IpAddr = "10.0.0.2".parse().unwrap();
+
+            assert!(!is_known_peer_ip(known));
+            let device = request_from("device-a", "10.0.0.1").from_device;
+            get_lan_transfer_state()
+                .devices
+                .write()
+                .insert(device.device_id.clone(), device);
+            assert!(is_known_peer_ip(known));
+            assert!(!is_known_peer_ip(other));
+
+            for _ in 0..MAX_PROBES_PER_WINDOW {
+                assert!(allow_probe(known));
+            }
+            assert!(!allow_probe(known));
+
+            // 其他 IP 单独计数
+            assert!(allow_probe(other));
+        });
+    }
+}
@@ -0,0 +1,352 @@
+/*!
+ * 局域网传输服务对象
+ *
+ * 原先各子模块的状态分散在全局 OnceCell 单例中，事件直接通过全局 APP_HANDLE 发送，
+ * 同一进程内无法运行两个实例，也无法脱离 Tauri 使用
+ *
+ * 功能：
+ * - LanTransferService：持有一个实例的全部运行状态（设备列表、配置、上传会话、
+ *   待处理请求、点对点连接、发送会话、心跳、事件日志、传输统计、聊天投递、离线队列、
+ *   文本片段、文件夹同步、网页分享）和端口
+ * - LanEventSink：可插拔的事件输出（Tauri 窗口、测试收集器等），一个实例可挂多个
+ * - 当前实例：子模块通过 current() 获取所在实例，原有的 get_xxx() 函数签名不变
+ *
+ * 实例上下文：
+ * - LanTransferService::scope 在指定实例的上下文中运行 Future（tokio task-local）
+ * - 子模块使用 service::spawn / service::spawn_blocking 启动后台任务，自动继承当前实例
+ * - 不在任何实例上下文中时使用默认实例（default_service，Tauri 命令使用）；
+ *   进程内存在其他实例时这种回退通常意味着漏用了 service::spawn，会按调用位置记录一次警告
+ *
+ * 说明：
+ * - 聊天消息本身保存在当前登录用户的数据库中（进程级），投递状态属于实例
+ * - ServiceOptions 可指定端口（0 表示系统分配）、对外 IP、数据目录和是否启用 mDNS，
+ *   用于回环测试时在同一进程内启动两个实例
+ */
+
+use super::chat::ChatState;
+use super::config::{self, ConfigManager};
+use super::discovery::{self, DiscoveryError, DiscoveryState};
+use super::heartbeat::HeartbeatState;
+use super::journal::Journal;
+use super::protocol::{LanTransferEvent, SERVICE_PORT};
+use super::send_queue::SendQueueState;
+use super::server::ServerState;
+use super::snippets::SnippetState;
+use super::stats::StatsStore;
+use super::sync::SyncState;
+use super::transfer::TransferState;
+use super::web_share::WebShareSlot;
+use super::LanTransferState;
+use once_cell::sync::OnceCell;
+use parking_lot::{Mutex, RwLock};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::future::Future;
+use std::net::IpAddr;
+use std::panic::Location;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU16, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tauri::Emitter;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+// ============================================================================
+// 事件输出
+// ============================================================================
+
+/// 事件输出（实例产生的每个事件都会发送给所有已挂载的输出）
+pub trait LanEventSink: Send + Sync {
+    fn emit(&self, event: &LanTransferEvent);
+}
+
+/// 发送到 Tauri 前端（lan-transfer-event）
+pub struct TauriEventSink {
+    handle: tauri::AppHandle,
+}
+
+impl TauriEventSink {
+    pub fn new(handle: tauri::AppHandle) -> Self {
+        Self { handle }
+    }
+}
+
+impl LanEventSink for TauriEventSink {
+    fn emit(&self, event: &LanTransferEvent) {
+        if let Err(e) = self.handle.emit("lan-transfer-event", event) {
+            log::error!("[LanTransfer] 发送事件失败: {}", e);
+        }
+    }
+}
+
+// ============================================================================
+// 服务实例
+// ============================================================================
+
+/// 实例选项
+#[derive(Debug, Clone)]
+pub struct ServiceOptions {
+    /// HTTP 端口（0 表示由系统分配）
+    pub port: u16,
+    /// 对外公布的 IP（为空时自动检测本机局域网 IP）
+    pub advertise_ip: Option<IpAddr>,
+    /// 数据目录（配置文件、设备 ID 等，为空时使用默认目录）
+    pub data_directory: Option<PathBuf>,
+    /// 是否启用 mDNS 广播与发现（关闭时设备需手动加入设备列表）
+    pub enable_mdns: bool,
+}
+
+impl Default for ServiceOptions {
+    fn default() -> Self {
+        Self {
+            port: SERVICE_PORT,
+            advertise_ip: None,
+            data_directory: None,
+            enable_mdns: true,
+        }
+    }
+}
+
+/// 局域网传输服务实例
+pub struct LanTransferService {
+    options: ServiceOptions,
+    /// 实际监听的端口（服务启动后确定）
+    bound_port: AtomicU16,
+    state: Arc<LanTransferState>,
+    config: OnceCell<Arc<RwLock<ConfigManager>>>,
+    stats: OnceCell<Arc<Mutex<StatsStore>>>,
+    sinks: RwLock<Vec<Arc<dyn LanEventSink>>>,
+    events: broadcast::Sender<LanTransferEvent>,
+    pub(crate) journal: Arc<Mutex<Journal>>,
+    pub(crate) discovery: DiscoveryState,
+    pub(crate) server: ServerState,
+    pub(crate) transfers: TransferState,
+    pub(crate) heartbeat: HeartbeatState,
+    pub(crate) chat: ChatState,
+    pub(crate) send_queue: SendQueueState,
+    pub(crate) snippets: SnippetState,
+    pub(crate) sync: SyncState,
+    pub(crate) web_share: WebShareSlot,
+    pub(crate) request_expiry_running: Arc<AtomicBool>,
+}
+
+impl LanTransferService {
+    /// 创建实例（不启动服务）
+    pub fn new(options: ServiceOptions) -> Arc<Self> {
+        let (events, _) = broadcast::channel(100);
+        LIVE_INSTANCES.fetch_add(1, Ordering::SeqCst);
+        Arc::new(Self {
+            bound_port: AtomicU16::new(options.port),
+            options,
+            state: Arc::new(LanTransferState::new()),
+            config: OnceCell::new(),
+            stats: OnceCell::new(),
+            sinks: RwLock::new(Vec::new()),
+            events,
+            journal: Arc::new(Mutex::new(Journal::new())),
+            discovery: DiscoveryState::default(),
+            server: ServerState::default(),
+            transfers: TransferState::default(),
+            heartbeat: HeartbeatState::default(),
+            chat: ChatState::default(),
+            send_queue: SendQueueState::default(),
+            snippets: SnippetState::default(),
+            sync: SyncState::default(),
+            web_share: WebShareSlot::default(),
+            request_expiry_running: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// 实例选项
+    pub fn options(&self) -> &ServiceOptions {
+        &self.options
+    }
+
+    /// 监听端口（端口为 0 时，服务启动后为系统分配的端口）
+    pub fn port(&self) -> u16 {
+        self.bound_port.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn set_bound_port(&self, port: u16) {
+        self.bound_port.store(port, Ordering::SeqCst);
+    }
+
+    /// 运行状态（设备列表、本机信息等）
+    pub fn state(&self) -> Arc<LanTransferState> {
+        self.state.clone()
+    }
+
+    /// 配置（首次访问时从数据目录加载）
+    pub fn config(&self) -> Arc<RwLock<ConfigManager>> {
+        self.config
+            .get_or_init(|| Arc::new(RwLock::new(ConfigManager::load(&self.data_directory()))))
+            .clone()
+    }
+
+    /// 传输统计（首次访问时从数据目录加载）
+    pub(crate) fn stats(&self) -> Arc<Mutex<StatsStore>> {
+        self.stats
+            .get_or_init(|| Arc::new(Mutex::new(StatsStore::load(&self.data_directory()))))
+            .clone()
+    }
+
+    /// 数据目录
+    pub fn data_directory(&self) -> PathBuf {
+        self.options
+            .data_directory
+            .clone()
+            .unwrap_or_else(config::default_base_directory)
+    }
+
+    /// 挂载事件输出
+    pub fn add_event_sink(&self, sink: Arc<dyn LanEventSink>) {
+        self.sinks.write().push(sink);
+    }
+
+    /// 事件广播通道（子模块内部通知使用）
+    pub fn event_sender(&self) -> broadcast::Sender<LanTransferEvent> {
+        self.events.clone()
+    }
+
+    /// 订阅事件广播
+    pub fn subscribe(&self) -> broadcast::Receiver<LanTransferEvent> {
+        self.events.subscribe()
+    }
+
+    /// 输出事件（记录到事件日志并发送给所有事件输出）
+    pub fn emit(&self, event: &LanTransferEvent) {
+        self.journal.lock().record(event);
+
+        let sinks = self.sinks.read().clone();
+        for sink in sinks {
+            sink.emit(event);
+        }
+    }
+
+    /// 在本实例的上下文中运行 Future
+    pub async fn scope<F: Future>(self: &Arc<Self>, future: F) -> F::Output {
+        CURRENT_SERVICE.scope(self.clone(), future).await
+    }
+
+    /// 在本实例的上下文中运行同步代码（读取会话、设备列表等）
+    pub fn enter<R>(self: &Arc<Self>, f: impl FnOnce() -> R) -> R {
+        CURRENT_SERVICE.sync_scope(self.clone(), f)
+    }
+
+    /// 启动服务（HTTP 服务器、mDNS、后台任务）
+    pub async fn start(
+        self: &Arc<Self>,
+        user_id: String,
+        user_nickname: String,
+        device_name: Option<String>,
+    ) -> Result<(), DiscoveryError> {
+        self.scope(discovery::start_service(user_id, user_nickname, device_name))
+            .await
+    }
+
+    /// 停止服务
+    pub async fn stop(self: &Arc<Self>) -> Result<(), DiscoveryError> {
+        self.scope(discovery::stop_service()).await
+    }
+}
+
+impl Drop for LanTransferService {
+    fn drop(&mut self) {
+        LIVE_INSTANCES.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// ============================================================================
+// 当前实例
+// ============================================================================
+
+tokio::task_local! {
+    static CURRENT_SERVICE: Arc<LanTransferService>;
+}
+
+thread_local! {
+    /// spawn_blocking 线程上的当前实例（task-local 不会传递到阻塞线程）
+    static BLOCKING_SERVICE: RefCell<Option<Arc<LanTransferService>>> = const { RefCell::new(None) };
+}
+
+/// 默认实例（Tauri 命令和命令行工具使用）
+static DEFAULT_SERVICE: OnceCell<Arc<LanTransferService>> = OnceCell::new();
+
+/// 获取默认实例
+pub fn default_service() -> Arc<LanTransferService> {
+    DEFAULT_SERVICE
+        .get_or_init(|| LanTransferService::new(ServiceOptions::default()))
+        .clone()
+}
+
+/// 获取当前实例（不在任何实例上下文中时返回默认实例）
+///
+/// 进程内只有默认实例时（桌面应用、命令行工具）回退是正常的；存在其他实例时，
+/// 回退通常是后台任务漏用了 service::spawn / spawn_blocking，按调用位置记录一次警告
+#[track_caller]
+pub fn current() -> Arc<LanTransferService> {
+    CURRENT_SERVICE
+        .try_with(Arc::clone)
+        .ok()
+        .or_else(|| BLOCKING_SERVICE.with(|s| s.borrow().clone()))
+        .unwrap_or_else(|| {
+            warn_default_fallback(Location::caller());
+            default_service()
+        })
+}
+
+/// 存活的实例数（含默认实例）
+static LIVE_INSTANCES: AtomicUsize = AtomicUsize::new(0);
+
+/// 已记录过回退警告的调用位置
+static FALLBACK_WARNED: Mutex<Option<HashSet<&'static Location<'static>>>> = Mutex::new(None);
+
+/// 存在非默认实例时，记录回退到默认实例的调用位置（每个位置一次）
+fn warn_default_fallback(location: &'static Location<'static>) {
+    let default_instances = usize::from(DEFAULT_SERVICE.get().is_some());
+    if LIVE_INSTANCES.load(Ordering::SeqCst) <= default_instances {
+        return;
+    }
+
+    if FALLBACK_WARNED
+        .lock()
+        .get_or_insert_with(HashSet::new)
+        .insert(location)
+    {
+        log::warn!(
+            "[LanTransfer] ⚠️ {} 不在任何实例上下文中，已回退到默认实例（后台任务应使用 service::spawn）",
+            location
+        );
+    }
+}
+
+/// 启动后台任务（继承当前实例）
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    tokio::spawn(CURRENT_SERVICE.scope(current(), future))
+}
+
+/// 在阻塞线程池中运行（继承当前实例）
+pub fn spawn_blocking<F, R>(f: F) -> JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let service = current();
+    tokio::task::spawn_blocking(move || {
+        /// 离开时清除线程上的实例（线程会被复用）
+        struct Reset;
+        impl Drop for Reset {
+            fn drop(&mut self) {
+                BLOCKING_SERVICE.with(|s| *s.borrow_mut() = None);
+            }
+        }
+
+        BLOCKING_SERVICE.with(|s| *s.borrow_mut() = Some(service));
+        let _reset = Reset;
+        f()
+    })
+}
@@ -0,0 +1,566 @@
+/*!
+ * 共享文件夹模块
+ *
+ * 设备发布命名的共享文件夹，已连接的对端可以浏览目录并拉取指定文件或文件夹
+ *
+ * 权限：
+ * - 共享文件夹保存在配置中（config::SharedFolder）
+ * - 只有已信任的设备可以访问，每个共享可进一步限定允许的设备
+ * - 请求必须来自已建立的点对点连接，且来源 IP 与连接的对端一致
+ *
+ * 拉取流程：
+ * 1. 请求方调用 pull_from_peer，POST /api/shares/pull
+ * 2. 持有方校验权限、展开文件夹后立即回复会话 ID 和文件数
+ * 3. 持有方在后台计算哈希并通过 transfer::send_tree_in_session 向请求方发送
+ *    （携带相对路径，请求方按原目录结构保存）
+ * 4. 传输使用与普通发送相同的分块、断点续传和进度机制；请求方按已连接设备自动接收
+ *
+ * 路径安全：
+ * - 相对路径不允许包含 `..` 或绝对路径
+ * - 解析后的真实路径必须位于共享根目录内（防止符号链接逃逸）
+ *
+ * 更新日志：
+ * - 2026-10-18: 拉取请求不再等待哈希计算，校验通过后立即回复，发送在后台进行
+ */
+
+use super::config::{self, SharedFolder};
+use super::discovery::get_event_sender;
+use super::protocol::*;
+use super::server::get_active_peer_connections_map;
+use super::service;
+use super::transfer;
+use super::{emit_lan_event, get_lan_transfer_state};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+use uuid::Uuid;
+
+// ============================================================================
+// 错误类型
+// ============================================================================
+
+#[derive(Error, Debug)]
+pub enum ShareError {
+    #[error("连接不存在或已断开")]
+    NotConnected,
+    #[error("无权访问该共享")]
+    Forbidden,
+    #[error("共享不存在: {0}")]
+    ShareNotFound(String),
+    #[error("路径无效: {0}")]
+    InvalidPath(String),
+    #[error("文件数量超过上限 ({0})")]
+    TooManyFiles(usize),
+    #[error("读取目录失败: {0}")]
+    Io(String),
+    #[error("请求失败: {0}")]
+    RequestFailed(String),
+    #[error("传输失败: {0}")]
+    Transfer(String),
+}
+
+// ============================================================================
+// 请求体
+// ============================================================================
+
+/// 浏览共享请求（POST /api/shares）
+///
+/// `share_id` 为空时返回共享列表，否则返回 `path` 目录下的条目
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BrowseSharesRequest {
+    pub connection_id: String,
+    pub device_id: String,
+    #[serde(default)]
+    pub share_id: Option<String>,
+    #[serde(default)]
+    pub path: String,
+}
+
+/// 拉取请求（POST /api/shares/pull）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PullRequest {
+    pub connection_id: String,
+    pub device_id: String,
+    pub share_id: String,
+    /// 要拉取的相对路径（文件或文件夹）
+    pub paths: Vec<String>,
+}
+
+/// 浏览共享响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BrowseSharesResponse {
+    #[serde(default)]
+    pub shares: Vec<SharedFolderInfo>,
+    #[serde(default)]
+    pub entries: Vec<ShareEntry>,
+}
+
+/// 拉取响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PullResponse {
+    /// 持有方的传输会话 ID
+    pub session_id: String,
+    /// 将要发送的文件数
+    pub file_count: u32,
+}
+
+// ============================================================================
+// 持有方：处理对端请求（由 server 调用）
+// ============================================================================
+
+/// 校验请求来自已建立的连接
+pub fn authorize_peer(
+    connection_id: &str,
+    device_id: &str,
+    peer_ip: &str,
+) -> Result<(), ShareError> {
+    let connections = get_active_peer_connections_map();
+    let connections = connections.lock();
+
+    let authorized = connections.get(connection_id).is_some_and(|conn| {
+        conn.status == PeerConnectionStatus::Connected
+            && conn.peer_device.device_id == device_id
+            && conn.peer_device.ip_address == peer_ip
+    });
+
+    if authorized {
+        Ok(())
+    } else {
+        Err(ShareError::NotConnected)
+    }
+}
+
+/// 浏览共享（共享列表或目录条目）
+pub fn browse(request: &BrowseSharesRequest) -> Result<BrowseSharesResponse, ShareError> {
+    let Some(share_id) = request.share_id.as_deref() else {
+        let shares = config::get_accessible_shared_folders(&request.device_id)
+            .into_iter()
+            .map(|f| SharedFolderInfo {
+                share_id: f.share_id,
+                name: f.name,
+            })
+            .collect();
+
+        return Ok(BrowseSharesResponse {
+            shares,
+            entries: Vec::new(),
+        });
+    };
+
+    let share = get_accessible_share(&request.device_id, share_id)?;
+    let dir = resolve_in_share(&share, &request.path)?;
+    if !dir.is_dir() {
+        return Err(ShareError::InvalidPath(request.path.clone()));
+    }
+
+    let read_dir = std::fs::read_dir(&dir).map_err(|e| ShareError::Io(e.to_string()))?;
+    let base = request.path.trim_matches('/');
+
+    let mut entries: Vec<ShareEntry> = read_dir
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let relative_path = if base.is_empty() {
+                name.clone()
+            } else {
+                format!("{}/{}", base, name)
+            };
+
+            Some(ShareEntry {
+                name,
+                relative_path,
+                is_dir: metadata.is_dir(),
+                size: if metadata.is_dir() { 0 } else { metadata.len() },
+                modified_at: metadata
+                    .modified()
+                    .ok()
+                    .map(|t| DateTime::<Utc>::from(t).to_rfc3339()),
+            })
+        })
+        .collect();
+
+    // 目录在前，按名称排序
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+
+    Ok(BrowseSharesResponse {
+        shares: Vec::new(),
+        entries,
+    })
+}
+
+/// 处理拉取请求：校验权限并展开文件夹后立即回复，文件在后台发送
+///
+/// 哈希计算和会话建立在后台任务中进行，失败时只记录日志（请求方不会收到传输）
+pub async fn serve_pull(request: &PullRequest) -> Result<PullResponse, ShareError> {
+    let share = get_accessible_share(&request.device_id, &request.share_id)?;
+
+    let (file_paths, relative_paths) = {
+        let share = share.clone();
+        let paths = request.paths.clone();
+        service::spawn_blocking(move || expand_pull_paths(&share, &paths))
+            .await
+            .map_err(|e| ShareError::Io(e.to_string()))??
+    };
+
+    let file_count = file_paths.len() as u32;
+    let session_id = Uuid::new_v4().to_string();
+
+    let connection_id = request.connection_id.clone();
+    let pull_session_id = session_id.clone();
+    service::spawn(async move {
+        let result = transfer::send_tree_in_session(
+            &pull_session_id,
+            &connection_id,
+            file_paths,
+            relative_paths,
+        )
+        .await;
+
+        if let Err(e) = result {
+            log::error!("[LanTransfer] 共享 {} 发送失败: {}", share.name, e);
+            return;
+        }
+
+        log::info!(
+            "[LanTransfer] 📂 对端拉取共享 {}: {} 个文件 (会话: {})",
+            share.name, file_count, pull_session_id
+        );
+
+        let event = LanTransferEvent::SharePullStarted {
+            connection_id,
+            share_id: share.share_id.clone(),
+            session_id: pull_session_id,
+            file_count,
+        };
+        let _ = get_event_sender().send(event.clone());
+        emit_lan_event(&event);
+    });
+
+    Ok(PullResponse {
+        session_id,
+        file_count,
+    })
+}
+
+/// 展开拉取的路径，返回 (文件路径, 以所选条目为根的相对路径)
+fn expand_pull_paths(
+    share: &SharedFolder,
+    paths: &[String],
+) -> Result<(Vec<String>, Vec<String>), ShareError> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    let mut relative_paths: Vec<String> = Vec::new();
+    for path in paths {
+        let resolved = resolve_in_share(share, path)?;
+        let start = files.len();
+        collect_files(&resolved, &mut files)?;
+        if files.len() > MAX_SHARE_PULL_FILES {
+            return Err(ShareError::TooManyFiles(MAX_SHARE_PULL_FILES));
+        }
+
+        // 相对路径以所选条目为根（拉取文件夹时保留文件夹名）
+        let base = resolved.parent().unwrap_or(&resolved);
+        relative_paths.extend(files[start..].iter().map(|file| {
+            file.strip_prefix(base)
+                .unwrap_or(file)
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/")
+        }));
+    }
+
+    if files.is_empty() {
+        return Err(ShareError::InvalidPath("没有可发送的文件".to_string()));
+    }
+
+    let file_paths = files
+        .into_iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+
+    Ok((file_paths, relative_paths))
+}
+
+// ============================================================================
+// 请求方：浏览和拉取对端共享
+// ============================================================================
+
+/// 获取对端的共享文件夹列表
+pub async fn browse_peer_shares(connection_id: &str) -> Result<Vec<SharedFolderInfo>, ShareError> {
+    Ok(browse_peer(connection_id, None, String::new()).await?.shares)
+}
+
+/// 浏览对端共享文件夹中的目录
+pub async fn browse_peer_share(
+    connection_id: &str,
+    share_id: &str,
+    path: String,
+) -> Result<Vec<ShareEntry>, ShareError> {
+    Ok(browse_peer(connection_id, Some(share_id.to_string()), path)
+        .await?
+        .entries)
+}
+
+/// 从对端共享文件夹拉取文件或文件夹
+///
+/// 返回对端的传输会话 ID；文件通过普通传输流程接收（TransferProgress 等事件）
+pub async fn pull_from_peer(
+    connection_id: &str,
+    share_id: &str,
+    paths: Vec<String>,
+) -> Result<PullResponse, ShareError> {
+    let (peer, my_device_id) = get_connection_target(connection_id)?;
+
+    let url = format!(
+        "http://{}:{}/api/shares/pull",
+        peer.ip_address, peer.port
+    );
+
+    let request = PullRequest {
+        connection_id: connection_id.to_string(),
+        device_id: my_device_id,
+        share_id: share_id.to_string(),
+        paths,
+    };
+
+    let response = reqwest::Client::new()
+        .post(&url)
+        .json(&request)
+        .timeout(std::time::Duration::from_secs(30))
+        .send()
+        .await
+        .map_err(|e| ShareError::RequestFailed(e.to_string()))?;
+
+    if !response.status().is_success() {
+        return Err(ShareError::RequestFailed(format!("HTTP {}", response.status())));
+    }
+
+    let result: PullResponse = response
+        .json()
+        .await
+        .map_err(|e| ShareError::RequestFailed(e.to_string()))?;
+
+    log::info!(
+        "[LanTransfer] 📂 已请求拉取 {} 个文件，来自 {}",
+        result.file_count, peer.device_name
+    );
+
+    Ok(result)
+}
+
+// ============================================================================
+// 内部函数
+// ============================================================================
+
+/// 向对端发送浏览请求
+async fn browse_peer(
+    connection_id: &str,
+    share_id: Option<String>,
+    path: String,
+) -> Result<BrowseSharesResponse, ShareError> {
+    let (peer, my_device_id) = get_connection_target(connection_id)?;
+
+    let url = format!("http://{}:{}/api/shares", peer.ip_address, peer.port);
+
+    let request = BrowseSharesRequest {
+        connection_id: connection_id.to_string(),
+        device_id: my_device_id,
+        share_id,
+        path,
+    };
+
+    let response = reqwest::Client::new()
+        .post(&url)
+        .json(&request)
+        .timeout(std::time::Duration::from_secs(10))
+        .send()
+        .await
+        .map_err(|e| ShareError::RequestFailed(e.to_string()))?;
+
+    match response.status().as_u16() {
+        200 => response
+            .json()
+            .await
+            .map_err(|e| ShareError::RequestFailed(e.to_string())),
+        403 => Err(ShareError::Forbidden),
+        404 => Err(ShareError::ShareNotFound(request.share_id.unwrap_or_default())),
+        status => Err(ShareError::RequestFailed(format!("HTTP {}", status))),
+    }
+}
+
+/// 获取连接的对端设备和本机设备 ID
+fn get_connection_target(connection_id: &str) -> Result<(DiscoveredDevice, String), ShareError> {
+    let peer = {
+        let connections = get_active_peer_connections_map();
+        let connections = connections.lock();
+        connections
+            .get(connection_id)
+            .filter(|c| c.status == PeerConnectionStatus::Connected)
+            .map(|c| c.peer_device.clone())
+            .ok_or(ShareError::NotConnected)?
+    };
+
+    let my_device_id = get_lan_transfer_state()
+        .local_device
+        .read()
+        .as_ref()
+        .map(|d| d.device_id.clone())
+        .ok_or(ShareError::NotConnected)?;
+
+    Ok((peer, my_device_id))
+}
+
+/// 获取设备有权访问的共享
+fn get_accessible_share(device_id: &str, share_id: &str) -> Result<SharedFolder, ShareError> {
+    let share = config::get_shared_folders()
+        .into_iter()
+        .find(|f| f.share_id == share_id)
+        .ok_or_else(|| ShareError::ShareNotFound(share_id.to_string()))?;
+
+    if !share.is_accessible_by(device_id, &config::get_trusted_devices()) {
+        return Err(ShareError::Forbidden);
+    }
+
+    Ok(share)
+}
+
+/// 将相对路径解析为共享目录内的真实路径
+fn resolve_in_share(share: &SharedFolder, relative_path: &str) -> Result<PathBuf, ShareError> {
+    let relative = Path::new(relative_path.trim_matches('/'));
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(ShareError::InvalidPath(relative_path.to_string()));
+    }
+
+    let root = share
+        .path
+        .canonicalize()
+        .map_err(|e| ShareError::Io(e.to_string()))?;
+    let resolved = root
+        .join(relative)
+        .canonicalize()
+        .map_err(|_| ShareError::InvalidPath(relative_path.to_string()))?;
+
+    if !resolved.starts_with(&root) {
+        return Err(ShareError::InvalidPath(relative_path.to_string()));
+    }
+
+    Ok(resolved)
+}
+
+/// 递归收集文件（跳过符号链接，避免循环）
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<(), ShareError> {
+    if path.is_file() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let read_dir = std::fs::read_dir(path).map_err(|e| ShareError::Io(e.to_string()))?;
+    for entry in read_dir.filter_map(|e| e.ok()) {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_symlink() {
+            continue;
+        }
+
+        collect_files(&entry.path(), files)?;
+        if files.len() > MAX_SHARE_PULL_FILES {
+            return Err(ShareError::TooManyFiles(MAX_SHARE_PULL_FILES));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 创建共享目录：root/docs/readme.txt，以及共享目录外的 secret.txt
+    fn setup() -> (PathBuf, SharedFolder) {
+        let base = std::env::temp_dir().join(format!("huanvae-share-{}", Uuid::new_v4()));
+        let root = base.join("root");
+        std::fs::create_dir_all(root.join("docs")).unwrap();
+        std::fs::write(root.join("docs").join("readme.txt"), b"readme").unwrap();
+        std::fs::write(base.join("secret.txt"), b"secret").unwrap();
+
+        let share = SharedFolder {
+            share_id: "share-1".to_string(),
+            name: "Root".to_string(),
+            path: root,
+            allowed_devices: Vec::new(),
+            created_at: Utc::now().to_rfc3339(),
+        };
+        (base, share)
+    }
+
+    #[test]
+    fn resolve_in_share_stays_inside_root() {
+        let (base, share) = setup();
+        let root = share.path.canonicalize().unwrap();
+
+        assert_eq!(resolve_in_share(&share, "").unwrap(), root);
+        assert_eq!(
+            resolve_in_share(&share, "/docs/readme.txt/").unwrap(),
+            root.join("docs").join("readme.txt")
+        );
+        assert_eq!(
+            resolve_in_share(&share, "./docs").unwrap(),
+            root.join("docs")
+        );
+
+        for path in [
+            "..",
+            "../secret.txt",
+            "docs/../../secret.txt",
+            "docs/../readme.txt",
+            "missing.txt",
+        ] {
+            assert!(
+                matches!(resolve_in_share(&share, path), Err(ShareError::InvalidPath(_))),
+                "{} should be rejected",
+                path
+            );
+        }
+
+        // 绝对路径去掉开头的 / 后按相对路径处理，不会访问共享外的文件
+        let absolute = base.join("secret.txt").to_string_lossy().to_string();
+        assert!(resolve_in_share(&share, &absolute).is_err());
+
+        let _ = std::fs::remove_dir_all(&base);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_in_share_rejects_symlink_escape() {
+        let (base, share) = setup();
+        std::os::unix::fs::symlink(base.join("secret.txt"), share.path.join("docs").join("link"))
+            .unwrap();
+        std::os::unix::fs::symlink(&base, share.path.join("up")).unwrap();
+
+        assert!(matches!(
+            resolve_in_share(&share, "docs/link"),
+            Err(ShareError::InvalidPath(_))
+        ));
+        assert!(matches!(
+            resolve_in_share(&share, "up/secret.txt"),
+            Err(ShareError::InvalidPath(_))
+        ));
+
+        // 展开文件夹时跳过符号链接
+        let (files, relative_paths) = expand_pull_paths(&share, &["docs".to_string()]).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(relative_paths, vec!["docs/readme.txt".to_string()]);
+
+        let _ = std::fs::remove_dir_all(&base);
+    }
+}
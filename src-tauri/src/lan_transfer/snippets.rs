@@ -0,0 +1,334 @@
+/*!
+ * 文本片段历史模块
+ *
+ * 保存通过点对点连接收到的文本片段（URL、密码、代码片段等）
+ *
+ * 功能：
+ * - 记录收到的文本片段（最新的排在最前）
+ * - 持久化到配置目录下的 snippets.json（Unix 上仅本人可读写，片段可能包含密码）
+ * - 历史记录数量上限 MAX_SNIPPET_HISTORY，超出后丢弃最旧的记录
+ * - 接收超过 SNIPPET_RETENTION_HOURS 的记录自动过期（加载、读取、写入时清理）
+ * - 支持删除单条记录和清空历史
+ *
+ * 说明：
+ * - 写文件不持有历史锁：修改在锁内完成并标记未保存，随后在锁外写入快照
+ *
+ * 更新日志：
+ * - 2026-10-18: 历史文件限制为仅本人可读写，记录自动过期，写文件移出历史锁
+ */
+
+use super::config;
+use super::protocol::TextSnippet;
+use super::service;
+use chrono::{DateTime, Duration, Utc};
+use once_cell::sync::OnceCell;
+use parking_lot::{Mutex, RwLock};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// 历史记录最大条数
+const MAX_SNIPPET_HISTORY: usize = 200;
+
+/// 历史记录保留时长（小时），超过后自动删除
+const SNIPPET_RETENTION_HOURS: i64 = 24 * 7;
+
+// ============================================================================
+// 错误类型
+// ============================================================================
+
+#[derive(Error, Debug)]
+pub enum SnippetError {
+    #[error("历史记录写入失败: {0}")]
+    WriteFailed(String),
+}
+
+// ============================================================================
+// 历史记录管理
+// ============================================================================
+
+/// 片段历史状态（每个服务实例一份，见 LanTransferService）
+#[derive(Default)]
+pub(crate) struct SnippetState {
+    /// 历史存储（首次访问时从实例数据目录加载）
+    store: OnceCell<Arc<RwLock<SnippetStore>>>,
+}
+
+/// 文本片段历史存储
+pub struct SnippetStore {
+    /// 已接收的片段（最新的在前）
+    snippets: Vec<TextSnippet>,
+    /// 历史文件路径
+    history_path: PathBuf,
+    /// 有尚未写盘的修改
+    save_pending: bool,
+    /// 写文件锁（保证按快照顺序写入，不占用历史锁）
+    write_lock: Arc<Mutex<()>>,
+}
+
+impl SnippetStore {
+    /// 创建新的历史存储
+    fn new() -> Self {
+        let history_path = config::get_snippet_history_path();
+        let snippets = Self::load_or_default(&history_path);
+
+        let mut store = Self {
+            snippets,
+            history_path,
+            save_pending: false,
+            write_lock: Arc::new(Mutex::new(())),
+        };
+        // 加载时已过期的记录在下次写入时从文件中删除
+        store.prune_expired();
+        store
+    }
+
+    /// 加载历史记录或使用空列表
+    fn load_or_default(path: &Path) -> Vec<TextSnippet> {
+        if !path.exists() {
+            return Vec::new();
+        }
+
+        match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                log::warn!("[LanTransfer] 片段历史解析失败，使用空历史: {}", e);
+                Vec::new()
+            }),
+            Err(e) => {
+                log::warn!("[LanTransfer] 片段历史读取失败，使用空历史: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// 删除过期的记录（接收时间无法解析的记录同样视为过期）
+    fn prune_expired(&mut self) {
+        let cutoff = Utc::now() - Duration::hours(SNIPPET_RETENTION_HOURS);
+        let before = self.snippets.len();
+        self.snippets.retain(|s| {
+            DateTime::parse_from_rfc3339(&s.received_at).is_ok_and(|t| t > cutoff)
+        });
+        if self.snippets.len() != before {
+            self.save_pending = true;
+        }
+    }
+
+    /// 生成待写入的快照（路径, JSON 内容），清除未保存标记
+    fn snapshot(&mut self) -> Result<(PathBuf, String), SnippetError> {
+        self.save_pending = false;
+        let content = serde_json::to_string_pretty(&self.snippets)
+            .map_err(|e| SnippetError::WriteFailed(e.to_string()))?;
+        Ok((self.history_path.clone(), content))
+    }
+
+    /// 记录收到的片段（需随后调用 save_pending 写盘）
+    pub fn add(&mut self, snippet: TextSnippet) {
+        self.prune_expired();
+        self.snippets.insert(0, snippet);
+        self.snippets.truncate(MAX_SNIPPET_HISTORY);
+        self.save_pending = true;
+    }
+
+    /// 删除单条记录（需随后调用 save_pending 写盘）
+    pub fn remove(&mut self, snippet_id: &str) {
+        self.snippets.retain(|s| s.snippet_id != snippet_id);
+        self.save_pending = true;
+    }
+
+    /// 清空历史（需随后调用 save_pending 写盘）
+    pub fn clear(&mut self) {
+        self.snippets.clear();
+        self.save_pending = true;
+    }
+
+    /// 获取所有未过期的记录
+    pub fn list(&self) -> Vec<TextSnippet> {
+        let cutoff = Utc::now() - Duration::hours(SNIPPET_RETENTION_HOURS);
+        self.snippets
+            .iter()
+            .filter(|s| DateTime::parse_from_rfc3339(&s.received_at).is_ok_and(|t| t > cutoff))
+            .cloned()
+            .collect()
+    }
+}
+
+/// 写入尚未保存的修改（没有未保存的修改时不写）
+///
+/// 先取得写文件锁再生成快照，先写入的快照总是较旧的；写文件时不持有历史锁
+fn save_pending(store: &RwLock<SnippetStore>) -> Result<(), SnippetError> {
+    let write_lock = store.read().write_lock.clone();
+    let _guard = write_lock.lock();
+
+    let (path, content) = {
+        let mut store = store.write();
+        if !store.save_pending {
+            return Ok(());
+        }
+        store.snapshot()?
+    };
+    write_private(&path, &content).map_err(|e| SnippetError::WriteFailed(e.to_string()))
+}
+
+/// 写入只有本人可读写的文件（Unix 上权限为 0600，已存在的文件同样收紧权限）
+fn write_private(path: &Path, content: &str) -> std::io::Result<()> {
+    use std::io::Write;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+
+    let mut file = options.open(path)?;
+    file.write_all(content.as_bytes())
+}
+
+// ============================================================================
+// 便捷函数
+// ============================================================================
+
+/// 获取当前实例的片段历史存储
+fn get_snippet_store() -> Arc<RwLock<SnippetStore>> {
+    service::current()
+        .snippets
+        .store
+        .get_or_init(|| Arc::new(RwLock::new(SnippetStore::new())))
+        .clone()
+}
+
+/// 记录收到的片段（同步写文件，异步上下文中应在 spawn_blocking 中调用）
+pub fn record_received_snippet(snippet: TextSnippet) -> Result<(), SnippetError> {
+    let store = get_snippet_store();
+    store.write().add(snippet);
+    save_pending(&store)
+}
+
+/// 获取已接收的片段历史
+pub fn get_received_snippets() -> Vec<TextSnippet> {
+    let store = get_snippet_store();
+    let store = store.read();
+    store.list()
+}
+
+/// 删除单条片段记录
+pub fn delete_snippet(snippet_id: &str) -> Result<(), SnippetError> {
+    let store = get_snippet_store();
+    store.write().remove(snippet_id);
+    save_pending(&store)
+}
+
+/// 清空片段历史
+pub fn clear_snippet_history() -> Result<(), SnippetError> {
+    let store = get_snippet_store();
+    store.write().clear();
+    save_pending(&store)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lan_transfer::protocol::{DiscoveredDevice, SnippetFormat};
+    use crate::lan_transfer::service::{LanTransferService, ServiceOptions};
+
+    fn snippet(content: &str, received_at: chrono::DateTime<Utc>) -> TextSnippet {
+        let now = Utc::now().to_rfc3339();
+        TextSnippet {
+            snippet_id: uuid::Uuid::new_v4().to_string(),
+            connection_id: "c".into(),
+            from_device: DiscoveredDevice {
+                device_id: "peer".into(),
+                device_name: "peer".into(),
+                user_id: String::new(),
+                user_nickname: String::new(),
+                ip_address: "10.0.0.2".into(),
+                port: 0,
+                discovered_at: now.clone(),
+                last_seen: now.clone(),
+            },
+            content: content.into(),
+            format: SnippetFormat::Plain,
+            sent_at: now,
+            received_at: received_at.to_rfc3339(),
+        }
+    }
+
+    fn service(dir: &Path) -> std::sync::Arc<LanTransferService> {
+        LanTransferService::new(ServiceOptions {
+            enable_mdns: false,
+            data_directory: Some(dir.to_path_buf()),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn history_is_newest_first_capped_and_persisted_privately() {
+        let dir = std::env::temp_dir().join(format!("huanvae-snippets-{}", uuid::Uuid::new_v4()));
+
+        service(&dir).enter(|| {
+            for i in 0..MAX_SNIPPET_HISTORY + 2 {
+                record_received_snippet(snippet(&format!("s{}", i), Utc::now())).unwrap();
+            }
+            let history = get_received_snippets();
+            assert_eq!(history.len(), MAX_SNIPPET_HISTORY);
+            assert_eq!(history[0].content, format!("s{}", MAX_SNIPPET_HISTORY + 1));
+
+            delete_snippet(&history[0].snippet_id).unwrap();
+        });
+
+        // 新实例从文件加载
+        let path = dir.join("snippets.json");
+        service(&dir).enter(|| {
+            let history = get_received_snippets();
+            assert_eq!(history.len(), MAX_SNIPPET_HISTORY - 1);
+            assert_eq!(history[0].content, format!("s{}", MAX_SNIPPET_HISTORY));
+
+            clear_snippet_history().unwrap();
+            assert!(get_received_snippets().is_empty());
+        });
+        assert_eq!(fs::read_to_string(&path).unwrap().trim(), "[]");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn expired_snippets_are_dropped() {
+        let dir = std::env::temp_dir().join(format!("huanvae-snippets-{}", uuid::Uuid::new_v4()));
+        let old = Utc::now() - Duration::hours(SNIPPET_RETENTION_HOURS + 1);
+        let mut unparsable = snippet("bad", Utc::now());
+        unparsable.received_at = String::new();
+        let stored = vec![snippet("fresh", Utc::now()), snippet("old", old), unparsable];
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("snippets.json"), serde_json::to_string(&stored).unwrap()).unwrap();
+
+        service(&dir).enter(|| {
+            let history = get_received_snippets();
+            assert_eq!(history.len(), 1);
+            assert_eq!(history[0].content, "fresh");
+
+            // 下一次写入时过期记录也从文件中删除
+            record_received_snippet(snippet("new", Utc::now())).unwrap();
+        });
+        let saved: Vec<TextSnippet> =
+            serde_json::from_str(&fs::read_to_string(dir.join("snippets.json")).unwrap()).unwrap();
+        let contents: Vec<&str> = saved.iter().map(|s| s.content.as_str()).collect();
+        assert_eq!(contents, ["new", "fresh"]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
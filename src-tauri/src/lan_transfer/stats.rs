@@ -0,0 +1,628 @@
+/*!
+ * 传输统计模块
+ *
+ * 记录每次文件发送的性能指标，按对端设备汇总，供前端绘制历史吞吐曲线、标记慢速链路
+ * （判断瓶颈在 Wi-Fi 还是有线网络）
+ *
+ * 功能：
+ * - 每个文件一条记录：平均速度、峰值速度（PEAK_WINDOW 窗口内的最高速度）、块数与重试次数、
+ *   哈希耗时与发送耗时、使用的本机网络接口与 IP、成功/失败
+ * - 按对端设备汇总：传输次数、失败次数、总字节数、平均/峰值速度、平均每块重试次数、
+ *   按网络接口分组的平均速度；平均速度低于 SLOW_LINK_SPEED 时标记为慢速链路
+ * - 持久化到数据目录下的 transfer_stats.json，记录数上限 MAX_RECORDS，超出后丢弃最旧的记录；
+ *   新记录不立即写盘，SAVE_DELAY 后合并保存一次（在阻塞线程池中写入，不占用统计锁），
+ *   服务停止时写入尚未保存的记录
+ *
+ * 说明：
+ * - 统计由发送方记录（块重试、哈希都发生在发送方），接收方不记录
+ * - 哈希在建立会话前计算，按 file_id 暂存耗时，文件发送结束时计入该文件的记录；
+ *   群发时同一文件只计算一次哈希，耗时只计入第一个完成的设备
+ * - 使用的网络接口通过路由查询确定（向对端 IP connect 一个 UDP 套接字读取本地地址，不发送数据）
+ * - 统计属于服务实例（LanTransferService），同一进程内的多个实例各自记录
+ *
+ * 更新日志：
+ * - 2026-10-18: 新记录延迟合并保存，写文件移出统计锁和异步线程（群发大量小文件时不再逐个文件同步写盘）
+ */
+
+use super::protocol::{DiscoveredDevice, TransferDirection};
+use super::service;
+use chrono::Utc;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::net::{IpAddr, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// 统计文件名（位于数据目录）
+const STATS_FILE_NAME: &str = "transfer_stats.json";
+
+/// 保存的记录最大条数
+const MAX_RECORDS: usize = 1000;
+
+/// 暂存哈希耗时的最大条数（未被使用的条目超出后清空）
+const MAX_PENDING_HASHING: usize = 4096;
+
+/// 新记录的保存延迟（期间的记录合并为一次写入）
+const SAVE_DELAY: Duration = Duration::from_secs(2);
+
+/// 峰值速度的采样窗口
+const PEAK_WINDOW: Duration = Duration::from_secs(1);
+
+/// 慢速链路阈值：平均速度低于 2 MB/s
+pub const SLOW_LINK_SPEED: u64 = 2 * 1024 * 1024;
+
+// ============================================================================
+// 错误类型
+// ============================================================================
+
+#[derive(Error, Debug)]
+pub enum StatsError {
+    #[error("统计写入失败: {0}")]
+    WriteFailed(String),
+}
+
+// ============================================================================
+// 数据结构
+// ============================================================================
+
+/// 单次文件传输的指标
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferMetrics {
+    /// 文件 ID
+    pub file_id: String,
+    /// 会话 ID
+    pub session_id: String,
+    /// 传输方向
+    pub direction: TransferDirection,
+    /// 对端设备 ID
+    pub peer_device_id: String,
+    /// 对端设备名称
+    pub peer_device_name: String,
+    /// 对端 IP
+    pub peer_ip: String,
+    /// 本机使用的网络接口（无法确定时为 None）
+    pub local_interface: Option<String>,
+    /// 本机使用的 IP
+    pub local_ip: Option<String>,
+    /// 文件名
+    pub file_name: String,
+    /// 文件大小
+    pub file_size: u64,
+    /// 本次实际传输的字节数（断点续传时不含已传部分）
+    pub transferred_bytes: u64,
+    /// 开始时间
+    pub started_at: String,
+    /// 哈希耗时（毫秒）
+    pub hashing_ms: u64,
+    /// 发送耗时（毫秒，从 prepare-upload 到 finish 响应）
+    pub sending_ms: u64,
+    /// 平均速度（字节/秒）
+    pub average_speed: u64,
+    /// 峰值速度（字节/秒）
+    pub peak_speed: u64,
+    /// 发送的块数
+    pub chunk_count: u64,
+    /// 重试总次数
+    pub retry_count: u64,
+    /// 单块最多重试次数
+    pub max_chunk_retries: u32,
+    /// 是否成功
+    pub success: bool,
+    /// 失败原因
+    pub error: Option<String>,
+}
+
+/// 按网络接口分组的统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InterfaceTransferStats {
+    /// 网络接口名称（未知时为 "unknown"）
+    pub interface: String,
+    /// 成功传输次数
+    pub transfer_count: u32,
+    /// 总字节数
+    pub total_bytes: u64,
+    /// 平均速度（字节/秒）
+    pub average_speed: u64,
+}
+
+/// 对端设备的汇总统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerTransferStats {
+    /// 对端设备 ID
+    pub device_id: String,
+    /// 对端设备名称（最近一次记录）
+    pub device_name: String,
+    /// 成功传输次数
+    pub transfer_count: u32,
+    /// 失败次数
+    pub failed_count: u32,
+    /// 总字节数（成功的传输）
+    pub total_bytes: u64,
+    /// 平均速度（字节/秒，总字节数 / 总发送耗时）
+    pub average_speed: u64,
+    /// 峰值速度（字节/秒）
+    pub peak_speed: u64,
+    /// 平均每块重试次数
+    pub retries_per_chunk: f64,
+    /// 哈希总耗时（毫秒）
+    pub hashing_ms: u64,
+    /// 发送总耗时（毫秒）
+    pub sending_ms: u64,
+    /// 按网络接口分组的统计（平均速度从高到低）
+    pub interfaces: Vec<InterfaceTransferStats>,
+    /// 是否为慢速链路（平均速度低于 SLOW_LINK_SPEED）
+    pub slow_link: bool,
+    /// 最近一次传输时间
+    pub last_transfer_at: String,
+}
+
+// ============================================================================
+// 统计存储
+// ============================================================================
+
+/// 传输统计存储（每个服务实例一份，见 LanTransferService）
+pub(crate) struct StatsStore {
+    /// 传输记录（按时间升序）
+    records: Vec<TransferMetrics>,
+    /// 暂存的哈希耗时（file_id -> 毫秒）
+    pending_hashing: HashMap<String, u64>,
+    /// 统计文件路径
+    path: PathBuf,
+    /// 有尚未写盘的改动
+    save_pending: bool,
+    /// 写文件锁（保证按快照顺序写入，不占用统计锁）
+    write_lock: Arc<Mutex<()>>,
+}
+
+impl StatsStore {
+    /// 从数据目录加载
+    pub(crate) fn load(data_directory: &Path) -> Self {
+        let path = data_directory.join(STATS_FILE_NAME);
+        let records = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                log::warn!("[LanTransfer] 传输统计解析失败，使用空统计: {}", e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+
+        Self {
+            records,
+            pending_hashing: HashMap::new(),
+            path,
+            save_pending: false,
+            write_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// 生成待写入的快照（路径, JSON 内容），清除未保存标记
+    fn snapshot(&mut self) -> Result<(PathBuf, String), StatsError> {
+        self.save_pending = false;
+        let content = serde_json::to_string(&self.records)
+            .map_err(|e| StatsError::WriteFailed(e.to_string()))?;
+        Ok((self.path.clone(), content))
+    }
+
+    /// 暂存哈希耗时
+    fn set_hashing(&mut self, file_id: &str, hashing_ms: u64) {
+        if self.pending_hashing.len() >= MAX_PENDING_HASHING {
+            self.pending_hashing.clear();
+        }
+        self.pending_hashing.insert(file_id.to_string(), hashing_ms);
+    }
+
+    /// 添加记录（计入暂存的哈希耗时）
+    ///
+    /// 不写盘，返回 true 表示调用方需要安排一次延迟保存（尚未安排过）
+    fn add(&mut self, mut metrics: TransferMetrics) -> bool {
+        if let Some(hashing_ms) = self.pending_hashing.remove(&metrics.file_id) {
+            metrics.hashing_ms = hashing_ms;
+        }
+
+        self.records.push(metrics);
+        if self.records.len() > MAX_RECORDS {
+            let excess = self.records.len() - MAX_RECORDS;
+            self.records.drain(..excess);
+        }
+        !std::mem::replace(&mut self.save_pending, true)
+    }
+
+    /// 最近的记录（最新的在前）
+    fn history(&self, device_id: Option<&str>, limit: usize) -> Vec<TransferMetrics> {
+        self.records
+            .iter()
+            .rev()
+            .filter(|m| device_id.is_none_or(|id| m.peer_device_id == id))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// 清空记录（指定设备时只清空该设备），需随后调用 save_pending 写盘
+    fn clear(&mut self, device_id: Option<&str>) {
+        match device_id {
+            Some(id) => self.records.retain(|m| m.peer_device_id != id),
+            None => self.records.clear(),
+        }
+        self.save_pending = true;
+    }
+}
+
+/// 写入尚未保存的记录（没有未保存的记录时不写）
+///
+/// 先取得写文件锁再生成快照，先写入的快照总是较旧的；写文件时不持有统计锁
+fn save_pending(stats: &Mutex<StatsStore>) -> Result<(), StatsError> {
+    let write_lock = stats.lock().write_lock.clone();
+    let _guard = write_lock.lock();
+
+    let (path, content) = {
+        let mut store = stats.lock();
+        if !store.save_pending {
+            return Ok(());
+        }
+        store.snapshot()?
+    };
+    write_file(&path, &content)
+}
+
+/// 写入统计文件
+fn write_file(path: &Path, content: &str) -> Result<(), StatsError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| StatsError::WriteFailed(e.to_string()))?;
+    }
+    fs::write(path, content).map_err(|e| StatsError::WriteFailed(e.to_string()))
+}
+
+/// 按对端设备汇总（最近传输的设备在前）
+fn aggregate(records: &[TransferMetrics]) -> Vec<PeerTransferStats> {
+    let mut by_peer: HashMap<&str, Vec<&TransferMetrics>> = HashMap::new();
+    for record in records {
+        by_peer.entry(&record.peer_device_id).or_default().push(record);
+    }
+
+    let mut peers: Vec<PeerTransferStats> = by_peer
+        .into_iter()
+        .map(|(device_id, records)| {
+            let succeeded: Vec<&&TransferMetrics> = records.iter().filter(|m| m.success).collect();
+            let total_bytes: u64 = succeeded.iter().map(|m| m.transferred_bytes).sum();
+            let sending_ms: u64 = succeeded.iter().map(|m| m.sending_ms).sum();
+            let chunk_count: u64 = records.iter().map(|m| m.chunk_count).sum();
+            let retry_count: u64 = records.iter().map(|m| m.retry_count).sum();
+            let average_speed = speed(total_bytes, sending_ms);
+
+            let mut interfaces: HashMap<&str, (u32, u64, u64)> = HashMap::new();
+            for m in &succeeded {
+                let entry = interfaces
+                    .entry(m.local_interface.as_deref().unwrap_or("unknown"))
+                    .or_default();
+                entry.0 += 1;
+                entry.1 += m.transferred_bytes;
+                entry.2 += m.sending_ms;
+            }
+            let mut interfaces: Vec<InterfaceTransferStats> = interfaces
+                .into_iter()
+                .map(|(name, (count, bytes, ms))| InterfaceTransferStats {
+                    interface: name.to_string(),
+                    transfer_count: count,
+                    total_bytes: bytes,
+                    average_speed: speed(bytes, ms),
+                })
+                .collect();
+            interfaces.sort_by_key(|i| std::cmp::Reverse(i.average_speed));
+
+            let latest = records.last().copied();
+            PeerTransferStats {
+                device_id: device_id.to_string(),
+                device_name: latest.map(|m| m.peer_device_name.clone()).unwrap_or_default(),
+                transfer_count: succeeded.len() as u32,
+                failed_count: (records.len() - succeeded.len()) as u32,
+                total_bytes,
+                average_speed,
+                peak_speed: succeeded.iter().map(|m| m.peak_speed).max().unwrap_or(0),
+                retries_per_chunk: if chunk_count == 0 {
+                    0.0
+                } else {
+                    retry_count as f64 / chunk_count as f64
+                },
+                hashing_ms: records.iter().map(|m| m.hashing_ms).sum(),
+                sending_ms,
+                interfaces,
+                slow_link: !succeeded.is_empty() && average_speed < SLOW_LINK_SPEED,
+                last_transfer_at: latest.map(|m| m.started_at.clone()).unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    peers.sort_by(|a, b| b.last_transfer_at.cmp(&a.last_transfer_at));
+    peers
+}
+
+/// 字节数 / 毫秒 -> 字节/秒
+fn speed(bytes: u64, ms: u64) -> u64 {
+    bytes.saturating_mul(1000).checked_div(ms).unwrap_or(0)
+}
+
+// ============================================================================
+// 指标采集
+// ============================================================================
+
+/// 单个文件发送过程中的指标采集器
+pub(crate) struct TransferRecorder {
+    metrics: TransferMetrics,
+    start: Instant,
+    window_start: Instant,
+    window_bytes: u64,
+    /// 对方已同意接收（开始上传）
+    upload_started: bool,
+}
+
+impl TransferRecorder {
+    /// 开始采集（同时查询到对端使用的本机网络接口）
+    pub(crate) fn start(
+        session_id: &str,
+        file_id: &str,
+        file_name: &str,
+        file_size: u64,
+        peer: &DiscoveredDevice,
+    ) -> Self {
+        let (local_interface, local_ip) = match local_route(&peer.ip_address) {
+            Some((name, ip)) => (name, Some(ip.to_string())),
+            None => (None, None),
+        };
+        let now = Instant::now();
+
+        Self {
+            metrics: TransferMetrics {
+                file_id: file_id.to_string(),
+                session_id: session_id.to_string(),
+                direction: TransferDirection::Send,
+                peer_device_id: peer.device_id.clone(),
+                peer_device_name: peer.device_name.clone(),
+                peer_ip: peer.ip_address.clone(),
+                local_interface,
+                local_ip,
+                file_name: file_name.to_string(),
+                file_size,
+                transferred_bytes: 0,
+                started_at: Utc::now().to_rfc3339(),
+                hashing_ms: 0,
+                sending_ms: 0,
+                average_speed: 0,
+                peak_speed: 0,
+                chunk_count: 0,
+                retry_count: 0,
+                max_chunk_retries: 0,
+                success: false,
+                error: None,
+            },
+            start: now,
+            window_start: now,
+            window_bytes: 0,
+            upload_started: false,
+        }
+    }
+
+    /// 对方已同意接收，开始上传
+    pub(crate) fn upload_started(&mut self) {
+        self.upload_started = true;
+    }
+
+    /// 记录一个已发送的块（retries 为该块的重试次数）
+    pub(crate) fn chunk_sent(&mut self, bytes: u64, retries: u32) {
+        self.metrics.chunk_count += 1;
+        self.metrics.retry_count += retries as u64;
+        self.metrics.max_chunk_retries = self.metrics.max_chunk_retries.max(retries);
+        self.metrics.transferred_bytes += bytes;
+
+        self.window_bytes += bytes;
+        let window = self.window_start.elapsed();
+        if window >= PEAK_WINDOW {
+            let window_speed = (self.window_bytes as f64 / window.as_secs_f64()) as u64;
+            self.metrics.peak_speed = self.metrics.peak_speed.max(window_speed);
+            self.window_start = Instant::now();
+            self.window_bytes = 0;
+        }
+    }
+
+    /// 记录一个最终失败的块（所有重试都失败）
+    pub(crate) fn chunk_failed(&mut self, retries: u32) {
+        self.metrics.retry_count += retries as u64;
+        self.metrics.max_chunk_retries = self.metrics.max_chunk_retries.max(retries);
+    }
+
+    /// 是否已开始上传（对方拒绝接收、prepare-upload 失败等情况不计入统计）
+    pub(crate) fn attempted(&self) -> bool {
+        self.upload_started
+    }
+
+    /// 结束采集
+    pub(crate) fn finish(mut self, error: Option<String>) -> TransferMetrics {
+        let elapsed = self.start.elapsed();
+        self.metrics.sending_ms = elapsed.as_millis() as u64;
+        self.metrics.average_speed = if elapsed.is_zero() {
+            0
+        } else {
+            (self.metrics.transferred_bytes as f64 / elapsed.as_secs_f64()) as u64
+        };
+        // 传输时间不足一个采样窗口时，峰值取平均速度
+        self.metrics.peak_speed = self.metrics.peak_speed.max(self.metrics.average_speed);
+        self.metrics.success = error.is_none();
+        self.metrics.error = error;
+        self.metrics
+    }
+}
+
+/// 查询到对端使用的本机 IP 与网络接口名称
+fn local_route(peer_ip: &str) -> Option<(Option<String>, IpAddr)> {
+    let peer_ip: IpAddr = peer_ip.parse().ok()?;
+    let bind_addr = if peer_ip.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let socket = UdpSocket::bind(bind_addr).ok()?;
+    socket.connect((peer_ip, 9)).ok()?;
+    let local_ip = socket.local_addr().ok()?.ip();
+
+    let interface = if local_ip.is_loopback() {
+        Some("loopback".to_string())
+    } else {
+        local_ip_address::list_afinet_netifas()
+            .ok()
+            .and_then(|list| list.into_iter().find(|(_, ip)| *ip == local_ip))
+            .map(|(name, _)| name)
+    };
+
+    Some((interface, local_ip))
+}
+
+// ============================================================================
+// 便捷函数
+// ============================================================================
+
+/// 暂存文件的哈希耗时（文件发送结束时计入记录）
+pub fn record_hashing(file_id: &str, elapsed: Duration) {
+    service::current()
+        .stats()
+        .lock()
+        .set_hashing(file_id, elapsed.as_millis() as u64);
+}
+
+/// 记录一次文件传输
+pub fn record_transfer(metrics: TransferMetrics) {
+    let failed = !metrics.success;
+    log::info!(
+        "[LanTransfer] 📈 传输统计: {} -> {} ({}), 平均 {}/s, 峰值 {}/s, 重试 {}/{} 块{}",
+        metrics.file_name,
+        metrics.peer_device_name,
+        metrics.local_interface.as_deref().unwrap_or("unknown"),
+        super::transfer::format_bytes(metrics.average_speed),
+        super::transfer::format_bytes(metrics.peak_speed),
+        metrics.retry_count,
+        metrics.chunk_count,
+        if failed { "（失败）" } else { "" }
+    );
+
+    if service::current().stats().lock().add(metrics) {
+        service::spawn(async {
+            tokio::time::sleep(SAVE_DELAY).await;
+            let stats = service::current().stats();
+            let result = service::spawn_blocking(move || save_pending(&stats)).await;
+
+            match result {
+                Ok(Err(e)) => log::warn!("[LanTransfer] 保存传输统计失败: {}", e),
+                Err(e) => log::warn!("[LanTransfer] 保存传输统计失败: {}", e),
+                Ok(Ok(())) => {}
+            }
+        });
+    }
+}
+
+/// 立即写入尚未保存的记录（服务停止时调用）
+pub(crate) fn flush() {
+    if let Err(e) = save_pending(&service::current().stats()) {
+        log::warn!("[LanTransfer] 保存传输统计失败: {}", e);
+    }
+}
+
+/// 按对端设备汇总的统计
+pub fn get_peer_stats() -> Vec<PeerTransferStats> {
+    aggregate(&service::current().stats().lock().records)
+}
+
+/// 最近的传输记录（最新的在前，可按设备过滤）
+pub fn get_history(device_id: Option<&str>, limit: usize) -> Vec<TransferMetrics> {
+    service::current().stats().lock().history(device_id, limit)
+}
+
+/// 清空统计（指定设备时只清空该设备）
+pub fn clear(device_id: Option<&str>) -> Result<(), StatsError> {
+    let stats = service::current().stats();
+    stats.lock().clear(device_id);
+    save_pending(&stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(peer: &str, interface: &str, bytes: u64, ms: u64, success: bool) -> TransferMetrics {
+        TransferMetrics {
+            file_id: uuid::Uuid::new_v4().to_string(),
+            session_id: "s".into(),
+            direction: TransferDirection::Send,
+            peer_device_id: peer.into(),
+            peer_device_name: format!("{}-name", peer),
+            peer_ip: "192.168.1.2".into(),
+            local_interface: Some(interface.into()),
+            local_ip: None,
+            file_name: "a.bin".into(),
+            file_size: bytes,
+            transferred_bytes: bytes,
+            started_at: Utc::now().to_rfc3339(),
+            hashing_ms: 0,
+            sending_ms: ms,
+            average_speed: speed(bytes, ms),
+            peak_speed: speed(bytes, ms),
+            chunk_count: 4,
+            retry_count: if success { 1 } else { 3 },
+            max_chunk_retries: 1,
+            success,
+            error: (!success).then(|| "网络错误".to_string()),
+        }
+    }
+
+    #[test]
+    fn aggregates_per_peer_and_interface() {
+        let mb = 1024 * 1024;
+        let records = vec![
+            metrics("a", "eth0", 100 * mb, 1000, true),
+            metrics("a", "wlan0", 10 * mb, 1000, true),
+            metrics("a", "wlan0", mb, 1000, false),
+            metrics("b", "wlan0", mb, 1000, true),
+        ];
+        let peers = aggregate(&records);
+        assert_eq!(peers.len(), 2);
+
+        let a = peers.iter().find(|p| p.device_id == "a").unwrap();
+        assert_eq!(a.transfer_count, 2);
+        assert_eq!(a.failed_count, 1);
+        assert_eq!(a.total_bytes, 110 * mb);
+        assert_eq!(a.average_speed, 55 * mb);
+        assert_eq!(a.peak_speed, 100 * mb);
+        assert!((a.retries_per_chunk - 5.0 / 12.0).abs() < 1e-9);
+        assert_eq!(a.interfaces[0].interface, "eth0");
+        assert_eq!(a.interfaces[1].average_speed, 10 * mb);
+        assert!(!a.slow_link);
+
+        let b = peers.iter().find(|p| p.device_id == "b").unwrap();
+        assert!(b.slow_link);
+    }
+
+    #[test]
+    fn store_persists_and_applies_pending_hashing() {
+        let dir = std::env::temp_dir().join(format!("huanvae-stats-{}", uuid::Uuid::new_v4()));
+        let stats = Mutex::new(StatsStore::load(&dir));
+        {
+            let mut store = stats.lock();
+            let record = metrics("a", "eth0", 1024, 10, true);
+            store.set_hashing(&record.file_id, 42);
+            assert!(store.add(record));
+            assert!(!store.add(metrics("a", "eth0", 1024, 10, true)), "已安排保存时不重复安排");
+        }
+        assert!(!dir.join(STATS_FILE_NAME).exists(), "添加记录时不写盘");
+        save_pending(&stats).unwrap();
+        assert!(!stats.lock().save_pending);
+
+        let reloaded = StatsStore::load(&dir);
+        assert_eq!(reloaded.records.len(), 2);
+        assert_eq!(reloaded.records[0].hashing_ms, 42);
+        assert!(reloaded.pending_hashing.is_empty());
+        assert_eq!(reloaded.history(Some("b"), 10).len(), 0);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,1482 @@
+/*!
+ * 文件夹同步模块
+ *
+ * 在同一账号的两台设备之间持续同步一个文件夹（例如台式机和笔记本的"交接"目录）
+ *
+ * 配对：
+ * 1. 设备 A 调用 create_sync_pair 选择本地目录，向设备 B 发送邀请（/api/sync/invite）
+ * 2. 设备 B 收到 SyncPairInvited 事件，用户选择本地目录后调用 join_sync_pair
+ * 3. 只允许同一账号的设备配对，所有请求都校验来源 IP 与发现的设备一致
+ * 4. 账号来自未认证的 mDNS 广播，邀请事件标明邀请方是否为信任设备，未信任时由界面提示未验证
+ *
+ * 同步流程（由设备 ID 较小的一方作为协调方执行，避免双方同时决策）：
+ * 1. 定期扫描本地目录（SYNC_INTERVAL_SECS），对端在线时交换文件清单
+ * 2. 与上次同步的基准清单比较，得出每个文件是哪一端发生了变化
+ * 3. 仅传输变化的文件：本地变化 → 推送到对端，对端变化 → 请求对端推送
+ * 4. 一端删除且另一端未修改 → 删除另一端的文件；删除与修改冲突时保留修改
+ *
+ * 冲突处理：
+ * - 两端都修改了同一文件（哈希不同）时，按修改时间较新的版本为准
+ * - 被覆盖的一端先将原文件重命名为冲突副本，不会丢失数据
+ *
+ * 文件传输：
+ * - 分块上传（CHUNK_SIZE），数据先写入临时目录，提交时校验 CRC32 后移动到目标位置
+ * - 中断后下次同步从临时文件已有的长度继续（断点续传）
+ * - 接收方恢复文件的修改时间，使两端清单一致
+ *
+ * 状态持久化：
+ * - 配对信息和基准清单保存在实例数据目录的 sync_state.json（每个服务实例一份）
+ * - 文件哈希按 (大小, 修改时间) 缓存在内存中，避免重复计算
+ */
+
+use super::config;
+use super::discovery::get_event_sender;
+use super::protocol::*;
+use super::resume::get_resume_manager;
+use super::service;
+use super::transfer::calculate_file_hash;
+use super::{emit_lan_event, get_lan_transfer_state};
+use chrono::Utc;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use uuid::Uuid;
+
+/// 同步检查间隔（秒）
+const SYNC_INTERVAL_SECS: u64 = 10;
+
+// ============================================================================
+// 错误类型
+// ============================================================================
+
+#[derive(Error, Debug)]
+pub enum SyncError {
+    #[error("同步配对不存在: {0}")]
+    PairNotFound(String),
+    #[error("只能与本账号的设备同步")]
+    NotOwnDevice,
+    #[error("对端设备不在线")]
+    DeviceOffline,
+    #[error("同步目录无效: {0}")]
+    InvalidDirectory(String),
+    #[error("路径无效: {0}")]
+    InvalidPath(String),
+    #[error("文件已被修改，跳过删除: {0}")]
+    Modified(String),
+    #[error("文件校验失败: {0}")]
+    HashMismatch(String),
+    #[error("文件读写失败: {0}")]
+    Io(String),
+    #[error("请求失败: {0}")]
+    RequestFailed(String),
+    #[error("同步状态保存失败: {0}")]
+    PersistFailed(String),
+}
+
+// ============================================================================
+// 数据结构
+// ============================================================================
+
+/// 同步配对
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncPair {
+    /// 配对 ID（两端相同）
+    pub pair_id: String,
+    /// 本地同步目录
+    pub local_dir: PathBuf,
+    /// 对端设备 ID
+    pub peer_device_id: String,
+    /// 对端设备名称
+    pub peer_device_name: String,
+    /// 是否启用
+    pub enabled: bool,
+    /// 创建时间
+    pub created_at: String,
+    /// 最近一次同步完成时间
+    pub last_synced_at: Option<String>,
+}
+
+/// 持久化的同步状态
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SyncStateFile {
+    pairs: Vec<SyncPair>,
+    /// 基准清单：pair_id -> (相对路径 -> 上次同步时两端一致的条目)
+    #[serde(default)]
+    bases: HashMap<String, HashMap<String, SyncEntry>>,
+}
+
+/// 同步状态存储
+struct SyncStore {
+    state: SyncStateFile,
+    state_path: PathBuf,
+    /// 哈希缓存：绝对路径 -> (大小, 修改时间, 哈希)
+    hash_cache: HashMap<PathBuf, (u64, i64, String)>,
+    /// 正在同步的配对（防止同一配对的同步重叠执行）
+    running: HashSet<String>,
+    /// 已准备接收的文件：file_key -> 文件大小（数据块只能写入这些临时文件）
+    prepared: HashMap<String, u64>,
+}
+
+impl SyncStore {
+    fn new() -> Self {
+        let state_path = config::get_sync_state_path();
+        let state = Self::load_or_default(&state_path);
+
+        Self {
+            state,
+            state_path,
+            hash_cache: HashMap::new(),
+            running: HashSet::new(),
+            prepared: HashMap::new(),
+        }
+    }
+
+    fn load_or_default(path: &PathBuf) -> SyncStateFile {
+        if !path.exists() {
+            return SyncStateFile::default();
+        }
+
+        match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                log::warn!("[LanTransfer] 同步状态解析失败，使用空状态: {}", e);
+                SyncStateFile::default()
+            }),
+            Err(e) => {
+                log::warn!("[LanTransfer] 同步状态读取失败，使用空状态: {}", e);
+                SyncStateFile::default()
+            }
+        }
+    }
+
+    fn save(&self) -> Result<(), SyncError> {
+        if let Some(parent) = self.state_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| SyncError::PersistFailed(e.to_string()))?;
+        }
+
+        let content = serde_json::to_string_pretty(&self.state)
+            .map_err(|e| SyncError::PersistFailed(e.to_string()))?;
+
+        fs::write(&self.state_path, content).map_err(|e| SyncError::PersistFailed(e.to_string()))
+    }
+
+    fn pair(&self, pair_id: &str) -> Result<SyncPair, SyncError> {
+        self.state
+            .pairs
+            .iter()
+            .find(|p| p.pair_id == pair_id)
+            .cloned()
+            .ok_or_else(|| SyncError::PairNotFound(pair_id.to_string()))
+    }
+}
+
+/// 同步状态（每个服务实例一份，见 LanTransferService）
+#[derive(Default)]
+pub(crate) struct SyncState {
+    /// 配对与基准清单（首次访问时从实例数据目录加载）
+    store: OnceCell<Arc<Mutex<SyncStore>>>,
+    /// 同步任务运行标志
+    task_running: Arc<AtomicBool>,
+}
+
+fn get_sync_store() -> Arc<Mutex<SyncStore>> {
+    service::current()
+        .sync
+        .store
+        .get_or_init(|| Arc::new(Mutex::new(SyncStore::new())))
+        .clone()
+}
+
+fn get_sync_task_flag() -> Arc<AtomicBool> {
+    service::current().sync.task_running.clone()
+}
+
+// ============================================================================
+// 请求体
+// ============================================================================
+
+/// 同步邀请
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncInviteRequest {
+    pub pair_id: String,
+    pub device_id: String,
+    pub folder_name: String,
+}
+
+/// 配对内的通用请求（清单交换、手动触发）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncPairRequest {
+    pub pair_id: String,
+    pub device_id: String,
+}
+
+/// 请求对端推送文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncPushRequest {
+    pub pair_id: String,
+    pub device_id: String,
+    pub files: Vec<SyncPushFile>,
+}
+
+/// 需要推送的文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncPushFile {
+    pub relative_path: String,
+    /// 接收方是否需要将现有文件保留为冲突副本
+    pub conflict: bool,
+}
+
+/// 推送结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncPushResponse {
+    pub pushed: u32,
+    pub failed: u32,
+}
+
+/// 文件上传头（prepare / commit）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncFileHeader {
+    pub pair_id: String,
+    pub device_id: String,
+    pub entry: SyncEntry,
+    pub conflict: bool,
+}
+
+/// prepare 响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncPrepareResponse {
+    /// 临时文件标识（上传数据块时使用）
+    pub file_key: String,
+    /// 已接收的字节数（断点续传起点）
+    pub offset: u64,
+}
+
+/// 删除请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncDeleteRequest {
+    pub pair_id: String,
+    pub device_id: String,
+    /// 删除前文件应有的状态（与基准一致才删除）
+    pub entry: SyncEntry,
+}
+
+// ============================================================================
+// 配对管理
+// ============================================================================
+
+/// 创建同步配对并邀请对端设备
+pub async fn create_sync_pair(device_id: &str, local_dir: PathBuf) -> Result<SyncPair, SyncError> {
+    let peer = get_own_device(device_id)?;
+    validate_directory(&local_dir)?;
+
+    let pair = SyncPair {
+        pair_id: Uuid::new_v4().to_string(),
+        local_dir: local_dir.clone(),
+        peer_device_id: peer.device_id.clone(),
+        peer_device_name: peer.device_name.clone(),
+        enabled: true,
+        created_at: Utc::now().to_rfc3339(),
+        last_synced_at: None,
+    };
+
+    let invite = SyncInviteRequest {
+        pair_id: pair.pair_id.clone(),
+        device_id: get_my_device_id()?,
+        folder_name: local_dir
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    };
+
+    post_json::<_, serde_json::Value>(&peer, "invite", &invite, Some(10)).await?;
+
+    {
+        let store = get_sync_store();
+        let mut store = store.lock();
+        store.state.pairs.push(pair.clone());
+        store.save()?;
+    }
+
+    log::info!(
+        "[LanTransfer] 🔁 已创建同步配对: {:?} <-> {}",
+        local_dir, peer.device_name
+    );
+
+    Ok(pair)
+}
+
+/// 接受同步邀请（使用对端提供的配对 ID）
+pub fn join_sync_pair(
+    pair_id: &str,
+    device_id: &str,
+    local_dir: PathBuf,
+) -> Result<SyncPair, SyncError> {
+    let peer = get_own_device(device_id)?;
+    validate_directory(&local_dir)?;
+
+    let pair = SyncPair {
+        pair_id: pair_id.to_string(),
+        local_dir,
+        peer_device_id: peer.device_id.clone(),
+        peer_device_name: peer.device_name.clone(),
+        enabled: true,
+        created_at: Utc::now().to_rfc3339(),
+        last_synced_at: None,
+    };
+
+    let store = get_sync_store();
+    let mut store = store.lock();
+    store.state.pairs.retain(|p| p.pair_id != pair_id);
+    store.state.pairs.push(pair.clone());
+    store.save()?;
+
+    log::info!("[LanTransfer] 🔁 已加入同步配对: {}", pair_id);
+
+    Ok(pair)
+}
+
+/// 获取所有同步配对
+pub fn get_sync_pairs() -> Vec<SyncPair> {
+    get_sync_store().lock().state.pairs.clone()
+}
+
+/// 启用/暂停同步配对
+pub fn set_sync_pair_enabled(pair_id: &str, enabled: bool) -> Result<(), SyncError> {
+    let store = get_sync_store();
+    let mut store = store.lock();
+    let pair = store
+        .state
+        .pairs
+        .iter_mut()
+        .find(|p| p.pair_id == pair_id)
+        .ok_or_else(|| SyncError::PairNotFound(pair_id.to_string()))?;
+
+    pair.enabled = enabled;
+    store.save()
+}
+
+/// 移除同步配对（不删除任何文件）
+pub fn remove_sync_pair(pair_id: &str) -> Result<(), SyncError> {
+    let store = get_sync_store();
+    let mut store = store.lock();
+    store.state.pairs.retain(|p| p.pair_id != pair_id);
+    store.state.bases.remove(pair_id);
+    store.save()
+}
+
+/// 立即同步
+///
+/// 本机为协调方时直接执行，否则请求对端执行（返回的统计为本机视角）
+pub async fn sync_now(pair_id: &str) -> Result<SyncSummary, SyncError> {
+    let pair = get_sync_store().lock().pair(pair_id)?;
+
+    if is_coordinator(&pair)? {
+        return run_sync_cycle(&pair).await;
+    }
+
+    let peer = get_online_peer(&pair)?;
+    let request = SyncPairRequest {
+        pair_id: pair.pair_id.clone(),
+        device_id: get_my_device_id()?,
+    };
+    let summary: SyncSummary = post_json(&peer, "trigger", &request, None).await?;
+
+    Ok(SyncSummary {
+        uploaded: summary.downloaded,
+        downloaded: summary.uploaded,
+        ..summary
+    })
+}
+
+// ============================================================================
+// 后台同步任务
+// ============================================================================
+
+/// 启动后台同步任务（服务启动时调用）
+pub fn start_sync_task() {
+    let flag = get_sync_task_flag();
+    if flag.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    service::spawn(async move {
+        log::info!("[LanTransfer] 🔁 文件夹同步任务已启动");
+
+        while get_sync_task_flag().load(Ordering::SeqCst) {
+            tokio::time::sleep(Duration::from_secs(SYNC_INTERVAL_SECS)).await;
+
+            if !get_sync_task_flag().load(Ordering::SeqCst) {
+                break;
+            }
+
+            for pair in get_sync_pairs().into_iter().filter(|p| p.enabled) {
+                // 只有协调方主动同步，且对端必须在线
+                if !matches!(is_coordinator(&pair), Ok(true)) || get_online_peer(&pair).is_err() {
+                    continue;
+                }
+
+                if let Err(e) = run_sync_cycle(&pair).await {
+                    log::error!("[LanTransfer] 🔁 同步失败 {}: {}", pair.pair_id, e);
+                    let event = LanTransferEvent::SyncFailed {
+                        pair_id: pair.pair_id.clone(),
+                        error: e.to_string(),
+                    };
+                    let _ = get_event_sender().send(event.clone());
+                    emit_lan_event(&event);
+                }
+            }
+        }
+
+        log::info!("[LanTransfer] 🔁 文件夹同步任务已停止");
+    });
+}
+
+/// 停止后台同步任务（服务停止时调用）
+pub fn stop_sync_task() {
+    get_sync_task_flag().store(false, Ordering::SeqCst);
+}
+
+// ============================================================================
+// 同步执行（协调方）
+// ============================================================================
+
+/// 同步操作
+#[derive(Debug, Clone, PartialEq)]
+enum SyncAction {
+    /// 两端一致，更新基准
+    MarkSynced(SyncEntry),
+    /// 两端都已删除，移除基准
+    Forget(String),
+    /// 推送本地文件到对端
+    Push { entry: SyncEntry, conflict: bool },
+    /// 请求对端推送文件
+    Pull { entry: SyncEntry, conflict: bool },
+    /// 删除对端文件
+    DeleteRemote(SyncEntry),
+    /// 删除本地文件
+    DeleteLocal(SyncEntry),
+}
+
+/// 执行一次同步
+async fn run_sync_cycle(pair: &SyncPair) -> Result<SyncSummary, SyncError> {
+    {
+        let store = get_sync_store();
+        let mut store = store.lock();
+        if !store.running.insert(pair.pair_id.clone()) {
+            return Ok(SyncSummary::default());
+        }
+    }
+
+    let result = do_sync_cycle(pair).await;
+
+    get_sync_store().lock().running.remove(&pair.pair_id);
+
+    result
+}
+
+async fn do_sync_cycle(pair: &SyncPair) -> Result<SyncSummary, SyncError> {
+    let peer = get_online_peer(pair)?;
+    let my_device_id = get_my_device_id()?;
+
+    // 扫描本地目录（可能需要计算哈希，在阻塞线程执行）
+    let local_dir = pair.local_dir.clone();
+    let local = service::spawn_blocking(move || scan_directory(&local_dir))
+        .await
+        .map_err(|e| SyncError::Io(e.to_string()))??;
+
+    // 获取对端清单
+    let request = SyncPairRequest {
+        pair_id: pair.pair_id.clone(),
+        device_id: my_device_id.clone(),
+    };
+    let remote: Vec<SyncEntry> = post_json(&peer, "manifest", &request, Some(60)).await?;
+    let remote: HashMap<String, SyncEntry> = remote
+        .into_iter()
+        .map(|e| (e.relative_path.clone(), e))
+        .collect();
+
+    let base = get_sync_store()
+        .lock()
+        .state
+        .bases
+        .get(&pair.pair_id)
+        .cloned()
+        .unwrap_or_default();
+
+    let actions = plan_sync(&local, &remote, &base);
+    let mut summary = SyncSummary::default();
+    let mut pulls: Vec<SyncPushFile> = Vec::new();
+
+    for action in actions {
+        match action {
+            SyncAction::MarkSynced(entry) => update_base(&pair.pair_id, entry),
+            SyncAction::Forget(path) => remove_base(&pair.pair_id, &path),
+            SyncAction::Push { entry, conflict } => {
+                match push_file(pair, &peer, &my_device_id, &entry, conflict).await {
+                    Ok(()) => {
+                        summary.uploaded += 1;
+                        summary.conflicts += conflict as u32;
+                        update_base(&pair.pair_id, entry);
+                    }
+                    Err(e) => {
+                        log::error!(
+                            "[LanTransfer] 🔁 推送失败 {}: {}",
+                            entry.relative_path, e
+                        );
+                    }
+                }
+            }
+            SyncAction::Pull { entry, conflict } => {
+                summary.conflicts += conflict as u32;
+                pulls.push(SyncPushFile {
+                    relative_path: entry.relative_path,
+                    conflict,
+                });
+            }
+            SyncAction::DeleteRemote(entry) => {
+                let request = SyncDeleteRequest {
+                    pair_id: pair.pair_id.clone(),
+                    device_id: my_device_id.clone(),
+                    entry: entry.clone(),
+                };
+                match post_json::<_, serde_json::Value>(&peer, "delete", &request, Some(10)).await
+                {
+                    Ok(_) => {
+                        summary.deleted += 1;
+                        remove_base(&pair.pair_id, &entry.relative_path);
+                    }
+                    Err(e) => {
+                        log::error!(
+                            "[LanTransfer] 🔁 删除对端文件失败 {}: {}",
+                            entry.relative_path, e
+                        );
+                    }
+                }
+            }
+            SyncAction::DeleteLocal(entry) => match delete_local_file(pair, &entry) {
+                Ok(()) => {
+                    summary.deleted += 1;
+                    remove_base(&pair.pair_id, &entry.relative_path);
+                }
+                Err(e) => {
+                    log::error!(
+                        "[LanTransfer] 🔁 删除本地文件失败 {}: {}",
+                        entry.relative_path, e
+                    );
+                }
+            },
+        }
+    }
+
+    // 请求对端推送变化的文件（接收时在 handle_commit 中更新基准）
+    if !pulls.is_empty() {
+        let request = SyncPushRequest {
+            pair_id: pair.pair_id.clone(),
+            device_id: my_device_id.clone(),
+            files: pulls,
+        };
+        let response: SyncPushResponse = post_json(&peer, "request-push", &request, None).await?;
+        summary.downloaded += response.pushed;
+    }
+
+    {
+        let store = get_sync_store();
+        let mut store = store.lock();
+        if let Some(p) = store
+            .state
+            .pairs
+            .iter_mut()
+            .find(|p| p.pair_id == pair.pair_id)
+        {
+            p.last_synced_at = Some(Utc::now().to_rfc3339());
+        }
+        store.save()?;
+    }
+
+    let changed = summary.uploaded + summary.downloaded + summary.deleted + summary.conflicts;
+    if changed > 0 {
+        log::info!(
+            "[LanTransfer] 🔁 同步完成 {}: ↑{} ↓{} 删除{} 冲突{}",
+            pair.pair_id, summary.uploaded, summary.downloaded, summary.deleted, summary.conflicts
+        );
+
+        let event = LanTransferEvent::SyncCompleted {
+            pair_id: pair.pair_id.clone(),
+            summary: summary.clone(),
+        };
+        let _ = get_event_sender().send(event.clone());
+        emit_lan_event(&event);
+    }
+
+    Ok(summary)
+}
+
+/// 根据本地清单、对端清单和基准清单生成同步操作
+fn plan_sync(
+    local: &HashMap<String, SyncEntry>,
+    remote: &HashMap<String, SyncEntry>,
+    base: &HashMap<String, SyncEntry>,
+) -> Vec<SyncAction> {
+    fn same(a: Option<&SyncEntry>, b: Option<&SyncEntry>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => a.hash == b.hash && a.size == b.size,
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    let mut paths: Vec<&String> = local.keys().chain(remote.keys()).chain(base.keys()).collect();
+    paths.sort();
+    paths.dedup();
+
+    let mut actions = Vec::new();
+    for path in paths {
+        let (l, r, b) = (local.get(path), remote.get(path), base.get(path));
+
+        if same(l, r) {
+            match l {
+                Some(l) if !same(Some(l), b) => actions.push(SyncAction::MarkSynced(l.clone())),
+                None if b.is_some() => actions.push(SyncAction::Forget(path.clone())),
+                _ => {}
+            }
+            continue;
+        }
+
+        let local_changed = !same(l, b);
+        let remote_changed = !same(r, b);
+
+        let action = match (l, r, local_changed, remote_changed) {
+            // 只有一端变化
+            (Some(l), _, true, false) => SyncAction::Push {
+                entry: l.clone(),
+                conflict: false,
+            },
+            (None, Some(r), true, false) => SyncAction::DeleteRemote(r.clone()),
+            (_, Some(r), false, true) => SyncAction::Pull {
+                entry: r.clone(),
+                conflict: false,
+            },
+            (Some(l), None, false, true) => SyncAction::DeleteLocal(l.clone()),
+            // 两端都修改：修改时间较新的版本为准，另一端保留冲突副本
+            (Some(l), Some(r), _, _) => {
+                if l.modified_at >= r.modified_at {
+                    SyncAction::Push {
+                        entry: l.clone(),
+                        conflict: true,
+                    }
+                } else {
+                    SyncAction::Pull {
+                        entry: r.clone(),
+                        conflict: true,
+                    }
+                }
+            }
+            // 一端删除、另一端修改：保留修改
+            (Some(l), None, _, _) => SyncAction::Push {
+                entry: l.clone(),
+                conflict: false,
+            },
+            (None, Some(r), _, _) => SyncAction::Pull {
+                entry: r.clone(),
+                conflict: false,
+            },
+            (None, None, _, _) => SyncAction::Forget(path.clone()),
+        };
+
+        actions.push(action);
+    }
+
+    actions
+}
+
+/// 推送单个文件到对端（支持断点续传）
+async fn push_file(
+    pair: &SyncPair,
+    peer: &DiscoveredDevice,
+    my_device_id: &str,
+    entry: &SyncEntry,
+    conflict: bool,
+) -> Result<(), SyncError> {
+    let path = safe_join(&pair.local_dir, &entry.relative_path)?;
+
+    let header = SyncFileHeader {
+        pair_id: pair.pair_id.clone(),
+        device_id: my_device_id.to_string(),
+        entry: entry.clone(),
+        conflict,
+    };
+
+    let prepared: SyncPrepareResponse = post_json(peer, "prepare", &header, Some(10)).await?;
+
+    let mut file = tokio::fs::File::open(&path)
+        .await
+        .map_err(|e| SyncError::Io(e.to_string()))?;
+    file.seek(std::io::SeekFrom::Start(prepared.offset))
+        .await
+        .map_err(|e| SyncError::Io(e.to_string()))?;
+
+    let client = reqwest::Client::new();
+    let mut offset = prepared.offset;
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+
+    while offset < entry.size {
+        let n = file
+            .read(&mut buffer)
+            .await
+            .map_err(|e| SyncError::Io(e.to_string()))?;
+        if n == 0 {
+            break;
+        }
+
+        let url = format!(
+            "http://{}:{}/api/sync/chunk?pairId={}&deviceId={}&fileKey={}&offset={}",
+            peer.ip_address, peer.port, pair.pair_id, my_device_id, prepared.file_key, offset
+        );
+
+        let response = client
+            .post(&url)
+            .body(buffer[..n].to_vec())
+            .timeout(Duration::from_secs(60))
+            .send()
+            .await
+            .map_err(|e| SyncError::RequestFailed(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(SyncError::RequestFailed(format!("HTTP {}", response.status())));
+        }
+
+        offset += n as u64;
+    }
+
+    post_json::<_, serde_json::Value>(peer, "commit", &header, Some(120)).await?;
+
+    Ok(())
+}
+
+/// 删除本地文件（文件与基准一致时才删除）
+fn delete_local_file(pair: &SyncPair, entry: &SyncEntry) -> Result<(), SyncError> {
+    let path = safe_join(&pair.local_dir, &entry.relative_path)?;
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let current = hash_file(&path)?;
+    if current.hash != entry.hash {
+        return Err(SyncError::Modified(entry.relative_path.clone()));
+    }
+
+    fs::remove_file(&path).map_err(|e| SyncError::Io(e.to_string()))?;
+    log::info!("[LanTransfer] 🔁 已删除本地文件: {}", entry.relative_path);
+
+    Ok(())
+}
+
+// ============================================================================
+// 对端请求处理（由 server 调用）
+// ============================================================================
+
+/// 处理同步邀请
+pub fn handle_invite(request: &SyncInviteRequest, peer_ip: &str) -> Result<(), SyncError> {
+    let device = get_own_device(&request.device_id)?;
+    if device.ip_address != peer_ip {
+        return Err(SyncError::NotOwnDevice);
+    }
+
+    let trusted = config::is_device_trusted(&device.device_id);
+    log::info!(
+        "[LanTransfer] 🔁 收到同步邀请: {} 来自 {}{}",
+        request.folder_name,
+        device.device_name,
+        if trusted { "" } else { "（未验证）" }
+    );
+
+    let event = LanTransferEvent::SyncPairInvited {
+        pair_id: request.pair_id.clone(),
+        device,
+        folder_name: request.folder_name.clone(),
+        trusted,
+    };
+    let _ = get_event_sender().send(event.clone());
+    emit_lan_event(&event);
+
+    Ok(())
+}
+
+/// 返回本地清单
+pub async fn handle_manifest(
+    request: &SyncPairRequest,
+    peer_ip: &str,
+) -> Result<Vec<SyncEntry>, SyncError> {
+    let pair = authorize(&request.pair_id, &request.device_id, peer_ip)?;
+
+    let local_dir = pair.local_dir.clone();
+    let local = service::spawn_blocking(move || scan_directory(&local_dir))
+        .await
+        .map_err(|e| SyncError::Io(e.to_string()))??;
+
+    Ok(local.into_values().collect())
+}
+
+/// 对端（非协调方）请求立即同步
+pub async fn handle_trigger(
+    request: &SyncPairRequest,
+    peer_ip: &str,
+) -> Result<SyncSummary, SyncError> {
+    let pair = authorize(&request.pair_id, &request.device_id, peer_ip)?;
+    run_sync_cycle(&pair).await
+}
+
+/// 按协调方的请求推送文件
+pub async fn handle_push_request(
+    request: &SyncPushRequest,
+    peer_ip: &str,
+) -> Result<SyncPushResponse, SyncError> {
+    let pair = authorize(&request.pair_id, &request.device_id, peer_ip)?;
+    let peer = get_online_peer(&pair)?;
+    let my_device_id = get_my_device_id()?;
+
+    let mut response = SyncPushResponse::default();
+    for file in &request.files {
+        // 使用文件当前的状态（可能在清单交换后又有变化）
+        let result = match safe_join(&pair.local_dir, &file.relative_path) {
+            Ok(path) => match hash_file(&path) {
+                Ok(mut entry) => {
+                    entry.relative_path = file.relative_path.clone();
+                    push_file(&pair, &peer, &my_device_id, &entry, file.conflict).await
+                }
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(()) => response.pushed += 1,
+            Err(e) => {
+                response.failed += 1;
+                log::error!(
+                    "[LanTransfer] 🔁 推送失败 {}: {}",
+                    file.relative_path, e
+                );
+            }
+        }
+    }
+
+    Ok(response)
+}
+
+/// 准备接收文件（返回断点续传偏移量）
+pub fn handle_prepare(
+    header: &SyncFileHeader,
+    peer_ip: &str,
+) -> Result<SyncPrepareResponse, SyncError> {
+    let pair = authorize(&header.pair_id, &header.device_id, peer_ip)?;
+    safe_join(&pair.local_dir, &header.entry.relative_path)?;
+
+    let file_key = file_key(&pair.pair_id, &header.entry);
+    let temp_path = config::get_temp_file_path(&file_key);
+
+    let offset = match fs::metadata(&temp_path) {
+        Ok(metadata) if metadata.len() <= header.entry.size => metadata.len(),
+        _ => {
+            get_resume_manager()
+                .create_temp_file(&file_key)
+                .map_err(|e| SyncError::Io(e.to_string()))?;
+            0
+        }
+    };
+
+    get_sync_store()
+        .lock()
+        .prepared
+        .insert(file_key.clone(), header.entry.size);
+
+    Ok(SyncPrepareResponse { file_key, offset })
+}
+
+/// 写入数据块（必须按顺序）
+///
+/// file_key 必须是 handle_prepare 生成的键，写入范围不能超过准备时的文件大小
+pub fn handle_chunk(
+    pair_id: &str,
+    device_id: &str,
+    file_key: &str,
+    offset: u64,
+    data: &[u8],
+    peer_ip: &str,
+) -> Result<(), SyncError> {
+    let pair = authorize(pair_id, device_id, peer_ip)?;
+    if !file_key.starts_with(&format!("sync-{}-", pair.pair_id)) {
+        return Err(SyncError::InvalidPath(file_key.to_string()));
+    }
+    let size = get_sync_store()
+        .lock()
+        .prepared
+        .get(file_key)
+        .copied()
+        .ok_or_else(|| SyncError::InvalidPath(file_key.to_string()))?;
+    if offset.saturating_add(data.len() as u64) > size {
+        return Err(SyncError::InvalidPath(format!(
+            "数据块超出文件大小: offset={}, 长度={}, 文件大小={}",
+            offset,
+            data.len(),
+            size
+        )));
+    }
+
+    let temp_path = config::get_temp_file_path(file_key);
+    let received = fs::metadata(&temp_path)
+        .map_err(|e| SyncError::Io(e.to_string()))?
+        .len();
+    if received != offset {
+        return Err(SyncError::InvalidPath(format!(
+            "数据块位置无效: offset={}, 已接收={}",
+            offset, received
+        )));
+    }
+
+    let mut file = get_resume_manager()
+        .open_temp_file(file_key, offset)
+        .map_err(|e| SyncError::Io(e.to_string()))?;
+    file.write_all(data).map_err(|e| SyncError::Io(e.to_string()))
+}
+
+/// 提交文件：校验后移动到同步目录
+pub fn handle_commit(header: &SyncFileHeader, peer_ip: &str) -> Result<(), SyncError> {
+    let pair = authorize(&header.pair_id, &header.device_id, peer_ip)?;
+    let entry = &header.entry;
+    let dest = safe_join(&pair.local_dir, &entry.relative_path)?;
+
+    let file_key = file_key(&pair.pair_id, entry);
+    let temp_path = config::get_temp_file_path(&file_key);
+    get_sync_store().lock().prepared.remove(&file_key);
+
+    let hash = calculate_file_hash(&temp_path).map_err(|e| SyncError::Io(e.to_string()))?;
+    if hash != entry.hash {
+        let _ = fs::remove_file(&temp_path);
+        return Err(SyncError::HashMismatch(entry.relative_path.clone()));
+    }
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(|e| SyncError::Io(e.to_string()))?;
+    }
+
+    // 冲突：保留本地版本为冲突副本
+    if header.conflict && dest.exists() && hash_file(&dest)?.hash != entry.hash {
+        let copy = conflict_copy_path(&dest, &pair.peer_device_name);
+        fs::rename(&dest, &copy).map_err(|e| SyncError::Io(e.to_string()))?;
+
+        log::info!(
+            "[LanTransfer] 🔁 同步冲突，已保留本地副本: {:?}",
+            copy
+        );
+
+        let event = LanTransferEvent::SyncConflict {
+            pair_id: pair.pair_id.clone(),
+            relative_path: entry.relative_path.clone(),
+            conflict_copy: copy.to_string_lossy().to_string(),
+        };
+        let _ = get_event_sender().send(event.clone());
+        emit_lan_event(&event);
+    }
+
+    // 临时目录可能与同步目录不在同一文件系统，rename 失败时回退到复制
+    if fs::rename(&temp_path, &dest).is_err() {
+        fs::copy(&temp_path, &dest).map_err(|e| SyncError::Io(e.to_string()))?;
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    // 恢复修改时间，使两端清单一致
+    let modified = UNIX_EPOCH + Duration::from_millis(entry.modified_at.max(0) as u64);
+    if let Err(e) = fs::File::options()
+        .write(true)
+        .open(&dest)
+        .and_then(|f| f.set_modified(modified))
+    {
+        log::error!("[LanTransfer] 🔁 设置修改时间失败 {:?}: {}", dest, e);
+    }
+
+    {
+        let store = get_sync_store();
+        let mut store = store.lock();
+        store
+            .hash_cache
+            .insert(dest.clone(), (entry.size, entry.modified_at, entry.hash.clone()));
+    }
+
+    if matches!(is_coordinator(&pair), Ok(true)) {
+        update_base(&pair.pair_id, entry.clone());
+    }
+
+    log::info!("[LanTransfer] 🔁 已同步文件: {}", entry.relative_path);
+
+    Ok(())
+}
+
+/// 删除文件（文件与协调方记录的状态一致时才删除）
+pub fn handle_delete(request: &SyncDeleteRequest, peer_ip: &str) -> Result<(), SyncError> {
+    let pair = authorize(&request.pair_id, &request.device_id, peer_ip)?;
+    delete_local_file(&pair, &request.entry)
+}
+
+/// 正在接收的同步文件（已准备、尚未提交的 file_key），清理未完成接收时跳过
+pub fn get_receiving_file_keys() -> HashSet<String> {
+    get_sync_store().lock().prepared.keys().cloned().collect()
+}
+
+// ============================================================================
+// 内部函数
+// ============================================================================
+
+/// 获取本机设备 ID
+fn get_my_device_id() -> Result<String, SyncError> {
+    get_lan_transfer_state()
+        .local_device
+        .read()
+        .as_ref()
+        .map(|d| d.device_id.clone())
+        .ok_or(SyncError::DeviceOffline)
+}
+
+/// 获取本账号的在线设备
+fn get_own_device(device_id: &str) -> Result<DiscoveredDevice, SyncError> {
+    let state = get_lan_transfer_state();
+    let my_user_id = state
+        .local_device
+        .read()
+        .as_ref()
+        .map(|d| d.user_id.clone())
+        .unwrap_or_default();
+
+    let device = state
+        .devices
+        .read()
+        .get(device_id)
+        .cloned()
+        .ok_or(SyncError::DeviceOffline)?;
+
+    if my_user_id.is_empty() || device.user_id != my_user_id {
+        return Err(SyncError::NotOwnDevice);
+    }
+
+    Ok(device)
+}
+
+/// 获取配对的在线对端
+fn get_online_peer(pair: &SyncPair) -> Result<DiscoveredDevice, SyncError> {
+    get_own_device(&pair.peer_device_id)
+}
+
+/// 本机是否为配对的协调方（设备 ID 较小的一方）
+fn is_coordinator(pair: &SyncPair) -> Result<bool, SyncError> {
+    Ok(get_my_device_id()? < pair.peer_device_id)
+}
+
+/// 校验对端请求：配对存在、设备匹配、来源 IP 一致且为本账号设备
+fn authorize(pair_id: &str, device_id: &str, peer_ip: &str) -> Result<SyncPair, SyncError> {
+    let pair = get_sync_store().lock().pair(pair_id)?;
+    if pair.peer_device_id != device_id {
+        return Err(SyncError::PairNotFound(pair_id.to_string()));
+    }
+
+    let device = get_own_device(device_id)?;
+    if device.ip_address != peer_ip {
+        return Err(SyncError::NotOwnDevice);
+    }
+
+    Ok(pair)
+}
+
+/// 校验同步目录
+fn validate_directory(dir: &Path) -> Result<(), SyncError> {
+    if !dir.is_dir() {
+        return Err(SyncError::InvalidDirectory(dir.display().to_string()));
+    }
+    Ok(())
+}
+
+/// 将相对路径拼接到同步目录（只允许普通路径组件）
+fn safe_join(root: &Path, relative_path: &str) -> Result<PathBuf, SyncError> {
+    let relative = Path::new(relative_path);
+    if relative_path.is_empty()
+        || relative
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        return Err(SyncError::InvalidPath(relative_path.to_string()));
+    }
+
+    Ok(root.join(relative))
+}
+
+/// 临时文件标识（配对 + 路径 + 内容哈希，内容变化后不会续传到旧数据上）
+fn file_key(pair_id: &str, entry: &SyncEntry) -> String {
+    let digest = crc32fast::hash(format!("{}|{}", entry.relative_path, entry.hash).as_bytes());
+    format!("sync-{}-{:08x}", pair_id, digest)
+}
+
+/// 冲突副本路径：{名称} (冲突 {设备} {时间}).{扩展名}
+fn conflict_copy_path(path: &Path, device_name: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let timestamp = Utc::now().format("%Y%m%d-%H%M%S");
+    let name = match path.extension() {
+        Some(ext) => format!(
+            "{} (冲突 {} {}).{}",
+            stem,
+            device_name,
+            timestamp,
+            ext.to_string_lossy()
+        ),
+        None => format!("{} (冲突 {} {})", stem, device_name, timestamp),
+    };
+    path.with_file_name(name)
+}
+
+/// 计算文件条目（使用哈希缓存）
+fn hash_file(path: &Path) -> Result<SyncEntry, SyncError> {
+    let metadata = fs::metadata(path).map_err(|e| SyncError::Io(e.to_string()))?;
+    let size = metadata.len();
+    let modified_at = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0);
+
+    let cached = {
+        let store = get_sync_store();
+        let store = store.lock();
+        store
+            .hash_cache
+            .get(path)
+            .filter(|(s, m, _)| *s == size && *m == modified_at)
+            .map(|(_, _, h)| h.clone())
+    };
+
+    let hash = match cached {
+        Some(hash) => hash,
+        None => {
+            let hash = calculate_file_hash(path).map_err(|e| SyncError::Io(e.to_string()))?;
+            get_sync_store()
+                .lock()
+                .hash_cache
+                .insert(path.to_path_buf(), (size, modified_at, hash.clone()));
+            hash
+        }
+    };
+
+    Ok(SyncEntry {
+        relative_path: String::new(),
+        size,
+        modified_at,
+        hash,
+    })
+}
+
+/// 扫描同步目录（跳过符号链接）
+fn scan_directory(root: &Path) -> Result<HashMap<String, SyncEntry>, SyncError> {
+    validate_directory(root)?;
+
+    let mut entries = HashMap::new();
+    let mut dirs = vec![root.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let read_dir = fs::read_dir(&dir).map_err(|e| SyncError::Io(e.to_string()))?;
+        for entry in read_dir.filter_map(|e| e.ok()) {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let path = entry.path();
+
+            if file_type.is_dir() {
+                dirs.push(path);
+            } else if file_type.is_file() {
+                let Ok(relative) = path.strip_prefix(root) else {
+                    continue;
+                };
+                let relative_path = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().to_string())
+                    .collect::<Vec<_>>()
+                    .join("/");
+
+                match hash_file(&path) {
+                    Ok(mut sync_entry) => {
+                        sync_entry.relative_path = relative_path.clone();
+                        entries.insert(relative_path, sync_entry);
+                    }
+                    Err(e) => {
+                        log::error!("[LanTransfer] 🔁 读取文件失败 {:?}: {}", path, e);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+/// 更新基准清单
+fn update_base(pair_id: &str, entry: SyncEntry) {
+    let store = get_sync_store();
+    let mut store = store.lock();
+    store
+        .state
+        .bases
+        .entry(pair_id.to_string())
+        .or_default()
+        .insert(entry.relative_path.clone(), entry);
+}
+
+/// 从基准清单中移除
+fn remove_base(pair_id: &str, relative_path: &str) {
+    let store = get_sync_store();
+    let mut store = store.lock();
+    if let Some(base) = store.state.bases.get_mut(pair_id) {
+        base.remove(relative_path);
+    }
+}
+
+/// 向对端发送 JSON 请求（/api/sync/{action}）
+async fn post_json<T: Serialize, R: serde::de::DeserializeOwned>(
+    peer: &DiscoveredDevice,
+    action: &str,
+    body: &T,
+    timeout_secs: Option<u64>,
+) -> Result<R, SyncError> {
+    let url = format!(
+        "http://{}:{}/api/sync/{}",
+        peer.ip_address, peer.port, action
+    );
+
+    let mut request = reqwest::Client::new().post(&url).json(body);
+    if let Some(secs) = timeout_secs {
+        request = request.timeout(Duration::from_secs(secs));
+    }
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| SyncError::RequestFailed(e.to_string()))?;
+
+    if !response.status().is_success() {
+        return Err(SyncError::RequestFailed(format!("HTTP {}", response.status())));
+    }
+
+    response
+        .json()
+        .await
+        .map_err(|e| SyncError::RequestFailed(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lan_transfer::service::{LanTransferService, ServiceOptions};
+
+    fn entry(path: &str, hash: &str, modified_at: i64) -> SyncEntry {
+        SyncEntry {
+            relative_path: path.to_string(),
+            size: 1,
+            modified_at,
+            hash: hash.to_string(),
+        }
+    }
+
+    fn manifest(entries: &[SyncEntry]) -> HashMap<String, SyncEntry> {
+        entries
+            .iter()
+            .map(|e| (e.relative_path.clone(), e.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_sync_one_side_changes() {
+        let base = manifest(&[entry("a.txt", "1", 0), entry("b.txt", "1", 0)]);
+        let local = manifest(&[entry("a.txt", "2", 5), entry("b.txt", "1", 0)]);
+        let remote = manifest(&[entry("a.txt", "1", 0)]);
+
+        let actions = plan_sync(&local, &remote, &base);
+
+        assert_eq!(
+            actions,
+            vec![
+                SyncAction::Push {
+                    entry: entry("a.txt", "2", 5),
+                    conflict: false
+                },
+                SyncAction::DeleteLocal(entry("b.txt", "1", 0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_sync_conflict_newer_wins() {
+        let base = manifest(&[entry("a.txt", "1", 0)]);
+        let local = manifest(&[entry("a.txt", "2", 5)]);
+        let remote = manifest(&[entry("a.txt", "3", 9), entry("new.txt", "4", 1)]);
+
+        let actions = plan_sync(&local, &remote, &base);
+
+        assert_eq!(
+            actions,
+            vec![
+                SyncAction::Pull {
+                    entry: entry("a.txt", "3", 9),
+                    conflict: true
+                },
+                SyncAction::Pull {
+                    entry: entry("new.txt", "4", 1),
+                    conflict: false
+                },
+            ]
+        );
+    }
+
+    /// 创建本机为 "a"、已发现同账号设备 "b"（127.0.0.1）的服务实例
+    fn own_devices_service(dir: &Path) -> Arc<LanTransferService> {
+        let service = LanTransferService::new(ServiceOptions {
+            enable_mdns: false,
+            data_directory: Some(dir.to_path_buf()),
+            ..Default::default()
+        });
+        let now = Utc::now().to_rfc3339();
+        *service.state().local_device.write() = Some(DeviceInfo {
+            device_id: "a".to_string(),
+            device_name: "Desktop".to_string(),
+            user_id: "me".to_string(),
+            user_nickname: "Me".to_string(),
+            ip_address: "127.0.0.1".to_string(),
+            port: 0,
+            version: PROTOCOL_VERSION.to_string(),
+            os: std::env::consts::OS.to_string(),
+        });
+        service.state().devices.write().insert(
+            "b".to_string(),
+            DiscoveredDevice {
+                device_id: "b".to_string(),
+                device_name: "Laptop".to_string(),
+                user_id: "me".to_string(),
+                user_nickname: "Me".to_string(),
+                ip_address: "127.0.0.1".to_string(),
+                port: 0,
+                discovered_at: now.clone(),
+                last_seen: now,
+            },
+        );
+        service
+    }
+
+    #[test]
+    fn invites_report_whether_the_inviter_is_trusted() {
+        let dir = std::env::temp_dir().join(format!("huanvae-sync-{}", Uuid::new_v4()));
+        let service = own_devices_service(&dir);
+        let mut events = service.subscribe();
+
+        service.enter(|| {
+            let request = SyncInviteRequest {
+                pair_id: "p".to_string(),
+                device_id: "b".to_string(),
+                folder_name: "交接".to_string(),
+            };
+            // 来源 IP 与发现的不一致
+            assert!(matches!(
+                handle_invite(&request, "10.0.0.9"),
+                Err(SyncError::NotOwnDevice)
+            ));
+
+            handle_invite(&request, "127.0.0.1").unwrap();
+            config::add_trusted_device("b".to_string(), "Laptop".to_string()).unwrap();
+            handle_invite(&request, "127.0.0.1").unwrap();
+        });
+
+        for expected in [false, true] {
+            match events.try_recv().unwrap() {
+                LanTransferEvent::SyncPairInvited { trusted, .. } => assert_eq!(trusted, expected),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn chunks_only_write_prepared_files_within_size() {
+        let dir = std::env::temp_dir().join(format!("huanvae-sync-{}", Uuid::new_v4()));
+        let local_dir = dir.join("sync");
+        fs::create_dir_all(&local_dir).unwrap();
+        let service = own_devices_service(&dir);
+
+        service.enter(|| {
+            let now = Utc::now().to_rfc3339();
+            get_sync_store().lock().state.pairs.push(SyncPair {
+                pair_id: "p".to_string(),
+                local_dir: local_dir.clone(),
+                peer_device_id: "b".to_string(),
+                peer_device_name: "Laptop".to_string(),
+                enabled: true,
+                created_at: now,
+                last_synced_at: None,
+            });
+
+            let header = SyncFileHeader {
+                pair_id: "p".to_string(),
+                device_id: "b".to_string(),
+                entry: SyncEntry {
+                    relative_path: "a.txt".to_string(),
+                    size: 4,
+                    modified_at: 0,
+                    hash: "h".to_string(),
+                },
+                conflict: false,
+            };
+            let prepared = handle_prepare(&header, "127.0.0.1").unwrap();
+            assert_eq!(prepared.offset, 0);
+
+            // 未准备过的键（包括路径穿越）不能写入
+            for key in ["sync-p-../../escape", "sync-p-00000000"] {
+                assert!(matches!(
+                    handle_chunk("p", "b", key, 0, b"data", "127.0.0.1"),
+                    Err(SyncError::InvalidPath(_))
+                ));
+            }
+            assert!(!dir.join("escape.part").exists());
+
+            // 超出准备时的文件大小
+            assert!(matches!(
+                handle_chunk("p", "b", &prepared.file_key, 0, b"too long", "127.0.0.1"),
+                Err(SyncError::InvalidPath(_))
+            ));
+
+            handle_chunk("p", "b", &prepared.file_key, 0, b"da", "127.0.0.1").unwrap();
+            handle_chunk("p", "b", &prepared.file_key, 2, b"ta", "127.0.0.1").unwrap();
+            assert_eq!(
+                fs::read(config::get_temp_file_path(&prepared.file_key)).unwrap(),
+                b"data"
+            );
+            assert!(handle_chunk("p", "b", &prepared.file_key, 4, b"x", "127.0.0.1").is_err());
+        });
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
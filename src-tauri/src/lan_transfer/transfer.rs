@@ -7,6 +7,7 @@
  * - 点对点连接管理（请求、响应、断开）
  * - 连接请求失败自动重试（刷新设备信息后重试一次）
  * - 向已连接设备发送文件（无需再次确认）
+ * - 向已连接设备发送文本片段（剪贴板共享）
  * - 多文件并行批量传输（可配置并行度）
  * - 单文件取消支持（CancellationToken）
  * - 会话级批量取消支持
@@ -15,6 +16,8 @@
  * - 取消传输
  * - 详细传输调试日志
  * - 块上传自动重试（最多 3 次）
+ * - 共享读取缓存（ChunkCache，群发时多个接收方共用文件块）
+ * - 传输统计（每个文件发送结束后记录到 stats 模块，按对端设备汇总）
  *
  * 连接请求重试机制：
  * - 如果 HTTP 请求失败（连接超时/拒绝），可能是设备 IP 已变化
@@ -44,6 +47,11 @@
  * - ❌ 错误信息: 详细的错误位置和原因
  *
  * 更新日志：
+ * - 2026-10-18: 记录每个文件的传输统计（平均/峰值速度、块重试、哈希与发送耗时、网络接口）
+ * - 2026-10-18: 文件元信息携带修改时间、创建时间、权限位；向对端发送文件夹时保留相对路径
+ * - 2026-10-18: 传输请求/连接请求的响应支持附带拒绝原因（用于超时自动拒绝）
+ * - 2026-10-18: 请求连接时检测已降级的连接，失效则关闭后重新请求
+ * - 2026-10-18: 拆分直连会话创建与哈希计算，批量传输支持外部进度跟踪与共享读取缓存（用于多设备群发）
  * - 2026-01-25: 添加连接请求失败自动重试机制（刷新设备 IP 后重试）
  * - 2026-01-25: 修复批量进度不更新问题，在并行传输中同步发送 BatchProgress 事件
  * - 2026-01-25: 修复会话取消不生效问题，取消时正确触发所有文件的 CancellationToken
@@ -54,18 +62,20 @@
 
 use super::discovery::get_event_sender;
 use super::protocol::*;
+use super::service;
+use super::stats::TransferRecorder;
 use super::{emit_lan_event, get_lan_transfer_state};
 use chrono::Utc;
 use crc32fast::Hasher as Crc32Hasher;
 use futures::future::join_all;
 use parking_lot::RwLock;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::error::Error as StdError;
 use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
 use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 use tokio::sync::Semaphore;
 use tokio_util::sync::CancellationToken;
@@ -100,27 +110,23 @@ pub enum TransferError {
 // 传输会话管理
 // ============================================================================
 
-/// 活跃的传输会话
-static ACTIVE_SESSIONS: once_cell::sync::OnceCell<Arc<RwLock<HashMap<String, TransferSession>>>> =
-    once_cell::sync::OnceCell::new();
+/// 传输状态（每个服务实例一份，见 LanTransferService）
+#[derive(Default)]
+pub(crate) struct TransferState {
+    /// 活跃的传输会话
+    active_sessions: Arc<RwLock<HashMap<String, TransferSession>>>,
+    /// 文件取消令牌存储（file_id -> CancellationToken）
+    file_cancel_tokens: Arc<RwLock<HashMap<String, CancellationToken>>>,
+}
 
 /// 获取活跃会话
 fn get_active_sessions() -> Arc<RwLock<HashMap<String, TransferSession>>> {
-    ACTIVE_SESSIONS
-        .get_or_init(|| Arc::new(RwLock::new(HashMap::new())))
-        .clone()
+    service::current().transfers.active_sessions.clone()
 }
 
-/// 文件取消令牌存储（file_id -> CancellationToken）
-static FILE_CANCEL_TOKENS: once_cell::sync::OnceCell<
-    Arc<RwLock<HashMap<String, CancellationToken>>>,
-> = once_cell::sync::OnceCell::new();
-
 /// 获取文件取消令牌存储
 fn get_file_cancel_tokens() -> Arc<RwLock<HashMap<String, CancellationToken>>> {
-    FILE_CANCEL_TOKENS
-        .get_or_init(|| Arc::new(RwLock::new(HashMap::new())))
-        .clone()
+    service::current().transfers.file_cancel_tokens.clone()
 }
 
 /// 为文件创建取消令牌
@@ -147,7 +153,7 @@ pub async fn cancel_file_transfer(file_id: &str) -> Result<(), TransferError> {
 
     if let Some(token) = token {
         token.cancel();
-        println!("[LanTransfer] 📛 文件传输已取消: {}", file_id);
+        log::info!("[LanTransfer] 📛 文件传输已取消: {}", file_id);
 
         // 发送取消事件
         let event = LanTransferEvent::TransferFailed {
@@ -162,7 +168,7 @@ pub async fn cancel_file_transfer(file_id: &str) -> Result<(), TransferError> {
 }
 
 /// 并行传输进度跟踪
-struct ParallelProgress {
+pub(crate) struct ParallelProgress {
     /// 总字节数
     total_bytes: u64,
     /// 已传输字节数（原子更新）
@@ -175,6 +181,78 @@ struct ParallelProgress {
     session_id: String,
 }
 
+impl ParallelProgress {
+    pub(crate) fn new(session_id: &str, files: &[FileMetadata]) -> Self {
+        Self {
+            total_bytes: files.iter().map(|f| f.file_size).sum(),
+            transferred_bytes: AtomicU64::new(0),
+            completed_files: AtomicU32::new(0),
+            total_files: files.len() as u32,
+            session_id: session_id.to_string(),
+        }
+    }
+
+    pub(crate) fn transferred_bytes(&self) -> u64 {
+        self.transferred_bytes.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn completed_files(&self) -> u32 {
+        self.completed_files.load(Ordering::Relaxed)
+    }
+}
+
+/// 共享读取缓存（群发时多个接收方共用同一份文件块）
+///
+/// 按 (文件路径, 偏移量) 缓存最近读取的块，超出容量时淘汰最早的块
+pub(crate) struct ChunkCache {
+    capacity: usize,
+    chunks: parking_lot::Mutex<VecDeque<((String, u64), Arc<Vec<u8>>)>>,
+}
+
+impl ChunkCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            chunks: parking_lot::Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// 读取指定偏移量的块（命中缓存时不读盘）
+    fn read_chunk(
+        &self,
+        file_path: &str,
+        offset: u64,
+        file: &mut std::fs::File,
+    ) -> Result<Arc<Vec<u8>>, TransferError> {
+        if let Some((_, data)) = self
+            .chunks
+            .lock()
+            .iter()
+            .find(|((path, off), _)| *off == offset && path == file_path)
+        {
+            return Ok(data.clone());
+        }
+
+        file.seek(SeekFrom::Start(offset))
+            .map_err(|e| TransferError::FileReadFailed(e.to_string()))?;
+
+        let mut data = Vec::with_capacity(CHUNK_SIZE);
+        file.by_ref()
+            .take(CHUNK_SIZE as u64)
+            .read_to_end(&mut data)
+            .map_err(|e| TransferError::FileReadFailed(e.to_string()))?;
+        let data = Arc::new(data);
+
+        let mut chunks = self.chunks.lock();
+        if chunks.len() >= self.capacity {
+            chunks.pop_front();
+        }
+        chunks.push_back(((file_path.to_string(), offset), data.clone()));
+
+        Ok(data)
+    }
+}
+
 // ============================================================================
 // 连接管理（旧版兼容）
 // ============================================================================
@@ -244,7 +322,7 @@ pub async fn send_connection_request(device_id: &str) -> Result<String, Transfer
         .await
         .map_err(|e| TransferError::ConnectionFailed(e.to_string()))?;
 
-    println!(
+    log::info!(
         "[LanTransfer] 连接请求已发送到 {} ({})",
         target_device.device_name, target_device.ip_address
     );
@@ -270,7 +348,7 @@ pub async fn respond_to_request(request_id: &str, accept: bool) -> Result<(), Tr
         accepted: accept,
     });
 
-    println!(
+    log::info!(
         "[LanTransfer] 连接请求 {} 已{}: {} ({})",
         request_id,
         if accept { "接受" } else { "拒绝" },
@@ -296,43 +374,59 @@ pub async fn request_peer_connection(device_id: &str) -> Result<String, Transfer
     use super::server::get_active_peer_connections_map;
 
     // ========== 检查是否已存在与该设备的连接（去重）==========
-    {
+    let existing: Option<PeerConnection> = {
         let connections = get_active_peer_connections_map();
         let connections = connections.lock();
-        for (conn_id, conn) in connections.iter() {
-            if conn.peer_device.device_id == device_id
-                && conn.status == PeerConnectionStatus::Connected
-            {
-                println!(
-                    "[LanTransfer] 已存在与 {} 的连接: {}，跳过重复请求",
-                    device_id, conn_id
-                );
-                return Ok(conn_id.clone());
-            }
+        connections
+            .values()
+            .find(|conn| {
+                conn.peer_device.device_id == device_id
+                    && conn.status != PeerConnectionStatus::Disconnected
+            })
+            .cloned()
+    };
+
+    if let Some(conn) = existing {
+        if conn.status == PeerConnectionStatus::Connected {
+            log::info!(
+                "[LanTransfer] 已存在与 {} 的连接: {}，跳过重复请求",
+                device_id, conn.connection_id
+            );
+            return Ok(conn.connection_id);
+        }
+
+        // 连接已降级：立即检测一次，仍有效则复用，否则关闭后重新请求
+        if super::heartbeat::probe_connection(&conn.connection_id).await {
+            log::info!(
+                "[LanTransfer] 降级连接已恢复: {}，复用现有连接",
+                conn.connection_id
+            );
+            return Ok(conn.connection_id);
         }
+        super::heartbeat::close_connection(&conn.connection_id, "重新连接前检测失败");
     }
 
     // 尝试发送请求，失败后刷新设备信息并重试一次
     match do_request_peer_connection(device_id).await {
         Ok(connection_id) => Ok(connection_id),
         Err(first_error) => {
-            println!(
+            log::error!(
                 "[LanTransfer] ⚠️ 连接请求失败: {}，尝试刷新设备信息后重试",
                 first_error
             );
 
             // 触发 mDNS 刷新
             if let Err(e) = super::discovery::refresh_device(device_id) {
-                println!("[LanTransfer] 刷新设备失败: {}", e);
+                log::error!("[LanTransfer] 刷新设备失败: {}", e);
             }
 
             // 等待 mDNS 事件处理（1.5 秒）
             tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
 
             // 用最新信息重试一次
-            println!("[LanTransfer] 🔄 使用最新设备信息重试连接请求...");
+            log::info!("[LanTransfer] 🔄 使用最新设备信息重试连接请求...");
             do_request_peer_connection(device_id).await.map_err(|retry_error| {
-                println!(
+                log::error!(
                     "[LanTransfer] ❌ 重试失败: {}（原始错误: {}）",
                     retry_error, first_error
                 );
@@ -346,27 +440,27 @@ pub async fn request_peer_connection(device_id: &str) -> Result<String, Transfer
 async fn do_request_peer_connection(device_id: &str) -> Result<String, TransferError> {
     let state = get_lan_transfer_state();
 
-    println!("[LanTransfer] ========== 发起连接请求 ==========");
-    println!("[LanTransfer] 目标设备 ID: {}", device_id);
+    log::info!("[LanTransfer] ========== 发起连接请求 ==========");
+    log::info!("[LanTransfer] 目标设备 ID: {}", device_id);
 
     // 获取目标设备信息
     let target_device = {
         let devices = state.devices.read();
-        println!("[LanTransfer] 当前设备列表 ({} 个):", devices.len());
+        log::info!("[LanTransfer] 当前设备列表 ({} 个):", devices.len());
         for (id, dev) in devices.iter() {
-            println!("[LanTransfer]   - {} ({}) @ {}:{}", 
+            log::info!("[LanTransfer]   - {} ({}) @ {}:{}", 
                 dev.device_name, id, dev.ip_address, dev.port);
         }
         devices
             .get(device_id)
             .cloned()
             .ok_or_else(|| {
-                println!("[LanTransfer] ❌ 目标设备不在列表中: {}", device_id);
+                log::error!("[LanTransfer] ❌ 目标设备不在列表中: {}", device_id);
                 TransferError::DeviceNotFound(device_id.to_string())
             })?
     };
 
-    println!("[LanTransfer] ✓ 找到目标设备: {} @ {}:{}", 
+    log::info!("[LanTransfer] ✓ 找到目标设备: {} @ {}:{}", 
         target_device.device_name, target_device.ip_address, target_device.port);
 
     // 获取本机设备信息
@@ -375,12 +469,12 @@ async fn do_request_peer_connection(device_id: &str) -> Result<String, TransferE
         local
             .clone()
             .ok_or_else(|| {
-                println!("[LanTransfer] ❌ 本地服务未启动");
+                log::error!("[LanTransfer] ❌ 本地服务未启动");
                 TransferError::ConnectionFailed("本地服务未启动".to_string())
             })?
     };
 
-    println!("[LanTransfer] 本机信息: {} @ {}:{}", 
+    log::info!("[LanTransfer] 本机信息: {} @ {}:{}", 
         local_device.device_name, local_device.ip_address, local_device.port);
 
     // 构建请求数据
@@ -407,11 +501,11 @@ async fn do_request_peer_connection(device_id: &str) -> Result<String, TransferE
         target_device.ip_address, target_device.port
     );
 
-    println!("[LanTransfer] 📡 HTTP POST 请求:");
-    println!("[LanTransfer]   URL: {}", url);
-    println!("[LanTransfer]   本机 IP: {}:{}", local_device.ip_address, local_device.port);
-    println!("[LanTransfer]   目标 IP: {}:{}", target_device.ip_address, target_device.port);
-    println!("[LanTransfer]   超时: 5 秒");
+    log::info!("[LanTransfer] 📡 HTTP POST 请求:");
+    log::info!("[LanTransfer]   URL: {}", url);
+    log::info!("[LanTransfer]   本机 IP: {}:{}", local_device.ip_address, local_device.port);
+    log::info!("[LanTransfer]   目标 IP: {}:{}", target_device.ip_address, target_device.port);
+    log::info!("[LanTransfer]   超时: 5 秒");
 
     let start_time = std::time::Instant::now();
     let client = reqwest::Client::new();
@@ -423,15 +517,15 @@ async fn do_request_peer_connection(device_id: &str) -> Result<String, TransferE
         .await
         .map_err(|e| {
             let elapsed = start_time.elapsed();
-            println!("[LanTransfer] ❌ HTTP 请求失败 (耗时 {:?}): {}", elapsed, e);
+            log::error!("[LanTransfer] ❌ HTTP 请求失败 (耗时 {:?}): {}", elapsed, e);
             TransferError::ConnectionFailed(format!("{} (目标: {}:{})", e, target_device.ip_address, target_device.port))
         })?;
 
     let elapsed = start_time.elapsed();
-    println!("[LanTransfer] ✓ HTTP 响应收到 (耗时 {:?}): 状态码 {}", elapsed, response.status());
+    log::info!("[LanTransfer] ✓ HTTP 响应收到 (耗时 {:?}): 状态码 {}", elapsed, response.status());
 
     if !response.status().is_success() {
-        println!("[LanTransfer] ❌ 服务器返回错误状态码");
+        log::error!("[LanTransfer] ❌ 服务器返回错误状态码");
         return Err(TransferError::ConnectionFailed(format!(
             "服务器返回错误: {}",
             response.status()
@@ -448,7 +542,7 @@ async fn do_request_peer_connection(device_id: &str) -> Result<String, TransferE
         .json()
         .await
         .map_err(|e| {
-            println!("[LanTransfer] ❌ 解析响应 JSON 失败: {}", e);
+            log::error!("[LanTransfer] ❌ 解析响应 JSON 失败: {}", e);
             TransferError::ConnectionFailed(e.to_string())
         })?;
 
@@ -456,8 +550,8 @@ async fn do_request_peer_connection(device_id: &str) -> Result<String, TransferE
     // 连接只在对方接受后，通过 handle_peer_connection_response 创建
     // 这样可以避免去重检查误判，以及拒绝后需要清理的问题
 
-    println!("[LanTransfer] ✅ 连接请求成功，connection_id: {}", resp.connection_id);
-    println!("[LanTransfer] ========== 等待对方确认 ==========");
+    log::info!("[LanTransfer] ✅ 连接请求成功，connection_id: {}", resp.connection_id);
+    log::info!("[LanTransfer] ========== 等待对方确认 ==========");
 
     Ok(resp.connection_id)
 }
@@ -466,33 +560,50 @@ async fn do_request_peer_connection(device_id: &str) -> Result<String, TransferE
 pub async fn respond_peer_connection(
     connection_id: &str,
     accept: bool,
+) -> Result<(), TransferError> {
+    answer_peer_connection(connection_id, accept, None).await
+}
+
+/// 响应点对点连接请求（拒绝时可附带原因，超时自动拒绝也使用此函数）
+pub(crate) async fn answer_peer_connection(
+    connection_id: &str,
+    accept: bool,
+    reject_reason: Option<&str>,
 ) -> Result<(), TransferError> {
     use super::server::{get_active_peer_connections_map, get_pending_peer_connection_requests_map};
 
-    println!("[LanTransfer] ========== 响应连接请求 ==========");
-    println!("[LanTransfer] 连接 ID: {}", connection_id);
-    println!("[LanTransfer] 接受连接: {}", accept);
+    log::info!("[LanTransfer] ========== 响应连接请求 ==========");
+    log::info!("[LanTransfer] 连接 ID: {}", connection_id);
+    log::info!("[LanTransfer] 接受连接: {}", accept);
 
     // 获取待处理的连接请求
     let request = {
         let requests = get_pending_peer_connection_requests_map();
         let mut requests = requests.lock();
-        println!("[LanTransfer] 待处理请求列表 ({} 个):", requests.len());
+        log::info!("[LanTransfer] 待处理请求列表 ({} 个):", requests.len());
         for (id, req) in requests.iter() {
-            println!("[LanTransfer]   - {} 来自 {} @ {}:{}", 
+            log::info!("[LanTransfer]   - {} 来自 {} @ {}:{}", 
                 id, req.from_device.device_name, req.from_device.ip_address, req.from_device.port);
         }
         requests
             .remove(connection_id)
             .ok_or_else(|| {
-                println!("[LanTransfer] ❌ 找不到连接请求: {}", connection_id);
+                log::error!("[LanTransfer] ❌ 找不到连接请求: {}", connection_id);
                 TransferError::RequestNotFound(connection_id.to_string())
             })?
     };
 
-    println!("[LanTransfer] ✓ 找到请求，来自: {} @ {}:{}", 
+    log::info!("[LanTransfer] ✓ 找到请求，来自: {} @ {}:{}", 
         request.from_device.device_name, request.from_device.ip_address, request.from_device.port);
 
+    // 请求已从待处理列表移除：通知前端（含重放事件日志的视图）不再显示该请求
+    let event = LanTransferEvent::PeerConnectionRequestResolved {
+        connection_id: connection_id.to_string(),
+        accepted: accept,
+    };
+    let _ = get_event_sender().send(event.clone());
+    emit_lan_event(&event);
+
     let state = get_lan_transfer_state();
 
     // 获取本机设备信息
@@ -501,12 +612,12 @@ pub async fn respond_peer_connection(
         local
             .clone()
             .ok_or_else(|| {
-                println!("[LanTransfer] ❌ 本地服务未启动");
+                log::error!("[LanTransfer] ❌ 本地服务未启动");
                 TransferError::ConnectionFailed("本地服务未启动".to_string())
             })?
     };
 
-    println!("[LanTransfer] 本机信息: {} @ {}:{}", 
+    log::info!("[LanTransfer] 本机信息: {} @ {}:{}", 
         local_device.device_name, local_device.ip_address, local_device.port);
 
     // 构建响应数据
@@ -531,6 +642,8 @@ pub async fn respond_peer_connection(
         connection_id: String,
         accepted: bool,
         from_device: Option<DiscoveredDevice>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reject_reason: Option<String>,
     }
 
     // 发送响应到发起方
@@ -539,11 +652,11 @@ pub async fn respond_peer_connection(
         request.from_device.ip_address, request.from_device.port
     );
 
-    println!("[LanTransfer] 📡 发送 HTTP 响应:");
-    println!("[LanTransfer]   URL: {}", url);
-    println!("[LanTransfer]   本机 IP: {}:{}", local_device.ip_address, local_device.port);
-    println!("[LanTransfer]   目标 IP: {}:{}", request.from_device.ip_address, request.from_device.port);
-    println!("[LanTransfer]   超时: 10 秒");
+    log::info!("[LanTransfer] 📡 发送 HTTP 响应:");
+    log::info!("[LanTransfer]   URL: {}", url);
+    log::info!("[LanTransfer]   本机 IP: {}:{}", local_device.ip_address, local_device.port);
+    log::info!("[LanTransfer]   目标 IP: {}:{}", request.from_device.ip_address, request.from_device.port);
+    log::info!("[LanTransfer]   超时: 10 秒");
 
     let start_time = std::time::Instant::now();
     let client = reqwest::Client::new();
@@ -553,18 +666,19 @@ pub async fn respond_peer_connection(
             connection_id: connection_id.to_string(),
             accepted: accept,
             from_device: from_device.clone(),
+            reject_reason: reject_reason.map(|r| r.to_string()),
         })
         .timeout(std::time::Duration::from_secs(10))
         .send()
         .await
         .map_err(|e| {
             let elapsed = start_time.elapsed();
-            println!("[LanTransfer] ❌ HTTP 响应发送失败 (耗时 {:?}): {}", elapsed, e);
+            log::error!("[LanTransfer] ❌ HTTP 响应发送失败 (耗时 {:?}): {}", elapsed, e);
             TransferError::ConnectionFailed(format!("{} (目标: {}:{})", e, request.from_device.ip_address, request.from_device.port))
         })?;
 
     let elapsed = start_time.elapsed();
-    println!("[LanTransfer] ✓ HTTP 响应发送成功 (耗时 {:?})", elapsed);
+    log::info!("[LanTransfer] ✓ HTTP 响应发送成功 (耗时 {:?})", elapsed);
 
     if accept {
         // 接收方也创建连接
@@ -580,21 +694,27 @@ pub async fn respond_peer_connection(
             let connections = get_active_peer_connections_map();
             let mut connections = connections.lock();
             connections.insert(connection_id.to_string(), connection.clone());
-            println!("[LanTransfer] ✓ 连接已保存 (共 {} 个活跃连接)", connections.len());
+            log::info!("[LanTransfer] ✓ 连接已保存 (共 {} 个活跃连接)", connections.len());
         }
 
+        // 投递之前离线时排队的聊天消息
+        let peer_device_id = connection.peer_device.device_id.clone();
+        service::spawn(async move {
+            super::chat::flush_outbox(&peer_device_id).await;
+        });
+
         // 发送事件通知前端
         let event = LanTransferEvent::PeerConnectionEstablished { connection };
         let _ = get_event_sender().send(event.clone());
         emit_lan_event(&event);
-        println!("[LanTransfer] ✓ 已发送 PeerConnectionEstablished 事件到前端");
+        log::info!("[LanTransfer] ✓ 已发送 PeerConnectionEstablished 事件到前端");
     }
 
-    println!(
+    log::info!(
         "[LanTransfer] ========== {} 完成 ==========",
         if accept { "接受连接" } else { "拒绝连接" }
     );
-    println!(
+    log::info!(
         "[LanTransfer] 连接请求 {} 已{}: {} ({})",
         connection_id,
         if accept { "接受" } else { "拒绝" },
@@ -646,7 +766,7 @@ pub async fn disconnect_peer(connection_id: &str) -> Result<(), TransferError> {
         let _ = get_event_sender().send(event.clone());
         emit_lan_event(&event);
 
-        println!("[LanTransfer] 连接已断开: {}", connection_id);
+        log::info!("[LanTransfer] 连接已断开: {}", connection_id);
     }
 
     Ok(())
@@ -670,11 +790,130 @@ pub fn get_pending_peer_connection_requests() -> Vec<PeerConnectionRequest> {
     requests.values().cloned().collect()
 }
 
+/// 向已连接的设备发送文本片段（URL、密码、代码片段等）
+///
+/// 返回片段 ID
+pub async fn send_text_snippet(
+    connection_id: &str,
+    content: String,
+    format: SnippetFormat,
+) -> Result<String, TransferError> {
+    use super::server::get_active_peer_connections_map;
+
+    if content.is_empty() {
+        return Err(TransferError::TransferFailed("文本内容为空".to_string()));
+    }
+
+    if content.len() > MAX_SNIPPET_BYTES {
+        return Err(TransferError::TransferFailed(format!(
+            "文本过长（{} 字节，上限 {} 字节），请改用文件传输",
+            content.len(),
+            MAX_SNIPPET_BYTES
+        )));
+    }
+
+    // 获取连接信息
+    let connection = {
+        let connections = get_active_peer_connections_map();
+        let connections = connections.lock();
+        connections
+            .get(connection_id)
+            .cloned()
+            .ok_or_else(|| TransferError::ConnectionFailed("连接不存在".to_string()))?
+    };
+
+    if connection.status != PeerConnectionStatus::Connected {
+        return Err(TransferError::ConnectionFailed("连接已断开".to_string()));
+    }
+
+    // 获取本机设备信息
+    let state = get_lan_transfer_state();
+    let local_device = {
+        let local = state.local_device.read();
+        local
+            .clone()
+            .ok_or_else(|| TransferError::ConnectionFailed("本地服务未启动".to_string()))?
+    };
+
+    let now = Utc::now().to_rfc3339();
+    let snippet = TextSnippet {
+        snippet_id: Uuid::new_v4().to_string(),
+        connection_id: connection_id.to_string(),
+        from_device: DiscoveredDevice {
+            device_id: local_device.device_id.clone(),
+            device_name: local_device.device_name.clone(),
+            user_id: local_device.user_id.clone(),
+            user_nickname: local_device.user_nickname.clone(),
+            ip_address: local_device.ip_address.clone(),
+            port: local_device.port,
+            discovered_at: now.clone(),
+            last_seen: now.clone(),
+        },
+        content,
+        format,
+        sent_at: now,
+        received_at: String::new(),
+    };
+
+    let url = format!(
+        "http://{}:{}/api/text-snippet",
+        connection.peer_device.ip_address, connection.peer_device.port
+    );
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(&url)
+        .json(&snippet)
+        .timeout(std::time::Duration::from_secs(5))
+        .send()
+        .await
+        .map_err(|e| TransferError::ConnectionFailed(e.to_string()))?;
+
+    if !response.status().is_success() {
+        return Err(TransferError::TransferFailed(format!(
+            "服务器返回错误: {}",
+            response.status()
+        )));
+    }
+
+    log::info!(
+        "[LanTransfer] 文本片段已发送: {} -> {} ({} 字节)",
+        snippet.snippet_id,
+        connection.peer_device.device_name,
+        snippet.content.len()
+    );
+
+    Ok(snippet.snippet_id)
+}
+
 /// 向已连接的设备发送文件（无需再次确认）
 pub async fn send_files_to_peer(
     connection_id: &str,
     file_paths: Vec<String>,
 ) -> Result<String, TransferError> {
+    send_tree_to_peer(connection_id, file_paths, Vec::new()).await
+}
+
+/// 向已连接的对端发送文件夹中的文件（保留相对路径）
+///
+/// relative_paths 与 file_paths 一一对应，使用 / 分隔，接收方据此建立子目录
+pub async fn send_tree_to_peer(
+    connection_id: &str,
+    file_paths: Vec<String>,
+    relative_paths: Vec<String>,
+) -> Result<String, TransferError> {
+    let session_id = Uuid::new_v4().to_string();
+    send_tree_in_session(&session_id, connection_id, file_paths, relative_paths).await?;
+    Ok(session_id)
+}
+
+/// 使用指定的会话 ID 向已连接的对端发送文件（共享拉取先回复会话 ID，再在后台开始发送）
+pub(crate) async fn send_tree_in_session(
+    session_id: &str,
+    connection_id: &str,
+    file_paths: Vec<String>,
+    relative_paths: Vec<String>,
+) -> Result<(), TransferError> {
     use super::server::get_active_peer_connections_map;
 
     // 获取连接信息
@@ -692,33 +931,56 @@ pub async fn send_files_to_peer(
     }
 
     // 使用现有的批量传输逻辑
-    let session_id = start_direct_batch_transfer(
+    start_direct_batch_transfer(
+        session_id,
         connection_id,
         &connection.peer_device,
         file_paths,
+        &relative_paths,
     )
-    .await?;
-
-    Ok(session_id)
+    .await
 }
 
 /// 直接开始批量传输（已建立连接，无需确认）
 async fn start_direct_batch_transfer(
+    session_id: &str,
     connection_id: &str,
     target_device: &DiscoveredDevice,
     file_paths: Vec<String>,
-) -> Result<String, TransferError> {
-    let state = get_lan_transfer_state();
+    relative_paths: &[String],
+) -> Result<(), TransferError> {
+    // 计算哈希在阻塞线程池中进行，不占用异步运行时
+    let paths = file_paths.clone();
+    let (mut files, _total_size) = service::spawn_blocking(move || collect_file_metadata(&paths))
+        .await
+        .map_err(|e| TransferError::FileReadFailed(e.to_string()))??;
+    for (file, relative_path) in files.iter_mut().zip(relative_paths) {
+        file.relative_path = Some(relative_path.clone());
+    }
+    open_direct_session(session_id, connection_id, target_device, &files, &file_paths).await?;
 
-    // 获取本机设备信息
-    let local_device = {
-        let local = state.local_device.read();
-        local
-            .clone()
-            .ok_or_else(|| TransferError::ConnectionFailed("本地服务未启动".to_string()))?
-    };
+    // 启动批量传输
+    let session_id_clone = session_id.to_string();
+    let file_paths_clone = file_paths.clone();
+    service::spawn(async move {
+        if let Err(e) = start_batch_transfer(&session_id_clone, file_paths_clone).await {
+            log::error!("[LanTransfer] 批量传输失败: {}", e);
+        }
+    });
 
-    // 收集文件信息
+    log::info!(
+        "[LanTransfer] 开始向 {} 传输 {} 个文件",
+        target_device.device_name,
+        files.len()
+    );
+
+    Ok(())
+}
+
+/// 收集文件信息并计算哈希（大文件时发送 HashingProgress 事件）
+pub(crate) fn collect_file_metadata(
+    file_paths: &[String],
+) -> Result<(Vec<FileMetadata>, u64), TransferError> {
     let mut files: Vec<FileMetadata> = Vec::new();
     let mut total_size: u64 = 0;
 
@@ -747,6 +1009,7 @@ async fn start_direct_batch_transfer(
         // 计算文件哈希（大文件时显示进度）
         let file_name_for_progress = file_name.clone();
         let current_file = (index + 1) as u32;
+        let hash_start = Instant::now();
         let sha256 = calculate_file_hash_with_progress(path, Some(|processed, total| {
             emit_lan_event(&LanTransferEvent::HashingProgress {
                 file_name: file_name_for_progress.clone(),
@@ -761,20 +1024,71 @@ async fn start_direct_batch_transfer(
             .first_or_octet_stream()
             .to_string();
 
+        let file_id = Uuid::new_v4().to_string();
+        super::stats::record_hashing(&file_id, hash_start.elapsed());
+
         files.push(FileMetadata {
-            file_id: Uuid::new_v4().to_string(),
+            file_id,
             file_name,
             file_size,
             mime_type,
             sha256,
+            ..file_attributes(&metadata)
         });
     }
 
-    let session_id = Uuid::new_v4().to_string();
+    Ok((files, total_size))
+}
+
+/// 读取文件的时间戳和权限位（其余字段为默认值）
+fn file_attributes(metadata: &std::fs::Metadata) -> FileMetadata {
+    let to_millis = |time: std::io::Result<SystemTime>| {
+        time.ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_millis() as i64)
+    };
+
+    #[cfg(unix)]
+    let mode = {
+        use std::os::unix::fs::PermissionsExt;
+        Some(metadata.permissions().mode() & 0o7777)
+    };
+    #[cfg(not(unix))]
+    let mode = None;
+
+    FileMetadata {
+        modified_at: to_millis(metadata.modified()),
+        created_at: to_millis(metadata.created()),
+        mode,
+        ..Default::default()
+    }
+}
+
+/// 为已连接设备创建发送会话，并通知对方（标记为自动接受）
+///
+/// 会话 ID 由调用方生成，调用方负责启动实际的批量传输
+pub(crate) async fn open_direct_session(
+    session_id: &str,
+    connection_id: &str,
+    target_device: &DiscoveredDevice,
+    files: &[FileMetadata],
+    file_paths: &[String],
+) -> Result<(), TransferError> {
+    let state = get_lan_transfer_state();
+
+    // 获取本机设备信息
+    let local_device = {
+        let local = state.local_device.read();
+        local
+            .clone()
+            .ok_or_else(|| TransferError::ConnectionFailed("本地服务未启动".to_string()))?
+    };
+
+    let total_size: u64 = files.iter().map(|f| f.file_size).sum();
 
     // 创建传输会话
     let session = TransferSession {
-        session_id: session_id.clone(),
+        session_id: session_id.to_string(),
         connection_id: connection_id.to_string(),
         request_id: String::new(),
         files: files
@@ -786,7 +1100,7 @@ async fn start_direct_batch_transfer(
                 resume_info: None,
             })
             .collect(),
-        file_paths: file_paths.clone(),
+        file_paths: file_paths.to_vec(),
         status: SessionStatus::Transferring,
         created_at: Utc::now().to_rfc3339(),
         target_device: target_device.clone(),
@@ -797,7 +1111,7 @@ async fn start_direct_batch_transfer(
     {
         let sessions = get_active_sessions();
         let mut sessions = sessions.write();
-        sessions.insert(session_id.clone(), session);
+        sessions.insert(session_id.to_string(), session);
     }
 
     // 发送事件通知前端
@@ -833,7 +1147,7 @@ async fn start_direct_batch_transfer(
         .post(&url)
         .json(&TransferRequestBody {
             from_device,
-            files: files.clone(),
+            files: files.to_vec(),
             total_size,
             connection_id: connection_id.to_string(),
             auto_accept: true, // 已建立连接，自动接受
@@ -842,22 +1156,7 @@ async fn start_direct_batch_transfer(
         .send()
         .await;
 
-    // 启动批量传输
-    let session_id_clone = session_id.clone();
-    let file_paths_clone = file_paths.clone();
-    tokio::spawn(async move {
-        if let Err(e) = start_batch_transfer(&session_id_clone, file_paths_clone).await {
-            eprintln!("[LanTransfer] 批量传输失败: {}", e);
-        }
-    });
-
-    println!(
-        "[LanTransfer] 开始向 {} 传输 {} 个文件",
-        target_device.device_name,
-        files.len()
-    );
-
-    Ok(session_id)
+    Ok(())
 }
 
 // ============================================================================
@@ -911,6 +1210,7 @@ pub async fn send_transfer_request(
         // 计算文件哈希（大文件时显示进度）
         let file_name_for_progress = file_name.clone();
         let current_file = (index + 1) as u32;
+        let hash_start = Instant::now();
         let file_hash = calculate_file_hash_with_progress(path, Some(|processed, total| {
             emit_lan_event(&LanTransferEvent::HashingProgress {
                 file_name: file_name_for_progress.clone(),
@@ -927,6 +1227,7 @@ pub async fn send_transfer_request(
             .to_string();
 
         let file_id = Uuid::new_v4().to_string();
+        super::stats::record_hashing(&file_id, hash_start.elapsed());
 
         files.push(FileMetadata {
             file_id,
@@ -934,6 +1235,7 @@ pub async fn send_transfer_request(
             file_size,
             mime_type,
             sha256: file_hash,
+            ..file_attributes(&metadata)
         });
     }
 
@@ -1036,7 +1338,7 @@ pub async fn send_transfer_request(
 
     // 如果已经被接受（信任设备），直接开始传输
     if resp.accepted == Some(true) {
-        println!(
+        log::info!(
             "[LanTransfer] 传输请求已自动接受: {} -> {}",
             files.len(),
             target_device.device_name
@@ -1045,11 +1347,11 @@ pub async fn send_transfer_request(
         // 在后台开始传输
         let file_paths_clone = file_paths.clone();
         let request_id_clone = request_id.clone();
-        tokio::spawn(async move {
+        service::spawn(async move {
             let _ = start_batch_transfer(&request_id_clone, file_paths_clone).await;
         });
     } else {
-        println!(
+        log::info!(
             "[LanTransfer] 传输请求已发送，等待确认: {} -> {} ({} 个文件, {} 字节)",
             request_id,
             target_device.device_name,
@@ -1065,6 +1367,15 @@ pub async fn send_transfer_request(
 pub async fn respond_to_transfer_request(
     request_id: &str,
     accept: bool,
+) -> Result<(), TransferError> {
+    answer_transfer_request(request_id, accept, "用户拒绝").await
+}
+
+/// 响应传输请求（拒绝时附带原因，超时自动拒绝也使用此函数）
+pub(crate) async fn answer_transfer_request(
+    request_id: &str,
+    accept: bool,
+    reject_reason: &str,
 ) -> Result<(), TransferError> {
     use super::server::get_pending_transfer_requests_map;
 
@@ -1077,6 +1388,9 @@ pub async fn respond_to_transfer_request(
 
     let request = request.ok_or_else(|| TransferError::RequestNotFound(request_id.to_string()))?;
 
+    // 网页访客的请求由浏览器轮询状态，无需通知发送方
+    let is_web_request = super::web_share::resolve_request(request_id, accept);
+
     // 向发送方发送响应
     let url = format!(
         "http://{}:{}/api/transfer-response",
@@ -1097,17 +1411,19 @@ pub async fn respond_to_transfer_request(
         reject_reason: if accept {
             None
         } else {
-            Some("用户拒绝".to_string())
+            Some(reject_reason.to_string())
         },
     };
 
-    let client = reqwest::Client::new();
-    let _ = client
-        .post(&url)
-        .json(&body)
-        .timeout(std::time::Duration::from_secs(10))
-        .send()
-        .await;
+    if !is_web_request {
+        let client = reqwest::Client::new();
+        let _ = client
+            .post(&url)
+            .json(&body)
+            .timeout(std::time::Duration::from_secs(10))
+            .send()
+            .await;
+    }
 
     // 发送本地事件
     let event = LanTransferEvent::TransferRequestResponse {
@@ -1116,13 +1432,13 @@ pub async fn respond_to_transfer_request(
         reject_reason: if accept {
             None
         } else {
-            Some("用户拒绝".to_string())
+            Some(reject_reason.to_string())
         },
     };
     let _ = get_event_sender().send(event.clone());
     emit_lan_event(&event);
 
-    println!(
+    log::info!(
         "[LanTransfer] 传输请求 {} 已{}: {} 个文件来自 {}",
         request_id,
         if accept { "接受" } else { "拒绝" },
@@ -1146,6 +1462,19 @@ pub async fn respond_to_transfer_request(
 pub async fn start_batch_transfer(
     request_id: &str,
     file_paths: Vec<String>,
+) -> Result<(), TransferError> {
+    run_batch_transfer(request_id, file_paths, None, None).await
+}
+
+/// 批量传输实现
+///
+/// - progress: 调用方提供的进度跟踪（群发时用于汇总各设备进度），为空时内部创建
+/// - chunk_cache: 共享读取缓存（群发时多个设备共用），为空时直接读盘
+pub(crate) async fn run_batch_transfer(
+    request_id: &str,
+    file_paths: Vec<String>,
+    progress: Option<Arc<ParallelProgress>>,
+    chunk_cache: Option<Arc<ChunkCache>>,
 ) -> Result<(), TransferError> {
     // 获取会话信息
     let session = {
@@ -1175,15 +1504,11 @@ pub async fn start_batch_transfer(
     }
 
     let total_files = files.len() as u32;
-    let total_bytes: u64 = files.iter().map(|f| f.file.file_size).sum();
 
     // 创建并行进度跟踪
-    let progress = Arc::new(ParallelProgress {
-        total_bytes,
-        transferred_bytes: AtomicU64::new(0),
-        completed_files: AtomicU32::new(0),
-        total_files,
-        session_id: session_id.clone(),
+    let progress = progress.unwrap_or_else(|| {
+        let metas: Vec<FileMetadata> = files.iter().map(|f| f.file.clone()).collect();
+        Arc::new(ParallelProgress::new(&session_id, &metas))
     });
 
     // 发送初始进度
@@ -1192,7 +1517,7 @@ pub async fn start_batch_transfer(
     // 创建信号量限制并发数
     let semaphore = Arc::new(Semaphore::new(MAX_PARALLEL_TRANSFERS));
 
-    println!(
+    log::info!(
         "[LanTransfer] 🚀 开始并行批量传输: {} 个文件, 并行度 {}",
         total_files, MAX_PARALLEL_TRANSFERS
     );
@@ -1210,11 +1535,12 @@ pub async fn start_batch_transfer(
             let _request_id = request_id_owned.clone();
             let sem = semaphore.clone();
             let progress = progress.clone();
+            let chunk_cache = chunk_cache.clone();
 
             // 为每个文件创建取消令牌
             let cancel_token = create_cancel_token(&file_meta.file_id);
 
-            tokio::spawn(async move {
+            service::spawn(async move {
                 // 获取信号量许可（限制并发）
                 let _permit = sem.acquire().await.expect("Semaphore closed");
 
@@ -1232,6 +1558,7 @@ pub async fn start_batch_transfer(
                         &file_path,
                         index,
                         progress.clone(),
+                        chunk_cache,
                     ) => result,
                     _ = cancel_token.cancelled() => {
                         Err(TransferError::TransferFailed("用户取消".to_string()))
@@ -1271,7 +1598,7 @@ pub async fn start_batch_transfer(
                     }
                     Err(e) => {
                         fail_count += 1;
-                        eprintln!(
+                        log::error!(
                             "[LanTransfer] 文件传输失败: {} - {}",
                             file_meta.file_name, e
                         );
@@ -1293,7 +1620,7 @@ pub async fn start_batch_transfer(
             }
             Err(e) => {
                 fail_count += 1;
-                eprintln!("[LanTransfer] 任务执行错误: {}", e);
+                log::error!("[LanTransfer] 任务执行错误: {}", e);
             }
         }
     }
@@ -1323,7 +1650,7 @@ pub async fn start_batch_transfer(
     let _ = get_event_sender().send(event.clone());
     emit_lan_event(&event);
 
-    println!(
+    log::info!(
         "[LanTransfer] 批量传输完成: {}/{} 成功, {} 失败 -> {}",
         success_count, total_files, fail_count, target_device.device_name
     );
@@ -1370,7 +1697,7 @@ fn emit_batch_progress(progress: &ParallelProgress, current_file: Option<FileMet
     emit_lan_event(&event);
 }
 
-/// 执行单文件传输（并行版本）
+/// 执行单文件传输（并行版本），结束后记录传输统计
 async fn do_file_transfer_with_resume_parallel(
     target_device: &DiscoveredDevice,
     session_id: &str,
@@ -1378,10 +1705,48 @@ async fn do_file_transfer_with_resume_parallel(
     file_path: &str,
     _index: usize,
     progress: Arc<ParallelProgress>,
+    chunk_cache: Option<Arc<ChunkCache>>,
+) -> Result<u64, TransferError> {
+    let mut recorder = TransferRecorder::start(
+        session_id,
+        &file_meta.file_id,
+        &file_meta.file_name,
+        file_meta.file_size,
+        target_device,
+    );
+
+    let result = send_file_chunks(
+        target_device,
+        session_id,
+        file_meta,
+        file_path,
+        progress,
+        chunk_cache,
+        &mut recorder,
+    )
+    .await;
+
+    if recorder.attempted() {
+        let error = result.as_ref().err().map(|e| e.to_string());
+        super::stats::record_transfer(recorder.finish(error));
+    }
+
+    result
+}
+
+/// 单文件传输流程：prepare-upload、分块上传（带重试）、finish
+async fn send_file_chunks(
+    target_device: &DiscoveredDevice,
+    session_id: &str,
+    file_meta: &FileMetadata,
+    file_path: &str,
+    progress: Arc<ParallelProgress>,
+    chunk_cache: Option<Arc<ChunkCache>>,
+    recorder: &mut TransferRecorder,
 ) -> Result<u64, TransferError> {
     let base_url = format!("http://{}:{}", target_device.ip_address, target_device.port);
 
-    println!(
+    log::info!(
         "[LanTransfer] 📤 [并行] 开始传输文件: {} ({}) -> {}:{}",
         file_meta.file_name,
         format_bytes(file_meta.file_size),
@@ -1420,6 +1785,7 @@ async fn do_file_transfer_with_resume_parallel(
         return Err(TransferError::TransferFailed(reason));
     }
 
+    recorder.upload_started();
     let resume_offset = prepare_resp.resume_offset;
 
     // 2. 打开文件
@@ -1439,16 +1805,23 @@ async fn do_file_transfer_with_resume_parallel(
     let mut last_progress_time = Instant::now();
 
     loop {
-        let bytes_read = file
-            .read(&mut buffer)
-            .map_err(|e| TransferError::FileReadFailed(e.to_string()))?;
+        // 群发时通过共享缓存读取，避免同一文件被多次读盘
+        let cached_chunk;
+        let chunk_data: &[u8] = if let Some(cache) = &chunk_cache {
+            cached_chunk = cache.read_chunk(file_path, offset, &mut file)?;
+            &cached_chunk
+        } else {
+            let bytes_read = file
+                .read(&mut buffer)
+                .map_err(|e| TransferError::FileReadFailed(e.to_string()))?;
+            &buffer[..bytes_read]
+        };
+        let bytes_read = chunk_data.len();
 
         if bytes_read == 0 {
             break;
         }
 
-        let chunk_data = &buffer[..bytes_read];
-
         // 发送块（带重试）
         let upload_url = format!(
             "{}/api/upload?sessionId={}&fileId={}",
@@ -1457,8 +1830,10 @@ async fn do_file_transfer_with_resume_parallel(
 
         const MAX_RETRIES: u32 = 3;
         let mut last_error: Option<TransferError> = None;
+        let mut retries = 0;
 
         for retry in 0..=MAX_RETRIES {
+            retries = retry;
             if retry > 0 {
                 tokio::time::sleep(std::time::Duration::from_millis(500 * retry as u64)).await;
             }
@@ -1488,9 +1863,11 @@ async fn do_file_transfer_with_resume_parallel(
         }
 
         if let Some(e) = last_error {
+            recorder.chunk_failed(retries);
             return Err(e);
         }
 
+        recorder.chunk_sent(bytes_read as u64, retries);
         offset += bytes_read as u64;
 
         // 更新全局进度
@@ -1588,7 +1965,7 @@ async fn do_file_transfer_with_resume_parallel(
     let _ = get_event_sender().send(event.clone());
     emit_lan_event(&event);
 
-    println!(
+    log::info!(
         "[LanTransfer] ✅ [并行] 文件传输完成: {} -> {}",
         file_meta.file_name, saved_path
     );
@@ -1597,7 +1974,7 @@ async fn do_file_transfer_with_resume_parallel(
 }
 
 /// 格式化字节大小为人类可读格式
-fn format_bytes(bytes: u64) -> String {
+pub(crate) fn format_bytes(bytes: u64) -> String {
     const KB: u64 = 1024;
     const MB: u64 = KB * 1024;
     const GB: u64 = MB * 1024;
@@ -1630,7 +2007,7 @@ async fn do_file_transfer_with_resume(
     let base_url = format!("http://{}:{}", target_device.ip_address, target_device.port);
 
     // 调试日志：传输开始
-    println!(
+    log::info!(
         "[LanTransfer] 📤 开始传输文件 [{}/{}]: {} ({}) -> {}:{}",
         file_index + 1,
         total_files,
@@ -1644,7 +2021,7 @@ async fn do_file_transfer_with_resume(
 
     // 1. 发送准备上传请求
     let prepare_url = format!("{}/api/prepare-upload", base_url);
-    println!("[LanTransfer] 📡 发送 prepare-upload 请求: {}", prepare_url);
+    log::info!("[LanTransfer] 📡 发送 prepare-upload 请求: {}", prepare_url);
 
     let prepare_request = PrepareUploadRequest {
         session_id: session_id.to_string(),
@@ -1660,21 +2037,21 @@ async fn do_file_transfer_with_resume(
         .send()
         .await
         .map_err(|e| {
-            println!("[LanTransfer] ❌ prepare-upload 请求失败: {}", e);
+            log::error!("[LanTransfer] ❌ prepare-upload 请求失败: {}", e);
             TransferError::TransferFailed(format!("prepare-upload 失败: {}", e))
         })?;
 
-    println!(
+    log::info!(
         "[LanTransfer] 📡 prepare-upload 响应状态: {}",
         prepare_response.status()
     );
 
     let prepare_resp: PrepareUploadResponse = prepare_response.json().await.map_err(|e| {
-        println!("[LanTransfer] ❌ prepare-upload 响应解析失败: {}", e);
+        log::error!("[LanTransfer] ❌ prepare-upload 响应解析失败: {}", e);
         TransferError::TransferFailed(format!("prepare-upload 响应解析失败: {}", e))
     })?;
 
-    println!(
+    log::info!(
         "[LanTransfer] 📡 prepare-upload 结果: accepted={}, resume_offset={}",
         prepare_resp.accepted, prepare_resp.resume_offset
     );
@@ -1683,13 +2060,13 @@ async fn do_file_transfer_with_resume(
         let reason = prepare_resp
             .reject_reason
             .unwrap_or_else(|| "对方拒绝接收".to_string());
-        println!("[LanTransfer] ❌ 传输被拒绝: {}", reason);
+        log::error!("[LanTransfer] ❌ 传输被拒绝: {}", reason);
         return Err(TransferError::TransferFailed(reason));
     }
 
     let resume_offset = prepare_resp.resume_offset;
     if resume_offset > 0 {
-        println!(
+        log::info!(
             "[LanTransfer] 🔄 断点续传: {} 从 {} 字节继续",
             file_meta.file_name,
             format_bytes(resume_offset)
@@ -1697,21 +2074,21 @@ async fn do_file_transfer_with_resume(
     }
 
     // 2. 打开文件并定位到续传位置
-    println!("[LanTransfer] 📂 打开文件: {}", file_path);
+    log::info!("[LanTransfer] 📂 打开文件: {}", file_path);
     let mut file = std::fs::File::open(file_path).map_err(|e| {
-        println!("[LanTransfer] ❌ 文件打开失败: {}", e);
+        log::error!("[LanTransfer] ❌ 文件打开失败: {}", e);
         TransferError::FileReadFailed(e.to_string())
     })?;
 
     if resume_offset > 0 {
         file.seek(SeekFrom::Start(resume_offset)).map_err(|e| {
-            println!("[LanTransfer] ❌ 文件定位失败: {}", e);
+            log::error!("[LanTransfer] ❌ 文件定位失败: {}", e);
             TransferError::FileReadFailed(e.to_string())
         })?;
     }
 
     // 3. 分块上传文件
-    println!(
+    log::info!(
         "[LanTransfer] 📦 开始分块上传，块大小: {}",
         format_bytes(CHUNK_SIZE as u64)
     );
@@ -1725,12 +2102,12 @@ async fn do_file_transfer_with_resume(
 
     loop {
         let bytes_read = file.read(&mut buffer).map_err(|e| {
-            println!("[LanTransfer] ❌ 文件读取失败: {}", e);
+            log::error!("[LanTransfer] ❌ 文件读取失败: {}", e);
             TransferError::FileReadFailed(e.to_string())
         })?;
 
         if bytes_read == 0 {
-            println!("[LanTransfer] 📦 文件读取完成，共 {} 个块", chunk_count);
+            log::info!("[LanTransfer] 📦 文件读取完成，共 {} 个块", chunk_count);
             break;
         }
 
@@ -1749,7 +2126,7 @@ async fn do_file_transfer_with_resume(
 
         for retry in 0..=MAX_RETRIES {
             if retry > 0 {
-                println!(
+                log::info!(
                     "[LanTransfer] 🔄 重试块上传 (块 #{}, 第 {}/{} 次重试)",
                     chunk_count, retry, MAX_RETRIES
                 );
@@ -1776,7 +2153,7 @@ async fn do_file_transfer_with_resume(
                             } else {
                                 let error =
                                     chunk_resp.error.unwrap_or_else(|| "块传输失败".to_string());
-                                println!(
+                                log::error!(
                                     "[LanTransfer] ❌ 块传输失败 (块 #{}, offset={}): {}",
                                     chunk_count, offset, error
                                 );
@@ -1784,7 +2161,7 @@ async fn do_file_transfer_with_resume(
                             }
                         }
                         Err(e) => {
-                            println!(
+                            log::error!(
                                 "[LanTransfer] ❌ 块响应解析失败 (块 #{}, status={}): {}",
                                 chunk_count, response_status, e
                             );
@@ -1815,7 +2192,7 @@ async fn do_file_transfer_with_resume(
                         .map(|s| format!(" (底层: {})", s))
                         .unwrap_or_default();
 
-                    println!(
+                    log::error!(
                         "[LanTransfer] ❌ 块上传请求失败 (块 #{}, offset={}, 类型={}, 重试={}/{}): {}{}",
                         chunk_count, offset, error_type, retry, MAX_RETRIES, e, source_error
                     );
@@ -1829,7 +2206,7 @@ async fn do_file_transfer_with_resume(
 
         // 如果所有重试都失败了
         if let Some(err) = last_error {
-            println!(
+            log::error!(
                 "[LanTransfer] ❌ 块 #{} 在 {} 次重试后仍然失败",
                 chunk_count, MAX_RETRIES
             );
@@ -1861,7 +2238,7 @@ async fn do_file_transfer_with_resume(
             if offset - last_log_offset >= 5 * 1024 * 1024 {
                 last_log_offset = offset;
                 let progress_pct = (offset as f64 / file_meta.file_size as f64) * 100.0;
-                println!(
+                log::info!(
                     "[LanTransfer] 📊 传输进度: {}/{} ({:.1}%), 速度: {}/s, 剩余: {}",
                     format_bytes(offset),
                     format_bytes(file_meta.file_size),
@@ -1929,7 +2306,7 @@ async fn do_file_transfer_with_resume(
     );
 
     let elapsed_total = start_time.elapsed();
-    println!(
+    log::info!(
         "[LanTransfer] 📡 发送 finish 请求: {} (耗时: {:.2}s)",
         finish_url,
         elapsed_total.as_secs_f64()
@@ -1941,17 +2318,17 @@ async fn do_file_transfer_with_resume(
         .send()
         .await
         .map_err(|e| {
-            println!("[LanTransfer] ❌ finish 请求失败: {}", e);
+            log::error!("[LanTransfer] ❌ finish 请求失败: {}", e);
             TransferError::TransferFailed(format!("finish 请求失败: {}", e))
         })?;
 
-    println!(
+    log::info!(
         "[LanTransfer] 📡 finish 响应状态: {}",
         finish_response.status()
     );
 
     let finish_resp: FinishUploadResponse = finish_response.json().await.map_err(|e| {
-        println!("[LanTransfer] ❌ finish 响应解析失败: {}", e);
+        log::error!("[LanTransfer] ❌ finish 响应解析失败: {}", e);
         TransferError::TransferFailed(format!("finish 响应解析失败: {}", e))
     })?;
 
@@ -1959,7 +2336,7 @@ async fn do_file_transfer_with_resume(
         let error = finish_resp
             .error
             .unwrap_or_else(|| "传输完成验证失败".to_string());
-        println!("[LanTransfer] ❌ finish 验证失败: {}", error);
+        log::error!("[LanTransfer] ❌ finish 验证失败: {}", error);
         return Err(TransferError::TransferFailed(error));
     }
 
@@ -1977,7 +2354,7 @@ async fn do_file_transfer_with_resume(
     let _ = get_event_sender().send(event.clone());
     emit_lan_event(&event);
 
-    println!(
+    log::info!(
         "[LanTransfer] 文件传输完成 [{}/{}]: {} -> {}",
         file_index + 1,
         total_files,
@@ -2013,8 +2390,7 @@ pub async fn send_file(
 /// - 速度: ~7.3 GB/s (比 SHA-256 快约 14 倍)
 /// - 流式处理: 无需将整个文件读入内存
 /// - 跨平台: 支持 Android AOSP, Windows, macOS, Linux, iOS
-#[allow(dead_code)]
-fn calculate_file_hash(path: &Path) -> Result<String, TransferError> {
+pub fn calculate_file_hash(path: &Path) -> Result<String, TransferError> {
     calculate_file_hash_with_progress(path, Option::<fn(u64, u64)>::None)
 }
 
@@ -2093,7 +2469,7 @@ pub async fn cancel_transfer(transfer_id: &str) -> Result<(), TransferError> {
     let _ = get_event_sender().send(event.clone());
     emit_lan_event(&event);
 
-    println!("[LanTransfer] 传输已取消: {}", transfer_id);
+    log::info!("[LanTransfer] 传输已取消: {}", transfer_id);
 
     Ok(())
 }
@@ -2137,7 +2513,7 @@ pub async fn cancel_session(request_id: &str) -> Result<(), TransferError> {
         for file_id in &file_ids_to_cancel {
             if let Some(token) = tokens_read.get(file_id) {
                 token.cancel();
-                println!("[LanTransfer] 📛 取消文件传输: {}", file_id);
+                log::info!("[LanTransfer] 📛 取消文件传输: {}", file_id);
             }
         }
     }
@@ -2151,7 +2527,7 @@ pub async fn cancel_session(request_id: &str) -> Result<(), TransferError> {
     let _ = get_event_sender().send(event.clone());
     emit_lan_event(&event);
 
-    println!(
+    log::info!(
         "[LanTransfer] 会话已取消: {}, 取消了 {} 个文件",
         request_id,
         file_ids_to_cancel.len()
@@ -2172,4 +2548,43 @@ pub fn get_all_sessions() -> Vec<TransferSession> {
     let sessions = get_active_sessions();
     let sessions = sessions.read();
     sessions.values().cloned().collect()
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunk_cache_hits_and_evicts_oldest() {
+        let dir = std::env::temp_dir().join(format!("huanvae-chunk-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("data.bin");
+        std::fs::write(&path, vec![1u8; CHUNK_SIZE * 2 + 10]).unwrap();
+        let key = path.to_string_lossy().to_string();
+        let offsets = [0, CHUNK_SIZE as u64, CHUNK_SIZE as u64 * 2];
+
+        let cache = ChunkCache::new(2);
+        let mut file = std::fs::File::open(&path).unwrap();
+        let first = cache.read_chunk(&key, offsets[0], &mut file).unwrap();
+        assert_eq!(first.len(), CHUNK_SIZE);
+
+        // 文件内容变化后，命中缓存的块仍返回缓存中的数据（未读盘）
+        std::fs::write(&path, vec![2u8; CHUNK_SIZE * 2 + 10]).unwrap();
+        let mut file = std::fs::File::open(&path).unwrap();
+        let hit = cache.read_chunk(&key, offsets[0], &mut file).unwrap();
+        assert!(Arc::ptr_eq(&first, &hit));
+
+        // 读入两个新块后最早的块被淘汰，重新从磁盘读取
+        cache.read_chunk(&key, offsets[1], &mut file).unwrap();
+        let last = cache.read_chunk(&key, offsets[2], &mut file).unwrap();
+        assert_eq!(*last, vec![2u8; 10]);
+        let reread = cache.read_chunk(&key, offsets[0], &mut file).unwrap();
+        assert!(!Arc::ptr_eq(&first, &reread));
+        assert_eq!(*reread, vec![2u8; CHUNK_SIZE]);
+
+        // 不同文件的相同偏移量不会命中
+        let other = cache.read_chunk("other", offsets[2], &mut file).unwrap();
+        assert!(!Arc::ptr_eq(&last, &other));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,914 @@
+/*!
+ * 网页分享模块
+ *
+ * 未安装本应用的访客可通过浏览器访问局域网服务端口，与本机互传文件
+ *
+ * 功能：
+ * - 手动开启（默认关闭），停止局域网服务时自动关闭
+ * - 浏览器访问 http://{本机IP}:53317/web 打开分享页面
+ * - 一次性 PIN 码：在本机界面显示，访客输入正确后 PIN 立即失效（需要时重新生成）
+ * - PIN 连续输错 MAX_PIN_ATTEMPTS 次后作废，防止暴力猜测
+ * - 访客上传：文件保存到接收目录（与普通传输相同的临时文件 + finalize 流程）
+ * - 访客下载：只能下载本机选择分享的文件
+ *
+ * 确认机制：
+ * - 访客的每次上传/下载都会生成 TransferRequest，走普通的 TransferRequestReceived 事件
+ * - 用户通过 respond_to_transfer_request 接受/拒绝，浏览器轮询请求状态
+ * - 访客设备 ID 以 "web-" 开头，前端可据此区分显示
+ *
+ * 访客身份：
+ * - 登录成功后返回访问令牌，浏览器后续请求通过 X-Web-Token 头（下载链接通过 token 参数）携带
+ * - 令牌与登录时的 IP 绑定
+ *
+ * 上传顺序：
+ * - 同一文件同时只允许写入一个数据块：校验 offset 时在锁内登记该文件，写入完成后再更新进度并解除，
+ *   并发到达的数据块直接拒绝（浏览器按顺序重试）
+ *
+ * 更新日志：
+ * - 2026-10-18: 修复并发上传数据块时 offset 校验与进度更新之间的竞争
+ */
+
+use super::discovery::get_event_sender;
+use super::post_receive;
+use super::protocol::*;
+use super::service;
+use super::resume::get_resume_manager;
+use super::server::{get_pending_transfer_requests_map, insert_pending_transfer_request};
+use super::{config, emit_lan_event, get_lan_transfer_state};
+use chrono::Utc;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// 网页分享页面
+pub const WEB_SHARE_PAGE: &str = include_str!("web_share.html");
+
+/// 访客设备 ID 前缀
+pub const WEB_GUEST_DEVICE_PREFIX: &str = "web-";
+
+/// PIN 最大尝试次数，超过后 PIN 作废
+const MAX_PIN_ATTEMPTS: u32 = 5;
+
+// ============================================================================
+// 错误类型
+// ============================================================================
+
+#[derive(Error, Debug)]
+pub enum WebShareError {
+    #[error("网页分享未开启")]
+    Disabled,
+    #[error("局域网服务未启动")]
+    ServiceNotRunning,
+    #[error("PIN 码错误或已失效")]
+    InvalidPin,
+    #[error("未授权的访问")]
+    Unauthorized,
+    #[error("请求未找到: {0}")]
+    RequestNotFound(String),
+    #[error("请求尚未被接受")]
+    NotAccepted,
+    #[error("文件未找到: {0}")]
+    FileNotFound(String),
+    #[error("无效的请求: {0}")]
+    InvalidRequest(String),
+    #[error("文件读写失败: {0}")]
+    Io(String),
+    #[error("待确认的请求过多，请稍后再试")]
+    TooManyRequests,
+}
+
+// ============================================================================
+// 数据结构
+// ============================================================================
+
+/// 分享给访客的文件
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebSharedFile {
+    /// 文件 ID
+    pub file_id: String,
+    /// 文件名
+    pub file_name: String,
+    /// 文件大小（字节）
+    pub file_size: u64,
+    /// 本地路径（不返回给浏览器）
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+/// 网页分享状态（返回给本机前端）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebShareInfo {
+    /// 当前 PIN 码（已使用或作废时为 None）
+    pub pin: Option<String>,
+    /// 访问地址
+    pub url: String,
+    /// 分享的文件
+    pub shared_files: Vec<WebSharedFile>,
+    /// 已登录的访客数
+    pub guest_count: usize,
+    /// 开启时间
+    pub started_at: String,
+}
+
+/// 浏览器上传请求中的文件条目
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebUploadEntry {
+    pub name: String,
+    pub size: u64,
+}
+
+/// 已登录的访客
+#[derive(Debug, Clone)]
+struct WebGuest {
+    guest_id: String,
+    ip: String,
+    user_agent: String,
+}
+
+/// 访客请求类型
+#[derive(Debug, Clone, PartialEq)]
+enum WebRequestKind {
+    Upload,
+    Download,
+}
+
+/// 访客请求
+#[derive(Debug, Clone)]
+struct WebRequest {
+    token: String,
+    kind: WebRequestKind,
+    files: Vec<FileMetadata>,
+    status: TransferRequestStatus,
+    /// 上传进度：file_id -> 已接收字节数
+    received: HashMap<String, u64>,
+    /// 正在写入数据块的文件（file_id）
+    writing: HashSet<String>,
+    /// 已完成的文件数
+    completed_files: u32,
+}
+
+/// 网页分享运行状态
+struct WebShareState {
+    pin: Option<String>,
+    failed_attempts: u32,
+    shared_files: Vec<WebSharedFile>,
+    guests: HashMap<String, WebGuest>,
+    requests: HashMap<String, WebRequest>,
+    started_at: String,
+}
+
+/// 网页分享状态槽（每个服务实例一份，见 LanTransferService；None 表示未开启）
+#[derive(Default)]
+pub(crate) struct WebShareSlot {
+    state: Arc<Mutex<Option<WebShareState>>>,
+}
+
+fn get_web_share_state() -> Arc<Mutex<Option<WebShareState>>> {
+    service::current().web_share.state.clone()
+}
+
+// ============================================================================
+// 本机控制
+// ============================================================================
+
+/// 开启网页分享
+///
+/// 已开启时更新分享文件列表并生成新的 PIN
+pub fn start_web_share(file_paths: Vec<String>) -> Result<WebShareInfo, WebShareError> {
+    if !*get_lan_transfer_state().is_running.read() {
+        return Err(WebShareError::ServiceNotRunning);
+    }
+
+    let shared_files = file_paths
+        .iter()
+        .map(|p| {
+            let path = PathBuf::from(p);
+            let metadata =
+                std::fs::metadata(&path).map_err(|_| WebShareError::FileNotFound(p.clone()))?;
+            if !metadata.is_file() {
+                return Err(WebShareError::FileNotFound(p.clone()));
+            }
+            Ok(WebSharedFile {
+                file_id: Uuid::new_v4().to_string(),
+                file_name: path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "file".to_string()),
+                file_size: metadata.len(),
+                path,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    {
+        let state = get_web_share_state();
+        let mut state = state.lock();
+        match state.as_mut() {
+            Some(share) => {
+                share.shared_files = shared_files;
+                share.pin = Some(generate_pin());
+                share.failed_attempts = 0;
+            }
+            None => {
+                *state = Some(WebShareState {
+                    pin: Some(generate_pin()),
+                    failed_attempts: 0,
+                    shared_files,
+                    guests: HashMap::new(),
+                    requests: HashMap::new(),
+                    started_at: Utc::now().to_rfc3339(),
+                });
+            }
+        }
+    }
+
+    log::info!("[LanTransfer] 🌐 网页分享已开启");
+
+    get_web_share_info().ok_or(WebShareError::Disabled)
+}
+
+/// 关闭网页分享（清除所有访客和未完成的请求）
+pub fn stop_web_share() {
+    let removed = get_web_share_state().lock().take();
+
+    if let Some(share) = removed {
+        let requests = get_pending_transfer_requests_map();
+        let mut requests = requests.lock();
+        for request_id in share.requests.keys() {
+            requests.remove(request_id);
+        }
+        log::info!("[LanTransfer] 🌐 网页分享已关闭");
+    }
+}
+
+/// 生成新的 PIN 码
+pub fn regenerate_pin() -> Result<String, WebShareError> {
+    let state = get_web_share_state();
+    let mut state = state.lock();
+    let share = state.as_mut().ok_or(WebShareError::Disabled)?;
+
+    let pin = generate_pin();
+    share.pin = Some(pin.clone());
+    share.failed_attempts = 0;
+
+    Ok(pin)
+}
+
+/// 获取网页分享状态
+pub fn get_web_share_info() -> Option<WebShareInfo> {
+    let lan_state = get_lan_transfer_state();
+    let url = lan_state
+        .local_device
+        .read()
+        .as_ref()
+        .map(|d| format!("http://{}:{}/web", d.ip_address, d.port))
+        .unwrap_or_default();
+
+    let state = get_web_share_state();
+    let state = state.lock();
+    state.as_ref().map(|share| WebShareInfo {
+        pin: share.pin.clone(),
+        url,
+        shared_files: share.shared_files.clone(),
+        guest_count: share.guests.len(),
+        started_at: share.started_at.clone(),
+    })
+}
+
+/// 网页分享是否开启
+pub fn is_enabled() -> bool {
+    get_web_share_state().lock().is_some()
+}
+
+/// 判断传输请求是否来自网页访客，是则记录用户的决定
+///
+/// 由 transfer::respond_to_transfer_request 调用；返回 true 表示已处理（无需通知发送方设备）
+pub fn resolve_request(request_id: &str, accept: bool) -> bool {
+    let state = get_web_share_state();
+    let mut state = state.lock();
+    let Some(request) = state
+        .as_mut()
+        .and_then(|share| share.requests.get_mut(request_id))
+    else {
+        return false;
+    };
+
+    request.status = if accept {
+        TransferRequestStatus::Accepted
+    } else {
+        TransferRequestStatus::Rejected
+    };
+
+    true
+}
+
+// ============================================================================
+// 访客操作（由 server 调用）
+// ============================================================================
+
+/// 访客登录，返回访问令牌
+///
+/// PIN 为一次性：登录成功后立即失效
+pub fn login(pin: &str, ip: &str, user_agent: &str) -> Result<String, WebShareError> {
+    let state = get_web_share_state();
+    let mut state = state.lock();
+    let share = state.as_mut().ok_or(WebShareError::Disabled)?;
+
+    let expected = share.pin.clone().ok_or(WebShareError::InvalidPin)?;
+    if pin.trim() != expected {
+        share.failed_attempts += 1;
+        if share.failed_attempts >= MAX_PIN_ATTEMPTS {
+            share.pin = None;
+            log::info!("[LanTransfer] 🌐 PIN 连续输错 {} 次，已作废", MAX_PIN_ATTEMPTS);
+        }
+        return Err(WebShareError::InvalidPin);
+    }
+
+    share.pin = None;
+    share.failed_attempts = 0;
+
+    let token = Uuid::new_v4().simple().to_string();
+    share.guests.insert(
+        token.clone(),
+        WebGuest {
+            guest_id: Uuid::new_v4().simple().to_string()[..8].to_string(),
+            ip: ip.to_string(),
+            user_agent: user_agent.to_string(),
+        },
+    );
+
+    log::info!("[LanTransfer] 🌐 网页访客已登录: {}", ip);
+
+    Ok(token)
+}
+
+/// 获取分享的文件列表
+pub fn list_shared_files(token: &str, ip: &str) -> Result<Vec<WebSharedFile>, WebShareError> {
+    let state = get_web_share_state();
+    let state = state.lock();
+    let share = state.as_ref().ok_or(WebShareError::Disabled)?;
+    authorize(share, token, ip)?;
+
+    Ok(share.shared_files.clone())
+}
+
+/// 访客请求上传文件（等待本机确认）
+pub fn create_upload_request(
+    token: &str,
+    ip: &str,
+    entries: Vec<WebUploadEntry>,
+) -> Result<TransferRequest, WebShareError> {
+    if entries.is_empty() {
+        return Err(WebShareError::InvalidRequest("文件列表为空".to_string()));
+    }
+
+    let files = entries
+        .into_iter()
+        .map(|entry| {
+            let file_name = sanitize_file_name(&entry.name);
+            FileMetadata {
+                file_id: Uuid::new_v4().to_string(),
+                mime_type: mime_guess::from_path(&file_name)
+                    .first_or_octet_stream()
+                    .to_string(),
+                file_name,
+                file_size: entry.size,
+                sha256: String::new(),
+                ..Default::default()
+            }
+        })
+        .collect();
+
+    submit_request(token, ip, WebRequestKind::Upload, files)
+}
+
+/// 访客请求下载分享的文件（等待本机确认）
+pub fn create_download_request(
+    token: &str,
+    ip: &str,
+    file_ids: Vec<String>,
+) -> Result<TransferRequest, WebShareError> {
+    let files = {
+        let state = get_web_share_state();
+        let state = state.lock();
+        let share = state.as_ref().ok_or(WebShareError::Disabled)?;
+
+        file_ids
+            .iter()
+            .map(|id| {
+                share
+                    .shared_files
+                    .iter()
+                    .find(|f| &f.file_id == id)
+                    .map(|f| FileMetadata {
+                        file_id: f.file_id.clone(),
+                        file_name: f.file_name.clone(),
+                        file_size: f.file_size,
+                        mime_type: mime_guess::from_path(&f.path)
+                            .first_or_octet_stream()
+                            .to_string(),
+                        sha256: String::new(),
+                        ..Default::default()
+                    })
+                    .ok_or_else(|| WebShareError::FileNotFound(id.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    if files.is_empty() {
+        return Err(WebShareError::InvalidRequest("文件列表为空".to_string()));
+    }
+
+    submit_request(token, ip, WebRequestKind::Download, files)
+}
+
+/// 查询访客请求状态
+pub fn get_request_status(
+    token: &str,
+    ip: &str,
+    request_id: &str,
+) -> Result<(TransferRequestStatus, Vec<FileMetadata>), WebShareError> {
+    let state = get_web_share_state();
+    let state = state.lock();
+    let share = state.as_ref().ok_or(WebShareError::Disabled)?;
+    authorize(share, token, ip)?;
+
+    let request = share
+        .requests
+        .get(request_id)
+        .filter(|r| r.token == token)
+        .ok_or_else(|| WebShareError::RequestNotFound(request_id.to_string()))?;
+
+    Ok((request.status.clone(), request.files.clone()))
+}
+
+/// 写入访客上传的数据块
+///
+/// 数据块必须按顺序上传（offset 等于已接收字节数），同一文件的数据块不能并发写入；
+/// 文件接收完成时返回保存路径
+pub fn write_upload_chunk(
+    token: &str,
+    ip: &str,
+    request_id: &str,
+    file_id: &str,
+    offset: u64,
+    data: &[u8],
+) -> Result<Option<PathBuf>, WebShareError> {
+    let file_meta = {
+        let state = get_web_share_state();
+        let mut state = state.lock();
+        let share = state.as_mut().ok_or(WebShareError::Disabled)?;
+        authorize(share, token, ip)?;
+
+        let request = accepted_request(share, token, request_id, WebRequestKind::Upload)?;
+        let file_meta = request
+            .files
+            .iter()
+            .find(|f| f.file_id == file_id)
+            .cloned()
+            .ok_or_else(|| WebShareError::FileNotFound(file_id.to_string()))?;
+
+        let received = request.received.get(file_id).copied();
+        if received == Some(file_meta.file_size) {
+            return Err(WebShareError::InvalidRequest("文件已接收完成".to_string()));
+        }
+
+        let received = received.unwrap_or(0);
+        if offset != received || offset + data.len() as u64 > file_meta.file_size {
+            return Err(WebShareError::InvalidRequest(format!(
+                "数据块位置无效: offset={}, 已接收={}",
+                offset, received
+            )));
+        }
+
+        // 登记写入中的文件，写入完成前同一文件的其他数据块都会被拒绝
+        let Some(request) = share.requests.get_mut(request_id) else {
+            return Err(WebShareError::RequestNotFound(request_id.to_string()));
+        };
+        if !request.writing.insert(file_id.to_string()) {
+            return Err(WebShareError::InvalidRequest(
+                "该文件有数据块正在写入".to_string(),
+            ));
+        }
+
+        file_meta
+    };
+
+    // 写入临时文件（与普通传输相同的临时目录）
+    let manager = get_resume_manager();
+    let write_result = if offset == 0 {
+        manager.create_temp_file(file_id)
+    } else {
+        manager.open_temp_file(file_id, offset)
+    }
+    .map_err(|e| WebShareError::Io(e.to_string()))
+    .and_then(|mut file| {
+        file.write_all(data)
+            .map_err(|e| WebShareError::Io(e.to_string()))
+    });
+
+    let new_received = offset + data.len() as u64;
+    let is_complete = new_received == file_meta.file_size;
+
+    let all_completed = {
+        let state = get_web_share_state();
+        let mut state = state.lock();
+        let Some(request) = state
+            .as_mut()
+            .and_then(|share| share.requests.get_mut(request_id))
+        else {
+            return Err(WebShareError::RequestNotFound(request_id.to_string()));
+        };
+
+        request.writing.remove(file_id);
+        write_result?;
+
+        request.received.insert(file_id.to_string(), new_received);
+        if is_complete {
+            request.completed_files += 1;
+        }
+        (request.completed_files as usize == request.files.len())
+            .then_some(request.completed_files)
+    };
+
+    if !is_complete {
+        return Ok(None);
+    }
+
+    let saved_path = manager
+        .finalize_transfer(file_id, &file_meta.file_name)
+        .map_err(|e| WebShareError::Io(e.to_string()))?;
+
+    log::info!(
+        "[LanTransfer] 🌐 网页访客上传完成: {} -> {:?}",
+        file_meta.file_name, saved_path
+    );
+
+    let event = LanTransferEvent::TransferCompleted {
+        task_id: file_id.to_string(),
+        saved_path: saved_path.to_string_lossy().to_string(),
+    };
+    let _ = get_event_sender().send(event.clone());
+    emit_lan_event(&event);
+
+    post_receive::spawn_post_receive(
+        file_id.to_string(),
+        saved_path.clone(),
+        &file_meta,
+        format!("网页访客 ({})", ip),
+    );
+
+    if let Some(total_files) = all_completed {
+        let event = LanTransferEvent::BatchTransferCompleted {
+            session_id: request_id.to_string(),
+            total_files,
+            save_directory: config::get_save_directory().to_string_lossy().to_string(),
+        };
+        let _ = get_event_sender().send(event.clone());
+        emit_lan_event(&event);
+    }
+
+    Ok(Some(saved_path))
+}
+
+/// 获取访客可下载的文件（请求必须已被接受）
+pub fn get_download_file(
+    token: &str,
+    ip: &str,
+    request_id: &str,
+    file_id: &str,
+) -> Result<WebSharedFile, WebShareError> {
+    let state = get_web_share_state();
+    let state = state.lock();
+    let share = state.as_ref().ok_or(WebShareError::Disabled)?;
+    authorize(share, token, ip)?;
+
+    let request = accepted_request(share, token, request_id, WebRequestKind::Download)?;
+    if !request.files.iter().any(|f| f.file_id == file_id) {
+        return Err(WebShareError::FileNotFound(file_id.to_string()));
+    }
+
+    share
+        .shared_files
+        .iter()
+        .find(|f| f.file_id == file_id)
+        .cloned()
+        .ok_or_else(|| WebShareError::FileNotFound(file_id.to_string()))
+}
+
+// ============================================================================
+// 内部函数
+// ============================================================================
+
+/// 校验访客令牌（令牌与登录 IP 绑定）
+fn authorize<'a>(
+    share: &'a WebShareState,
+    token: &str,
+    ip: &str,
+) -> Result<&'a WebGuest, WebShareError> {
+    share
+        .guests
+        .get(token)
+        .filter(|guest| guest.ip == ip)
+        .ok_or(WebShareError::Unauthorized)
+}
+
+/// 获取已被接受的访客请求
+fn accepted_request<'a>(
+    share: &'a WebShareState,
+    token: &str,
+    request_id: &str,
+    kind: WebRequestKind,
+) -> Result<&'a WebRequest, WebShareError> {
+    let request = share
+        .requests
+        .get(request_id)
+        .filter(|r| r.token == token && r.kind == kind)
+        .ok_or_else(|| WebShareError::RequestNotFound(request_id.to_string()))?;
+
+    if request.status != TransferRequestStatus::Accepted {
+        return Err(WebShareError::NotAccepted);
+    }
+
+    Ok(request)
+}
+
+/// 创建访客请求，加入待处理传输请求并通知前端
+fn submit_request(
+    token: &str,
+    ip: &str,
+    kind: WebRequestKind,
+    files: Vec<FileMetadata>,
+) -> Result<TransferRequest, WebShareError> {
+    let state = get_web_share_state();
+    let mut state = state.lock();
+    let share = state.as_mut().ok_or(WebShareError::Disabled)?;
+    let guest = authorize(share, token, ip)?.clone();
+
+    let now = Utc::now().to_rfc3339();
+    let request = TransferRequest {
+        request_id: Uuid::new_v4().to_string(),
+        from_device: DiscoveredDevice {
+            device_id: format!("{}{}", WEB_GUEST_DEVICE_PREFIX, guest.guest_id),
+            device_name: format!("浏览器 ({})", describe_user_agent(&guest.user_agent)),
+            user_id: String::new(),
+            user_nickname: match kind {
+                WebRequestKind::Upload => "网页访客（发送文件）".to_string(),
+                WebRequestKind::Download => "网页访客（下载分享）".to_string(),
+            },
+            ip_address: guest.ip.clone(),
+            port: 0,
+            discovered_at: now.clone(),
+            last_seen: now.clone(),
+        },
+        total_size: files.iter().map(|f| f.file_size).sum(),
+        files: files.clone(),
+        requested_at: now,
+        status: TransferRequestStatus::Pending,
+    };
+
+    share.requests.insert(
+        request.request_id.clone(),
+        WebRequest {
+            token: token.to_string(),
+            kind,
+            files,
+            status: TransferRequestStatus::Pending,
+            received: HashMap::new(),
+            writing: HashSet::new(),
+            completed_files: 0,
+        },
+    );
+    drop(state);
+
+    if !insert_pending_transfer_request(&request) {
+        if let Some(share) = get_web_share_state().lock().as_mut() {
+            share.requests.remove(&request.request_id);
+        }
+        return Err(WebShareError::TooManyRequests);
+    }
+
+    let event = LanTransferEvent::TransferRequestReceived {
+        request: request.clone(),
+    };
+    let _ = get_event_sender().send(event.clone());
+    emit_lan_event(&event);
+
+    Ok(request)
+}
+
+/// 生成 6 位数字 PIN
+fn generate_pin() -> String {
+    format!("{:06}", Uuid::new_v4().as_u128() % 1_000_000)
+}
+
+/// 从 User-Agent 提取简短的浏览器描述
+fn describe_user_agent(user_agent: &str) -> &'static str {
+    let ua = user_agent.to_lowercase();
+    let browser = ["edg/", "firefox/", "chrome/", "safari/"]
+        .into_iter()
+        .find(|b| ua.contains(b));
+
+    match browser {
+        Some("edg/") => "Edge",
+        Some("firefox/") => "Firefox",
+        Some("chrome/") => "Chrome",
+        Some("safari/") => "Safari",
+        _ => "未知浏览器",
+    }
+}
+
+/// 清理浏览器提供的文件名（去除路径部分和非法字符）
+fn sanitize_file_name(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or("");
+    let cleaned: String = base
+        .chars()
+        .map(|c| match c {
+            ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            _ => c,
+        })
+        .collect();
+    let cleaned = cleaned.trim().trim_start_matches('.');
+
+    if cleaned.is_empty() {
+        "file".to_string()
+    } else {
+        Path::new(cleaned).to_string_lossy().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lan_transfer::service::{LanTransferService, ServiceOptions};
+
+    const GUEST_IP: &str = "192.168.1.50";
+
+    /// 开启网页分享的测试服务实例（独立临时数据目录）
+    fn start_service(dir: &Path) -> Arc<LanTransferService> {
+        let service = LanTransferService::new(ServiceOptions {
+            data_directory: Some(dir.to_path_buf()),
+            enable_mdns: false,
+            ..Default::default()
+        });
+        *service.state().is_running.write() = true;
+
+        let shared = dir.join("shared.txt");
+        std::fs::write(&shared, b"shared").unwrap();
+        service
+            .enter(|| start_web_share(vec![shared.to_string_lossy().to_string()]))
+            .unwrap();
+        service
+    }
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("huanvae-web-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn current_pin() -> String {
+        get_web_share_info().unwrap().pin.unwrap()
+    }
+
+    #[test]
+    fn pin_is_single_use_and_invalidated_after_repeated_failures() {
+        let dir = temp_dir();
+        let service = start_service(&dir);
+
+        service.enter(|| {
+            let pin = current_pin();
+            let wrong = if pin == "000000" { "111111" } else { "000000" };
+
+            assert!(matches!(login(wrong, GUEST_IP, "Firefox/1"), Err(WebShareError::InvalidPin)));
+            assert!(login(&pin, GUEST_IP, "Firefox/1").is_ok());
+            // 一次性：登录成功后 PIN 失效
+            assert!(matches!(login(&pin, GUEST_IP, "Firefox/1"), Err(WebShareError::InvalidPin)));
+            assert!(get_web_share_info().unwrap().pin.is_none());
+
+            // 连续输错 MAX_PIN_ATTEMPTS 次后作废，正确的 PIN 也无法登录
+            let pin = regenerate_pin().unwrap();
+            let wrong = if pin == "000000" { "111111" } else { "000000" };
+            for _ in 0..MAX_PIN_ATTEMPTS {
+                assert!(login(wrong, GUEST_IP, "").is_err());
+            }
+            assert!(matches!(login(&pin, GUEST_IP, ""), Err(WebShareError::InvalidPin)));
+        });
+
+        drop(service);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn token_is_bound_to_login_ip() {
+        let dir = temp_dir();
+        let service = start_service(&dir);
+
+        service.enter(|| {
+            let token = login(&current_pin(), GUEST_IP, "").unwrap();
+
+            assert_eq!(list_shared_files(&token, GUEST_IP).unwrap().len(), 1);
+            assert!(matches!(
+                list_shared_files(&token, "192.168.1.51"),
+                Err(WebShareError::Unauthorized)
+            ));
+            assert!(matches!(
+                list_shared_files("unknown-token", GUEST_IP),
+                Err(WebShareError::Unauthorized)
+            ));
+            assert!(matches!(
+                create_upload_request(
+                    &token,
+                    "192.168.1.51",
+                    vec![WebUploadEntry { name: "a.txt".to_string(), size: 1 }],
+                ),
+                Err(WebShareError::Unauthorized)
+            ));
+        });
+
+        drop(service);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn upload_chunks_must_arrive_in_order() {
+        let dir = temp_dir();
+        let service = start_service(&dir);
+
+        service
+            .scope(async {
+                let token = login(&current_pin(), GUEST_IP, "").unwrap();
+                let request = create_upload_request(
+                    &token,
+                    GUEST_IP,
+                    vec![WebUploadEntry { name: "../notes.txt".to_string(), size: 8 }],
+                )
+                .unwrap();
+                let request_id = request.request_id.as_str();
+                let file_id = request.files[0].file_id.as_str();
+                assert_eq!(request.files[0].file_name, "notes.txt");
+
+                // 接受前不能上传
+                assert!(matches!(
+                    write_upload_chunk(&token, GUEST_IP, request_id, file_id, 0, b"abcd"),
+                    Err(WebShareError::NotAccepted)
+                ));
+                assert!(resolve_request(request_id, true));
+
+                // 跳过前面的数据块
+                assert!(write_upload_chunk(&token, GUEST_IP, request_id, file_id, 4, b"efgh").is_err());
+                assert_eq!(
+                    write_upload_chunk(&token, GUEST_IP, request_id, file_id, 0, b"abcd").unwrap(),
+                    None
+                );
+                // 重复的数据块
+                assert!(write_upload_chunk(&token, GUEST_IP, request_id, file_id, 0, b"abcd").is_err());
+
+                // 同一文件正在写入时，其他数据块被拒绝
+                get_web_share_state()
+                    .lock()
+                    .as_mut()
+                    .unwrap()
+                    .requests
+                    .get_mut(request_id)
+                    .unwrap()
+                    .writing
+                    .insert(file_id.to_string());
+                assert!(write_upload_chunk(&token, GUEST_IP, request_id, file_id, 4, b"efgh").is_err());
+                get_web_share_state()
+                    .lock()
+                    .as_mut()
+                    .unwrap()
+                    .requests
+                    .get_mut(request_id)
+                    .unwrap()
+                    .writing
+                    .remove(file_id);
+
+                // 超出文件大小
+                assert!(write_upload_chunk(&token, GUEST_IP, request_id, file_id, 4, b"efghi").is_err());
+
+                let saved = write_upload_chunk(&token, GUEST_IP, request_id, file_id, 4, b"efgh")
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(std::fs::read(&saved).unwrap(), b"abcdefgh");
+                assert!(saved.starts_with(&dir));
+
+                // 文件已接收完成
+                assert!(write_upload_chunk(&token, GUEST_IP, request_id, file_id, 8, b"").is_err());
+            })
+            .await;
+
+        drop(service);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
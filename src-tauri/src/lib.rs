@@ -17,6 +17,7 @@
 //! - 窗口状态：记忆窗口位置和大小，下次启动时恢复
 //! - 局域网传输：局域网内设备发现和文件互传
 //! - Android 更新：应用内 APK 下载和安装（Android 专属）
+//! - 日志：分级、按模块过滤的结构化日志，写入轮转日志文件
 //!
 //! ## 平台支持
 //! - 桌面端 (Windows/macOS/Linux): 完整功能
@@ -24,6 +25,8 @@
 //! - 移动端 (iOS): 暂未支持
 //!
 //! ## 更新日志
+//! - 2026-10-18: 新增 logging 模块，日志写入 logs/app.log（轮转），注册日志相关命令
+//! - 2026-10-18: 导出 run_lan_cli，供命令行局域网传输工具 huanvae-lan 使用
 //! - 2026-01-21: Android 数据目录初始化修复，使用 app.path().app_data_dir() 替代 TMPDIR
 //! - 2026-01-22: 添加桌面/移动端条件编译，分离平台专属模块
 
@@ -34,6 +37,7 @@ mod db;
 mod device_info;
 mod download;
 mod lan_transfer;
+mod logging;
 mod permissions;
 mod sounds;
 mod storage;
@@ -56,6 +60,10 @@ mod mobile_media_server;
 // ============================================
 mod android_update;
 
+/// 命令行局域网传输工具入口（huanvae-lan 二进制使用，返回进程退出码）
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+pub use lan_transfer::cli::run as run_lan_cli;
+
 use db::{LocalConversation, LocalFileMapping, LocalFriend, LocalGroup, LocalMessage};
 use storage::SavedAccount;
 
@@ -247,11 +255,11 @@ async fn get_local_video_url(_file_hash: String) -> Option<String> {
 /// 初始化数据库
 #[tauri::command]
 fn db_init() -> Result<(), String> {
-    println!("[Command] db_init 被调用");
+    log::info!("[Command] db_init 被调用");
     let result = db::init_database();
     match &result {
-        Ok(_) => println!("[Command] db_init 成功"),
-        Err(e) => println!("[Command] db_init 失败: {}", e),
+        Ok(_) => log::info!("[Command] db_init 成功"),
+        Err(e) => log::error!("[Command] db_init 失败: {}", e),
     }
     result
 }
@@ -450,11 +458,11 @@ fn db_delete_group(group_id: String) -> Result<(), String> {
 /// 这会创建用户数据目录并设置上下文
 #[tauri::command(rename_all = "camelCase")]
 fn set_current_user(user_id: String, server_url: String) -> Result<(), String> {
-    println!("[Command] set_current_user 被调用: {} @ {}", user_id, server_url);
+    log::info!("[Command] set_current_user 被调用: {} @ {}", user_id, server_url);
     let result = user_data::set_current_user(&user_id, &server_url);
     match &result {
-        Ok(_) => println!("[Command] set_current_user 成功"),
-        Err(e) => println!("[Command] set_current_user 失败: {}", e),
+        Ok(_) => log::info!("[Command] set_current_user 成功"),
+        Err(e) => log::error!("[Command] set_current_user 失败: {}", e),
     }
     result
 }
@@ -523,6 +531,11 @@ fn reset_webview_permissions(app: tauri::AppHandle) -> Result<String, String> {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // 桌面端：数据根目录已确定，尽早初始化日志
+    // Android 的数据根目录在 setup 中确定，日志在其后初始化
+    #[cfg(not(target_os = "android"))]
+    logging::init();
+
     // 桌面端：包含 updater 和 window-state 插件
     #[cfg(not(any(target_os = "android", target_os = "ios")))]
     let builder = tauri::Builder::default()
@@ -563,13 +576,13 @@ pub fn run() {
             // 桌面端：清理过期的会话锁
             #[cfg(not(any(target_os = "android", target_os = "ios")))]
             if let Err(e) = desktop::cleanup_stale_locks(app.handle()) {
-                eprintln!("[SessionLock] 清理过期锁失败: {}", e);
+                log::error!("[SessionLock] 清理过期锁失败: {}", e);
             }
 
             // 桌面端：初始化系统托盘
             #[cfg(not(any(target_os = "android", target_os = "ios")))]
             if let Err(e) = desktop::setup_tray(app) {
-                eprintln!("[Tray] 初始化托盘失败: {}", e);
+                log::error!("[Tray] 初始化托盘失败: {}", e);
             }
 
             // Android/iOS：注册返回按钮监听插件（必须在 setup 中注册）
@@ -589,15 +602,17 @@ pub fn run() {
 
                         // 初始化 storage 模块的数据目录
                         if let Err(e) = storage::init_android_data_dir(data_dir.clone()) {
-                            eprintln!("[Storage] Android 数据目录初始化失败: {}", e);
+                            log::error!("[Storage] Android 数据目录初始化失败: {}", e);
                         }
                         // 初始化 user_data 模块的数据根目录
                         if let Err(e) = user_data::init_android_app_root(data_dir.clone()) {
-                            eprintln!("[UserData] Android 数据根目录初始化失败: {}", e);
+                            log::error!("[UserData] Android 数据根目录初始化失败: {}", e);
                         }
+                        // 数据根目录确定后初始化日志
+                        logging::init();
                         // 初始化 lan_transfer 模块的数据目录（接收文件保存位置）
                         if let Err(e) = lan_transfer::config::init_android_data_dir(data_dir.clone()) {
-                            eprintln!("[LanTransfer] Android 数据目录初始化失败: {}", e);
+                            log::error!("[LanTransfer] Android 数据目录初始化失败: {}", e);
                         }
 
                         // 启动本地媒体服务器（后台异步）
@@ -607,16 +622,16 @@ pub fn run() {
                         tauri::async_runtime::spawn(async move {
                             match mobile_media_server::start_server(data_dir_str).await {
                                 Ok(port) => {
-                                    println!("[MobileMediaServer] 服务器已启动，端口: {}", port);
+                                    log::info!("[MobileMediaServer] 服务器已启动，端口: {}", port);
                                 }
                                 Err(e) => {
-                                    eprintln!("[MobileMediaServer] 服务器启动失败: {}", e);
+                                    log::error!("[MobileMediaServer] 服务器启动失败: {}", e);
                                 }
                             }
                         });
                     }
                     Err(e) => {
-                        eprintln!("[Storage] 获取 Android app_data_dir 失败: {}", e);
+                        log::error!("[Storage] 获取 Android app_data_dir 失败: {}", e);
                     }
                 }
             }
@@ -724,6 +739,7 @@ pub fn run() {
             lan_transfer::send_file_to_device,
             lan_transfer::get_pending_connection_requests,
             lan_transfer::get_active_transfers,
+            lan_transfer::get_lan_events_since,
             lan_transfer::cancel_transfer,
             lan_transfer::get_lan_debug_info,
             // 局域网传输（点对点连接）
@@ -733,6 +749,43 @@ pub fn run() {
             lan_transfer::get_active_peer_connections,
             lan_transfer::get_pending_peer_connection_requests,
             lan_transfer::send_files_to_peer,
+            lan_transfer::send_files_to_devices,
+            // 局域网传输（文本片段）
+            lan_transfer::send_text_snippet,
+            lan_transfer::get_received_snippets,
+            lan_transfer::delete_received_snippet,
+            lan_transfer::clear_received_snippets,
+            // 局域网传输（离线聊天）
+            lan_transfer::send_lan_chat_message,
+            lan_transfer::retry_lan_chat_messages,
+            lan_transfer::get_lan_message_delivery,
+            // 局域网传输（联系人关联）
+            lan_transfer::get_lan_contacts,
+            lan_transfer::get_friend_lan_devices,
+            // 局域网传输（离线发送队列）
+            lan_transfer::queue_transfer_for_device,
+            lan_transfer::get_queued_transfers,
+            lan_transfer::cancel_queued_transfer,
+            // 局域网传输（共享文件夹）
+            lan_transfer::get_shared_folders,
+            lan_transfer::add_shared_folder,
+            lan_transfer::set_shared_folder_devices,
+            lan_transfer::remove_shared_folder,
+            lan_transfer::browse_peer_shares,
+            lan_transfer::browse_peer_share,
+            lan_transfer::pull_from_peer_share,
+            // 局域网传输（文件夹同步）
+            lan_transfer::create_sync_pair,
+            lan_transfer::join_sync_pair,
+            lan_transfer::get_sync_pairs,
+            lan_transfer::set_sync_pair_enabled,
+            lan_transfer::remove_sync_pair,
+            lan_transfer::sync_folder_now,
+            // 局域网传输（网页分享）
+            lan_transfer::start_web_share,
+            lan_transfer::stop_web_share,
+            lan_transfer::regenerate_web_share_pin,
+            lan_transfer::get_web_share_info,
             // 局域网传输（旧版兼容：多文件、确认、断点续传）
             lan_transfer::send_transfer_request,
             lan_transfer::respond_to_transfer_request,
@@ -751,8 +804,25 @@ pub fn run() {
             lan_transfer::get_trusted_devices,
             lan_transfer::set_auto_accept_trusted,
             lan_transfer::set_group_by_date,
+            lan_transfer::set_peer_heartbeat_settings,
+            lan_transfer::get_partial_transfers,
+            lan_transfer::cleanup_partial_transfers,
+            lan_transfer::set_partial_max_age_hours,
+            lan_transfer::set_post_receive_actions,
+            // 局域网传输统计
+            lan_transfer::get_lan_transfer_stats,
+            lan_transfer::get_lan_transfer_history,
+            lan_transfer::clear_lan_transfer_stats,
             // 局域网传输诊断
             lan_transfer::diagnostics::diagnose_lan_transfer,
+            lan_transfer::diagnostics::probe_lan_peer,
+            lan_transfer::diagnostics::apply_lan_diagnostic_fix,
+            lan_transfer::diagnostics::export_lan_diagnostics_bundle,
+            // 日志
+            logging::get_log_settings,
+            logging::set_log_level,
+            logging::get_log_tail,
+            logging::export_logs,
             // 媒体权限管理
             permissions::open_media_permission_settings,
             permissions::get_media_permission_guide,
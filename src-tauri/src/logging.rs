@@ -0,0 +1,556 @@
+//! 日志模块
+//!
+//! 各模块通过 `log` 门面（`log::info!` / `log::warn!` / `log::error!` 等）输出日志，
+//! 本模块负责过滤与落盘。安装版桌面端和 Android 上标准输出不可见，
+//! 日志必须写入文件才能在用户反馈问题时取回。
+//!
+//! ## 功能
+//! - 分级：error / warn / info / debug / trace，默认 info
+//! - 按模块过滤：目标为模块路径（如 `lan_transfer::server`），可为模块单独设置级别，
+//!   按最长前缀匹配（`lan_transfer` 覆盖其所有子模块）
+//! - 结构化：每行一条 JSON（时间、级别、目标、消息），便于前端展示和问题分析
+//! - 轮转：写入 `{get_app_root()}/logs/app.log`，超过 MAX_FILE_BYTES 时轮转为
+//!   app.log.1 ~ app.log.{MAX_FILES - 1}，最旧的删除
+//! - 同时输出到标准输出/标准错误（开发时在终端查看）；命令行工具 huanvae-lan
+//!   只把 warn 及以上输出到标准错误（标准输出留给命令结果）
+//!
+//! ## 日志目录
+//!
+//! ```text
+//! data/
+//!   └── logs/
+//!       ├── app.log       # 当前日志
+//!       ├── app.log.1     # 上一个日志
+//!       └── exports/      # 导出的日志压缩包
+//! ```
+//!
+//! ## 更新日志
+//! - 2026-10-18: 新增 init_cli（命令行工具使用）；get_log_tail 在阻塞线程池中读取日志文件
+//! - 2026-10-18: 新增，替代各模块的 println!/eprintln!
+
+use chrono::Local;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use once_cell::sync::OnceCell;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// 单个日志文件最大字节数：5MB
+const MAX_FILE_BYTES: u64 = 5 * 1024 * 1024;
+
+/// 保留的日志文件数（含当前文件）
+const MAX_FILES: usize = 5;
+
+/// 当前日志文件名
+const LOG_FILE_NAME: &str = "app.log";
+
+/// 模块路径中的 crate 前缀（目标显示时去掉）
+const CRATE_PREFIX: &str = "huanvae_chat_app_lib::";
+
+/// 读取日志末尾时的默认行数
+const DEFAULT_TAIL_LINES: usize = 200;
+
+// ============================================================================
+// 数据结构
+// ============================================================================
+
+/// 一条日志（日志文件中每行一条 JSON）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEntry {
+    /// 时间（本地时间，RFC 3339）
+    pub timestamp: String,
+    /// 级别（ERROR / WARN / INFO / DEBUG / TRACE）
+    pub level: String,
+    /// 目标（模块路径，如 lan_transfer::server）
+    pub target: String,
+    /// 消息
+    pub message: String,
+}
+
+/// 日志设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogSettings {
+    /// 默认级别
+    pub level: String,
+    /// 按模块设置的级别（模块路径前缀 -> 级别）
+    pub targets: HashMap<String, String>,
+    /// 日志目录
+    pub directory: String,
+}
+
+/// 级别设置
+struct LevelConfig {
+    default: LevelFilter,
+    targets: HashMap<String, LevelFilter>,
+}
+
+impl LevelConfig {
+    /// 目标对应的级别（最长前缀匹配）
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .filter(|(prefix, _)| {
+                target == prefix.as_str()
+                    || target
+                        .strip_prefix(prefix.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default)
+    }
+}
+
+// ============================================================================
+// 轮转文件
+// ============================================================================
+
+/// 轮转日志文件
+struct RotatingFile {
+    dir: PathBuf,
+    file: Option<File>,
+    size: u64,
+}
+
+impl RotatingFile {
+    fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            file: None,
+            size: 0,
+        }
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if self.file.is_none() {
+            self.open();
+        }
+        if self.size + line.len() as u64 + 1 > MAX_FILE_BYTES && self.size > 0 {
+            self.rotate();
+        }
+        if let Some(file) = self.file.as_mut()
+            && writeln!(file, "{}", line).is_ok()
+        {
+            self.size += line.len() as u64 + 1;
+        }
+    }
+
+    fn open(&mut self) {
+        if fs::create_dir_all(&self.dir).is_err() {
+            return;
+        }
+        let path = self.dir.join(LOG_FILE_NAME);
+        self.file = OpenOptions::new().create(true).append(true).open(&path).ok();
+        self.size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    }
+
+    /// app.log -> app.log.1 -> ... -> app.log.{MAX_FILES - 1}（最旧的删除）
+    fn rotate(&mut self) {
+        self.file = None;
+        let _ = fs::remove_file(rotated_path(&self.dir, MAX_FILES - 1));
+        for index in (1..MAX_FILES - 1).rev() {
+            let _ = fs::rename(
+                rotated_path(&self.dir, index),
+                rotated_path(&self.dir, index + 1),
+            );
+        }
+        let _ = fs::rename(self.dir.join(LOG_FILE_NAME), rotated_path(&self.dir, 1));
+        self.open();
+    }
+}
+
+/// 第 index 个轮转文件（0 为当前文件）
+fn rotated_path(dir: &Path, index: usize) -> PathBuf {
+    if index == 0 {
+        dir.join(LOG_FILE_NAME)
+    } else {
+        dir.join(format!("{}.{}", LOG_FILE_NAME, index))
+    }
+}
+
+// ============================================================================
+// 日志实现
+// ============================================================================
+
+/// 日志回显方式
+#[derive(Clone, Copy)]
+enum Echo {
+    /// warn 及以上输出到标准错误，其余输出到标准输出（开发时在终端查看）
+    Console,
+    /// 只把不低于指定级别的日志输出到标准错误（命令行工具）
+    Stderr(Level),
+}
+
+struct AppLogger {
+    levels: RwLock<LevelConfig>,
+    file: Mutex<RotatingFile>,
+    echo: Echo,
+}
+
+impl Log for AppLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.levels.read().level_for(short_target(metadata.target()))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let entry = LogEntry {
+            timestamp: Local::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+            level: record.level().to_string(),
+            target: short_target(record.target()).to_string(),
+            message: record.args().to_string(),
+        };
+
+        match self.echo {
+            Echo::Console if record.level() > Level::Warn => println!("{}", entry.message),
+            Echo::Console => eprintln!("{}", entry.message),
+            Echo::Stderr(level) if record.level() <= level => eprintln!("{}", entry.message),
+            Echo::Stderr(_) => {}
+        }
+
+        if let Ok(line) = serde_json::to_string(&entry) {
+            self.file.lock().write_line(&line);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = self.file.lock().file.as_mut() {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// 去掉 crate 前缀的目标
+fn short_target(target: &str) -> &str {
+    target.strip_prefix(CRATE_PREFIX).unwrap_or(target)
+}
+
+static LOGGER: OnceCell<&'static AppLogger> = OnceCell::new();
+
+/// 初始化日志（写入 get_app_root()/logs）
+///
+/// Android 需在 init_android_app_root 之后调用；重复调用无效果
+pub fn init() {
+    init_in(crate::user_data::get_app_root().join("logs"), Echo::Console);
+}
+
+/// 初始化命令行工具的日志（写入 get_app_root()/logs，warn 及以上同时输出到标准错误）
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+pub fn init_cli() {
+    init_in(crate::user_data::get_app_root().join("logs"), Echo::Stderr(Level::Warn));
+}
+
+/// 在指定目录初始化日志
+fn init_in(dir: PathBuf, echo: Echo) {
+    LOGGER.get_or_init(|| {
+        let logger: &'static AppLogger = Box::leak(Box::new(AppLogger {
+            levels: RwLock::new(LevelConfig {
+                default: LevelFilter::Info,
+                targets: HashMap::new(),
+            }),
+            file: Mutex::new(RotatingFile::new(dir)),
+            echo,
+        }));
+        if log::set_logger(logger).is_ok() {
+            log::set_max_level(LevelFilter::Trace);
+        }
+        logger
+    });
+}
+
+/// 日志目录
+pub fn log_directory() -> PathBuf {
+    match LOGGER.get() {
+        Some(logger) => logger.file.lock().dir.clone(),
+        None => crate::user_data::get_app_root().join("logs"),
+    }
+}
+
+// ============================================================================
+// 级别设置
+// ============================================================================
+
+/// 解析级别名称（不区分大小写，off 表示关闭）
+fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    level
+        .trim()
+        .parse::<LevelFilter>()
+        .map_err(|_| format!("无效的日志级别: {}（可选 off/error/warn/info/debug/trace）", level))
+}
+
+/// 设置日志级别
+///
+/// target 为空时设置默认级别；否则为该模块（路径前缀，如 lan_transfer、db）单独设置，
+/// level 为 "default" 时清除该模块的单独设置
+pub fn set_level(level: &str, target: Option<&str>) -> Result<(), String> {
+    let logger = LOGGER.get().ok_or("日志未初始化")?;
+    let mut levels = logger.levels.write();
+
+    match target.map(str::trim).filter(|t| !t.is_empty()) {
+        None => levels.default = parse_level(level)?,
+        Some(target) if level.eq_ignore_ascii_case("default") => {
+            levels.targets.remove(target);
+        }
+        Some(target) => {
+            let level = parse_level(level)?;
+            levels.targets.insert(target.to_string(), level);
+        }
+    }
+
+    Ok(())
+}
+
+/// 当前日志设置
+pub fn settings() -> LogSettings {
+    let (level, targets) = match LOGGER.get() {
+        Some(logger) => {
+            let levels = logger.levels.read();
+            (
+                levels.default.to_string(),
+                levels
+                    .targets
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.to_string()))
+                    .collect(),
+            )
+        }
+        None => (LevelFilter::Off.to_string(), HashMap::new()),
+    };
+
+    LogSettings {
+        level,
+        targets,
+        directory: log_directory().to_string_lossy().to_string(),
+    }
+}
+
+// ============================================================================
+// 读取与导出
+// ============================================================================
+
+/// 读取最近的日志（从新到旧读取轮转文件，返回按时间升序的最后 lines 条）
+///
+/// min_level 过滤最低级别，target 过滤模块路径前缀
+pub fn tail(lines: usize, min_level: Option<&str>, target: Option<&str>) -> Vec<LogEntry> {
+    tail_in(&log_directory(), lines, min_level, target)
+}
+
+/// 从指定目录读取最近的日志
+fn tail_in(
+    dir: &Path,
+    lines: usize,
+    min_level: Option<&str>,
+    target: Option<&str>,
+) -> Vec<LogEntry> {
+    let min_level = min_level
+        .and_then(|l| parse_level(l).ok())
+        .unwrap_or(LevelFilter::Trace);
+    let target = target.map(str::trim).filter(|t| !t.is_empty());
+
+    let mut collected: Vec<LogEntry> = Vec::new();
+    for index in 0..MAX_FILES {
+        let Ok(content) = fs::read_to_string(rotated_path(dir, index)) else {
+            continue;
+        };
+
+        let mut entries: Vec<LogEntry> = content
+            .lines()
+            .filter_map(|line| serde_json::from_str::<LogEntry>(line).ok())
+            .filter(|e| e.level.parse::<Level>().is_ok_and(|l| l <= min_level))
+            .filter(|e| target.is_none_or(|t| e.target.starts_with(t)))
+            .collect();
+
+        // 较旧文件的内容排在前面
+        entries.append(&mut collected);
+        collected = entries;
+        if collected.len() >= lines {
+            break;
+        }
+    }
+
+    let skip = collected.len().saturating_sub(lines);
+    collected.split_off(skip)
+}
+
+/// 导出全部日志文件为 zip，返回文件路径
+pub fn export() -> Result<PathBuf, String> {
+    let dir = log_directory();
+    let export_dir = dir.join("exports");
+    fs::create_dir_all(&export_dir).map_err(|e| format!("创建目录失败: {}", e))?;
+
+    if let Some(logger) = LOGGER.get() {
+        logger.flush();
+    }
+
+    let path = export_dir.join(format!(
+        "huanvae-logs-{}.zip",
+        Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    let file = File::create(&path).map_err(|e| format!("创建日志压缩包失败: {}", e))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+
+    for index in (0..MAX_FILES).rev() {
+        let source = rotated_path(&dir, index);
+        let Ok(content) = fs::read(&source) else {
+            continue;
+        };
+        let name = source
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        zip.start_file(name.as_str(), options)
+            .map_err(|e| format!("写入 {} 失败: {}", name, e))?;
+        zip.write_all(&content)
+            .map_err(|e| format!("写入 {} 失败: {}", name, e))?;
+    }
+
+    zip.finish().map_err(|e| format!("写入日志压缩包失败: {}", e))?;
+    log::info!("[Logging] 日志已导出: {}", path.display());
+    Ok(path)
+}
+
+// ============================================================================
+// Tauri 命令
+// ============================================================================
+
+/// 获取日志设置（默认级别、模块级别、日志目录）
+#[tauri::command]
+pub fn get_log_settings() -> LogSettings {
+    settings()
+}
+
+/// 运行时修改日志级别
+///
+/// 前端调用：
+/// ```typescript
+/// await invoke('set_log_level', { level: 'debug', target: 'lan_transfer' });
+/// await invoke('set_log_level', { level: 'warn' }); // 默认级别
+/// ```
+#[tauri::command]
+pub fn set_log_level(level: String, target: Option<String>) -> Result<(), String> {
+    set_level(&level, target.as_deref())?;
+    log::info!(
+        "[Logging] 日志级别已修改: {} -> {}",
+        target.as_deref().unwrap_or("默认"),
+        level
+    );
+    Ok(())
+}
+
+/// 读取最近的日志（读取文件在阻塞线程池中进行）
+#[tauri::command]
+pub async fn get_log_tail(
+    lines: Option<usize>,
+    min_level: Option<String>,
+    target: Option<String>,
+) -> Result<Vec<LogEntry>, String> {
+    tokio::task::spawn_blocking(move || {
+        tail(
+            lines.unwrap_or(DEFAULT_TAIL_LINES),
+            min_level.as_deref(),
+            target.as_deref(),
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// 导出日志压缩包，返回文件路径
+#[tauri::command]
+pub async fn export_logs() -> Result<String, String> {
+    tokio::task::spawn_blocking(export)
+        .await
+        .map_err(|e| e.to_string())?
+        .map(|path| path.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_levels_use_longest_prefix() {
+        let config = LevelConfig {
+            default: LevelFilter::Info,
+            targets: HashMap::from([
+                ("lan_transfer".to_string(), LevelFilter::Debug),
+                ("lan_transfer::server".to_string(), LevelFilter::Warn),
+            ]),
+        };
+        assert_eq!(config.level_for("lan_transfer::discovery"), LevelFilter::Debug);
+        assert_eq!(config.level_for("lan_transfer::server"), LevelFilter::Warn);
+        assert_eq!(config.level_for("lan_transfer_extra"), LevelFilter::Info);
+        assert_eq!(config.level_for("db"), LevelFilter::Info);
+    }
+
+    #[test]
+    fn rotation_keeps_bounded_files_and_tail_reads_across_them() {
+        let dir = std::env::temp_dir().join(format!("huanvae-logs-{}", uuid::Uuid::new_v4()));
+        let mut file = RotatingFile::new(dir.clone());
+        let padding = "x".repeat(1024);
+        let total = (MAX_FILE_BYTES as usize / 1024) * (MAX_FILES + 1);
+        for i in 0..total {
+            // 每 10 条有一条来自 lan_transfer::server 的 WARN
+            let (level, target) = if i % 10 == 0 {
+                ("WARN", "lan_transfer::server")
+            } else {
+                ("INFO", "db")
+            };
+            let entry = LogEntry {
+                timestamp: i.to_string(),
+                level: level.into(),
+                target: target.into(),
+                message: padding.clone(),
+            };
+            file.write_line(&serde_json::to_string(&entry).unwrap());
+        }
+        drop(file);
+
+        let count = fs::read_dir(&dir).unwrap().count();
+        assert_eq!(count, MAX_FILES);
+        assert!(fs::metadata(dir.join(LOG_FILE_NAME)).unwrap().len() <= MAX_FILE_BYTES);
+
+        let last = (0..MAX_FILES)
+            .filter_map(|i| fs::read_to_string(rotated_path(&dir, i)).ok())
+            .flat_map(|c| c.lines().map(str::to_string).collect::<Vec<_>>())
+            .filter_map(|l| serde_json::from_str::<LogEntry>(&l).ok())
+            .map(|e| e.timestamp.parse::<usize>().unwrap())
+            .max()
+            .unwrap();
+        assert_eq!(last, total - 1);
+
+        // 跨越多个轮转文件读取，按时间升序返回最后 N 条
+        let per_file = MAX_FILE_BYTES as usize / 1100;
+        let lines = per_file * 2;
+        let entries = tail_in(&dir, lines, None, None);
+        let seqs: Vec<usize> = entries
+            .iter()
+            .map(|e| e.timestamp.parse().unwrap())
+            .collect();
+        assert_eq!(seqs.len(), lines);
+        assert_eq!(seqs, ((total - lines)..total).collect::<Vec<_>>());
+
+        // 按级别和模块过滤
+        let warnings = tail_in(&dir, 3, Some("warn"), Some("lan_transfer"));
+        let seqs: Vec<usize> = warnings
+            .iter()
+            .map(|e| e.timestamp.parse().unwrap())
+            .collect();
+        let newest_warn = (total - 1) / 10 * 10;
+        assert_eq!(seqs, vec![newest_warn - 20, newest_warn - 10, newest_warn]);
+        assert!(tail_in(&dir, 5, Some("error"), None).is_empty());
+        assert!(tail_in(&dir, 5, None, Some("server")).is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -109,11 +109,11 @@ pub async fn start_server(data_dir: String) -> Result<u16, String> {
     // 后台运行服务器
     tokio::spawn(async move {
         if let Err(e) = axum::serve(listener, app).await {
-            eprintln!("[MobileMediaServer] 服务器错误: {}", e);
+            log::error!("[MobileMediaServer] 服务器错误: {}", e);
         }
     });
 
-    println!("[MobileMediaServer] 已启动，端口: {}", port);
+    log::info!("[MobileMediaServer] 已启动，端口: {}", port);
     Ok(port)
 }
 
@@ -148,7 +148,7 @@ async fn serve_video(
     let mut file = match File::open(&local_path).await {
         Ok(f) => f,
         Err(e) => {
-            eprintln!(
+            log::warn!(
                 "[MobileMediaServer] 无法打开文件 {}: {}",
                 local_path, e
             );
@@ -182,7 +182,7 @@ async fn serve_video(
 
             // 移动到起始位置
             if let Err(e) = file.seek(std::io::SeekFrom::Start(start)).await {
-                eprintln!("[MobileMediaServer] Seek 失败: {}", e);
+                log::error!("[MobileMediaServer] Seek 失败: {}", e);
                 return (StatusCode::INTERNAL_SERVER_ERROR, "Seek 失败").into_response();
             }
 
@@ -302,14 +302,14 @@ async fn serve_audio(
     Path(name): Path<String>,
     State(_state): State<Arc<ServerState>>,
 ) -> Response {
-    println!("[MobileMediaServer] 收到音频请求: {}", name);
+    log::info!("[MobileMediaServer] 收到音频请求: {}", name);
 
     // 1. 获取音频文件路径
     let sounds_dir = get_notification_sounds_dir();
     let file_path = sounds_dir.join(format!("{}.mp3", name));
 
     if !file_path.exists() {
-        println!("[MobileMediaServer] 音频文件不存在: {:?}", file_path);
+        log::info!("[MobileMediaServer] 音频文件不存在: {:?}", file_path);
         return (StatusCode::NOT_FOUND, "Audio not found").into_response();
     }
 
@@ -317,13 +317,13 @@ async fn serve_audio(
     let file_data = match tokio::fs::read(&file_path).await {
         Ok(data) => data,
         Err(e) => {
-            println!("[MobileMediaServer] 读取音频文件失败: {}", e);
+            log::error!("[MobileMediaServer] 读取音频文件失败: {}", e);
             return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read audio").into_response();
         }
     };
 
     let file_size = file_data.len();
-    println!("[MobileMediaServer] 返回音频: {} ({} bytes)", name, file_size);
+    log::info!("[MobileMediaServer] 返回音频: {} ({} bytes)", name, file_size);
 
     // 3. 返回音频内容
     Response::builder()
@@ -82,7 +82,7 @@ pub fn list_notification_sounds() -> Result<Vec<SoundInfo>, String> {
         }
     });
 
-    println!(
+    log::info!(
         "[Sounds] 列出提示音: {} 个, 目录: {:?}",
         sounds.len(),
         sounds_dir
@@ -113,7 +113,7 @@ pub fn save_notification_sound(source_path: String, name: String) -> Result<Soun
     fs::copy(&source_path, &target_path)
         .map_err(|e| format!("复制文件失败: {}", e))?;
 
-    println!(
+    log::info!(
         "[Sounds] 保存提示音: {} -> {:?}",
         name, target_path
     );
@@ -144,7 +144,7 @@ pub fn delete_notification_sound(name: String) -> Result<(), String> {
     fs::remove_file(&file_path)
         .map_err(|e| format!("删除文件失败: {}", e))?;
 
-    println!("[Sounds] 删除提示音: {}", name);
+    log::info!("[Sounds] 删除提示音: {}", name);
 
     Ok(())
 }
@@ -171,7 +171,7 @@ pub fn ensure_sounds_directory() -> Result<String, String> {
     fs::create_dir_all(&sounds_dir)
         .map_err(|e| format!("创建提示音目录失败: {}", e))?;
 
-    println!("[Sounds] 提示音目录: {:?}", sounds_dir);
+    log::info!("[Sounds] 提示音目录: {:?}", sounds_dir);
 
     Ok(sounds_dir.to_string_lossy().to_string())
 }
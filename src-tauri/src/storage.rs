@@ -58,7 +58,7 @@ pub fn init_android_data_dir(path: PathBuf) -> Result<(), StorageError> {
     // 设置全局变量（只能设置一次）
     let _ = ANDROID_APP_DATA_DIR.set(app_dir.clone());
 
-    println!("[Storage] Android 数据目录初始化: {:?}", app_dir);
+    log::info!("[Storage] Android 数据目录初始化: {:?}", app_dir);
     Ok(())
 }
 
@@ -54,7 +54,7 @@ pub fn init_android_app_root(path: PathBuf) -> Result<(), String> {
     // 设置全局变量（只能设置一次）
     let _ = ANDROID_APP_ROOT.set(app_root.clone());
 
-    println!("[UserData] Android 数据根目录初始化: {:?}", app_root);
+    log::info!("[UserData] Android 数据根目录初始化: {:?}", app_root);
     Ok(())
 }
 
@@ -94,7 +94,7 @@ pub fn get_app_root() -> PathBuf {
             return path.clone();
         }
         // 备用：如果未初始化，尝试使用默认路径（不应该发生）
-        eprintln!("[UserData] 警告: Android 数据根目录未初始化");
+        log::warn!("[UserData] 警告: Android 数据根目录未初始化");
         PathBuf::from("/data/local/tmp/huanvae-chat/data")
     }
 
@@ -270,7 +270,7 @@ pub fn set_current_user(user_id: &str, server_url: &str) -> Result<(), String> {
         server_url: server_url.to_string(),
     });
 
-    println!(
+    log::info!(
         "[UserData] 设置当前用户: {} @ {}",
         user_id,
         sanitize_server_url(server_url)
@@ -288,7 +288,7 @@ pub fn get_current_user() -> Option<UserContext> {
 pub fn clear_current_user() {
     let mut current = CURRENT_USER.write();
     *current = None;
-    println!("[UserData] 已清除当前用户");
+    log::info!("[UserData] 已清除当前用户");
 }
 
 /// 获取当前用户的数据库路径
@@ -326,7 +326,7 @@ pub fn ensure_user_directories(user_id: &str, server_url: &str) -> Result<(), St
         fs::create_dir_all(dir).map_err(|e| format!("创建目录失败 {:?}: {}", dir, e))?;
     }
 
-    println!(
+    log::info!(
         "[UserData] 用户目录已创建: {:?}",
         get_user_data_dir(user_id, server_url)
     );
@@ -360,6 +360,7 @@ export function MessageBubble({
                 fileSize={message.file_size}
                 fileHash={message.file_hash}
                 urlType="friend"
+                senderId={message.sender_id}
                 imageWidth={message.image_width}
                 imageHeight={message.image_height}
               />
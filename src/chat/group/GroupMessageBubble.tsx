@@ -368,6 +368,7 @@ export function GroupMessageBubble({
                 fileSize={message.file_size}
                 fileHash={message.file_hash}
                 urlType="group"
+                senderId={message.sender_id}
                 imageWidth={message.image_width}
                 imageHeight={message.image_height}
               />
@@ -95,6 +95,8 @@ export interface FileMessageContentProps {
   urlType?: 'user' | 'friend' | 'group';
   /** 好友 ID（用于错误上报） */
   friendId?: string;
+  /** 消息发送者 ID（局域网直传时优先尝试其设备） */
+  senderId?: string;
   /** 图片宽度（像素），从消息中获取 */
   imageWidth?: number | null;
   /** 图片高度（像素），从消息中获取 */
@@ -157,6 +159,7 @@ function ImageMessage({
   fileSize,
   urlType,
   friendId,
+  senderId,
   imageWidth,
   imageHeight,
 }: {
@@ -167,6 +170,8 @@ function ImageMessage({
   urlType: 'user' | 'friend' | 'group';
   /** 好友 ID（用于错误上报） */
   friendId?: string;
+  /** 消息发送者 ID */
+  senderId?: string;
   /** 消息中携带的图片宽度（后端返回） */
   imageWidth?: number | null;
   /** 消息中携带的图片高度（后端返回） */
@@ -179,6 +184,7 @@ function ImageMessage({
     filename,
     urlType,
     friendId,
+    senderId,
   );
 
   // 移动端预览模态框状态
@@ -215,6 +221,7 @@ function ImageMessage({
         fileSize: fileSize ?? undefined,
         fileHash,
         urlType,
+        senderId,
         localPath,
         // 传递已获取的预签名 URL，避免独立窗口重复请求
         presignedUrl: isLocal ? undefined : src,
@@ -224,7 +231,7 @@ function ImageMessage({
         accessToken: session.accessToken,
       },
     );
-  }, [session, fileUuid, filename, fileSize, fileHash, urlType, localPath, isLocal, src]);
+  }, [session, fileUuid, filename, fileSize, fileHash, urlType, senderId, localPath, isLocal, src]);
 
   // 容器样式：固定尺寸，不会因图片加载而改变
   const containerStyle: React.CSSProperties = {
@@ -305,6 +312,7 @@ function VideoMessage({
   fileSize,
   urlType,
   friendId,
+  senderId,
   imageWidth,
   imageHeight,
 }: {
@@ -315,6 +323,8 @@ function VideoMessage({
   urlType: 'user' | 'friend' | 'group';
   /** 好友 ID（用于错误上报） */
   friendId?: string;
+  /** 消息发送者 ID */
+  senderId?: string;
   /** 消息中携带的视频宽度（后端返回） */
   imageWidth?: number | null;
   /** 消息中携带的视频高度（后端返回） */
@@ -328,6 +338,7 @@ function VideoMessage({
     fileSize ?? undefined,
     urlType,
     friendId,
+    senderId,
   );
 
   // 移动端预览模态框状态
@@ -389,6 +400,7 @@ function VideoMessage({
           filename,
           'video',
           fileSize ?? undefined,
+          senderId,
         );
       }
       setShowMobilePreview(true);
@@ -406,6 +418,7 @@ function VideoMessage({
         filename,
         'video',
         fileSize ?? undefined,
+        senderId,
       );
     }
 
@@ -418,6 +431,7 @@ function VideoMessage({
         fileSize: fileSize ?? undefined,
         fileHash,
         urlType,
+        senderId,
         localPath: actualLocalPath,
         // 传递已获取的预签名 URL，避免独立窗口重复请求
         presignedUrl: isLocal ? undefined : src,
@@ -428,7 +442,7 @@ function VideoMessage({
       },
     );
   }, [
-    session, fileUuid, filename, fileSize, fileHash, urlType,
+    session, fileUuid, filename, fileSize, fileHash, urlType, senderId,
     actualLocalPath, isLocal, src, isDownloaded, isDownloading,
   ]);
 
@@ -513,6 +527,7 @@ function DocumentMessage({
   fileSize,
   urlType,
   friendId,
+  senderId,
 }: {
   fileUuid: string;
   fileHash: string | null | undefined;
@@ -521,6 +536,8 @@ function DocumentMessage({
   urlType: 'user' | 'friend' | 'group';
   /** 好友 ID（用于错误上报） */
   friendId?: string;
+  /** 消息发送者 ID */
+  senderId?: string;
 }) {
   const [showPreview, setShowPreview] = useState(false);
   const { src, isLocal, localPath, cacheFile } = useFileCache({
@@ -530,6 +547,7 @@ function DocumentMessage({
     fileType: 'document',
     urlType,
     friendId,
+    senderId,
     autoCache: false,
   });
 
@@ -604,6 +622,7 @@ export function FileMessageContent({
   fileHash,
   urlType = 'friend',
   friendId,
+  senderId,
   imageWidth,
   imageHeight,
 }: FileMessageContentProps) {
@@ -630,6 +649,7 @@ export function FileMessageContent({
           fileSize={fileSize}
           urlType={urlType}
           friendId={friendId}
+          senderId={senderId}
           imageWidth={imageWidth}
           imageHeight={imageHeight}
         />
@@ -644,6 +664,7 @@ export function FileMessageContent({
           fileSize={fileSize}
           urlType={urlType}
           friendId={friendId}
+          senderId={senderId}
           imageWidth={imageWidth}
           imageHeight={imageHeight}
         />
@@ -658,6 +679,7 @@ export function FileMessageContent({
           fileSize={fileSize}
           urlType={urlType}
           friendId={friendId}
+          senderId={senderId}
         />
       );
   }
@@ -66,6 +66,8 @@ export interface UseFileCacheOptions {
   urlType?: 'user' | 'friend' | 'group';
   /** 好友 ID（用于错误上报） */
   friendId?: string;
+  /** 消息发送者 ID（局域网直传时优先尝试其设备） */
+  senderId?: string;
   /** 是否自动缓存图片 */
   autoCache?: boolean;
   /** 是否启用（用于条件加载） */
@@ -119,6 +121,7 @@ export function useFileCache(options: UseFileCacheOptions): UseFileCacheResult {
     fileSize,
     urlType = 'user',
     friendId,
+    senderId,
     autoCache = true,
     enabled = true,
   } = options;
@@ -274,8 +277,9 @@ export function useFileCache(options: UseFileCacheOptions): UseFileCacheResult {
       fileName,
       fileType,
       fileSize,
+      senderId,
     );
-  }, [fileName, fileType, fileSize]);
+  }, [fileName, fileType, fileSize, senderId]);
 
   // 重新加载
   const reload = useCallback(() => {
@@ -315,6 +319,7 @@ export function useImageCache(
   fileName: string,
   urlType: 'user' | 'friend' | 'group' = 'user',
   friendId?: string,
+  senderId?: string,
 ) {
   const result = useFileCache({
     fileUuid,
@@ -323,6 +328,7 @@ export function useImageCache(
     fileType: 'image',
     urlType,
     friendId,
+    senderId,
     autoCache: true,
   });
 
@@ -372,6 +378,7 @@ export function useVideoCache(
   fileSize?: number,
   urlType: 'user' | 'friend' | 'group' = 'user',
   friendId?: string,
+  senderId?: string,
 ) {
   const result = useFileCache({
     fileUuid,
@@ -381,6 +388,7 @@ export function useVideoCache(
     fileSize,
     urlType,
     friendId,
+    senderId,
     autoCache: false, // 视频不自动缓存，等待播放
   });
 
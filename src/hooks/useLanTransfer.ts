@@ -35,7 +35,14 @@
  * - 后端：request_peer_connection 和 server 端都有去重检查
  * - 如果已存在连接，返回现有 connectionId 而不是创建新连接
  *
+ * 事件日志重放：
+ * - 先记录日志序号，再加载快照（设备、待处理请求、活跃连接），最后只重放该序号之后的事件
+ * - 日志已丢弃部分事件（truncated）时不重放，重新加载快照
+ * - 请求被处理（响应、超时、本机接受/拒绝连接）后从待处理列表移除，重放不会让其重新出现
+ *
  * 更新日志：
+ * - 2026-10-18: 事件日志重放改为快照 + 增量重放，处理过的请求不再重新出现
+ * - 2026-10-18: 启动时重放后端事件日志（get_lan_events_since），网页视图重新加载后恢复状态
  * - 2026-01-25: 修复设备 IP 不更新、批量进度不更新、取消按钮不工作问题
  * - 2026-01-25: 支持多个并行传输会话（batchProgressMap）
  */
@@ -69,7 +76,7 @@ export interface ConnectionRequest {
 }
 
 /** 点对点连接状态 */
-export type PeerConnectionStatus = 'connected' | 'disconnected';
+export type PeerConnectionStatus = 'connected' | 'degraded' | 'disconnected';
 
 /** 点对点连接 */
 export interface PeerConnection {
@@ -94,6 +101,14 @@ export interface FileMetadata {
   fileSize: number;
   mimeType: string;
   sha256: string;
+  /** 修改时间（Unix 毫秒） */
+  modifiedAt?: number;
+  /** 创建时间（Unix 毫秒） */
+  createdAt?: number;
+  /** Unix 权限位 */
+  mode?: number;
+  /** 相对路径（发送文件夹时） */
+  relativePath?: string;
 }
 
 /** 传输请求（新版，需确认） */
@@ -194,6 +209,13 @@ export interface HashingProgress {
   totalFiles: number;
 }
 
+/** 接收后处理动作结果 */
+export interface PostReceiveActionResult {
+  action: 'move' | 'extract' | 'command';
+  success: boolean;
+  message: string;
+}
+
 /** 局域网传输事件 */
 export type LanTransferEvent =
   | { type: 'device_discovered'; device: DiscoveredDevice }
@@ -202,21 +224,41 @@ export type LanTransferEvent =
   | { type: 'peer_connection_request'; request: PeerConnectionRequest }
   | { type: 'peer_connection_established'; connection: PeerConnection }
   | { type: 'peer_connection_closed'; connection_id: string }
+  | { type: 'peer_connection_status_changed'; connection_id: string; status: PeerConnectionStatus }
+  | { type: 'peer_connection_request_expired'; connection_id: string }
+  | { type: 'peer_connection_request_resolved'; connection_id: string; accepted: boolean }
   // 旧版连接事件
   | { type: 'connection_request'; request: ConnectionRequest }
   | { type: 'connection_response'; request_id: string; accepted: boolean }
   // 传输事件
   | { type: 'transfer_request_received'; request: TransferRequest }
   | { type: 'transfer_request_response'; request_id: string; accepted: boolean; reject_reason?: string }
+  | { type: 'transfer_request_expired'; request_id: string }
   | { type: 'transfer_progress'; task: TransferTask }
   | { type: 'batch_progress'; progress: BatchTransferProgress }
   | { type: 'transfer_completed'; task_id: string; saved_path: string }
   | { type: 'batch_transfer_completed'; session_id: string; total_files: number; save_directory: string }
   | { type: 'transfer_failed'; task_id: string; error: string }
+  | { type: 'post_receive_completed'; task_id: string; saved_path: string; final_path: string; results: PostReceiveActionResult[] }
   | { type: 'service_state_changed'; is_running: boolean }
   // 哈希计算进度（大文件预处理时显示）
   | { type: 'hashing_progress'; file_name: string; file_size: number; processed_bytes: number; current_file: number; total_files: number };
 
+/** 事件日志中的一条事件 */
+export interface LanEventRecord {
+  seq: number;
+  timestamp: string;
+  event: LanTransferEvent;
+}
+
+/** 增量拉取事件日志的结果 */
+export interface LanEventsSince {
+  events: LanEventRecord[];
+  latestSeq: number;
+  /** 有事件已被丢弃，需要全量刷新 */
+  truncated: boolean;
+}
+
 /** Hook 返回值 */
 export interface UseLanTransferReturn {
   /** 服务是否正在运行 */
@@ -508,153 +550,239 @@ export function useLanTransfer(): UseLanTransferReturn {
   useEffect(() => {
     let unlisten: UnlistenFn | null = null;
 
+    const handleEvent = (payload: LanTransferEvent) => {
+      switch (payload.type) {
+        case 'device_discovered':
+          setDevices((prev) => {
+            const exists = prev.some((d) => d.deviceId === payload.device.deviceId);
+            if (exists) {
+              return prev.map((d) =>
+                d.deviceId === payload.device.deviceId ? payload.device : d,
+              );
+            }
+            return [...prev, payload.device];
+          });
+          break;
+
+        case 'device_left':
+          setDevices((prev) => prev.filter((d) => d.deviceId !== payload.device_id));
+          break;
+
+        // 点对点连接事件
+        case 'peer_connection_request':
+          setPendingPeerConnectionRequests((prev) => {
+            const exists = prev.some((r) => r.connectionId === payload.request.connectionId);
+            if (exists) {
+              return prev;
+            }
+            return [...prev, payload.request];
+          });
+          break;
+
+        case 'peer_connection_established':
+          setActiveConnections((prev) => {
+            const exists = prev.some((c) => c.connectionId === payload.connection.connectionId);
+            if (exists) {
+              return prev.map((c) =>
+                c.connectionId === payload.connection.connectionId ? payload.connection : c,
+              );
+            }
+            return [...prev, payload.connection];
+          });
+          // 连接建立后，清理来自该设备的待处理请求（解决互相请求时的重复显示问题）
+          setPendingPeerConnectionRequests((prev) =>
+            prev.filter((r) => r.fromDevice.deviceId !== payload.connection.peerDevice.deviceId),
+          );
+          // 自动设置为当前连接（可以用于打开传输窗口）
+          setCurrentConnection(payload.connection);
+          break;
+
+        case 'peer_connection_closed':
+          setActiveConnections((prev) =>
+            prev.filter((c) => c.connectionId !== payload.connection_id),
+          );
+          // 如果关闭的是当前连接，清空
+          setCurrentConnection((prev) =>
+            prev?.connectionId === payload.connection_id ? null : prev,
+          );
+          break;
+
+        case 'peer_connection_request_expired':
+          // 超时未处理，已自动拒绝
+          setPendingPeerConnectionRequests((prev) =>
+            prev.filter((r) => r.connectionId !== payload.connection_id),
+          );
+          break;
+
+        case 'peer_connection_request_resolved':
+          // 本机已接受或拒绝
+          setPendingPeerConnectionRequests((prev) =>
+            prev.filter((r) => r.connectionId !== payload.connection_id),
+          );
+          break;
+
+        case 'peer_connection_status_changed':
+          // 心跳超时降级 / 恢复
+          setActiveConnections((prev) =>
+            prev.map((c) =>
+              c.connectionId === payload.connection_id ? { ...c, status: payload.status } : c,
+            ),
+          );
+          setCurrentConnection((prev) =>
+            prev?.connectionId === payload.connection_id ? { ...prev, status: payload.status } : prev,
+          );
+          break;
+
+        // 旧版连接事件
+        case 'connection_request':
+          setPendingRequests((prev) => {
+            const exists = prev.some((r) => r.requestId === payload.request.requestId);
+            if (exists) {
+              return prev;
+            }
+            return [...prev, payload.request];
+          });
+          break;
+
+        case 'connection_response':
+          // 处理连接响应
+          break;
+
+        case 'transfer_request_received':
+          setPendingTransferRequests((prev) => {
+            const exists = prev.some((r) => r.requestId === payload.request.requestId);
+            if (exists) {
+              return prev;
+            }
+            return [...prev, payload.request];
+          });
+          break;
+
+        case 'transfer_request_expired':
+          // 超时未处理，已自动拒绝
+          setPendingTransferRequests((prev) =>
+            prev.filter((r) => r.requestId !== payload.request_id),
+          );
+          break;
+
+        case 'transfer_request_response':
+          // 请求已处理，从待确认列表移除
+          setPendingTransferRequests((prev) =>
+            prev.filter((r) => r.requestId !== payload.request_id),
+          );
+          if (!payload.accepted) {
+            // 如果被拒绝，可以显示通知
+            console.warn('[LanTransfer] 传输请求被拒绝:', payload.reject_reason);
+          }
+          break;
+
+        case 'transfer_progress':
+          setActiveTransfers((prev) => {
+            const exists = prev.some((t) => t.taskId === payload.task.taskId);
+            if (exists) {
+              return prev.map((t) =>
+                t.taskId === payload.task.taskId ? payload.task : t,
+              );
+            }
+            return [...prev, payload.task];
+          });
+          break;
+
+        case 'batch_progress':
+          setBatchProgressMap((prev) => {
+            const newMap = new Map(prev);
+            newMap.set(payload.progress.sessionId, payload.progress);
+            return newMap;
+          });
+          // 传输开始后清除哈希进度
+          setHashingProgress(null);
+          break;
+
+        case 'hashing_progress':
+          // 大文件哈希计算进度
+          setHashingProgress({
+            fileName: payload.file_name,
+            fileSize: payload.file_size,
+            processedBytes: payload.processed_bytes,
+            currentFile: payload.current_file,
+            totalFiles: payload.total_files,
+          });
+          break;
+
+        case 'transfer_completed':
+          setActiveTransfers((prev) =>
+            prev.filter((t) => t.taskId !== payload.task_id),
+          );
+          break;
+
+        case 'post_receive_completed':
+          // 接收后处理（归类、解压、执行命令）失败时提示
+          payload.results
+            .filter((r) => !r.success)
+            .forEach((r) => console.warn('[LanTransfer] 接收后处理失败:', r.action, r.message));
+          break;
+
+        case 'batch_transfer_completed':
+          setBatchProgressMap((prev) => {
+            const newMap = new Map(prev);
+            newMap.delete(payload.session_id);
+            return newMap;
+          });
+          // 刷新会话列表
+          invoke<TransferSession[]>('get_all_transfer_sessions').then(setActiveSessions);
+          break;
+
+        case 'transfer_failed':
+          setActiveTransfers((prev) =>
+            prev.filter((t) => t.taskId !== payload.task_id),
+          );
+          break;
+
+        case 'service_state_changed':
+          setIsRunning(payload.is_running);
+          break;
+      }
+    };
+
+    // 加载快照：设备、待处理请求、活跃连接
+    const loadSnapshot = async () => {
+      const [devices, transferRequests, peerConnectionRequests, peerConnections] = await Promise.all([
+        invoke<DiscoveredDevice[]>('get_discovered_devices'),
+        invoke<TransferRequest[]>('get_pending_transfer_requests'),
+        invoke<PeerConnectionRequest[]>('get_pending_peer_connection_requests'),
+        invoke<PeerConnection[]>('get_active_peer_connections'),
+      ]);
+      setDevices(devices);
+      setPendingTransferRequests(transferRequests);
+      setPendingPeerConnectionRequests(peerConnectionRequests);
+      setActiveConnections(peerConnections);
+    };
+
     const setupListener = async () => {
       unlisten = await listen<LanTransferEvent>('lan-transfer-event', (event) => {
-        const payload = event.payload;
-
-        switch (payload.type) {
-          case 'device_discovered':
-            setDevices((prev) => {
-              const exists = prev.some((d) => d.deviceId === payload.device.deviceId);
-              if (exists) {
-                return prev.map((d) =>
-                  d.deviceId === payload.device.deviceId ? payload.device : d,
-                );
-              }
-              return [...prev, payload.device];
-            });
-            break;
-
-          case 'device_left':
-            setDevices((prev) => prev.filter((d) => d.deviceId !== payload.device_id));
-            break;
-
-          // 点对点连接事件
-          case 'peer_connection_request':
-            setPendingPeerConnectionRequests((prev) => {
-              const exists = prev.some((r) => r.connectionId === payload.request.connectionId);
-              if (exists) {
-                return prev;
-              }
-              return [...prev, payload.request];
-            });
-            break;
-
-          case 'peer_connection_established':
-            setActiveConnections((prev) => {
-              const exists = prev.some((c) => c.connectionId === payload.connection.connectionId);
-              if (exists) {
-                return prev.map((c) =>
-                  c.connectionId === payload.connection.connectionId ? payload.connection : c,
-                );
-              }
-              return [...prev, payload.connection];
-            });
-            // 连接建立后，清理来自该设备的待处理请求（解决互相请求时的重复显示问题）
-            setPendingPeerConnectionRequests((prev) =>
-              prev.filter((r) => r.fromDevice.deviceId !== payload.connection.peerDevice.deviceId),
-            );
-            // 自动设置为当前连接（可以用于打开传输窗口）
-            setCurrentConnection(payload.connection);
-            break;
-
-          case 'peer_connection_closed':
-            setActiveConnections((prev) =>
-              prev.filter((c) => c.connectionId !== payload.connection_id),
-            );
-            // 如果关闭的是当前连接，清空
-            setCurrentConnection((prev) =>
-              prev?.connectionId === payload.connection_id ? null : prev,
-            );
-            break;
-
-          // 旧版连接事件
-          case 'connection_request':
-            setPendingRequests((prev) => [...prev, payload.request]);
-            break;
-
-          case 'connection_response':
-            // 处理连接响应
-            break;
-
-          case 'transfer_request_received':
-            setPendingTransferRequests((prev) => {
-              const exists = prev.some((r) => r.requestId === payload.request.requestId);
-              if (exists) {
-                return prev;
-              }
-              return [...prev, payload.request];
-            });
-            break;
-
-          case 'transfer_request_response':
-            // 处理传输请求响应
-            if (!payload.accepted) {
-              // 如果被拒绝，可以显示通知
-              console.warn('[LanTransfer] 传输请求被拒绝:', payload.reject_reason);
-            }
-            break;
-
-          case 'transfer_progress':
-            setActiveTransfers((prev) => {
-              const exists = prev.some((t) => t.taskId === payload.task.taskId);
-              if (exists) {
-                return prev.map((t) =>
-                  t.taskId === payload.task.taskId ? payload.task : t,
-                );
-              }
-              return [...prev, payload.task];
-            });
-            break;
-
-          case 'batch_progress':
-            setBatchProgressMap((prev) => {
-              const newMap = new Map(prev);
-              newMap.set(payload.progress.sessionId, payload.progress);
-              return newMap;
-            });
-            // 传输开始后清除哈希进度
-            setHashingProgress(null);
-            break;
-
-          case 'hashing_progress':
-            // 大文件哈希计算进度
-            setHashingProgress({
-              fileName: payload.file_name,
-              fileSize: payload.file_size,
-              processedBytes: payload.processed_bytes,
-              currentFile: payload.current_file,
-              totalFiles: payload.total_files,
-            });
-            break;
-
-          case 'transfer_completed':
-            setActiveTransfers((prev) =>
-              prev.filter((t) => t.taskId !== payload.task_id),
-            );
-            break;
-
-          case 'batch_transfer_completed':
-            setBatchProgressMap((prev) => {
-              const newMap = new Map(prev);
-              newMap.delete(payload.session_id);
-              return newMap;
-            });
-            // 刷新会话列表
-            invoke<TransferSession[]>('get_all_transfer_sessions').then(setActiveSessions);
-            break;
-
-          case 'transfer_failed':
-            setActiveTransfers((prev) =>
-              prev.filter((t) => t.taskId !== payload.task_id),
-            );
-            break;
-
-          case 'service_state_changed':
-            setIsRunning(payload.is_running);
-            break;
-        }
+        handleEvent(event.payload);
       });
+
+      // 恢复网页视图重新加载前的状态：
+      // 先记录日志序号再加载快照，之后只重放该序号之后的事件（重复事件由各处理分支去重）
+      try {
+        const { latestSeq } = await invoke<LanEventsSince>('get_lan_events_since', {
+          since: Number.MAX_SAFE_INTEGER,
+        });
+        await loadSnapshot();
+
+        const { events, truncated } = await invoke<LanEventsSince>('get_lan_events_since', {
+          since: latestSeq,
+        });
+        if (truncated) {
+          // 快照之后的事件已被丢弃，无法增量重放，重新加载快照
+          await loadSnapshot();
+        } else {
+          events.forEach((record) => handleEvent(record.event));
+        }
+      } catch (error) {
+        console.error('[LanTransfer] 恢复事件状态失败:', error);
+      }
     };
 
     setupListener();
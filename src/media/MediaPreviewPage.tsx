@@ -60,6 +60,8 @@ interface MediaState {
   fileHash?: string | null;
   /** URL 类型 */
   urlType: 'user' | 'friend' | 'group';
+  /** 消息发送者 ID */
+  senderId?: string;
   /** 本地文件路径 */
   localPath?: string | null;
   /** 预获取的预签名 URL */
@@ -362,6 +364,7 @@ function ImageViewer({ state }: { state: MediaState }) {
         state.filename,
         'image',
         state.fileSize,
+        state.senderId,
       ).then((localPath) => {
         // eslint-disable-next-line no-console
         console.log('[MediaPreview] 后台下载完成:', localPath);
@@ -372,7 +375,7 @@ function ImageViewer({ state }: { state: MediaState }) {
         console.warn('[MediaPreview] 后台下载失败:', err);
       });
     }
-  }, [state.fileHash, state.filename, state.fileSize]);
+  }, [state.fileHash, state.filename, state.fileSize, state.senderId]);
 
   // 鼠标滚轮缩放
   const handleWheel = useCallback((e: React.WheelEvent) => {
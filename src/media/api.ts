@@ -31,6 +31,8 @@ export interface MediaWindowData {
   fileHash?: string | null;
   /** URL 类型（用于构建下载 URL） */
   urlType: 'user' | 'friend' | 'group';
+  /** 消息发送者 ID（局域网直传时优先尝试其设备） */
+  senderId?: string;
   /** 本地文件路径（如果有） */
   localPath?: string | null;
   /** 预获取的预签名 URL（可选，避免在媒体窗口中再次请求） */
@@ -160,6 +160,8 @@ export async function getCachedFilePath(fileHash: string): Promise<string | null
 
 /**
  * 下载文件并保存到本地
+ *
+ * 提供 senderId（消息发送者 ID）时，局域网直传优先尝试其设备
  */
 export function downloadAndSaveFile(
   url: string,
@@ -167,6 +169,7 @@ export function downloadAndSaveFile(
   fileName: string,
   fileType: 'image' | 'video' | 'document',
   fileSize?: number,
+  senderId?: string,
 ): Promise<string> {
   return invoke<string>('download_and_save_file', {
     url,
@@ -174,6 +177,7 @@ export function downloadAndSaveFile(
     fileName,
     fileType,
     fileSize: fileSize ?? null,
+    senderId: senderId ?? null,
   });
 }
 
@@ -420,6 +424,7 @@ export async function triggerBackgroundDownload(
   fileName: string,
   fileType: 'image' | 'video' | 'document',
   fileSize?: number,
+  senderId?: string,
 ): Promise<void> {
   const store = useFileCacheStore.getState();
 
@@ -472,6 +477,7 @@ export async function triggerBackgroundDownload(
       fileName,
       fileType,
       fileSize,
+      senderId,
     );
     store.completeDownload(fileHash, localPath);
 